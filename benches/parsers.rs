@@ -0,0 +1,77 @@
+//! Compares the throughput of the `FromNext` parsers of stats, dates and task prefixes against
+//! the regex parsers they replaced. Refer to `ttm::utils::scanner`.
+//!     cargo bench --bench parsers
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use ttm::ttm_io::date::{self, Date};
+use ttm::ttm_io::stat::{self, Stat};
+use ttm::ttm_io::task::{TaskFlags, TaskPrefix};
+use ttm::utils::scanner::StrScanner;
+
+const STATS: [&str; 12] = ["0/5", "2/020", "/0xFF", "5 /  5", "999", "/-", "-", "!/-", "0/-", "1/!", "?", "12/0b1100"];
+const DATES: [&str; 8] = ["W8T", "WAF", "W333", "Y20S-W8M", "Y22M-W1", "Y20S-WAU", "W0xAF", "Y21F-WCR"];
+const PREFIXES: [&str; 6] = ["(0/4)", ">(1/4, 2/8, 9/12)", "~(2/2)", "B(-, !/-)", "L(/5, , 3)", "(?, 1/!)"];
+
+/// parses a task prefix the way tasks were parsed before `TaskPrefix`: splitting it into its
+/// flags and stats, and parsing each stat with its regex
+fn prefix_regex(s: &str) -> Option<(TaskFlags, [Option<Stat>; 3])> {
+    let open = s.find('(')?;
+    let close = s.find(')')?;
+    let flags = s[..open].parse::<TaskFlags>().ok()?;
+    let mut stats = [None; 3];
+    for (i, token) in s[open + 1..close].split(',').enumerate() {
+        let token = token.trim();
+        if token.is_empty() {
+            continue;
+        }
+        *stats.get_mut(i)? = Some(stat::stat_parser_regex::parse(token).ok()?);
+    }
+    Some((flags, stats))
+}
+
+fn bench_parsers(c: &mut Criterion) {
+    fn bytes(inputs: &[&str]) -> Throughput {
+        Throughput::Bytes(inputs.iter().map(|s| s.len() as u64).sum())
+    }
+
+    // the regexes are compiled lazily, outside of the measurements
+    for s in STATS.iter() {
+        assert_eq!(s.parse::<Stat>().ok(), stat::stat_parser_regex::parse(s).ok());
+    }
+    for s in DATES.iter() {
+        assert_eq!(s.parse::<Date>().ok(), date::date_regex::parse(s).ok());
+    }
+    for s in PREFIXES.iter() {
+        let prefix = StrScanner::create(s).next::<TaskPrefix>().unwrap();
+        assert_eq!(Some((prefix.flags, prefix.stats)), prefix_regex(s));
+    }
+
+    let mut group = c.benchmark_group("parsers");
+    group.throughput(bytes(&STATS));
+    group.bench_function(BenchmarkId::new("stat", "scanner"), |b| b.iter(|| {
+        STATS.iter().map(|s| s.parse::<Stat>()).filter(Result::is_ok).count()
+    }));
+    group.bench_function(BenchmarkId::new("stat", "regex"), |b| b.iter(|| {
+        STATS.iter().map(|s| stat::stat_parser_regex::parse(s)).filter(Result::is_ok).count()
+    }));
+
+    group.throughput(bytes(&DATES));
+    group.bench_function(BenchmarkId::new("date", "scanner"), |b| b.iter(|| {
+        DATES.iter().map(|s| s.parse::<Date>()).filter(Result::is_ok).count()
+    }));
+    group.bench_function(BenchmarkId::new("date", "regex"), |b| b.iter(|| {
+        DATES.iter().map(|s| date::date_regex::parse(s)).filter(Result::is_ok).count()
+    }));
+
+    group.throughput(bytes(&PREFIXES));
+    group.bench_function(BenchmarkId::new("task prefix", "scanner"), |b| b.iter(|| {
+        PREFIXES.iter().map(|s| StrScanner::create(s).next::<TaskPrefix>()).filter(Result::is_ok).count()
+    }));
+    group.bench_function(BenchmarkId::new("task prefix", "regex"), |b| b.iter(|| {
+        PREFIXES.iter().filter_map(|s| prefix_regex(s)).count()
+    }));
+    group.finish();
+}
+
+criterion_group!(benches, bench_parsers);
+criterion_main!(benches);
@@ -0,0 +1,48 @@
+//! Parses a generated archive of several megabytes whole, and again after an edit to one of its
+//! tasks with the sections of the last parse cached. Refer to `ttm::ttm_io::document::SectionCache`.
+//!     cargo bench --bench section_cache
+
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+use ttm::ttm_io::document::{Document, Payloads, SectionCache};
+
+/// an archive of weeks, each a section holding a section for each area, each holding task trees
+fn archive(weeks: usize, areas: usize, tasks: usize) -> String {
+    let mut out = String::new();
+    for week in 1..=weeks {
+        out.push_str(&format!("[Week {}]\n", week));
+        for area in 1..=areas {
+            out.push_str(&format!("\t[Area {}]\n", area));
+            for task in 1..=tasks {
+                out.push_str(&format!("\t\t>(1/4, {}/8, 9/12) Task {} of week {} (due: W{}R; prior: {})\n", task % 8, task, week, week % 12 + 1, task));
+                out.push_str(&format!("\t\t\t~(2/2) Step {} (gPushups: 5/10)\n", task));
+                out.push_str("\t\t\tA note on the task\n");
+            }
+        }
+    }
+    out
+}
+
+fn bench_section_cache(c: &mut Criterion) {
+    let text = archive(100, 8, 40);
+    // the same archive with one task of one area edited
+    let edited = text.replacen("Task 20 of week 50 ", "Task 20 of week 50 edited ", 1);
+    assert!(text.len() > 3_000_000 && edited != text);
+    let payloads = Payloads::new();
+
+    let mut group = c.benchmark_group("section_cache");
+    group.throughput(Throughput::Bytes(text.len() as u64));
+    group.sample_size(10);
+    group.bench_function("full parse", |b| b.iter(|| Document::parse_with(&text, &payloads)));
+
+    let mut cache = SectionCache::new();
+    Document::parse_cached(&text, &payloads, &mut cache);
+    let mut texts = vec![&edited, &text].into_iter().cycle();
+    group.bench_function("parse after an edit", |b| b.iter(|| {
+        // every parse is of the other text, so one area and its week parse again each time
+        Document::parse_cached(texts.next().unwrap(), &payloads, &mut cache)
+    }));
+    group.finish();
+}
+
+criterion_group!(benches, bench_section_cache);
+criterion_main!(benches);
@@ -0,0 +1,43 @@
+//! ttm-lsp, the language server for TTM files. It speaks LSP over stdio.
+//! Refer to `ttm::ttm_lsp` for what it provides. Its config is taken from the
+//! ttm.toml found from the working directory, as for ttm. Refer to `ttm::config`.
+
+use lsp_server::Connection;
+use ttm::config::{self, Config};
+use ttm::ttm_lsp::{self, LspOptions};
+
+
+/// the config discovered from the working directory. A config that cannot be read is reported, and
+/// the default config is used instead, so that the server still runs.
+fn load_config() -> Config {
+    let env_var = |name: &str| std::env::var(name).ok().filter(|value| !value.is_empty());
+    let cwd = std::env::current_dir().unwrap_or_default();
+    let path = match config::discover(&cwd, env_var("XDG_CONFIG_HOME").as_deref(), env_var("HOME").as_deref()) {
+        Some(path) => path,
+        None => return Config::default(),
+    };
+    let config = std::fs::read_to_string(&path)
+        .map_err(|e| e.to_string())
+        .and_then(|s| s.parse::<Config>().map_err(|e| e.to_string()));
+    match config {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("ttm-lsp: {}: {}", path.display(), e);
+            Config::default()
+        }
+    }
+}
+
+fn main() {
+    let (connection, io_threads) = Connection::stdio();
+    let result = ttm_lsp::run(&connection, LspOptions::from_config(&load_config()));
+    // the connection must be dropped for the io threads to end
+    drop(connection);
+
+    let result = result.map_err(|e| e.to_string())
+        .and_then(|_| io_threads.join().map_err(|e| e.to_string()));
+    if let Err(e) = result {
+        eprintln!("ttm-lsp: {}", e);
+        std::process::exit(1);
+    }
+}
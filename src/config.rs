@@ -0,0 +1,473 @@
+//! user preferences, read from a ttm.toml file. The file is looked for in the working directory and
+//! each of its parents, then in the ttm directory of $XDG_CONFIG_HOME, or ~/.config. Refer to
+//! `discover`. Every table and key is optional:
+//!     [calendar]  the day each season starts at, as "MM-DD", keyed by winter, spring, summer and
+//!                 fall. Seasons start in that order, cyclically: winter may start in December
+//!                 of the year before.
+//!     [blocks]    minutes: the length of a block, 25 by default
+//!     [sections]  the payload of sections by specifier, as "tasks", "block-tracker" or "text".
+//!                 Refer to `crate::ttm_io::document::Payload`.
+//!     [paths]     notes, timer and sessions: the notes directory and the files ttm keeps its
+//!                 state in. Relative paths are taken from the directory of the config file.
+//!     [flags]     aliases of task flag characters, as "alias" = "flag name", such as "*" = "current"
+//!     [lint]      disable: the lint rules not checked. goals: goals known besides the block
+//!                 tracker entries. Refer to `crate::ttm_core::lint`.
+//!     [workspace] root: the directory of the workspace, taken from the directory of the config
+//!                 file if relative. include and exclude: the glob patterns its files match, and
+//!                 do not match. Refer to `crate::ttm_core::workspace`.
+//!     [time]      utc_offset: the offset of local time from UTC, as "+HH:MM" or "-HH:MM", which
+//!                 tells the day timers and sessions fall on. UTC by default.
+//! Unknown tables and keys are errors, so that misspelled ones are not silently ignored.
+
+#![allow(dead_code)]
+
+use crate::ttm_core::lint::{LintConfig, Rule};
+use crate::ttm_core::timer::DEFAULT_MINUTES;
+use crate::ttm_core::workspace::WorkspaceConfig;
+use crate::ttm_io::calendar::{CivilDate, SeasonCalendar};
+use crate::ttm_io::document::{Document, DocumentParseError, Payload, Payloads, SectionCache};
+use crate::ttm_io::edit;
+use crate::ttm_io::task::{TaskFlags, FLAG_NAMES};
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use toml::value::Table;
+use toml::Value;
+
+pub const FILE_NAME: &str = "ttm.toml";
+
+/// the keys of the [calendar] table, in `crate::ttm_io::date::Season::index` order
+const SEASON_KEYS: [&str; 4] = ["winter", "spring", "summer", "fall"];
+
+/// paths given in place of the defaults. Refer to `crate::ttm_cli`.
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct Paths {
+    pub notes: Option<PathBuf>,
+    pub timer: Option<PathBuf>,
+    pub sessions: Option<PathBuf>,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Config {
+    pub calendar: SeasonCalendar,
+    /// the length of a block in minutes
+    pub block_minutes: u32,
+    pub sections: Payloads,
+    pub paths: Paths,
+    /// the flag each alias character stands for
+    pub flag_aliases: BTreeMap<char, TaskFlags>,
+    pub lint: LintConfig,
+    pub workspace: WorkspaceConfig,
+    /// how many seconds local time is ahead of UTC
+    pub utc_offset: i64,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            calendar: SeasonCalendar::default(),
+            block_minutes: DEFAULT_MINUTES,
+            sections: Payloads::new(),
+            paths: Paths::default(),
+            flag_aliases: BTreeMap::new(),
+            lint: LintConfig::default(),
+            workspace: WorkspaceConfig::default(),
+            utc_offset: 0,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum ConfigError {
+    /// the file is not valid TOML. Holds the error of the TOML parser, along with its position.
+    Syntax(String),
+    UnknownKey(String),
+    /// the value of a key, given as table.key, is not valid
+    InvalidValue { key: String, msg: Cow<'static, str> },
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::result::Result<(), std::fmt::Error> {
+        let msg: Cow<'static, str> = match self {
+            Self::Syntax(message) => message.clone().into(),
+            Self::UnknownKey(key) => format!("unknown key '{}'", key).into(),
+            Self::InvalidValue { key, msg } => format!("{}: {}", key, msg).into(),
+        };
+        write!(f, "{}", msg)?;
+        Ok(())
+    }
+}
+
+fn invalid(key: &str, msg: impl Into<Cow<'static, str>>) -> ConfigError {
+    ConfigError::InvalidValue { key: key.to_string(), msg: msg.into() }
+}
+
+fn string<'a>(key: &str, value: &'a Value) -> Result<&'a str, ConfigError> {
+    value.as_str().ok_or_else(|| invalid(key, format!("expected a string, found {}", value.type_str())))
+}
+
+fn strings<'a>(key: &str, value: &'a Value) -> Result<Vec<&'a str>, ConfigError> {
+    value.as_array()
+        .ok_or_else(|| invalid(key, format!("expected an array of strings, found {}", value.type_str())))?
+        .iter()
+        .map(|value| string(key, value))
+        .collect()
+}
+
+/// parses an offset from UTC, given as +HH:MM or -HH:MM, into seconds
+fn utc_offset(key: &str, value: &Value) -> Result<i64, ConfigError> {
+    let s = string(key, value)?;
+    let error = || invalid(key, format!("'{}' is not an offset from UTC as +HH:MM or -HH:MM", s));
+    let (sign, rest) = match s.chars().next() {
+        Some('+') => (1, &s[1..]),
+        Some('-') => (-1, &s[1..]),
+        _ => return Err(error()),
+    };
+    let (hours, minutes) = rest.split_once(':').filter(|(hours, minutes)| hours.len() == 2 && minutes.len() == 2)
+        .ok_or_else(error)?;
+    let (hours, minutes): (i64, i64) = (hours.parse().map_err(|_| error())?, minutes.parse().map_err(|_| error())?);
+    if hours > 14 || minutes >= 60 || hours * 60 + minutes > 14 * 60 {
+        return Err(error());
+    }
+    Ok(sign * (hours * 3600 + minutes * 60))
+}
+
+/// parses a season start, given as MM-DD
+fn season_start(key: &str, value: &Value) -> Result<(u32, u32), ConfigError> {
+    let s = string(key, value)?;
+    // a year without a February 29th, which no season may start at
+    let civil: CivilDate = format!("2001-{}", s).parse()
+        .map_err(|_| invalid(key, format!("'{}' is not a day of the year as MM-DD", s)))?;
+    Ok((civil.month, civil.day))
+}
+
+impl Config {
+    fn read_calendar(&mut self, table: &Table) -> Result<(), ConfigError> {
+        for (name, value) in table.iter() {
+            let key = format!("calendar.{}", name);
+            let idx = SEASON_KEYS.iter().position(|season| season == name)
+                .ok_or_else(|| ConfigError::UnknownKey(key.clone()))?;
+            self.calendar.season_starts[idx] = season_start(&key, value)?;
+        }
+        // the seasons may wrap around the end of the Gregorian year once, if fall still starts
+        // before the following winter
+        let starts = &self.calendar.season_starts;
+        let wraps: Vec<usize> = (1..starts.len()).filter(|&idx| starts[idx - 1] >= starts[idx]).collect();
+        let out_of_order = match wraps[..] {
+            [] => None,
+            [_] if starts[starts.len() - 1] < starts[0] => None,
+            [idx] if starts[idx - 1] == starts[idx] => Some(idx),
+            [_] => Some(0),
+            [_, idx, ..] => Some(idx),
+        };
+        if let Some(idx) = out_of_order {
+            let before = (idx + starts.len() - 1) % starts.len();
+            return Err(invalid(&format!("calendar.{}", SEASON_KEYS[idx]),
+                format!("{} must start after {}", SEASON_KEYS[idx], SEASON_KEYS[before])));
+        }
+        Ok(())
+    }
+
+    fn read_blocks(&mut self, table: &Table) -> Result<(), ConfigError> {
+        for (name, value) in table.iter() {
+            match name.as_str() {
+                "minutes" => {
+                    self.block_minutes = value.as_integer()
+                        .filter(|minutes| (1..=24 * 60).contains(minutes))
+                        .ok_or_else(|| invalid("blocks.minutes", "expected a number of minutes within a day"))?
+                        as u32;
+                }
+                _ => return Err(ConfigError::UnknownKey(format!("blocks.{}", name))),
+            }
+        }
+        Ok(())
+    }
+
+    fn read_sections(&mut self, table: &Table) -> Result<(), ConfigError> {
+        for (specifier, value) in table.iter() {
+            let key = format!("sections.{}", specifier);
+            let payload: Payload = string(&key, value)?.parse().map_err(|e: String| invalid(&key, e))?;
+            self.sections.insert(specifier.clone(), payload);
+        }
+        Ok(())
+    }
+
+    fn read_paths(&mut self, table: &Table) -> Result<(), ConfigError> {
+        for (name, value) in table.iter() {
+            let key = format!("paths.{}", name);
+            let path = Some(PathBuf::from(string(&key, value)?));
+            match name.as_str() {
+                "notes" => self.paths.notes = path,
+                "timer" => self.paths.timer = path,
+                "sessions" => self.paths.sessions = path,
+                _ => return Err(ConfigError::UnknownKey(key)),
+            }
+        }
+        Ok(())
+    }
+
+    fn read_flags(&mut self, table: &Table) -> Result<(), ConfigError> {
+        for (alias, value) in table.iter() {
+            let key = format!("flags.{}", alias);
+            let mut chars = alias.chars();
+            let c = match (chars.next(), chars.next()) {
+                (Some(c), None) if !c.is_whitespace() && c != '(' => c,
+                _ => return Err(invalid(&key, "an alias is a single character, other than '(' and whitespace")),
+            };
+            if c.to_string().parse::<TaskFlags>().is_ok() {
+                return Err(invalid(&key, format!("'{}' is already a task flag", c)));
+            }
+            let name = string(&key, value)?;
+            let (flag, _name) = FLAG_NAMES.iter().find(|(_flag, flag_name)| *flag_name == name)
+                .ok_or_else(|| {
+                    let names: Vec<&str> = FLAG_NAMES.iter().map(|(_flag, name)| *name).collect();
+                    invalid(&key, format!("unknown task flag '{}', expected one of {}", name, names.join(", ")))
+                })?;
+            self.flag_aliases.insert(c, *flag);
+        }
+        Ok(())
+    }
+
+    fn read_lint(&mut self, table: &Table) -> Result<(), ConfigError> {
+        for (name, value) in table.iter() {
+            let key = format!("lint.{}", name);
+            match name.as_str() {
+                "disable" => {
+                    for rule in strings(&key, value)? {
+                        let rule: Rule = rule.parse().map_err(|e: String| invalid(&key, e))?;
+                        self.lint.disable(rule);
+                    }
+                }
+                "goals" => self.lint.goals = strings(&key, value)?.into_iter().map(String::from).collect(),
+                _ => return Err(ConfigError::UnknownKey(key)),
+            }
+        }
+        Ok(())
+    }
+
+    fn read_workspace(&mut self, table: &Table) -> Result<(), ConfigError> {
+        for (name, value) in table.iter() {
+            let key = format!("workspace.{}", name);
+            match name.as_str() {
+                "root" => self.workspace.root = Some(PathBuf::from(string(&key, value)?)),
+                "include" => self.workspace.include = strings(&key, value)?.into_iter().map(String::from).collect(),
+                "exclude" => self.workspace.exclude = strings(&key, value)?.into_iter().map(String::from).collect(),
+                _ => return Err(ConfigError::UnknownKey(key)),
+            }
+        }
+        Ok(())
+    }
+
+    fn read_time(&mut self, table: &Table) -> Result<(), ConfigError> {
+        for (name, value) in table.iter() {
+            let key = format!("time.{}", name);
+            match name.as_str() {
+                "utc_offset" => self.utc_offset = utc_offset(&key, value)?,
+                _ => return Err(ConfigError::UnknownKey(key)),
+            }
+        }
+        Ok(())
+    }
+
+    /// the config with its relative paths taken from `dir`, the directory of the config file
+    pub fn relative_to(mut self, dir: &Path) -> Self {
+        let paths = [&mut self.paths.notes, &mut self.paths.timer, &mut self.paths.sessions, &mut self.workspace.root];
+        for path in paths {
+            if let Some(path) = path.as_mut().filter(|path| path.is_relative()) {
+                *path = dir.join(&path);
+            }
+        }
+        self
+    }
+
+    /// `text` with the flag aliases of its task lines written as the flags they stand for, so
+    /// that the lines parse as tasks
+    pub fn unalias_flags<'a>(&self, text: &'a str) -> Cow<'a, str> {
+        if self.flag_aliases.is_empty() {
+            return text.into();
+        }
+        edit::edit_lines(text, |_i, line| {
+            let open = line.find('(')?;
+            let (tab, flags) = line[..open].split_at(line.len() - line.trim_start().len());
+            if !flags.chars().any(|c| self.flag_aliases.contains_key(&c)) {
+                return None;
+            }
+            let unaliased: String = flags.chars()
+                .map(|c| self.flag_aliases.get(&c).map_or(c.to_string(), |flag| flag.to_string()))
+                .collect();
+            unaliased.trim().parse::<TaskFlags>().ok()?;
+            Some(format!("{}{}{}", tab, unaliased, &line[open..]))
+        }).into()
+    }
+
+    /// parses the document in `text` with the flag aliases and section payloads of the config.
+    /// Refer to `Document::parse_with`.
+    pub fn parse_document(&self, text: &str) -> (Document, Vec<DocumentParseError>) {
+        Document::parse_with(&self.unalias_flags(text), &self.sections)
+    }
+
+    /// parses the document in `text` as `parse_document` does, taking the sections unchanged since
+    /// the last parse from `cache`. Refer to `Document::parse_cached`.
+    pub fn parse_document_cached(&self, text: &str, cache: &mut SectionCache) -> (Document, Vec<DocumentParseError>) {
+        Document::parse_cached(&self.unalias_flags(text), &self.sections, cache)
+    }
+}
+
+impl std::str::FromStr for Config {
+    type Err = ConfigError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let root: Table = toml::from_str(s).map_err(|e: toml::de::Error| ConfigError::Syntax(e.to_string()))?;
+        let mut config = Config::default();
+        for (name, value) in root.iter() {
+            let table = value.as_table()
+                .ok_or_else(|| invalid(name, format!("expected a table, found {}", value.type_str())))?;
+            match name.as_str() {
+                "calendar" => config.read_calendar(table)?,
+                "blocks" => config.read_blocks(table)?,
+                "sections" => config.read_sections(table)?,
+                "paths" => config.read_paths(table)?,
+                "flags" => config.read_flags(table)?,
+                "lint" => config.read_lint(table)?,
+                "workspace" => config.read_workspace(table)?,
+                "time" => config.read_time(table)?,
+                _ => return Err(ConfigError::UnknownKey(name.clone())),
+            }
+        }
+        Ok(config)
+    }
+}
+
+/// the config file for `cwd`: the first ttm.toml in `cwd` or one of its parents, or else the one in
+/// the ttm directory of `xdg_config_home`, which defaults to ~/.config. None if there is none.
+pub fn discover(cwd: &Path, xdg_config_home: Option<&str>, home: Option<&str>) -> Option<PathBuf> {
+    let config_home = xdg_config_home.map(PathBuf::from)
+        .or_else(|| home.map(|home| Path::new(home).join(".config")));
+    cwd.ancestors()
+        .map(|dir| dir.join(FILE_NAME))
+        .chain(config_home.map(|dir| dir.join("ttm").join(FILE_NAME)))
+        .find(|path| path.is_file())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONFIG: &str = r#"
+[calendar]
+winter = "12-21"
+spring = "03-20"
+summer = "06-21"
+fall = "09-22"
+
+[blocks]
+minutes = 30
+
+[sections]
+Journal = "text"
+"Block Tracker" = "block-tracker"
+
+[paths]
+notes = "notes"
+sessions = "/var/ttm/sessions"
+
+[flags]
+"*" = "current"
+"x" = "done"
+
+[lint]
+disable = ["priority-range", "unknown-goal"]
+goals = ["pushups"]
+
+[workspace]
+root = "."
+exclude = ["archive/**"]
+
+[time]
+utc_offset = "-05:30"
+"#;
+
+    fn error(s: &str) -> ConfigError {
+        s.parse::<Config>().unwrap_err()
+    }
+
+    #[test]
+    fn test_parse_config() {
+        let config: Config = CONFIG.parse().unwrap();
+        assert_eq!(config.calendar.season_starts, [(12, 21), (3, 20), (6, 21), (9, 22)]);
+        assert_eq!(config.block_minutes, 30);
+        assert_eq!(config.sections.get("Journal"), Some(&Payload::Text));
+        assert_eq!(config.flag_aliases.get(&'*'), Some(&TaskFlags::CURRENT));
+        assert!(!config.lint.is_enabled(Rule::PriorityRange));
+        assert!(config.lint.is_enabled(Rule::DoneUnmet));
+        assert_eq!(config.lint.goals, vec!["pushups".to_string()]);
+        assert_eq!(config.utc_offset, -(5 * 3600 + 30 * 60));
+
+        let config = config.relative_to(Path::new("/home/me/work"));
+        assert_eq!(config.paths.notes, Some(PathBuf::from("/home/me/work/notes")));
+        assert_eq!(config.paths.sessions, Some(PathBuf::from("/var/ttm/sessions")));
+        assert_eq!(config.paths.timer, None);
+        assert_eq!(config.workspace.root, Some(PathBuf::from("/home/me/work/.")));
+        assert_eq!(config.workspace.include, vec!["**/*.ttm".to_string()]);
+        assert_eq!(config.workspace.exclude, vec!["archive/**".to_string()]);
+
+        assert_eq!("".parse::<Config>().unwrap(), Config::default());
+    }
+
+    #[test]
+    fn test_config_errors() {
+        assert!(matches!(error("[blocks\nminutes = 30"), ConfigError::Syntax(_)));
+        assert_eq!(error("[block]\nminutes = 30"), ConfigError::UnknownKey("block".into()));
+        assert_eq!(error("[blocks]\nminute = 30"), ConfigError::UnknownKey("blocks.minute".into()));
+        assert!(matches!(error("[blocks]\nminutes = 0"), ConfigError::InvalidValue { .. }));
+        assert!(matches!(error("[blocks]\nminutes = \"30\""), ConfigError::InvalidValue { .. }));
+        assert!(matches!(error("[calendar]\nwinter = \"02-30\""), ConfigError::InvalidValue { .. }));
+        // the seasons of the calendar are out of order
+        assert_eq!(error(&CONFIG.replace("06-21", "01-05")), invalid("calendar.summer", "summer must start after spring"));
+        assert_eq!(error(&CONFIG.replace("12-21", "09-01")), invalid("calendar.winter", "winter must start after fall"));
+        assert_eq!(error("[calendar]\nspring = \"01-01\""), invalid("calendar.spring", "spring must start after winter"));
+        assert!(matches!(error("[sections]\nWork = \"notes\""), ConfigError::InvalidValue { .. }));
+        assert!(matches!(error("[flags]\n\">\" = \"current\""), ConfigError::InvalidValue { .. }));
+        assert!(matches!(error("[flags]\n\"**\" = \"current\""), ConfigError::InvalidValue { .. }));
+        assert!(matches!(error("[flags]\n\"*\" = \"urgent\""), ConfigError::InvalidValue { .. }));
+        assert!(matches!(error("[lint]\ndisable = [\"no-such-rule\"]"), ConfigError::InvalidValue { .. }));
+        for offset in ["02:00", "+2:00", "+15:00", "+02:60", "+02"].iter() {
+            assert!(matches!(error(&format!("[time]\nutc_offset = \"{}\"", offset)), ConfigError::InvalidValue { .. }), "{}", offset);
+        }
+        assert_eq!(error("[lint]\ngoals = [1]").to_string(), "lint.goals: expected a string, found integer");
+        assert_eq!(error("blocks = 30").to_string(), "blocks: expected a table, found integer");
+    }
+
+    #[test]
+    fn test_flag_aliases() {
+        let config: Config = "[flags]\n\"*\" = \"current\"\n\"x\" = \"done\"".parse().unwrap();
+        let text = "[Tasks]\n\t*(1/2) Write\n\t\tx(2) Outline\n\tx marks (the spot)\n";
+        assert_eq!(config.unalias_flags(text), "[Tasks]\n\t>(1/2) Write\n\t\t~(2) Outline\n\tx marks (the spot)\n");
+
+        let (document, errors) = config.parse_document(text);
+        assert!(errors.is_empty());
+        assert_eq!(document.tasks().len(), 2);
+        assert!(document.tasks()[1].flags().contains(TaskFlags::DONE));
+    }
+
+    #[test]
+    fn test_discover() {
+        let root = std::env::temp_dir().join(format!("ttm_config_{}", std::process::id()));
+        let project = root.join("project");
+        let nested = project.join("a").join("b");
+        let config_home = root.join("config");
+        std::fs::create_dir_all(&nested).unwrap();
+        std::fs::create_dir_all(config_home.join("ttm")).unwrap();
+        let home = root.to_str().unwrap();
+
+        assert_eq!(discover(&nested, config_home.to_str(), Some(home)), None);
+        std::fs::write(config_home.join("ttm").join(FILE_NAME), "").unwrap();
+        assert_eq!(discover(&nested, config_home.to_str(), Some(home)), Some(config_home.join("ttm").join(FILE_NAME)));
+        std::fs::write(project.join(FILE_NAME), "").unwrap();
+        assert_eq!(discover(&nested, config_home.to_str(), Some(home)), Some(project.join(FILE_NAME)));
+        assert_eq!(discover(&config_home, None, Some(home)), None);
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
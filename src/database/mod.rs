@@ -0,0 +1,8 @@
+//! stores what ttm records about tasks outside of TTM files
+pub mod sessions;
+
+
+#[cfg(test)]
+mod tests {
+    #[allow(unused_imports)] use super::*;
+}
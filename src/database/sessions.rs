@@ -0,0 +1,311 @@
+//! A log of the sessions spent on tasks. Blocks count time coarsely, so sessions keep the time
+//! actually spent: each session is a task, when it was started and stopped, and a note. A session
+//! that is not stopped yet is open; only one session is expected to be open at a time.
+//!
+//! The log is written one session per line, as <start>, <stop>, <task> and <note> separated by
+//! tabs.
+//! Times are seconds since 1970-01-01T00:00:00Z, and the stop of an open session is '-'. The task
+//! is given as #Id if it has one, or by its name otherwise. Refer to `crate::ttm_io::ids`.
+//!
+//! Days are split at local midnight, local time being `utc_offset` seconds ahead of UTC. Refer to
+//! `crate::config::Config::utc_offset`.
+//!
+//! The minutes of a task on a day become its blocks with `blocks`, rounding to the closest block.
+
+#![allow(dead_code)]
+
+use crate::ttm_io::calendar::CivilDate;
+use crate::ttm_io::stat::Stat;
+use std::borrow::Cow;
+use std::fmt;
+
+const SECS_PER_DAY: i64 = 86400;
+
+#[derive(Debug, PartialEq)]
+pub enum SessionError {
+    /// a line of the log does not hold a session
+    InvalidLine { line: usize, msg: Cow<'static, str> },
+    /// a session is open already, on the task held
+    AlreadyOpen(String),
+    NoneOpen,
+    /// a session would stop before it started
+    StopBeforeStart,
+}
+
+impl fmt::Display for SessionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::result::Result<(), std::fmt::Error> {
+        let msg: Cow<'static, str> = match self {
+            Self::InvalidLine { line, msg } => format!("line {}: {}", line + 1, msg).into(),
+            Self::AlreadyOpen(task) => format!("a session is open already on {}", task).into(),
+            Self::NoneOpen => "no session is open".into(),
+            Self::StopBeforeStart => "the session would stop before it started".into(),
+        };
+        write!(f, "{}", msg)?;
+        Ok(())
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Session {
+    /// the task, as #Id or by its name
+    pub task: String,
+    pub start: i64,
+    /// None while the session is open
+    pub stop: Option<i64>,
+    pub note: String,
+}
+
+impl Session {
+    pub fn is_open(&self) -> bool {
+        self.stop.is_none()
+    }
+
+    /// when the session stops, taking an open session to go on until `now`
+    pub fn end(&self, now: i64) -> i64 {
+        self.stop.unwrap_or(now).max(self.start)
+    }
+
+    /// the day the session started on, in local time
+    pub fn start_date(&self, utc_offset: i64) -> CivilDate {
+        CivilDate::from_timestamp(self.start, utc_offset)
+    }
+
+    /// seconds of the session within `date`, in local time
+    pub fn secs_on(&self, date: CivilDate, now: i64, utc_offset: i64) -> i64 {
+        let day_start = date.to_days() * SECS_PER_DAY - utc_offset;
+        let day_end = day_start + SECS_PER_DAY;
+        (self.end(now).min(day_end) - self.start.max(day_start)).max(0)
+    }
+}
+
+impl fmt::Display for Session {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let stop = self.stop.map_or("-".to_string(), |stop| stop.to_string());
+        // the fields are separated by tabs, so the note is kept to a single field
+        let note: String = self.note.chars().map(|c| if c == '\t' || c == '\n' || c == '\r' { ' ' } else { c }).collect();
+        write!(f, "{}\t{}\t{}\t{}", self.start, stop, self.task, note)
+    }
+}
+
+impl std::str::FromStr for Session {
+    type Err = Cow<'static, str>;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let fields: Vec<&str> = s.splitn(4, '\t').collect();
+        if fields.len() < 3 {
+            return Err("expected <start>\t<stop>\t<task>\t<note>".into());
+        }
+        let time = |field: &str| field.parse::<i64>().map_err(|_| format!("'{}' is not a time", field));
+        let start = time(fields[0])?;
+        let stop = match fields[1] {
+            "-" => None,
+            stop => Some(time(stop)?),
+        };
+        if stop.is_some_and(|stop| stop < start) {
+            return Err("the session stops before it starts".into());
+        }
+        if fields[2].is_empty() {
+            return Err("the session has no task".into());
+        }
+        Ok(Self { task: fields[2].to_string(), start, stop, note: fields.get(3).unwrap_or(&"").to_string() })
+    }
+}
+
+/// the sessions of the log, in the order they were started
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct SessionLog {
+    sessions: Vec<Session>,
+}
+
+impl SessionLog {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn sessions(&self) -> &[Session] {
+        &self.sessions
+    }
+
+    /// the last session that is still open
+    pub fn open_session(&self) -> Option<&Session> {
+        self.sessions.iter().rev().find(|session| session.is_open())
+    }
+
+    /// opens a session on `task` at `now`, failing if one is open already
+    pub fn start(&mut self, task: &str, now: i64, note: &str) -> Result<&Session, SessionError> {
+        if let Some(open) = self.open_session() {
+            return Err(SessionError::AlreadyOpen(open.task.clone()));
+        }
+        self.sessions.push(Session { task: task.to_string(), start: now, stop: None, note: note.to_string() });
+        Ok(self.sessions.last().unwrap())
+    }
+
+    /// stops the open session at `now`. A note given is added to the note of the session.
+    pub fn stop(&mut self, now: i64, note: &str) -> Result<&Session, SessionError> {
+        let session = self.sessions.iter_mut().rev().find(|session| session.is_open()).ok_or(SessionError::NoneOpen)?;
+        if now < session.start {
+            return Err(SessionError::StopBeforeStart);
+        }
+        session.stop = Some(now);
+        if !note.is_empty() {
+            session.note = if session.note.is_empty() { note.to_string() } else { format!("{}; {}", session.note, note) };
+        }
+        Ok(session)
+    }
+
+    /// stops the open session, if any, and opens one on `task`. Gives the task of the session stopped.
+    pub fn switch(&mut self, task: &str, now: i64, note: &str) -> Result<Option<String>, SessionError> {
+        let stopped = match self.open_session() {
+            Some(_) => Some(self.stop(now, "")?.task.clone()),
+            None => None,
+        };
+        self.start(task, now, note)?;
+        Ok(stopped)
+    }
+
+    /// pairs of sessions, by their indices, that overlap in time. Open sessions go on until `now`.
+    pub fn overlaps(&self, now: i64) -> Vec<(usize, usize)> {
+        let mut order: Vec<usize> = (0..self.sessions.len()).collect();
+        order.sort_by_key(|&i| self.sessions[i].start);
+
+        let mut overlaps: Vec<(usize, usize)> = vec![];
+        for (k, &i) in order.iter().enumerate() {
+            let end = self.sessions[i].end(now);
+            for &j in order[k + 1..].iter().take_while(|&&j| self.sessions[j].start < end) {
+                overlaps.push((i.min(j), i.max(j)));
+            }
+        }
+        overlaps.sort_unstable();
+        overlaps
+    }
+
+    /// sessions, by their indices, still open from a day before `today`
+    pub fn dangling(&self, today: CivilDate, utc_offset: i64) -> Vec<usize> {
+        (0..self.sessions.len())
+            .filter(|&i| self.sessions[i].is_open() && self.sessions[i].start_date(utc_offset) < today)
+            .collect()
+    }
+
+    /// minutes spent on `task` on `date`. Open sessions go on until `now`.
+    pub fn minutes_on(&self, task: &str, date: CivilDate, now: i64, utc_offset: i64) -> i64 {
+        self.sessions.iter()
+            .filter(|session| session.task == task)
+            .map(|session| session.secs_on(date, now, utc_offset))
+            .sum::<i64>() / 60
+    }
+
+    /// the tasks with time spent on `date`, in the order they were first started that day
+    pub fn tasks_on(&self, date: CivilDate, now: i64, utc_offset: i64) -> Vec<&str> {
+        let mut tasks: Vec<&str> = vec![];
+        for session in self.sessions.iter().filter(|session| session.secs_on(date, now, utc_offset) > 0) {
+            if !tasks.contains(&session.task.as_str()) {
+                tasks.push(&session.task);
+            }
+        }
+        tasks
+    }
+}
+
+impl fmt::Display for SessionLog {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for session in self.sessions.iter() {
+            writeln!(f, "{}", session)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::str::FromStr for SessionLog {
+    type Err = SessionError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let sessions = s.lines()
+            .enumerate()
+            .filter(|(_i, line)| !line.trim().is_empty())
+            .map(|(i, line)| line.parse::<Session>().map_err(|msg| SessionError::InvalidLine { line: i, msg }))
+            .collect::<Result<Vec<Session>, SessionError>>()?;
+        Ok(Self { sessions })
+    }
+}
+
+/// how many blocks of `block_minutes` minutes `minutes` make, rounding to the closest block
+pub fn blocks(minutes: i64, block_minutes: u32) -> i32 {
+    let block_minutes = block_minutes.max(1) as i64;
+    ((minutes.max(0) + block_minutes / 2) / block_minutes) as i32
+}
+
+/// `stat`, a day stat, with `blocks` blocks done. An objective or requirement is kept.
+pub fn with_blocks(stat: Option<Stat>, blocks: i32) -> Stat {
+    match stat {
+        Some(Stat::Count { exp, .. }) => Stat::Count { act: Some(blocks), exp },
+        Some(Stat::RequiredCount { exp, .. }) => Stat::RequiredCount { act: blocks, exp },
+        Some(Stat::Bool { exp, .. }) => Stat::Bool { act: blocks > 0, exp },
+        Some(Stat::Unknown) | None => Stat::from_count(Some(blocks), None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 2021-01-08T00:00:00Z
+    const DAY: i64 = 1_610_064_000;
+
+    #[test]
+    fn test_start_stop_switch() {
+        let mut log = SessionLog::new();
+        log.start("#report", DAY + 3600, "intro").unwrap();
+        assert_eq!(log.start("Email", DAY + 3700, ""), Err(SessionError::AlreadyOpen("#report".into())));
+        assert_eq!(log.switch("Email", DAY + 5400, "").unwrap(), Some("#report".to_string()));
+        assert_eq!(log.stop(DAY + 5300, ""), Err(SessionError::StopBeforeStart));
+        assert_eq!(log.stop(DAY + 6000, "inbox zero").unwrap().note, "inbox zero");
+        assert_eq!(log.stop(DAY + 6000, ""), Err(SessionError::NoneOpen));
+
+        assert_eq!(log.to_string(), format!(
+            "{}\t{}\t#report\tintro\n{}\t{}\tEmail\tinbox zero\n", DAY + 3600, DAY + 5400, DAY + 5400, DAY + 6000));
+        assert_eq!(log.to_string().parse::<SessionLog>().unwrap(), log);
+        assert_eq!(log.minutes_on("#report", CivilDate::new(2021, 1, 8), DAY + 6000, 0), 30);
+        assert_eq!(log.tasks_on(CivilDate::new(2021, 1, 8), DAY + 6000, 0), vec!["#report", "Email"]);
+    }
+
+    #[test]
+    fn test_overlaps_and_dangling() {
+        let log: SessionLog = format!("\
+{0}\t{1}\tA\t
+{2}\t{3}\tB\t
+{4}\t-\tC\t
+", DAY - 600, DAY + 1200, DAY + 600, DAY + 900, DAY + 1800).parse().unwrap();
+        assert_eq!(log.overlaps(DAY + 7200), vec![(0, 1)]);
+        assert_eq!(log.dangling(CivilDate::new(2021, 1, 8), 0), Vec::<usize>::new());
+        assert_eq!(log.dangling(CivilDate::new(2021, 1, 9), 0), vec![2]);
+
+        // only the part of a session within the day counts
+        assert_eq!(log.minutes_on("A", CivilDate::new(2021, 1, 8), DAY, 0), 20);
+        assert_eq!(log.minutes_on("A", CivilDate::new(2021, 1, 7), DAY, 0), 10);
+        assert_eq!(log.minutes_on("C", CivilDate::new(2021, 1, 8), DAY + 3600, 0), 30);
+        // an hour ahead of UTC, all of A falls on the 8th
+        assert_eq!(log.minutes_on("A", CivilDate::new(2021, 1, 8), DAY, 3600), 30);
+        assert_eq!(log.minutes_on("A", CivilDate::new(2021, 1, 7), DAY, 3600), 0);
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert!(matches!("x\t-\tA".parse::<SessionLog>(), Err(SessionError::InvalidLine { line: 0, .. })));
+        assert!(matches!("\n10\t5\tA".parse::<SessionLog>(), Err(SessionError::InvalidLine { line: 1, .. })));
+        assert!(matches!("10\t-\t".parse::<SessionLog>(), Err(SessionError::InvalidLine { .. })));
+        assert!(matches!("10 - A".parse::<SessionLog>(), Err(SessionError::InvalidLine { .. })));
+    }
+
+    #[test]
+    fn test_blocks() {
+        assert_eq!(blocks(0, 25), 0);
+        assert_eq!(blocks(12, 25), 0);
+        assert_eq!(blocks(13, 25), 1);
+        assert_eq!(blocks(60, 30), 2);
+        assert_eq!(blocks(-5, 30), 0);
+
+        assert_eq!(with_blocks(Some(Stat::from_count(Some(1), Some(4))), 3), Stat::from_count(Some(3), Some(4)));
+        assert_eq!(with_blocks(Some(Stat::from_bool(false, true)), 1), Stat::from_bool(true, true));
+        assert_eq!(with_blocks(None, 2), Stat::from_count(Some(2), None));
+    }
+}
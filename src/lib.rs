@@ -0,0 +1,13 @@
+#[macro_use] extern crate lazy_static;
+#[macro_use] extern crate bitflags;
+extern crate itertools;
+extern crate regex;
+
+pub mod utils;
+pub mod ttm_io;
+pub mod ttm_core;
+pub mod database;
+pub mod config;
+pub mod ttm_cli;
+#[cfg(feature = "lsp")]
+pub mod ttm_lsp;
@@ -1,13 +1,16 @@
-#[macro_use] extern crate lazy_static;
-#[macro_use] extern crate bitflags;
-extern crate itertools;
-extern crate regex;
-
-pub mod utils;
-pub mod ttm_io;
+use ttm::ttm_cli;
 
 
 fn main() {
-    println!("Hello world ...OK!");
-    println!("System all setup. Welcome!");
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.is_empty() {
+        println!("Hello world ...OK!");
+        println!("System all setup. Welcome!");
+        return;
+    }
+
+    if let Err(e) = ttm_cli::run(&args) {
+        eprintln!("ttm: {}", e);
+        std::process::exit(1);
+    }
 }
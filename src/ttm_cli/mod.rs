@@ -0,0 +1,1109 @@
+//! command line interface of ttm. Each command writes its output to stdout. The commands that write a
+//! document, which are export, import, migrate, and deps, ids and session blocks given a file, write
+//! it to the file given with -o <out> instead. Tasks edited in a file are written again on their own
+//! lines, and the rest of the file is kept as it was written. Preferences are read from the ttm.toml
+//! found from the working directory, if there is one. Refer to `crate::config`.
+//! The commands given [<file>] or [<file>...] work on the whole workspace when no file is given: the
+//! TTM files under the directory of ttm.toml, or under the working directory, as matched by its
+//! [workspace] table. Edits are written back to the files they were made in, and tasks are listed
+//! along with their file. Refer to `crate::ttm_core::workspace`.
+//!     ttm export --json <file>     writes the TTM document in <file> as JSON
+//!     ttm export --ics <file>      writes the deadlines of the tasks in <file> as an iCalendar file.
+//!                                  Short date codes are taken to be in the current season.
+//!     ttm export --markdown <file> writes the TTM document in <file> as a Markdown report
+//!     ttm export --html <file>     writes the TTM document in <file> as an HTML report
+//!     ttm import --json <file>     writes the JSON document in <file> as a TTM document
+//!     ttm import --todotxt <file>  writes the todo.txt file <file> as a TTM document
+//!     ttm import --org <file>      writes the Org-mode file <file> as a TTM document. Anything the
+//!                                  importers cannot represent is reported to stderr.
+//!     ttm links [<file>]           reports the note links in <file> that match no note. The files of
+//!                                  the workspace are notes too.
+//!     ttm backlinks <note> <file>  lists the tasks in <file> that link into <note>
+//!     ttm open <file> <task>       opens the note linked by <task> in $VISUAL or $EDITOR. The task is
+//!                                  given by its name, or as #Id.
+//! The note commands take the notes directory as --notes <dir>. Refer to `notes`.
+//!     ttm goals [<file>...]        sums the goal stats of the tasks in every <file>
+//!     ttm goals --behind [<file>...]
+//!                                  lists the goals short of their expected totals, furthest behind first
+//!     ttm goals --tree [<file>]    writes the task trees in <file> with their goals rolled up from
+//!                                  their subtasks. Refer to `crate::ttm_core::goals`.
+//!     ttm habits <file>...         writes the streaks and completion rates of the block tracker entries
+//!                                  in the files, one week per file, oldest first
+//!     ttm chart <entry> <file>...  draws a heatmap of the blocks of a block tracker entry, along with a
+//!                                  sparkline of its weekly totals, one week per file. With --ascii,
+//!                                  only ASCII characters are drawn.
+//!     ttm forecast <file>...       forecasts when the tasks of the last file will be done, from their
+//!                                  progress in the files before it. Each file is the file of a day,
+//!                                  named by its date as YYYY-MM-DD.ttm, oldest first. Refer to
+//!                                  `crate::ttm_core::forecast`.
+//!     ttm forecast --burndown <task> <file>...
+//!                                  draws the burndown chart of <task>
+//!     ttm migrate <file>           rewrites the tasks of <file> written in the format used before
+//!                                  07-Jan-21 in the current format. Refer to `crate::ttm_io::legacy`.
+//!     ttm migrate --in-place <file>...
+//!                                  migrates every <file>, writing each back to itself
+//!     ttm fmt [--check] [--dates] [<file>...]
+//!                                  formats every <file> canonically, writing each back to itself. With
+//!                                  --dates, date codes are canonicalized too. With --check, no file is
+//!                                  written, and the files that are not formatted are reported with a
+//!                                  diff, failing the command. A file that would not parse back into
+//!                                  the same document is not written. Refer to `crate::ttm_io::format`.
+//!     ttm lint [--enable <rule>]... [--disable <rule>]... [<file>...]
+//!                                  reports the parse errors of every <file>, along with the problems
+//!                                  found by the lint rules, failing if there are any. Every rule is
+//!                                  enabled unless disabled. Refer to `crate::ttm_core::lint`.
+//!     ttm deps [<file>]            writes <file> with the BLOCKED flag of every task with dependencies
+//!                                  set while any of them is not done, and cleared otherwise. References
+//!                                  that name no task and dependency cycles are reported to stderr.
+//!                                  Refer to `crate::ttm_core::deps`.
+//!     ttm ready [<file>]           lists the tasks in <file> that can be started, in dependency order
+//!     ttm ids [<file>]             writes <file> with a generated id given to every task without one.
+//!                                  Ids given to more than one task are reported to stderr. Refer to
+//!                                  `crate::ttm_io::ids`.
+//!     ttm query <term>... [<file>] lists the tasks in <file> matching every term, such as +tag, @context
+//!                                  or is:done. Refer to `crate::ttm_core::query`.
+//!     ttm watch [--debounce <ms>] [--once]
+//!                                  watches the files of the workspace, writing their diagnostics and
+//!                                  the agenda whenever they change, with context stats, LATE and
+//!                                  BLOCKED flags recomputed. Changes are taken once the files have been
+//!                                  left alone for <ms> milliseconds, 300 by default. With --once, the
+//!                                  workspace is reported as it is. Refer to `crate::ttm_core::watch`.
+//!     ttm tags <file>...           writes the blocks spent on each tag per week, from the files of days
+//!                                  named as for forecast. Refer to `crate::ttm_core::tags`.
+//!     ttm timer start <file> <task> [--minutes <n>]
+//!                                  starts a block of <n> minutes, 25 by default, on <task>. Once the
+//!                                  block is over, the day stat of the task and its block tracker entry
+//!                                  are ticked in <file>. Refer to `timer`.
+//!     ttm timer status             writes the time left in the block, ticking it if it is over
+//!     ttm timer wait               waits until the block is over, then ticks it
+//!     ttm timer cancel             stops the timer without ticking the block
+//!     ttm session start <file> <task> [--note <note>]
+//!                                  logs the start of a session on <task>, making it the CURRENT task
+//!                                  of <file>. Refer to `sessions`.
+//!     ttm session stop <file> [--note <note>]
+//!                                  logs the end of the open session, clearing the CURRENT flag
+//!     ttm session switch <file> <task> [--note <note>]
+//!                                  stops the open session and starts one on <task>
+//!     ttm session check            reports overlapping sessions, and sessions left open since a day
+//!                                  before today
+//!     ttm session blocks <file> [--minutes <n>]
+//!                                  writes <file> with the day stat of every task logged today set to
+//!                                  the blocks of <n> minutes, 25 by default, spent on it
+
+mod notes;
+mod sessions;
+mod timer;
+
+use crate::config::{self, Config};
+use crate::ttm_core::deps::{self, DepGraph};
+use crate::ttm_core::lint::{self, Rule};
+use crate::ttm_core::query::Query;
+use crate::ttm_core::timer::SystemClock;
+use crate::ttm_core::watch::{self, Change, WatchError, Watcher};
+use crate::ttm_core::workspace::{Workspace, WorkspaceError};
+use crate::ttm_core::{forecast, goals, habits, tags};
+use crate::ttm_io::calendar::CivilDate;
+use crate::ttm_io::date::Date;
+use crate::ttm_io::document::Document;
+use crate::ttm_io::ics::{self, IcsOptions};
+use crate::ttm_io::import::Import;
+use crate::ttm_io::charts::{self, DaySeries, Glyphs};
+use crate::ttm_io::format::FormatOptions;
+use crate::ttm_io::ids::{self, IdGenerator, TaskIndex};
+use crate::ttm_io::{edit, format, legacy, org, report, todotxt};
+use crate::utils::diff;
+use std::borrow::Cow;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::io::Write;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+const USAGE: &str = "\
+usage: ttm <command> [<args>]
+export, import, migrate, and deps, ids and session blocks given a file take -o <out> to write to <out>
+preferences are read from ttm.toml, in the working directory, a parent of it or $XDG_CONFIG_HOME/ttm
+without a file, [<file>] commands work on the TTM files of the workspace, writing edits back to them
+    export --json <file>     writes the TTM document in <file> as JSON
+    export --ics <file>      writes the deadlines of the tasks in <file> as an iCalendar file
+    export --markdown <file> writes the TTM document in <file> as a Markdown report
+    export --html <file>     writes the TTM document in <file> as an HTML report
+    import --json <file>     writes the JSON document in <file> as a TTM document
+    import --todotxt <file>  writes the todo.txt file <file> as a TTM document
+    import --org <file>      writes the Org-mode file <file> as a TTM document
+    links [<file>]           reports the note links in <file> that match no note
+    backlinks <note> <file>  lists the tasks in <file> that link into <note>
+    open <file> <task>       opens the note linked by <task> in $VISUAL or $EDITOR
+the note commands take --notes <dir>, or the TTM_NOTES environment variable
+    goals [<file>...]        sums the goal stats of the tasks in every <file>
+    goals --behind [<file>...]
+                             lists the goals short of their expected totals
+    goals --tree [<file>]    writes the task trees in <file> with their goals rolled up
+    habits <file>...         writes the habit statistics of the block trackers, one week per file
+    chart [--ascii] <entry> <file>...
+                             draws a heatmap and weekly sparkline of <entry>, one week per file
+    forecast [--burndown <task>] <file>...
+                             forecasts when the tasks of the last file will be done, one day per
+                             file, each named by its date as YYYY-MM-DD.ttm
+    migrate <file>           rewrites the legacy tasks of <file> in the current format
+    migrate --in-place <file>...
+                             migrates every <file>, writing each back to itself
+    fmt [--check] [--dates] [<file>...]
+                             formats every <file> in place, with --dates canonicalizing date codes.
+                             With --check, writes a diff of the files that are not formatted
+    lint [--enable <rule>]... [--disable <rule>]... [<file>...]
+                             reports the parse errors and lint problems of every <file>
+    deps [<file>]            sets the BLOCKED flag of the tasks in <file> from their dependencies
+    ready [<file>]           lists the tasks in <file> that can be started, in dependency order
+    ids [<file>]             gives every task in <file> without an id a generated one
+    query <term>... [<file>] lists the tasks in <file> matching every term, such as +tag or is:done
+    watch [--debounce <ms>] [--once]
+                             reports the diagnostics and agenda of the workspace whenever it changes
+    tags <file>...           writes the blocks spent on each tag per week, one day per file, each
+                             named by its date as YYYY-MM-DD.ttm
+    timer start <file> <task> [--minutes <n>]
+                             starts a block on <task>, ticking its day stat in <file> once over
+    timer status | wait | cancel
+                             writes the time left, waits for the block to be over, or stops the
+                             timer, kept in $TTM_TIMER or ~/.ttm_timer
+    session start | switch <file> <task> [--note <note>]
+                             logs a session on <task>, making it the CURRENT task of <file>
+    session stop <file> [--note <note>]
+                             logs the end of the open session
+    session check            reports overlapping sessions and sessions left open from other days
+    session blocks <file> [--minutes <n>]
+                             sets the day stats of <file> from the time logged today, kept in
+                             $TTM_SESSIONS or ~/.ttm_sessions";
+
+#[derive(Debug)]
+pub enum CliError {
+    /// the command line could not be understood. Holds what was wrong with it.
+    Usage(Cow<'static, str>),
+    Io { path: String, err: std::io::Error },
+    /// the input of a command is not valid
+    InvalidInput(Cow<'static, str>),
+    /// the command is not supported by this build
+    Unsupported(Cow<'static, str>),
+}
+
+impl fmt::Display for CliError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::result::Result<(), std::fmt::Error> {
+        let msg: Cow<'static, str> = match self {
+            Self::Usage(message) => format!("{}\n{}", message, USAGE).into(),
+            Self::Io { path, err } => format!("{}: {}", path, err).into(),
+            Self::InvalidInput(message) => message.clone(),
+            Self::Unsupported(message) => message.clone(),
+        };
+        write!(f, "{}", msg)?;
+        Ok(())
+    }
+}
+
+/// runs the command in `args`, which exclude the program name
+pub fn run(args: &[String]) -> Result<(), CliError> {
+    let out = run_with(args, &load_config()?)?;
+    print!("{}", out);
+    Ok(())
+}
+
+/// runs the command in `args` with the preferences of `config`, giving what it writes to stdout
+fn run_with(args: &[String], config: &Config) -> Result<String, CliError> {
+    let notes_dir = env_var(notes::NOTES_ENV).or_else(|| path_string(&config.paths.notes));
+    Ok(match args.first().map(|arg| arg.as_str()) {
+        Some("export") => export(&args[1..], config)?,
+        Some("import") => import(&args[1..], config)?,
+        Some("links") => notes::links(&args[1..], config, notes_dir.as_deref())?,
+        Some("backlinks") => notes::backlinks(&args[1..], config, notes_dir.as_deref())?,
+        Some("open") => {
+            let editor = env_var("VISUAL").or_else(|| env_var("EDITOR")).unwrap_or_else(|| "vi".to_string());
+            notes::open(&args[1..], config, notes_dir.as_deref(), &editor)?
+        }
+        Some("goals") => goals(&args[1..], config)?,
+        Some("habits") => habits(&args[1..], config)?,
+        Some("chart") => chart(&args[1..], config, CivilDate::today())?,
+        Some("forecast") => forecast(&args[1..], config)?,
+        Some("migrate") => migrate(&args[1..])?,
+        Some("fmt") => fmt(&args[1..], config)?,
+        Some("lint") => lint(&args[1..], config, CivilDate::today())?,
+        Some("deps") => deps(&args[1..], config)?,
+        Some("ready") => ready(&args[1..], config)?,
+        Some("ids") => assign_ids(&args[1..], config, now() as u64)?,
+        Some("query") => query(&args[1..], config)?,
+        Some("watch") => watch(&args[1..], config)?,
+        Some("tags") => tags(&args[1..], config)?,
+        Some("timer") => {
+            let env_path = env_var(timer::TIMER_ENV).or_else(|| path_string(&config.paths.timer));
+            let state = timer::state_path(env_path.as_deref(), env_var("HOME").as_deref());
+            timer::timer(&args[1..], config, &state, &SystemClock)?
+        }
+        Some("session") => {
+            let env_path = env_var(sessions::SESSIONS_ENV).or_else(|| path_string(&config.paths.sessions));
+            let log = sessions::log_path(env_path.as_deref(), env_var("HOME").as_deref());
+            sessions::session(&args[1..], config, &log, now())?
+        }
+        Some(command) => return Err(CliError::Usage(format!("unknown command '{}'", command).into())),
+        None => return Err(CliError::Usage("no command given".into())),
+    })
+}
+
+/// the config discovered from the working directory, or the default config if there is none.
+/// Refer to `crate::config::discover`.
+fn load_config() -> Result<Config, CliError> {
+    let cwd = std::env::current_dir().unwrap_or_default();
+    let path = match config::discover(&cwd, env_var("XDG_CONFIG_HOME").as_deref(), env_var("HOME").as_deref()) {
+        Some(path) => path,
+        None => return Ok(Config::default()),
+    };
+    let mut config: Config = read_file(&path.to_string_lossy())?.parse()
+        .map_err(|e| CliError::InvalidInput(format!("{}: {}", path.display(), e).into()))?;
+    let dir = path.parent().unwrap_or_else(|| Path::new("."));
+    // a config of a directory the working directory is in makes that directory the workspace
+    if config.workspace.root.is_none() && cwd.starts_with(dir) {
+        config.workspace.root = Some(dir.to_path_buf());
+    }
+    Ok(config.relative_to(dir))
+}
+
+/// the workspace of `config`, failing on the first line of its files that does not parse. Refer to
+/// `crate::ttm_core::workspace`.
+fn load_workspace(config: &Config) -> Result<Workspace, CliError> {
+    let workspace = Workspace::load(&workspace_root(config), config).map_err(workspace_error)?;
+    if let Some((path, err)) = workspace.errors().first() {
+        return Err(CliError::InvalidInput(format!("{}: {}", path.display(), err).into()));
+    }
+    Ok(workspace)
+}
+
+/// the paths of the files of the workspace of `config`
+fn workspace_paths(config: &Config) -> Result<Vec<String>, CliError> {
+    Ok(Workspace::find_files(&workspace_root(config), &config.workspace).map_err(workspace_error)?
+        .iter()
+        .map(|path| path.to_string_lossy().into_owned())
+        .collect())
+}
+
+/// the root of the workspace of `config`, or the working directory if it has none
+fn workspace_root(config: &Config) -> PathBuf {
+    config.workspace.root.clone().unwrap_or_else(|| std::env::current_dir().unwrap_or_default())
+}
+
+fn workspace_error(err: WorkspaceError) -> CliError {
+    match err {
+        WorkspaceError::Io { path, err } => CliError::Io { path: path.display().to_string(), err },
+    }
+}
+
+/// writes the files edited in `workspace` back, reporting each as `done`
+fn write_workspace(workspace: &Workspace, done: &str) -> Result<String, CliError> {
+    Ok(workspace.write_edited().map_err(workspace_error)?.iter()
+        .map(|path| format!("{}: {}\n", path.display(), done))
+        .collect())
+}
+
+fn path_string(path: &Option<PathBuf>) -> Option<String> {
+    path.as_ref().map(|path| path.to_string_lossy().into_owned())
+}
+
+/// the value of the environment variable `name`, if it is set and not empty
+fn env_var(name: &str) -> Option<String> {
+    std::env::var(name).ok().filter(|value| !value.is_empty())
+}
+
+/// the arguments of a command that converts a file from or to a format
+#[derive(Debug, PartialEq)]
+struct ConvertArgs<'a> {
+    /// the format flag, like --json
+    format: &'a str,
+    path: &'a str,
+    /// where to write the output to, instead of stdout
+    out: Option<&'a str>,
+}
+
+/// parses `<format> <file> [-o <out>]`, where the format is one of `formats`
+fn convert_args<'a>(command: &str, args: &'a [String], formats: &[&str]) -> Result<ConvertArgs<'a>, CliError> {
+    let expected = || CliError::Usage(
+        format!("{} expects {} <file> [-o <out>]", command, formats.join(" | ")).into());
+
+    let (args, out) = match args {
+        [rest @ .., flag, out] if flag == "-o" => (rest, Some(out.as_str())),
+        _ => (args, None),
+    };
+    match args {
+        [format, path] if formats.contains(&format.as_str()) => Ok(ConvertArgs { format, path, out }),
+        [format, _] if format.starts_with("--") => Err(CliError::Usage(
+            format!("{} only supports {}", command, formats.join(", ")).into())),
+        _ => Err(expected()),
+    }
+}
+
+fn read_file(path: &str) -> Result<String, CliError> {
+    std::fs::read_to_string(path).map_err(|err| CliError::Io { path: path.to_string(), err })
+}
+
+/// writes `s` to `out` if it is given. Otherwise, `s` is given back to be written to stdout.
+fn write_output(out: Option<&str>, s: String) -> Result<String, CliError> {
+    match out {
+        Some(path) => {
+            std::fs::write(path, s).map_err(|err| CliError::Io { path: path.to_string(), err })?;
+            Ok(String::new())
+        }
+        None => Ok(s),
+    }
+}
+
+/// a file ttm keeps its own state in: the path in an environment variable, `env_path`, if it is
+/// set, or else `name` in the home directory
+fn state_file(env_path: Option<&str>, home: Option<&str>, name: &str) -> PathBuf {
+    match (env_path, home) {
+        (Some(path), _) => PathBuf::from(path),
+        (None, Some(home)) => Path::new(home).join(name),
+        (None, None) => PathBuf::from(name),
+    }
+}
+
+/// parses the document in `s` as `config` has it, failing on the first line that does not parse
+fn parse_document(config: &Config, path: &str, s: &str) -> Result<Document, CliError> {
+    let (document, mut errors) = config.parse_document(s);
+    if !errors.is_empty() {
+        return Err(CliError::InvalidInput(format!("{}: {}", path, errors.remove(0)).into()));
+    }
+    Ok(document)
+}
+
+/// `text`, the text of `path`, with its tasks edited into those of `edited`. Each edited task is
+/// written again on its own line, or the whole of `edited` if tasks were added or removed. Refer to
+/// `crate::ttm_io::edit::set_tasks`.
+fn edited_text(config: &Config, path: &str, text: &str, edited: &Document) -> Result<String, CliError> {
+    let loaded = parse_document(config, path, text)?;
+    Ok(edit::set_tasks(text, &loaded.walk_trees(), &edited.walk_trees(), &config.flag_aliases)
+        .unwrap_or_else(|| edited.to_string()))
+}
+
+/// seconds since 1970-01-01 UTC
+fn now() -> i64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs() as i64)
+}
+
+fn export(args: &[String], config: &Config) -> Result<String, CliError> {
+    let args = convert_args("export", args, &["--json", "--ics", "--markdown", "--html"])?;
+    let document = parse_document(config, args.path, &read_file(args.path)?)?;
+    let out = match args.format {
+        "--json" => export_json(&document)?,
+        "--ics" => export_ics(&document, config, now())?,
+        "--markdown" => report::to_markdown(&document),
+        _ => {
+            let title = Path::new(args.path).file_stem()
+                .map_or(args.path.into(), |stem| stem.to_string_lossy());
+            report::to_html(&document, &title)
+        }
+    };
+    write_output(args.out, out)
+}
+
+/// writes the calendar with short date codes taken to be in the season of `timestamp`
+fn export_ics(document: &Document, config: &Config, timestamp: i64) -> Result<String, CliError> {
+    let calendar = &config.calendar;
+    let today = CivilDate::from_days(timestamp.div_euclid(86400));
+    let (year, season) = match calendar.from_civil(today) {
+        Ok(Date::DateCode { year, season, .. }) => (year, season),
+        _ => return Err(CliError::InvalidInput(format!("no season holds today, {}", today).into())),
+    };
+    Ok(ics::to_ics(document, &IcsOptions { calendar, year, season, timestamp }))
+}
+
+#[cfg(feature = "serde")]
+fn export_json(document: &Document) -> Result<String, CliError> {
+    Ok(format!("{}\n", crate::ttm_io::json::to_json(document)))
+}
+
+fn import(args: &[String], config: &Config) -> Result<String, CliError> {
+    let args = convert_args("import", args, &["--json", "--todotxt", "--org"])?;
+    let s = read_file(args.path)?;
+    let document = match args.format {
+        "--json" => import_json(args.path, &s)?,
+        format => {
+            let Import { document, warnings } = match format {
+                "--todotxt" => todotxt::import(&s, &config.calendar),
+                _ => org::import(&s, &config.calendar),
+            };
+            for warning in warnings.iter() {
+                eprintln!("ttm: {}: {}", args.path, warning);
+            }
+            document
+        }
+    };
+    write_output(args.out, document.to_string())
+}
+
+#[cfg(feature = "serde")]
+fn import_json(path: &str, s: &str) -> Result<Document, CliError> {
+    crate::ttm_io::json::from_json(s).map_err(|e| CliError::InvalidInput(format!("{}: {}", path, e).into()))
+}
+
+#[cfg(not(feature = "serde"))]
+fn export_json(_document: &Document) -> Result<String, CliError> {
+    Err(CliError::Unsupported("JSON export requires ttm to be built with the `serde` feature".into()))
+}
+
+#[cfg(not(feature = "serde"))]
+fn import_json(_path: &str, _s: &str) -> Result<Document, CliError> {
+    Err(CliError::Unsupported("JSON import requires ttm to be built with the `serde` feature".into()))
+}
+
+fn goals(args: &[String], config: &Config) -> Result<String, CliError> {
+    let (mode, paths) = match args {
+        [flag, paths @ ..] if flag == "--behind" || flag == "--tree" => (flag.as_str(), paths),
+        paths => ("", paths),
+    };
+    if mode == "--tree" && paths.len() > 1 {
+        return Err(CliError::Usage("goals expects [--behind] [<file>...] or --tree [<file>]".into()));
+    }
+
+    let documents = match paths {
+        [] => vec![load_workspace(config)?.into_document()],
+        paths => paths.iter()
+            .map(|path| parse_document(config, path, &read_file(path)?))
+            .collect::<Result<Vec<Document>, CliError>>()?,
+    };
+    let totals = goals::document_goals(&documents);
+    Ok(match mode {
+        "--behind" => goals::report(goals::behind_target(&totals)),
+        "--tree" => goals::tree_report(&documents[0]),
+        _ => goals::report(totals.iter().map(|(goal, total)| (goal.as_str(), total))),
+    })
+}
+
+fn habits(paths: &[String], config: &Config) -> Result<String, CliError> {
+    if paths.is_empty() {
+        return Err(CliError::Usage("habits expects <file>...".into()));
+    }
+    let documents = paths.iter()
+        .map(|path| parse_document(config, path, &read_file(path)?))
+        .collect::<Result<Vec<Document>, CliError>>()?;
+    let weeks: Vec<_> = documents.iter().map(|document| document.block_tracker_entries()).collect();
+    Ok(habits::report(&habits::history_stats(&weeks)))
+}
+
+/// charts the weeks in the files as the weeks up to the one holding `today`
+fn chart(args: &[String], config: &Config, today: CivilDate) -> Result<String, CliError> {
+    let (glyphs, args) = match args {
+        [flag, rest @ ..] if flag == "--ascii" => (Glyphs::Ascii, rest),
+        _ => (Glyphs::Unicode, args),
+    };
+    let (entry, paths) = match args {
+        [entry, paths @ ..] if !paths.is_empty() => (entry, paths),
+        _ => return Err(CliError::Usage("chart expects [--ascii] <entry> <file>...".into())),
+    };
+    let documents = paths.iter()
+        .map(|path| parse_document(config, path, &read_file(path)?))
+        .collect::<Result<Vec<Document>, CliError>>()?;
+
+    let weeks: Vec<_> = documents.iter()
+        .map(|document| document.block_tracker_entries().into_iter().find(|e| e.entry_name() == entry.as_str()))
+        .collect();
+    if weeks.iter().all(Option::is_none) {
+        return Err(CliError::InvalidInput(format!("no block tracker entry named '{}'", entry).into()));
+    }
+    let monday = today.to_days() - today.weekday().index() as i64 - 7 * (weeks.len() as i64 - 1);
+    let series = DaySeries::from_entry_weeks(CivilDate::from_days(monday), weeks);
+
+    let totals = series.weekly_totals();
+    let totals_text: Vec<String> = totals.iter().map(|total| total.to_string()).collect();
+    Ok(format!("{}  {} {}\n", charts::heatmap(&series, glyphs), charts::sparkline(&totals, glyphs), totals_text.join(" ")))
+}
+
+fn forecast(args: &[String], config: &Config) -> Result<String, CliError> {
+    let (burndown, paths) = match args {
+        [flag, task, paths @ ..] if flag == "--burndown" => (Some(task.as_str()), paths),
+        [flag, ..] if flag == "--burndown" => (None, &[][..]),
+        paths => (None, paths),
+    };
+    if paths.is_empty() {
+        return Err(CliError::Usage("forecast expects [--burndown <task>] <file>...".into()));
+    }
+
+    let days = read_days(paths, config)?;
+    let forecasts = forecast::forecast_documents(&days, &config.calendar);
+    match burndown {
+        Some(name) => forecasts.iter()
+            .find(|forecast| forecast.name == name)
+            .map(|forecast| forecast::burndown(forecast, 40))
+            .ok_or_else(|| CliError::InvalidInput(format!("no task named '{}' with an objective and a due date", name).into())),
+        None => {
+            let width = forecasts.iter().map(|forecast| forecast.name.chars().count()).max().unwrap_or(0);
+            Ok(forecasts.iter()
+                .map(|forecast| format!("{:width$}  {}\n", forecast.name, forecast, width = width))
+                .collect())
+        }
+    }
+}
+
+/// reads the files of days, each named by its date as YYYY-MM-DD.ttm, oldest first
+fn read_days(paths: &[String], config: &Config) -> Result<Vec<(CivilDate, Document)>, CliError> {
+    let mut days: Vec<(CivilDate, Document)> = vec![];
+    for path in paths.iter() {
+        let stem = Path::new(path).file_stem().map_or("".into(), |stem| stem.to_string_lossy());
+        let date: CivilDate = stem.parse()
+            .map_err(|e| CliError::InvalidInput(format!("{}: not named by its date: {}", path, e).into()))?;
+        days.push((date, parse_document(config, path, &read_file(path)?)?));
+    }
+    days.sort_by_key(|(date, _document)| *date);
+    Ok(days)
+}
+
+fn migrate(args: &[String]) -> Result<String, CliError> {
+    let paths = match args {
+        [flag, paths @ ..] if flag == "--in-place" && !paths.is_empty() => paths,
+        [path] if !path.starts_with("--") => return Ok(migrate_file(path)?.text),
+        [path, flag, out] if flag == "-o" => return write_output(Some(out), migrate_file(path)?.text),
+        _ => return Err(CliError::Usage("migrate expects <file> [-o <out>] or --in-place <file>...".into())),
+    };
+
+    let mut out = String::new();
+    for path in paths.iter() {
+        let migration = migrate_file(path)?;
+        if migration.migrated > 0 {
+            std::fs::write(path, &migration.text).map_err(|err| CliError::Io { path: path.to_string(), err })?;
+        }
+        out.push_str(&format!("{}: migrated {} tasks\n", path, migration.migrated));
+    }
+    Ok(out)
+}
+
+/// migrates the file at `path`, reporting the tasks that could not be migrated to stderr
+fn migrate_file(path: &str) -> Result<legacy::Migration, CliError> {
+    let migration = legacy::migrate(&read_file(path)?);
+    for warning in migration.warnings.iter() {
+        eprintln!("ttm: {}: {}", path, warning);
+    }
+    Ok(migration)
+}
+
+fn fmt(args: &[String], config: &Config) -> Result<String, CliError> {
+    let check = args.iter().any(|arg| arg == "--check");
+    let options = FormatOptions { canonical_dates: args.iter().any(|arg| arg == "--dates") };
+    let mut paths: Vec<String> = args.iter().filter(|arg| *arg != "--check" && *arg != "--dates").cloned().collect();
+    if paths.iter().any(|path| path.starts_with("--")) {
+        return Err(CliError::Usage("fmt expects [--check] [--dates] [<file>...]".into()));
+    }
+    if paths.is_empty() {
+        paths = workspace_paths(config)?;
+    }
+
+    let mut diffs = String::new();
+    for path in paths.iter() {
+        let text = read_file(path)?;
+        let formatted = format::format(&text, config, &options)
+            .map_err(|e| CliError::InvalidInput(format!("{}: {}", path, e).into()))?;
+        if formatted == text {
+            continue;
+        }
+        if check {
+            diffs.push_str(&format!("--- {0}\n+++ {0} (formatted)\n{1}", path, diff::unified_diff(&text, &formatted, 3)));
+        } else {
+            std::fs::write(path, &formatted).map_err(|err| CliError::Io { path: path.to_string(), err })?;
+        }
+    }
+    if !diffs.is_empty() {
+        return Err(CliError::InvalidInput(format!("files are not formatted\n{}", diffs.trim_end()).into()));
+    }
+    Ok(String::new())
+}
+
+/// lints files, taking short date codes to be in the season of `today`
+fn lint(args: &[String], config: &Config, today: CivilDate) -> Result<String, CliError> {
+    let mut config = config.clone();
+    let rules = &mut config.lint;
+    let mut paths: Vec<String> = vec![];
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            flag @ ("--enable" | "--disable") => {
+                let rule: Rule = args.next()
+                    .ok_or_else(|| CliError::Usage(format!("{} expects <rule>", flag).into()))?
+                    .parse()
+                    .map_err(|e: String| CliError::Usage(e.into()))?;
+                if flag == "--enable" { rules.enable(rule) } else { rules.disable(rule) };
+            }
+            path if !path.starts_with("--") => paths.push(path.to_string()),
+            _ => return Err(CliError::Usage("lint expects [--enable <rule>]... [--disable <rule>]... [<file>...]".into())),
+        }
+    }
+    if paths.is_empty() {
+        paths = workspace_paths(&config)?;
+    }
+
+    let reference = match config.calendar.from_civil(today) {
+        Ok(Date::DateCode { year, season, .. }) => (year, season),
+        _ => return Err(CliError::InvalidInput(format!("{} is in no season", today).into())),
+    };
+    let mut found: Vec<String> = vec![];
+    for path in paths.iter() {
+        let text = read_file(path)?;
+        found.extend(lint::lint(&text, &config, reference).iter().map(|diagnostic| format!("{}:{}", path, diagnostic)));
+    }
+    if found.is_empty() {
+        return Ok(String::new());
+    }
+    Err(CliError::InvalidInput(format!("{} problems found\n{}", found.len(), found.join("\n")).into()))
+}
+
+fn deps(args: &[String], config: &Config) -> Result<String, CliError> {
+    let (path, out) = match args {
+        [] => {
+            let mut workspace = load_workspace(config)?;
+            for error in deps::evaluate(workspace.document_mut()).errors.iter() {
+                eprintln!("ttm: workspace: {}", error);
+            }
+            return write_workspace(&workspace, "dependencies evaluated");
+        }
+        [path] => (path, None),
+        [path, flag, out] if flag == "-o" => (path, Some(out.as_str())),
+        _ => return Err(CliError::Usage("deps expects [<file> [-o <out>]]".into())),
+    };
+    let text = read_file(path)?;
+    let mut document = parse_document(config, path, &text)?;
+    let evaluation = deps::evaluate(&mut document);
+    for error in evaluation.errors.iter() {
+        eprintln!("ttm: {}: {}", path, error);
+    }
+    write_output(out, edited_text(config, path, &text, &document)?)
+}
+
+fn ready(args: &[String], config: &Config) -> Result<String, CliError> {
+    let (workspace, document);
+    let (document, files, label) = match args {
+        [] => {
+            workspace = load_workspace(config)?;
+            let files: Vec<&Path> = workspace.tasks().iter().map(|(path, _task)| *path).collect();
+            (workspace.document(), Some(files), "workspace")
+        }
+        [path] => {
+            document = parse_document(config, path, &read_file(path)?)?;
+            (&document, None, path.as_str())
+        }
+        _ => return Err(CliError::Usage("ready expects [<file>]".into())),
+    };
+    let graph = DepGraph::new(document);
+    for error in graph.errors().iter() {
+        eprintln!("ttm: {}: {}", label, error);
+    }
+    Ok(graph.ready().into_iter()
+        .map(|idx| match &files {
+            // the path of a task of the workspace starts with the section of its file
+            Some(files) => format!("{}: {}\n", files[idx].display(), graph.nodes()[idx].path[1..].join("/")),
+            None => format!("{}\n", graph.nodes()[idx].path.join("/")),
+        })
+        .collect())
+}
+
+/// gives ids to the tasks of a file, or of the workspace, generated from `seed`
+fn assign_ids(args: &[String], config: &Config, seed: u64) -> Result<String, CliError> {
+    let (path, out) = match args {
+        [] => {
+            // ids are generated for the merged document, so they are unique across its files
+            let mut workspace = load_workspace(config)?;
+            for id in TaskIndex::new(workspace.document()).duplicates() {
+                eprintln!("ttm: workspace: the id #{} is given to more than one task", id);
+            }
+            let mut generator = IdGenerator::for_document(seed, workspace.document());
+            ids::assign_ids(workspace.document_mut(), &mut generator);
+            return write_workspace(&workspace, "ids given");
+        }
+        [path] => (path, None),
+        [path, flag, out] if flag == "-o" => (path, Some(out.as_str())),
+        _ => return Err(CliError::Usage("ids expects [<file> [-o <out>]]".into())),
+    };
+    let text = read_file(path)?;
+    let mut document = parse_document(config, path, &text)?;
+    for id in TaskIndex::new(&document).duplicates() {
+        eprintln!("ttm: {}: the id #{} is given to more than one task", path, id);
+    }
+    let mut generator = IdGenerator::for_document(seed, &document);
+    ids::assign_ids(&mut document, &mut generator);
+    write_output(out, edited_text(config, path, &text, &document)?)
+}
+
+/// queries the tasks of a file, given as the last argument if it names one, or of the workspace
+fn query(args: &[String], config: &Config) -> Result<String, CliError> {
+    // the last argument is taken as a file if it is one, or if it is named as one
+    let is_file = |path: &str| Path::new(path).is_file() || path.ends_with(".ttm");
+    let (path, terms) = match args.split_last() {
+        Some((path, terms)) if is_file(path) => (Some(path), terms),
+        _ => (None, args),
+    };
+    if terms.is_empty() {
+        return Err(CliError::Usage("query expects <term>... [<file>]".into()));
+    }
+    let query: Query = terms.join(" ").parse().map_err(|e| CliError::Usage(format!("invalid query: {}", e).into()))?;
+    match path {
+        Some(path) => {
+            let document = parse_document(config, path, &read_file(path)?)?;
+            Ok(query.filter(&document).iter().map(|task| format!("{}\n", task)).collect())
+        }
+        None => Ok(load_workspace(config)?.tasks().iter()
+            .filter(|(_path, task)| query.matches(task))
+            .map(|(path, task)| format!("{}: {}\n", path.display(), task))
+            .collect()),
+    }
+}
+
+/// how often `ttm watch` polls the files of the workspace
+const WATCH_INTERVAL: Duration = Duration::from_millis(100);
+
+/// watches the workspace, writing a report to stdout with every change. With --once, the report
+/// of the workspace as it is is given back instead.
+fn watch(args: &[String], config: &Config) -> Result<String, CliError> {
+    let usage = || CliError::Usage("watch expects [--debounce <ms>] [--once]".into());
+    let (mut debounce, mut once) = (watch::DEFAULT_DEBOUNCE, false);
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--once" => once = true,
+            "--debounce" => {
+                let millis: u64 = args.next().and_then(|millis| millis.parse().ok()).ok_or_else(usage)?;
+                debounce = Duration::from_millis(millis);
+            }
+            _ => return Err(usage()),
+        }
+    }
+
+    let mut watcher = Watcher::new(&workspace_root(config), config.clone(), debounce, CivilDate::today())
+        .map_err(watch_error)?;
+    let report = watcher.report(&Change::default());
+    if once {
+        return Ok(report);
+    }
+    print!("{}", report);
+    loop {
+        std::io::stdout().flush().map_err(|err| CliError::Io { path: "stdout".to_string(), err })?;
+        std::thread::sleep(WATCH_INTERVAL);
+        if let Some(change) = watcher.poll(Instant::now(), CivilDate::today()).map_err(watch_error)? {
+            print!("\n{}", watcher.report(&change));
+        }
+    }
+}
+
+fn watch_error(err: WatchError) -> CliError {
+    match err {
+        WatchError::Workspace(err) => workspace_error(err),
+        WatchError::NoSeason(message) => CliError::InvalidInput(message),
+    }
+}
+
+fn tags(paths: &[String], config: &Config) -> Result<String, CliError> {
+    if paths.is_empty() {
+        return Err(CliError::Usage("tags expects <file>...".into()));
+    }
+    Ok(tags::report(&tags::tag_time(&read_days(paths, config)?, &config.calendar)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    pub(crate) fn args(s: &str) -> Vec<String> {
+        s.split_whitespace().map(|arg| arg.to_string()).collect()
+    }
+
+    /// a directory unique to a test, removed along with its files once dropped
+    pub(crate) struct TempDir(PathBuf);
+
+    impl TempDir {
+        pub(crate) fn new(name: &str) -> TempDir {
+            let path = std::env::temp_dir().join(format!("ttm_cli_{}_{}", std::process::id(), name));
+            std::fs::create_dir_all(&path).unwrap();
+            TempDir(path)
+        }
+
+        /// writes `contents` to the file `name` in the directory, and gets its path
+        pub(crate) fn file(&self, name: &str, contents: &str) -> String {
+            let path = self.0.join(name);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(&path, contents).unwrap();
+            path.to_str().unwrap().to_string()
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+
+    /// writes the files of a workspace to a directory unique to the test, given as (path, contents),
+    /// and gets a config with the directory as its root
+    pub(crate) fn temp_workspace(name: &str, files: &[(&str, &str)]) -> Config {
+        let root = std::env::temp_dir().join(format!("ttm_cli_{}_{}", std::process::id(), name));
+        for (path, contents) in files.iter() {
+            let path = root.join(path);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(&path, contents).unwrap();
+        }
+        let mut config = Config::default();
+        config.workspace.root = Some(root);
+        config
+    }
+
+    #[test]
+    fn test_usage_errors() {
+        // preferences are not read from the working directory
+        let config = Config::default();
+        assert!(matches!(run_with(&args(""), &config), Err(CliError::Usage(_))));
+        assert!(matches!(run_with(&args("frobnicate"), &config), Err(CliError::Usage(_))));
+        assert!(matches!(run_with(&args("export"), &config), Err(CliError::Usage(_))));
+        assert!(matches!(run_with(&args("export --yaml tasks.ttm"), &config), Err(CliError::Usage(_))));
+        assert!(matches!(run_with(&args("import --json"), &config), Err(CliError::Usage(_))));
+        assert!(matches!(run_with(&args("import --ics tasks.ttm"), &config), Err(CliError::Usage(_))));
+        assert!(matches!(run_with(&args("import --todotxt"), &config), Err(CliError::Usage(_))));
+        assert!(matches!(run_with(&args("export --ics tasks.ttm -o"), &config), Err(CliError::Usage(_))));
+        assert!(matches!(run_with(&args("goals --tree a.ttm b.ttm"), &config), Err(CliError::Usage(_))));
+        assert!(matches!(run_with(&args("habits"), &config), Err(CliError::Usage(_))));
+        assert!(matches!(run_with(&args("chart --ascii Run"), &config), Err(CliError::Usage(_))));
+        assert!(matches!(run_with(&args("forecast --burndown Report"), &config), Err(CliError::Usage(_))));
+        assert!(matches!(run_with(&args("migrate --in-place"), &config), Err(CliError::Usage(_))));
+        assert!(matches!(run_with(&args("migrate a.ttm b.ttm"), &config), Err(CliError::Usage(_))));
+        assert!(matches!(run_with(&args("deps a.ttm b.ttm"), &config), Err(CliError::Usage(_))));
+        assert!(matches!(run_with(&args("ready a.ttm b.ttm"), &config), Err(CliError::Usage(_))));
+        assert!(matches!(run_with(&args("ids a.ttm -o"), &config), Err(CliError::Usage(_))));
+        assert!(matches!(run_with(&args("query a.ttm"), &config), Err(CliError::Usage(_))));
+        assert!(matches!(run_with(&args("query is:someday a.ttm"), &config), Err(CliError::Usage(_))));
+        assert!(matches!(run_with(&args("tags"), &config), Err(CliError::Usage(_))));
+        assert!(matches!(run_with(&args("fmt --check --diff"), &config), Err(CliError::Usage(_))));
+        assert!(matches!(run_with(&args("lint --disable a.ttm"), &config), Err(CliError::Usage(_))));
+        assert!(matches!(run_with(&args("lint --disable"), &config), Err(CliError::Usage(_))));
+        assert!(matches!(run_with(&args("fmt --diff a.ttm"), &config), Err(CliError::Usage(_))));
+        assert!(matches!(run_with(&args("watch --debounce"), &config), Err(CliError::Usage(_))));
+        assert!(matches!(run_with(&args("watch a.ttm"), &config), Err(CliError::Usage(_))));
+    }
+
+    #[test]
+    fn test_convert_args() {
+        let parsed = args("--ics tasks.ttm -o tasks.ics");
+        assert_eq!(convert_args("export", &parsed, &["--json", "--ics"]).unwrap(),
+            ConvertArgs { format: "--ics", path: "tasks.ttm", out: Some("tasks.ics") });
+        let parsed = args("--json tasks.ttm");
+        assert_eq!(convert_args("export", &parsed, &["--json", "--ics"]).unwrap(),
+            ConvertArgs { format: "--json", path: "tasks.ttm", out: None });
+    }
+
+    #[test]
+    fn test_export_formats() {
+        let dir = TempDir::new("export_formats");
+        let ttm = dir.file("export_ics.ttm", "[Tasks]\n\t() Write the report (due: W3R)\n");
+        let out = dir.file("export_ics.ics", "");
+        assert_eq!(export(&args(&format!("--ics {} -o {}", ttm, out)), &Config::default()).unwrap(), "");
+
+        let ics = std::fs::read_to_string(&out).unwrap();
+        assert!(ics.starts_with("BEGIN:VCALENDAR\r\n"));
+        assert!(ics.contains("SUMMARY:Write the report\r\n"));
+
+        assert_eq!(export(&args(&format!("--markdown {}", ttm)), &Config::default()).unwrap(), "# Tasks\n\n- [ ] Write the report (due W3R)\n");
+        assert!(export(&args(&format!("--html {}", ttm)), &Config::default()).unwrap().contains("<h2>Tasks</h2>"));
+
+        // 2021-01-11 falls in Winter 2021
+        let document: Document = "() Task (due: W3R)\n".parse().unwrap();
+        assert!(export_ics(&document, &Config::default(), 1610368205).unwrap().contains("DUE;VALUE=DATE:20210114\r\n"));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_export_import() {
+        let dir = TempDir::new("export_import");
+        let ttm = dir.file("export.ttm", "[Tasks]\n\t>(2/15) Write the report (due: W3R)\n");
+        let json = export(&args(&format!("--json {}", ttm)), &Config::default()).unwrap();
+        let json_path = dir.file("import.json", &json);
+
+        assert_eq!(import(&args(&format!("--json {}", json_path)), &Config::default()).unwrap(),
+            "[Tasks]\n\t>(2/15) Write the report (due: W3R)\n");
+        assert!(matches!(export(&args("--json /nonexistent/tasks.ttm"), &Config::default()), Err(CliError::Io {..})));
+        assert!(matches!(import(&args(&format!("--json {}", ttm)), &Config::default()), Err(CliError::InvalidInput(_))));
+    }
+
+    #[test]
+    fn test_import_todotxt_org() {
+        let dir = TempDir::new("import_todotxt_org");
+        let todo_txt = dir.file("import.txt", "(A) Call mom +Family\n");
+        assert_eq!(import(&args(&format!("--todotxt {}", todo_txt)), &Config::default()).unwrap(),
+            "[Family]\n\t() Call mom +Family (prior: 0)\n");
+        let org = dir.file("import.org", "* Family\n** TODO Call mom\n");
+        assert_eq!(import(&args(&format!("--org {}", org)), &Config::default()).unwrap(), "[Family]\n\t() Call mom\n");
+    }
+
+    #[cfg(not(feature = "serde"))]
+    #[test]
+    fn test_json_unsupported() {
+        let dir = TempDir::new("json_unsupported");
+        let ttm = dir.file("unsupported.ttm", "() Task\n");
+        assert!(matches!(export(&args(&format!("--json {}", ttm)), &Config::default()), Err(CliError::Unsupported(_))));
+        assert!(matches!(import(&args(&format!("--json {}", ttm)), &Config::default()), Err(CliError::Unsupported(_))));
+    }
+
+    #[test]
+    fn test_goals() {
+        let dir = TempDir::new("goals");
+        let monday = dir.file("goals_monday.ttm", "() Fitness (gPushups: 5/10)\n\t() Run (gKm: 3/5)\n");
+        let tuesday = dir.file("goals_tuesday.ttm", "() Fitness (gPushups: 10/10)\n");
+
+        assert_eq!(goals(&args(&format!("{} {}", monday, tuesday)), &Config::default()).unwrap(), "Km       3/5 (60%)\nPushups  15/20 (75%)\n");
+        assert_eq!(goals(&args(&format!("--behind {}", tuesday)), &Config::default()).unwrap(), "");
+        assert_eq!(goals(&args(&format!("--tree {}", monday)), &Config::default()).unwrap(),
+            "Fitness: Km 3/5 (60%); Pushups 5/10 (50%)\n\tRun: Km 3/5 (60%)\n");
+    }
+
+    #[test]
+    fn test_habits() {
+        let dir = TempDir::new("habits");
+        let week1 = dir.file("habits_w1.ttm", "[Tracker]\n! ! ! ! ! ! ! Run\n");
+        let week2 = dir.file("habits_w2.ttm", "[Tracker]\n! - ? ? ? ? ? Run\n");
+        assert_eq!(habits(&args(&format!("{} {}", week1, week2)), &Config::default()).unwrap(),
+            "Run  streak 0 (longest 8); M 100% T 50% W 100% R 100% F 100% S 100% U 100%; 1/2 weeks over target\n");
+    }
+
+    #[test]
+    fn test_chart() {
+        let dir = TempDir::new("chart");
+        let week1 = dir.file("chart_w1.ttm", "[Tracker]\n1 2 3 4 ! - /- Run\n");
+        let week2 = dir.file("chart_w2.ttm", "[Tracker]\n4 ? ? ? ? ? ? Run\n");
+        let today = CivilDate::new(2021, 1, 13);
+        assert_eq!(chart(&args(&format!("--ascii Run {} {}", week1, week2)), &Config::default(), today).unwrap(),
+            "M :#\nT +\nW *\nR #\nF :\nS .\nU .\n  #- 11 4\n");
+        assert!(matches!(chart(&args(&format!("Walk {}", week1)), &Config::default(), today), Err(CliError::InvalidInput(_))));
+    }
+
+    #[test]
+    fn test_forecast() {
+        let dir = TempDir::new("forecast");
+        let day1 = dir.file("forecast/2021-01-04.ttm", "(0/10) Report (due: W3R)\n");
+        let day2 = dir.file("forecast/2021-01-06.ttm", "(4/10) Report (due: W3R)\n");
+        assert_eq!(forecast(&args(&format!("{} {}", day2, day1)), &Config::default()).unwrap(),
+            "Report  4/10, 2.0/day, finish 2021-01-09, due 2021-01-14: on track\n");
+        assert!(forecast(&args(&format!("--burndown Report {} {}", day1, day2)), &Config::default()).unwrap()
+            .ends_with("2021-01-14 |                                        | 0 due\n"));
+
+        let undated = dir.file("forecast_undated.ttm", "() Task\n");
+        assert!(matches!(forecast(&args(&undated), &Config::default()), Err(CliError::InvalidInput(_))));
+    }
+
+    #[test]
+    fn test_migrate() {
+        let dir = TempDir::new("migrate");
+        let old = dir.file("migrate.ttm", "[Tasks]\n\t~(2; W1M) Done task\n");
+        assert_eq!(migrate(&args(&old)).unwrap(), "[Tasks]\n\t~(2) Done task (done: W1M)\n");
+
+        assert_eq!(migrate(&args(&format!("--in-place {}", old))).unwrap(), format!("{}: migrated 1 tasks\n", old));
+        assert_eq!(std::fs::read_to_string(&old).unwrap(), "[Tasks]\n\t~(2) Done task (done: W1M)\n");
+        assert_eq!(migrate(&args(&format!("--in-place {}", old))).unwrap(), format!("{}: migrated 0 tasks\n", old));
+    }
+
+    #[test]
+    fn test_deps() {
+        let dir = TempDir::new("deps");
+        let path = dir.file("deps.ttm", "() Draft  (deps: Outline)\n() Outline\n() Errand\n");
+        assert_eq!(deps(&args(&path), &Config::default()).unwrap(), "B() Draft  (deps: Outline)\n() Outline\n() Errand\n");
+        assert_eq!(ready(&args(&path), &Config::default()).unwrap(), "Outline\nErrand\n");
+    }
+
+    #[test]
+    fn test_workspace_commands() {
+        let config = temp_workspace("workspace", &[
+            ("home.ttm", "[Errands]\n    (0) Groceries (deps: work/Tasks/Report)\n"),
+            ("work.ttm", "[Tasks]\n    (0/2) Report\n    (0/1) Review (deps: Groceries)\n"),
+        ]);
+        let root = config.workspace.root.clone().unwrap();
+        let (home, work) = (root.join("home.ttm"), root.join("work.ttm"));
+
+        assert_eq!(ready(&[], &config).unwrap(), format!("{}: Tasks/Report\n", work.display()));
+        assert_eq!(deps(&[], &config).unwrap(), format!("{0}: dependencies evaluated\n{1}: dependencies evaluated\n",
+            home.display(), work.display()));
+        assert_eq!(read_file(&work.to_string_lossy()).unwrap(), "[Tasks]\n    (0/2) Report\n    B(0/1) Review (deps: Groceries)\n");
+        assert_eq!(query(&args("is:blocked"), &config).unwrap(), format!("{}: B(0) Groceries (deps: work/Tasks/Report)\n{}: B(0/1) Review (deps: Groceries)\n",
+            home.display(), work.display()));
+        // nothing is left to write
+        assert_eq!(deps(&[], &config).unwrap(), "");
+
+        fmt(&[], &config).unwrap();
+        assert_eq!(fmt(&args("--check"), &config).unwrap(), "");
+        assert_eq!(assign_ids(&[], &config, 7).unwrap().lines().count(), 2);
+        assert!(watch(&args("--once"), &config).unwrap().starts_with("agenda:\n"));
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_fmt() {
+        let dir = TempDir::new("fmt");
+        let path = dir.file("fmt.ttm", "[Tasks]\n    (2/15,10) Report  (prior: 3;due: W0xA)\n");
+        match fmt(&args(&format!("--check {}", path)), &Config::default()) {
+            Err(CliError::InvalidInput(message)) => assert!(message.ends_with("\
+@@ -1,2 +1,2 @@
+ [Tasks]
+-    (2/15,10) Report  (prior: 3;due: W0xA)
++\t(2/15, 10) Report (due: W0xA; prior: 3)")),
+            other => panic!("expected a diff, got {:?}", other),
+        }
+        assert_eq!(fmt(&args(&path), &Config::default()).unwrap(), "");
+        assert_eq!(read_file(&path).unwrap(), "[Tasks]\n\t(2/15, 10) Report (due: W0xA; prior: 3)\n");
+        assert_eq!(fmt(&args(&format!("--check {}", path)), &Config::default()).unwrap(), "");
+
+        fmt(&args(&format!("--dates {}", path)), &Config::default()).unwrap();
+        assert_eq!(read_file(&path).unwrap(), "[Tasks]\n\t(2/15, 10) Report (due: WA; prior: 3)\n");
+
+        // aliased flags are kept, and text sections are left as they are
+        let config: Config = "[flags]\n\"*\" = \"current\"\n[sections]\nJournal = \"text\"\n".parse().unwrap();
+        let path = dir.file("fmt-config.ttm", "[Journal]\n\t(x) not a task\n[Tasks]\n    *(1)  Report\n");
+        fmt(&args(&path), &config).unwrap();
+        assert_eq!(read_file(&path).unwrap(), "[Journal]\n\t(x) not a task\n[Tasks]\n\t*(1) Report\n");
+        assert!(fmt(&args(&path), &Config::default()).is_err());
+    }
+
+    #[test]
+    fn test_lint() {
+        let dir = TempDir::new("lint");
+        let path = dir.file("lint.ttm", "[Tasks]\n\t>() Report\n\t>() Email (prior: 100)\n[Empty]\n");
+        let today = CivilDate::new(2021, 1, 11);
+        match lint(&args(&path), &Config::default(), today) {
+            Err(CliError::InvalidInput(message)) => assert_eq!(message, format!("\
+3 problems found
+{0}:3: warning: 'Email' is CURRENT, and so is the task on line 2 [multiple-current]
+{0}:3: warning: 'Email' has priority 100, out of 0-99 [priority-range]
+{0}:4: warning: the section [Empty] is empty [empty-section]", path)),
+            other => panic!("expected problems, got {:?}", other),
+        }
+        let disabled = format!("--disable multiple-current --disable priority-range --disable empty-section {}", path);
+        assert_eq!(lint(&args(&disabled), &Config::default(), today).unwrap(), "");
+
+        // rules disabled by the config may be enabled again, and flag aliases are read as flags
+        let config: Config = "[lint]\ndisable = [\"multiple-current\", \"priority-range\"]\n[flags]\n\"*\" = \"current\""
+            .parse().unwrap();
+        let aliased = dir.file("lint_aliased.ttm", "[Tasks]\n\t*() Report\n\t*() Email (prior: 100)\n");
+        assert_eq!(lint(&args(&aliased), &config, today).unwrap(), "");
+        let enabled = format!("--enable multiple-current {}", aliased);
+        assert!(matches!(lint(&args(&enabled), &config, today), Err(CliError::InvalidInput(_))));
+    }
+
+    #[test]
+    fn test_state_file() {
+        assert_eq!(state_file(Some("/tmp/timer"), Some("/home/me"), ".ttm_timer"), PathBuf::from("/tmp/timer"));
+        assert_eq!(state_file(None, Some("/home/me"), ".ttm_timer"), PathBuf::from("/home/me/.ttm_timer"));
+        assert_eq!(state_file(None, None, ".ttm_timer"), PathBuf::from(".ttm_timer"));
+    }
+
+    #[test]
+    fn test_query_and_tags() {
+        let dir = TempDir::new("query_and_tags");
+        let path = dir.file("tags/2021-01-08.ttm", "(2) Report +work\n~(1) Sink @home\n");
+        assert_eq!(query(&args(&format!("+work is:open {}", path)), &Config::default()).unwrap(), "(2) Report +work\n");
+        assert_eq!(tags(&args(&path), &Config::default()).unwrap(), "week     +work  @home\nY21W-W2      2      1\n");
+    }
+
+    #[test]
+    fn test_ids() {
+        let dir = TempDir::new("ids");
+        // only the lines of the tasks given an id are written again
+        let path = dir.file("ids.ttm", "() Report  (#report)\n    () Outline\n");
+        let out = assign_ids(&args(&path), &Config::default(), 1).unwrap();
+        let document: Document = out.parse().unwrap();
+        let id = document.tasks()[1].id().unwrap().to_string();
+        assert_eq!(out, format!("() Report  (#report)\n    () Outline (#{})\n", id));
+        assert_eq!(assign_ids(&args(&path), &Config::default(), 1).unwrap(), out);
+    }
+}
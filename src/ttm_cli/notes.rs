@@ -0,0 +1,175 @@
+//! commands resolving the note links of tasks. The notes directory is given with --notes <dir>, by
+//! the TTM_NOTES environment variable, or by paths.notes of the config. Refer to
+//! `crate::ttm_io::notes`.
+
+use super::{load_workspace, parse_document, read_file, CliError};
+use crate::config::Config;
+use crate::ttm_io::document::Document;
+use crate::ttm_io::ids;
+use crate::ttm_io::notes::{self, NoteIndex};
+use std::path::{Path, PathBuf};
+
+/// environment variable holding the notes directory, when --notes is not given
+pub const NOTES_ENV: &str = "TTM_NOTES";
+
+/// takes --notes <dir> out of `args`, giving back the other arguments along with the directory, if
+/// there is one. `env_dir` is the directory given by the environment or the config.
+fn take_notes_dir<'a>(args: &'a [String], env_dir: Option<&str>) -> Result<(Vec<&'a str>, Option<PathBuf>), CliError> {
+    let mut rest: Vec<&str> = vec![];
+    let mut dir: Option<PathBuf> = env_dir.map(PathBuf::from);
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        if arg == "--notes" {
+            let path = args.next().ok_or_else(|| CliError::Usage("--notes expects <dir>".into()))?;
+            dir = Some(PathBuf::from(path));
+        } else {
+            rest.push(arg);
+        }
+    }
+    Ok((rest, dir))
+}
+
+/// takes --notes <dir> out of `args` as `take_notes_dir` does, failing if there is no directory
+fn notes_dir<'a>(args: &'a [String], env_dir: Option<&str>) -> Result<(Vec<&'a str>, PathBuf), CliError> {
+    let (rest, dir) = take_notes_dir(args, env_dir)?;
+    let dir = dir.ok_or_else(|| CliError::Usage(
+        format!("no notes directory, give --notes <dir>, set {} or set paths.notes in ttm.toml", NOTES_ENV).into()))?;
+    Ok((rest, dir))
+}
+
+fn scan(dir: &Path) -> Result<NoteIndex, CliError> {
+    // paths are compared canonically, so notes may be named relative to any directory
+    let dir = dir.canonicalize().map_err(|err| CliError::Io { path: dir.display().to_string(), err })?;
+    NoteIndex::scan(&dir).map_err(|err| CliError::Io { path: dir.display().to_string(), err })
+}
+
+fn read_document(config: &Config, path: &str) -> Result<Document, CliError> {
+    parse_document(config, path, &read_file(path)?)
+}
+
+/// reports the note links in a TTM file that point nowhere. Without a file, the links of the
+/// workspace are resolved into its files, along with the notes directory if there is one.
+pub fn links(args: &[String], config: &Config, env_dir: Option<&str>) -> Result<String, CliError> {
+    let (args, dir) = take_notes_dir(args, env_dir)?;
+    let (label, broken): (String, Vec<String>) = match args.as_slice() {
+        [] => {
+            let workspace = load_workspace(config)?;
+            let index = match dir {
+                Some(dir) => scan(&dir)?,
+                None => NoteIndex::default(),
+            };
+            let index = index.with_files(workspace.note_files());
+            let tasks = workspace.tasks();
+            let broken = notes::broken_links(workspace.document(), &index).iter()
+                .map(|link| match tasks.iter().find(|(_path, task)| std::ptr::eq(*task, link.task)) {
+                    Some((path, _task)) => format!("{}: {}", path.display(), link),
+                    None => link.to_string(),
+                })
+                .collect();
+            ("workspace".to_string(), broken)
+        }
+        [path] => {
+            let dir = dir.ok_or_else(|| CliError::Usage(
+                format!("no notes directory, give --notes <dir>, set {} or set paths.notes in ttm.toml", NOTES_ENV).into()))?;
+            let document = read_document(config, path)?;
+            (path.to_string(), notes::broken_links(&document, &scan(&dir)?).iter().map(|link| link.to_string()).collect())
+        }
+        _ => return Err(CliError::Usage("links expects [<file>]".into())),
+    };
+    if broken.is_empty() {
+        return Ok(String::new());
+    }
+
+    let lines: Vec<String> = broken.iter().map(|link| format!("    {}", link)).collect();
+    Err(CliError::InvalidInput(format!("{}: {} broken note links\n{}", label, broken.len(), lines.join("\n")).into()))
+}
+
+/// lists the tasks of a TTM file that link into a note
+pub fn backlinks(args: &[String], config: &Config, env_dir: Option<&str>) -> Result<String, CliError> {
+    let (args, dir) = notes_dir(args, env_dir)?;
+    let (note, path) = match args.as_slice() {
+        [note, path] => (*note, *path),
+        _ => return Err(CliError::Usage("backlinks expects <note> <file>".into())),
+    };
+    let note = Path::new(note).canonicalize().map_err(|err| CliError::Io { path: note.to_string(), err })?;
+    let document = read_document(config, path)?;
+
+    Ok(notes::backlinks(&document, &scan(&dir)?, &note).iter()
+        .map(|task| format!("{}\n", task.name()))
+        .collect())
+}
+
+/// opens the note a task links to in `editor`. The task is given by its name, or as #Id.
+pub fn open(args: &[String], config: &Config, env_dir: Option<&str>, editor: &str) -> Result<String, CliError> {
+    let (args, dir) = notes_dir(args, env_dir)?;
+    let (path, name) = match args.as_slice() {
+        [path, name] => (*path, *name),
+        _ => return Err(CliError::Usage("open expects <file> <task>".into())),
+    };
+    let document = read_document(config, path)?;
+    let task = ids::find_task(&document, name)
+        .ok_or_else(|| CliError::InvalidInput(format!("{}: no task named '{}'", path, name).into()))?;
+
+    let locations = match notes::resolve_task(task, &scan(&dir)?) {
+        Some(Ok(locations)) => locations,
+        Some(Err(e)) => return Err(CliError::InvalidInput(format!("task '{}': {}", name, e).into())),
+        None => return Err(CliError::InvalidInput(format!("task '{}' has no note link", name).into())),
+    };
+    let location = locations.first().ok_or_else(|| CliError::InvalidInput(
+        format!("task '{}': no note matches *{}", name, task.note_link()).into()))?;
+
+    let status = notes::open_command(location, editor).status()
+        .map_err(|err| CliError::Io { path: editor.to_string(), err })?;
+    if !status.success() {
+        return Err(CliError::InvalidInput(format!("{} exited with {}", editor, status).into()));
+    }
+    Ok(String::new())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ttm_cli::tests::{args, temp_workspace, TempDir};
+
+    #[test]
+    fn test_notes_dir() {
+        let parsed = args("a --notes notes b");
+        assert_eq!(notes_dir(&parsed, None).unwrap(), (vec!["a", "b"], PathBuf::from("notes")));
+        assert_eq!(notes_dir(&args("a"), Some("env")).unwrap(), (vec!["a"], PathBuf::from("env")));
+        assert!(matches!(notes_dir(&args("a"), None), Err(CliError::Usage(_))));
+        assert!(matches!(notes_dir(&args("a --notes"), None), Err(CliError::Usage(_))));
+    }
+
+    #[test]
+    fn test_notes_commands() {
+        let temp = TempDir::new("notes_commands");
+        let note = temp.file("notes/work.md", "# Report\n");
+        let notes = Path::new(&note).parent().unwrap().to_str().unwrap().to_string();
+        let ttm = temp.file("notes_tasks.ttm", "() Write (*P[Report])\n() Lost (*P[Missing])\n() Renamed (#work)\n");
+
+        let err = links(&args(&format!("{} --notes {}", ttm, notes)), &Config::default(), None).unwrap_err();
+        assert!(err.to_string().ends_with("1 broken note links\n    task 'Lost': *P[Missing]: no note matches"));
+        assert_eq!(backlinks(&args(&format!("{} {}", note, ttm)), &Config::default(), Some(&notes)).unwrap(), "Write\nRenamed\n");
+
+        let parsed = vec![ttm.clone(), "Write".to_string()];
+        assert_eq!(open(&parsed, &Config::default(), Some(&notes), "true").unwrap(), "");
+        let parsed = vec![ttm.clone(), "#work".to_string()];
+        assert_eq!(open(&parsed, &Config::default(), Some(&notes), "true").unwrap(), "");
+        let parsed = vec![ttm, "Lost".to_string()];
+        assert!(matches!(open(&parsed, &Config::default(), Some(&notes), "true"), Err(CliError::InvalidInput(_))));
+    }
+
+    #[test]
+    fn test_workspace_links() {
+        let config = temp_workspace("workspace_links", &[
+            ("home.ttm", "[Errands]\n    () Groceries (*P[Tasks])\n    () Lost (*P[Missing])\n"),
+            ("work.ttm", "[Tasks]\n    () Report (*P[Errands])\n"),
+        ]);
+        let root = config.workspace.root.clone().unwrap();
+        // links point into the sections of the other files
+        let err = links(&[], &config, None).unwrap_err();
+        assert!(err.to_string().ends_with(&format!("workspace: 1 broken note links\n    {}: task 'Lost': *P[Missing]: no note matches",
+            root.join("home.ttm").display())));
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -0,0 +1,252 @@
+//! commands logging the sessions spent on tasks. The log is kept in the file given by the
+//! TTM_SESSIONS environment variable, by paths.sessions of the config, or ~/.ttm_sessions otherwise. The task of the open session is
+//! the CURRENT task of its file, so starting a session clears the flag on the other tasks of the
+//! file. Days are taken in local time, by time.utc_offset of the config. Refer to
+//! `crate::database::sessions`.
+
+use super::{parse_document, read_file, state_file, write_output, CliError};
+use crate::config::Config;
+use crate::database::sessions::{self, SessionLog};
+use crate::ttm_io::calendar::CivilDate;
+use crate::ttm_io::edit;
+use crate::ttm_io::ids;
+use crate::ttm_io::task::{Task, TaskFlags};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+/// environment variable holding the path of the session log
+pub const SESSIONS_ENV: &str = "TTM_SESSIONS";
+
+/// the session log, from the TTM_SESSIONS environment variable or else the home directory
+pub fn log_path(env_path: Option<&str>, home: Option<&str>) -> PathBuf {
+    state_file(env_path, home, ".ttm_sessions")
+}
+
+fn read_log(path: &Path) -> Result<SessionLog, CliError> {
+    match std::fs::read_to_string(path) {
+        Ok(s) => s.parse().map_err(|e| CliError::InvalidInput(format!("{}: {}", path.display(), e).into())),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(SessionLog::new()),
+        Err(err) => Err(CliError::Io { path: path.display().to_string(), err }),
+    }
+}
+
+fn write_log(path: &Path, log: &SessionLog) -> Result<(), CliError> {
+    std::fs::write(path, log.to_string()).map_err(|err| CliError::Io { path: path.display().to_string(), err })
+}
+
+/// takes `<name> <value>` out of `args`, giving back the value if it is there
+fn take_option<'a>(args: &mut Vec<&'a str>, name: &str) -> Result<Option<&'a str>, CliError> {
+    match args.iter().position(|arg| *arg == name) {
+        Some(idx) if idx + 1 < args.len() => {
+            let value = args.remove(idx + 1);
+            args.remove(idx);
+            Ok(Some(value))
+        }
+        Some(_) => Err(CliError::Usage(format!("{} expects a value", name).into())),
+        None => Ok(None),
+    }
+}
+
+/// the reference of the task of `file` named by `task`, as it is logged
+fn task_reference(config: &Config, file: &str, task: &str) -> Result<String, CliError> {
+    let document = parse_document(config, file, &read_file(file)?)?;
+    ids::find_task(&document, task)
+        .map(ids::reference)
+        .ok_or_else(|| CliError::InvalidInput(format!("{}: no task '{}'", file, task).into()))
+}
+
+/// sets or clears the CURRENT flag of the first task of `file` named by `reference`. Setting it
+/// clears it on every other task. Nothing else of the file is changed.
+fn set_current(file: &str, reference: &str, current: bool, config: &Config) -> Result<(), CliError> {
+    let text = read_file(file)?;
+    let (document, _errors) = config.parse_document(&text);
+    let tree = match ids::find_tree(&document, reference) {
+        Some(tree) => tree,
+        None => {
+            eprintln!("ttm: {}: no task '{}' to {} as current", file, reference, if current { "set" } else { "clear" });
+            return Ok(());
+        }
+    };
+    // the tasks whose flag changes, by line
+    let mut edits: HashMap<usize, (&Task, Task)> = HashMap::new();
+    for other in document.walk_trees() {
+        let wanted = match other.line() == tree.line() {
+            true => current,
+            false if current => false,
+            false => continue,
+        };
+        let task = other.task();
+        if task.flags().contains(TaskFlags::CURRENT) != wanted {
+            let mut edited = task.clone();
+            edited.build_flags(task.flags() ^ TaskFlags::CURRENT);
+            edits.insert(other.line(), (task, edited));
+        }
+    }
+    let text = edit::edit_lines(&text, |i, line| {
+        let (task, edited) = edits.get(&(i + 1))?;
+        edit::set_task(line, task, edited, &config.flag_aliases)
+    });
+    std::fs::write(file, text).map_err(|err| CliError::Io { path: file.to_string(), err })
+}
+
+fn format_minutes(secs: i64) -> String {
+    format!("{} minutes", secs / 60)
+}
+
+/// runs a session command, at `now` in seconds since 1970-01-01T00:00:00Z
+pub fn session(args: &[String], config: &Config, log_path: &Path, now: i64) -> Result<String, CliError> {
+    let mut args: Vec<&str> = args.iter().map(|arg| arg.as_str()).collect();
+    let note = take_option(&mut args, "--note")?.unwrap_or("");
+    let minutes = match take_option(&mut args, "--minutes")? {
+        Some(minutes) => minutes.parse::<u32>().ok().filter(|minutes| *minutes > 0)
+            .ok_or_else(|| CliError::Usage(format!("'{}' is not a number of minutes", minutes).into()))?,
+        None => config.block_minutes,
+    };
+    let mut log = read_log(log_path)?;
+    let today = CivilDate::from_timestamp(now, config.utc_offset);
+
+    match args.as_slice() {
+        ["start", file, task] => {
+            let reference = task_reference(config, file, task)?;
+            log.start(&reference, now, note).map_err(|e| CliError::InvalidInput(e.to_string().into()))?;
+            write_log(log_path, &log)?;
+            set_current(file, &reference, true, config)?;
+            Ok(format!("{}: started\n", reference))
+        }
+        ["stop", file] => {
+            let session = log.stop(now, note).map_err(|e| CliError::InvalidInput(e.to_string().into()))?.clone();
+            write_log(log_path, &log)?;
+            set_current(file, &session.task, false, config)?;
+            Ok(format!("{}: stopped after {}\n", session.task, format_minutes(session.end(now) - session.start)))
+        }
+        ["switch", file, task] => {
+            let reference = task_reference(config, file, task)?;
+            let mut out = String::new();
+            if let Some(open) = log.open_session().cloned() {
+                set_current(file, &open.task, false, config)?;
+                out.push_str(&format!("{}: stopped after {}\n", open.task, format_minutes(now - open.start)));
+            }
+            log.switch(&reference, now, note).map_err(|e| CliError::InvalidInput(e.to_string().into()))?;
+            write_log(log_path, &log)?;
+            set_current(file, &reference, true, config)?;
+            out.push_str(&format!("{}: started\n", reference));
+            Ok(out)
+        }
+        ["check"] => {
+            let sessions = log.sessions();
+            let mut problems: Vec<String> = vec![];
+            for (i, j) in log.overlaps(now) {
+                problems.push(format!("the sessions on {} and {} on lines {} and {} overlap",
+                    sessions[i].task, sessions[j].task, i + 1, j + 1));
+            }
+            for i in log.dangling(today, config.utc_offset) {
+                problems.push(format!("the session on {} on line {} is open since {}",
+                    sessions[i].task, i + 1, sessions[i].start_date(config.utc_offset)));
+            }
+            if problems.is_empty() {
+                return Ok(String::new());
+            }
+            Err(CliError::InvalidInput(format!("{}: {}", log_path.display(), problems.join("\n    ")).into()))
+        }
+        ["blocks", file, rest @ ..] => {
+            let out = match rest {
+                [] => None,
+                ["-o", out] => Some(*out),
+                _ => return Err(CliError::Usage("session blocks expects <file> [--minutes <n>] [-o <out>]".into())),
+            };
+            let text = read_file(file)?;
+            let (document, _errors) = config.parse_document(&text);
+            let tasks: HashMap<usize, &Task> = document.walk_trees().into_iter().map(|tree| (tree.line(), tree.task())).collect();
+            let text = edit::edit_lines(&text, |i, line| {
+                let task = tasks.get(&(i + 1))?;
+                let logged = log.minutes_on(&ids::reference(task), today, now, config.utc_offset);
+                if logged == 0 {
+                    return None;
+                }
+                let mut logged_task = (*task).clone();
+                logged_task.build_day_stat(sessions::with_blocks(task.day_stat(), sessions::blocks(logged, minutes)));
+                edit::set_task(line, task, &logged_task, &config.flag_aliases)
+            });
+            write_output(out, text)
+        }
+        _ => Err(CliError::Usage(
+            "session expects start <file> <task>, stop <file>, switch <file> <task>, check or blocks <file>".into())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ttm_cli::tests::{args, TempDir};
+
+    // 2021-01-08T09:00:00Z
+    const NOW: i64 = 1_610_096_400;
+
+    #[test]
+    fn test_sessions() {
+        let dir = TempDir::new("sessions");
+        let file = dir.file("sessions.ttm", "\
+[Work]
+    (1/4) Write the report  (#report)
+    >() Email
+");
+        let log = PathBuf::from(dir.file("sessions_log", ""));
+        let session = |s: &str, now: i64| super::session(&args(&s.replace("FILE", &file)), &Config::default(), &log, now);
+
+        assert_eq!(session("start FILE #report --note intro", NOW).unwrap(), "#report: started\n");
+        // the task current before is not anymore
+        assert_eq!(std::fs::read_to_string(&file).unwrap(), "\
+[Work]
+    >(1/4) Write the report  (#report)
+    () Email
+");
+        assert!(matches!(session("start FILE Email", NOW), Err(CliError::InvalidInput(_))));
+        assert!(matches!(session("start FILE Sleep", NOW), Err(CliError::InvalidInput(_))));
+        assert_eq!(session("switch FILE Email", NOW + 50 * 60).unwrap(), "#report: stopped after 50 minutes\nEmail: started\n");
+        assert_eq!(std::fs::read_to_string(&file).unwrap(), "\
+[Work]
+    (1/4) Write the report  (#report)
+    >() Email
+");
+        assert_eq!(session("stop FILE", NOW + 60 * 60).unwrap(), "Email: stopped after 10 minutes\n");
+        assert_eq!(std::fs::read_to_string(&file).unwrap(), "\
+[Work]
+    (1/4) Write the report  (#report)
+    () Email
+");
+        assert!(std::fs::read_to_string(&log).unwrap().starts_with(&format!("{}\t{}\t#report\tintro\n", NOW, NOW + 3000)));
+
+        assert_eq!(session("blocks FILE --minutes 25", NOW + 60 * 60).unwrap(), "\
+[Work]
+    (2/4) Write the report  (#report)
+    (0) Email
+");
+        assert!(matches!(session("blocks FILE --minutes 0", NOW), Err(CliError::Usage(_))));
+        assert_eq!(session("check", NOW + 60 * 60).unwrap(), "");
+
+        // a session left open overnight
+        session("start FILE Email", NOW + 2 * 60 * 60).unwrap();
+        assert!(matches!(session("check", NOW + 24 * 60 * 60), Err(CliError::InvalidInput(_))));
+        assert!(matches!(session("frobnicate", NOW), Err(CliError::Usage(_))));
+        std::fs::remove_file(&log).unwrap();
+
+        // tasks written with aliases are found, and keep them
+        let config: Config = "[flags]\n\"*\" = \"blocked\"\n".parse().unwrap();
+        std::fs::write(&file, "[Work]\n    *() Email\n").unwrap();
+        let session = |s: &str, now: i64| super::session(&args(&s.replace("FILE", &file)), &config, &log, now);
+        session("start FILE Email", NOW).unwrap();
+        assert_eq!(std::fs::read_to_string(&file).unwrap(), "[Work]\n    *>() Email\n");
+        session("stop FILE", NOW + 30 * 60).unwrap();
+        assert_eq!(std::fs::read_to_string(&file).unwrap(), "[Work]\n    *() Email\n");
+        assert_eq!(session("blocks FILE", NOW + 30 * 60).unwrap(), "[Work]\n    *(1) Email\n");
+        std::fs::remove_file(&log).unwrap();
+
+        // ten hours behind UTC, the session starts at 23:00 and is checked the next day
+        let config: Config = "[time]\nutc_offset = \"-10:00\"\n".parse().unwrap();
+        std::fs::write(&file, "[Work]\n    () Email\n").unwrap();
+        let session = |s: &str, now: i64| super::session(&args(&s.replace("FILE", &file)), &config, &log, now);
+        session("start FILE Email", NOW).unwrap();
+        assert!(matches!(session("check", NOW + 2 * 60 * 60), Err(CliError::InvalidInput(_))));
+        assert_eq!(super::session(&args("check"), &Config::default(), &log, NOW + 2 * 60 * 60).unwrap(), "");
+    }
+}
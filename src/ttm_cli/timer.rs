@@ -0,0 +1,188 @@
+//! commands running a block timer. The running timer is kept in a state file, given by the TTM_TIMER
+//! environment variable, by paths.timer of the config, or ~/.ttm_timer otherwise. Blocks are
+//! blocks.minutes of the config long unless given, and are ticked on the local day they end on, by
+//! time.utc_offset of the config. Refer to `crate::ttm_core::timer`.
+
+use super::{parse_document, read_file, state_file, CliError};
+use crate::config::Config;
+use crate::ttm_core::timer::{self, Clock, TimerState};
+use crate::ttm_io::ids;
+use std::path::{Path, PathBuf};
+
+/// environment variable holding the path of the state file
+pub const TIMER_ENV: &str = "TTM_TIMER";
+
+/// the state file, from the TTM_TIMER environment variable or else the home directory
+pub fn state_path(env_path: Option<&str>, home: Option<&str>) -> PathBuf {
+    state_file(env_path, home, ".ttm_timer")
+}
+
+fn io_error(path: &Path, err: std::io::Error) -> CliError {
+    CliError::Io { path: path.display().to_string(), err }
+}
+
+/// the running timer, if there is one
+fn read_state(path: &Path) -> Result<Option<TimerState>, CliError> {
+    match std::fs::read_to_string(path) {
+        Ok(s) => s.parse().map(Some)
+            .map_err(|e| CliError::InvalidInput(format!("{}: {}", path.display(), e).into())),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(err) => Err(io_error(path, err)),
+    }
+}
+
+/// writes seconds as MM:SS
+fn format_remaining(secs: i64) -> String {
+    format!("{:02}:{:02}", secs / 60, secs % 60)
+}
+
+/// ticks the block of a timer that is over into its file, and stops the timer
+fn finish(state: &TimerState, state_path: &Path, config: &Config) -> Result<String, CliError> {
+    let tick = timer::tick(&read_file(&state.file)?, &state.task, state.end_date(config.utc_offset), config)
+        .map_err(|e| CliError::InvalidInput(format!("{}: {}", state.file, e).into()))?;
+    std::fs::write(&state.file, &tick.text).map_err(|err| CliError::Io { path: state.file.clone(), err })?;
+    std::fs::remove_file(state_path).map_err(|err| io_error(state_path, err))?;
+
+    let mut out = format!("{}: block done ({})", state.task, tick.day_stat);
+    if let Some((entry, stat)) = tick.entry {
+        out.push_str(&format!(", {} {}", entry, stat));
+    }
+    out.push('\n');
+    Ok(out)
+}
+
+/// runs `timer start <file> <task> [--minutes <n>]`, `timer status`, `timer wait` or `timer cancel`
+pub fn timer(args: &[String], config: &Config, state_path: &Path, clock: &dyn Clock) -> Result<String, CliError> {
+    let args: Vec<&str> = args.iter().map(|arg| arg.as_str()).collect();
+    let state = read_state(state_path)?;
+    match args.as_slice() {
+        ["start", rest @ ..] => {
+            let (file, task, minutes) = match rest {
+                [file, task] => (*file, *task, config.block_minutes),
+                [file, task, "--minutes", minutes] => {
+                    let minutes = minutes.parse::<u32>().ok().filter(|minutes| *minutes > 0)
+                        .ok_or_else(|| CliError::Usage(format!("'{}' is not a number of minutes", minutes).into()))?;
+                    (*file, *task, minutes)
+                }
+                _ => return Err(CliError::Usage("timer start expects <file> <task> [--minutes <n>]".into())),
+            };
+
+            // a block that is over is ticked before the next one starts
+            let mut out = String::new();
+            if let Some(state) = state {
+                if !state.is_over(clock.now()) {
+                    return Err(CliError::InvalidInput(format!("a timer is already running on {}, {} left",
+                        state.task, format_remaining(state.remaining(clock.now()))).into()));
+                }
+                out.push_str(&finish(&state, state_path, config)?);
+            }
+
+            let document = parse_document(config, file, &read_file(file)?)?;
+            if ids::find_task(&document, task).is_none() {
+                return Err(CliError::InvalidInput(format!("{}: no task '{}'", file, task).into()));
+            }
+            let state = TimerState::new(file, task, clock.now(), minutes);
+            std::fs::write(state_path, state.to_string()).map_err(|err| io_error(state_path, err))?;
+            out.push_str(&format!("{}: {} left\n", task, format_remaining(state.remaining(clock.now()))));
+            Ok(out)
+        }
+        ["status"] => match state {
+            Some(state) if state.is_over(clock.now()) => finish(&state, state_path, config),
+            Some(state) => Ok(format!("{}: {} left\n", state.task, format_remaining(state.remaining(clock.now())))),
+            None => Ok("no timer running\n".to_string()),
+        },
+        ["wait"] => {
+            let state = state.ok_or_else(|| CliError::InvalidInput("no timer running".into()))?;
+            while !state.is_over(clock.now()) {
+                clock.sleep(state.remaining(clock.now()));
+            }
+            finish(&state, state_path, config)
+        }
+        ["cancel"] => {
+            let state = state.ok_or_else(|| CliError::InvalidInput("no timer running".into()))?;
+            std::fs::remove_file(state_path).map_err(|err| io_error(state_path, err))?;
+            Ok(format!("{}: cancelled\n", state.task))
+        }
+        _ => Err(CliError::Usage("timer expects start <file> <task> [--minutes <n>], status, wait or cancel".into())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ttm_cli::tests::{args, TempDir};
+    use std::cell::Cell;
+
+    /// a clock that only moves when slept on
+    struct FakeClock(Cell<i64>);
+
+    impl Clock for FakeClock {
+        fn now(&self) -> i64 {
+            self.0.get()
+        }
+
+        fn sleep(&self, secs: i64) {
+            self.0.set(self.0.get() + secs);
+        }
+    }
+
+    #[test]
+    fn test_timer() {
+        let dir = TempDir::new("timer");
+        let file = dir.file("timer.ttm", "\
+    0  0  0  0  0  0  0  PROJECT
+    >(1/4) Write the report +project
+");
+        let state = PathBuf::from(dir.file("timer_state", ""));
+        std::fs::remove_file(&state).unwrap();
+        // 2021-01-08T10:00:00Z, a Friday
+        let clock = FakeClock(Cell::new(1_610_100_000));
+
+        assert_eq!(timer(&args("status"), &Config::default(), &state, &clock).unwrap(), "no timer running\n");
+        // the words of a name must be given as one argument
+        let start = format!("start {} Write the report +project", file);
+        assert!(matches!(timer(&args(&start), &Config::default(), &state, &clock), Err(CliError::Usage(_))));
+        let start = format!("start {} #missing", file);
+        assert!(matches!(timer(&args(&start), &Config::default(), &state, &clock), Err(CliError::InvalidInput(_))));
+
+        let task = "Write the report +project";
+        let mut start: Vec<String> = vec!["start".into(), file.clone(), task.into()];
+        assert_eq!(timer(&start, &Config::default(), &state, &clock).unwrap(), format!("{}: 25:00 left\n", task));
+        // the timer outlives the process, as it is read back from the state file
+        clock.sleep(10 * 60 + 30);
+        assert_eq!(timer(&args("status"), &Config::default(), &state, &clock).unwrap(), format!("{}: 14:30 left\n", task));
+        assert!(matches!(timer(&start, &Config::default(), &state, &clock), Err(CliError::InvalidInput(_))));
+
+        assert_eq!(timer(&args("wait"), &Config::default(), &state, &clock).unwrap(), format!("{}: block done (2/4), PROJECT 1\n", task));
+        assert_eq!(std::fs::read_to_string(&file).unwrap(), "\
+    0  0  0  0  1  0  0  PROJECT
+    >(2/4) Write the report +project
+");
+        assert!(!state.exists());
+
+        // a block that is over is ticked by the next status, or before the next start
+        start.extend(vec!["--minutes".to_string(), "30".to_string()]);
+        timer(&start, &Config::default(), &state, &clock).unwrap();
+        clock.sleep(30 * 60);
+        assert_eq!(timer(&start, &Config::default(), &state, &clock).unwrap(),
+            format!("{0}: block done (3/4), PROJECT 2\n{0}: 30:00 left\n", task));
+        assert_eq!(timer(&args("cancel"), &Config::default(), &state, &clock).unwrap(), format!("{}: cancelled\n", task));
+        assert!(matches!(timer(&args("cancel"), &Config::default(), &state, &clock), Err(CliError::InvalidInput(_))));
+
+        // tasks written with aliases are found when the block is ticked, and keep them
+        let config: Config = "[flags]\n\"*\" = \"current\"\n".parse().unwrap();
+        std::fs::write(&file, "    *(1/4) Report\n").unwrap();
+        timer(&args(&format!("start {} Report", file)), &config, &state, &clock).unwrap();
+        assert_eq!(timer(&args("wait"), &config, &state, &clock).unwrap(), "Report: block done (2/4)\n");
+        assert_eq!(std::fs::read_to_string(&file).unwrap(), "    *(2/4) Report\n");
+        assert!(!state.exists());
+
+        // fourteen hours ahead of UTC, a block ending at 10:25 UTC on Friday ends on Saturday
+        let config: Config = "[time]\nutc_offset = \"+14:00\"\n".parse().unwrap();
+        let clock = FakeClock(Cell::new(1_610_100_000));
+        std::fs::write(&file, "    0  0  0  0  0  0  0  PROJECT\n    (0) Project\n").unwrap();
+        timer(&args(&format!("start {} Project", file)), &config, &state, &clock).unwrap();
+        assert_eq!(timer(&args("wait"), &config, &state, &clock).unwrap(), "Project: block done (1), PROJECT 1\n");
+        assert_eq!(std::fs::read_to_string(&file).unwrap(), "    0  0  0  0  0  1  0  PROJECT\n    (1) Project\n");
+    }
+}
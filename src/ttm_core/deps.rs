@@ -0,0 +1,435 @@
+//! Resolves the dependencies between the tasks of a document, given by their `deps:` fields.
+//! A reference names a task by its path: the specifiers of its sections and the names of its
+//! parent tasks, down to its own name, joined with '/'. A reference may leave out any leading part
+//! of the path, so "Outline" and "Report/Outline" both name the task below:
+//!     [Work]
+//!         (0/4) Report
+//!             ~(2/2) Outline
+//! A task with an id may also be referenced as #Id, which keeps naming it when it is renamed or
+//! moved. Refer to `crate::ttm_io::ids`.
+//! A reference must name a single task. References that name none or several are reported, and
+//! are not taken as dependencies.
+//!
+//! A task with dependencies is blocked while any of them is not done. Evaluating a document sets
+//! and clears the `BLOCKED` flag of such tasks; tasks without dependencies keep the flag as it was
+//! written. Tasks that depend on each other in a cycle are reported, and stay blocked until a task
+//! of the cycle is done.
+
+#![allow(dead_code)]
+
+use crate::ttm_io::document::{Document, Item};
+use crate::ttm_io::task::{Task, TaskFlags};
+use crate::ttm_io::task_tree::TaskTree;
+use std::borrow::Cow;
+use std::collections::BTreeSet;
+use std::fmt;
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum DepError {
+    /// the reference `dep` of `task` names no task
+    Unresolved { task: String, dep: String },
+    /// the reference `dep` of `task` names `matches` tasks
+    Ambiguous { task: String, dep: String, matches: usize },
+    /// tasks that depend on each other, each depending on the next and the last on the first
+    Cycle(Vec<String>),
+}
+
+impl fmt::Display for DepError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::result::Result<(), std::fmt::Error> {
+        let msg: Cow<'static, str> = match self {
+            Self::Unresolved { task, dep } => format!("'{}' depends on '{}', which names no task", task, dep).into(),
+            Self::Ambiguous { task, dep, matches } => {
+                format!("'{}' depends on '{}', which names {} tasks", task, dep, matches).into()
+            }
+            Self::Cycle(tasks) => {
+                let mut names: Vec<&str> = tasks.iter().map(|task| task.as_str()).collect();
+                names.extend(tasks.first().map(|task| task.as_str()));
+                format!("dependency cycle: {}", names.join(" -> ")).into()
+            }
+        };
+        write!(f, "{}", msg)?;
+        Ok(())
+    }
+}
+
+/// a task of the graph, along with its path in the document
+#[derive(Debug, PartialEq)]
+pub struct DepNode<'a> {
+    pub path: Vec<&'a str>,
+    pub task: &'a Task,
+}
+
+impl DepNode<'_> {
+    /// whether `reference` names this task
+    fn matches(&self, reference: &str) -> bool {
+        if let Some(id) = reference.strip_prefix('#') {
+            return self.task.id() == Some(id);
+        }
+        let parts: Vec<&str> = reference.split('/').collect();
+        self.path.ends_with(&parts)
+    }
+}
+
+/// the dependencies between the tasks of a document. Tasks are indexed in the order they are
+/// written, as in `Document::tasks`.
+#[derive(Debug)]
+pub struct DepGraph<'a> {
+    nodes: Vec<DepNode<'a>>,
+    /// the tasks each task depends on
+    deps: Vec<Vec<usize>>,
+    /// references that could not be resolved
+    errors: Vec<DepError>,
+}
+
+impl<'a> DepGraph<'a> {
+    pub fn new(document: &'a Document) -> Self {
+        let mut nodes: Vec<DepNode<'a>> = vec![];
+        collect_nodes(document.items(), &mut vec![], &mut nodes);
+
+        let mut errors: Vec<DepError> = vec![];
+        let deps = nodes.iter()
+            .map(|node| {
+                let mut deps: Vec<usize> = vec![];
+                for dep in node.task.deps() {
+                    let matches: Vec<usize> = (0..nodes.len()).filter(|idx| nodes[*idx].matches(dep)).collect();
+                    let (task, dep) = (node.task.name().to_string(), dep.to_string());
+                    match matches.as_slice() {
+                        [idx] => deps.push(*idx),
+                        [] => errors.push(DepError::Unresolved { task, dep }),
+                        _ => errors.push(DepError::Ambiguous { task, dep, matches: matches.len() }),
+                    }
+                }
+                deps
+            })
+            .collect();
+        Self { nodes, deps, errors }
+    }
+
+    pub fn nodes(&self) -> &[DepNode<'a>] {
+        &self.nodes
+    }
+
+    /// the tasks the task `idx` depends on
+    pub fn deps(&self, idx: usize) -> &[usize] {
+        &self.deps[idx]
+    }
+
+    /// the references that could not be resolved, followed by the cycles of the graph
+    pub fn errors(&self) -> Vec<DepError> {
+        let mut errors = self.errors.clone();
+        errors.extend(self.cycles().into_iter().map(|cycle| {
+            DepError::Cycle(cycle.into_iter().map(|idx| self.nodes[idx].task.name().to_string()).collect())
+        }));
+        errors
+    }
+
+    fn is_done(&self, idx: usize) -> bool {
+        self.nodes[idx].task.flags().contains(TaskFlags::DONE)
+    }
+
+    /// whether the task `idx` is blocked: by a dependency that is not done if it has any, or by
+    /// its `BLOCKED` flag otherwise
+    pub fn is_blocked(&self, idx: usize) -> bool {
+        match self.evaluated_blocked(idx) {
+            Some(blocked) => blocked,
+            None => self.nodes[idx].task.flags().contains(TaskFlags::BLOCKED),
+        }
+    }
+
+    /// whether the task `idx` is blocked by its dependencies, if it has any
+    fn evaluated_blocked(&self, idx: usize) -> Option<bool> {
+        if self.nodes[idx].task.deps().is_empty() {
+            return None;
+        }
+        Some(self.deps[idx].iter().any(|dep| !self.is_done(*dep)))
+    }
+
+    /// finds a cycle through every group of tasks that depend on each other, including tasks that
+    /// depend on themselves. Each cycle starts at its first task in the document.
+    pub fn cycles(&self) -> Vec<Vec<usize>> {
+        let mut cycles: Vec<Vec<usize>> = strongly_connected(&self.deps).into_iter()
+            .filter(|component| component.len() > 1 || self.deps[component[0]].contains(&component[0]))
+            .map(|component| {
+                let members: BTreeSet<usize> = component.iter().copied().collect();
+                // every task of a component depends on another of its tasks, so following these
+                // dependencies from any task must come back around
+                let mut walk = vec![*members.iter().next().unwrap()];
+                loop {
+                    let last = walk[walk.len() - 1];
+                    let next = *self.deps[last].iter().find(|dep| members.contains(dep)).unwrap();
+                    if let Some(start) = walk.iter().position(|idx| *idx == next) {
+                        let mut cycle = walk.split_off(start);
+                        let first = cycle.iter().enumerate().min_by_key(|(_, idx)| **idx).unwrap().0;
+                        cycle.rotate_left(first);
+                        break cycle;
+                    }
+                    walk.push(next);
+                }
+            })
+            .collect();
+        cycles.sort();
+        cycles
+    }
+
+    /// the tasks that are not done, ordered so that every task comes after the tasks it depends
+    /// on. Tasks are otherwise kept in the order they are written. Tasks in a cycle, and the tasks
+    /// that depend on them, have no such order and are left out.
+    pub fn topological_order(&self) -> Vec<usize> {
+        let pending = |idx: &usize| !self.is_done(*idx);
+        let mut waiting_on: Vec<usize> = self.deps.iter()
+            .map(|deps| deps.iter().filter(|dep| pending(dep)).count())
+            .collect();
+        let mut ready: BTreeSet<usize> = (0..self.nodes.len()).filter(|idx| pending(idx) && waiting_on[*idx] == 0).collect();
+
+        let mut order: Vec<usize> = vec![];
+        while let Some(idx) = ready.iter().next().copied() {
+            ready.remove(&idx);
+            order.push(idx);
+            for dependent in (0..self.nodes.len()).filter(|dependent| pending(dependent)) {
+                for _ in self.deps[dependent].iter().filter(|dep| **dep == idx) {
+                    waiting_on[dependent] -= 1;
+                    if waiting_on[dependent] == 0 {
+                        ready.insert(dependent);
+                    }
+                }
+            }
+        }
+        order
+    }
+
+    /// the tasks that can be started: those that are neither done nor blocked, in topological order
+    pub fn ready(&self) -> Vec<usize> {
+        self.topological_order().into_iter().filter(|idx| !self.is_blocked(*idx)).collect()
+    }
+}
+
+/// how many tasks an evaluation changed, and the dependency errors found
+#[derive(Debug, PartialEq)]
+pub struct Evaluation {
+    pub blocked: usize,
+    pub unblocked: usize,
+    pub errors: Vec<DepError>,
+}
+
+/// sets the `BLOCKED` flag of every task with dependencies in `document` if any of them is not
+/// done, and clears it otherwise
+pub fn evaluate(document: &mut Document) -> Evaluation {
+    let (evaluated, errors): (Vec<Option<bool>>, Vec<DepError>) = {
+        let graph = DepGraph::new(document);
+        ((0..graph.nodes.len()).map(|idx| graph.evaluated_blocked(idx)).collect(), graph.errors())
+    };
+
+    let mut evaluation = Evaluation { blocked: 0, unblocked: 0, errors };
+    let mut evaluated = evaluated.into_iter();
+    visit_tasks_mut(document.items_mut(), &mut |task| {
+        let blocked = match evaluated.next().flatten() {
+            Some(blocked) => blocked,
+            None => return,
+        };
+        let flags = task.flags();
+        if blocked && !flags.contains(TaskFlags::BLOCKED) {
+            task.build_flags(flags | TaskFlags::BLOCKED);
+            evaluation.blocked += 1;
+        } else if !blocked && flags.contains(TaskFlags::BLOCKED) {
+            task.build_flags(flags - TaskFlags::BLOCKED);
+            evaluation.unblocked += 1;
+        }
+    });
+    evaluation
+}
+
+fn collect_nodes<'a>(items: &'a [Item], path: &mut Vec<&'a str>, nodes: &mut Vec<DepNode<'a>>) {
+    for item in items.iter() {
+        match item {
+            Item::Section(section) => {
+                path.push(section.specifier());
+                collect_nodes(section.items(), path, nodes);
+                path.pop();
+            }
+            Item::Task(tree) => collect_tree_nodes(tree, path, nodes),
+            _ => {}
+        }
+    }
+}
+
+fn collect_tree_nodes<'a>(tree: &'a TaskTree, path: &mut Vec<&'a str>, nodes: &mut Vec<DepNode<'a>>) {
+    path.push(tree.task().name());
+    nodes.push(DepNode { path: path.clone(), task: tree.task() });
+    for subtask in tree.subtasks().iter() {
+        collect_tree_nodes(subtask, path, nodes);
+    }
+    path.pop();
+}
+
+/// visits every task of `items` in the order they are written
+fn visit_tasks_mut(items: &mut [Item], f: &mut dyn FnMut(&mut Task)) {
+    fn visit_tree(tree: &mut TaskTree, f: &mut dyn FnMut(&mut Task)) {
+        f(tree.task_mut());
+        for subtask in tree.subtasks_mut().iter_mut() {
+            visit_tree(subtask, f);
+        }
+    }
+
+    for item in items.iter_mut() {
+        match item {
+            Item::Section(section) => visit_tasks_mut(section.items_mut(), f),
+            Item::Task(tree) => visit_tree(tree, f),
+            _ => {}
+        }
+    }
+}
+
+/// the strongly connected components of the graph with the edges `edges`, by Tarjan's algorithm
+fn strongly_connected(edges: &[Vec<usize>]) -> Vec<Vec<usize>> {
+    struct State<'e> {
+        edges: &'e [Vec<usize>],
+        index: Vec<Option<usize>>,
+        low: Vec<usize>,
+        on_stack: Vec<bool>,
+        stack: Vec<usize>,
+        next: usize,
+        components: Vec<Vec<usize>>,
+    }
+
+    fn connect(state: &mut State, v: usize) {
+        state.index[v] = Some(state.next);
+        state.low[v] = state.next;
+        state.next += 1;
+        state.stack.push(v);
+        state.on_stack[v] = true;
+
+        for w in state.edges[v].iter().copied() {
+            match state.index[w] {
+                None => {
+                    connect(state, w);
+                    state.low[v] = state.low[v].min(state.low[w]);
+                }
+                Some(index) if state.on_stack[w] => state.low[v] = state.low[v].min(index),
+                Some(_) => {}
+            }
+        }
+
+        if Some(state.low[v]) == state.index[v] {
+            let mut component: Vec<usize> = vec![];
+            while let Some(w) = state.stack.pop() {
+                state.on_stack[w] = false;
+                component.push(w);
+                if w == v {
+                    break;
+                }
+            }
+            component.sort_unstable();
+            state.components.push(component);
+        }
+    }
+
+    let len = edges.len();
+    let mut state = State {
+        edges,
+        index: vec![None; len],
+        low: vec![0; len],
+        on_stack: vec![false; len],
+        stack: vec![],
+        next: 0,
+        components: vec![],
+    };
+    for v in 0..len {
+        if state.index[v].is_none() {
+            connect(&mut state, v);
+        }
+    }
+    state.components
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DOCUMENT: &str = "\
+() Errand
+[Work]
+\t(0/4) Report (deps: Sources)
+\t\t~(2/2) Outline
+\t\t(0/2) Draft (deps: Report/Outline, Review/Outline)
+\t(0/1) Sources
+\t[Review]
+\t\tB() Outline
+\t\t() Slides (deps: Report/Draft)
+";
+
+    fn names(graph: &DepGraph, tasks: &[usize]) -> Vec<String> {
+        tasks.iter().map(|idx| graph.nodes()[*idx].path.join("/")).collect()
+    }
+
+    #[test]
+    fn test_resolve() {
+        let document: Document = DOCUMENT.parse().unwrap();
+        let graph = DepGraph::new(&document);
+        assert_eq!(names(&graph, graph.deps(1)), vec!["Work/Sources"]);
+        assert_eq!(graph.deps(3), &[2, 5]);
+        assert_eq!(names(&graph, graph.deps(6)), vec!["Work/Report/Draft"]);
+        assert!(graph.errors().is_empty());
+
+        let document: Document = "() A (deps: #b)\n() Renamed B (#b)\n".parse().unwrap();
+        assert_eq!(DepGraph::new(&document).deps(0), &[1]);
+
+        let document: Document = "() A (deps: Outline, Missing)\n() Outline\n\t() Outline\n".parse().unwrap();
+        assert_eq!(DepGraph::new(&document).errors(), vec![
+            DepError::Ambiguous { task: "A".into(), dep: "Outline".into(), matches: 2 },
+            DepError::Unresolved { task: "A".into(), dep: "Missing".into() },
+        ]);
+    }
+
+    #[test]
+    fn test_ready() {
+        let document: Document = DOCUMENT.parse().unwrap();
+        let graph = DepGraph::new(&document);
+        assert!(graph.is_blocked(1));
+        // the review outline has no dependencies, so it keeps its flag
+        assert!(graph.is_blocked(5));
+        assert_eq!(names(&graph, &graph.topological_order()),
+            vec!["Errand", "Work/Sources", "Work/Report", "Work/Review/Outline", "Work/Report/Draft", "Work/Review/Slides"]);
+        assert_eq!(names(&graph, &graph.ready()), vec!["Errand", "Work/Sources"]);
+    }
+
+    #[test]
+    fn test_cycles() {
+        let document: Document = "\
+() A (deps: C)
+() B (deps: A)
+() C (deps: B)
+() D (deps: D)
+() E (deps: A)
+() F
+".parse().unwrap();
+        let graph = DepGraph::new(&document);
+        assert_eq!(graph.cycles(), vec![vec![0, 2, 1], vec![3]]);
+        assert_eq!(graph.errors().iter().map(|e| e.to_string()).collect::<Vec<String>>(),
+            vec!["dependency cycle: A -> C -> B -> A", "dependency cycle: D -> D"]);
+        // tasks in or after a cycle have no topological order
+        assert_eq!(graph.topological_order(), vec![5]);
+    }
+
+    #[test]
+    fn test_evaluate() {
+        let mut document: Document = DOCUMENT.parse().unwrap();
+        let evaluation = evaluate(&mut document);
+        assert_eq!((evaluation.blocked, evaluation.unblocked), (3, 0));
+        assert!(evaluation.errors.is_empty());
+        assert_eq!(document.to_string(), "\
+() Errand
+[Work]
+\tB(0/4) Report (deps: Sources)
+\t\t~(2/2) Outline
+\t\tB(0/2) Draft (deps: Report/Outline, Review/Outline)
+\t(0/1) Sources
+\t[Review]
+\t\tB() Outline
+\t\tB() Slides (deps: Report/Draft)
+");
+
+        let mut done: Document = "B() Draft (deps: Outline)\n~() Outline\n".parse().unwrap();
+        assert_eq!(evaluate(&mut done).unblocked, 1);
+        assert_eq!(done.to_string(), "() Draft (deps: Outline)\n~() Outline\n");
+    }
+}
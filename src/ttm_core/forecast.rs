@@ -0,0 +1,278 @@
+//! Forecasts when tasks will be done from the history of their progress.
+//! A task is forecast if it has a due date and a count with an objective, taking its lifetime count
+//! or its count for today when it has none. Its history is the task as it was on earlier days, as
+//! in the files of those days. The velocity of a task is the blocks it gained per day from the first
+//! day of its history to the last, and the remaining blocks are projected at that velocity from
+//! the last day on. A task will miss its deadline if the projected finish is after its due date, or
+//! if it gained no blocks at all.
+
+#![allow(dead_code)]
+
+use crate::ttm_io::calendar::{CivilDate, SeasonCalendar};
+use crate::ttm_io::document::Document;
+use crate::ttm_io::ids;
+use crate::ttm_io::stat::Stat;
+use crate::ttm_io::task::Task;
+use std::fmt;
+
+/// the blocks done of a task on a day
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Sample {
+    pub date: CivilDate,
+    pub act: i32,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum ForecastStatus {
+    Done,
+    /// will be done by the due date
+    OnTrack,
+    /// will be done after the due date
+    WillMiss,
+    /// gained no blocks over its history, so it will not be done at all
+    Stalled,
+    /// has a single day of history, giving no velocity
+    NoHistory,
+}
+
+impl ForecastStatus {
+    pub fn will_miss(self) -> bool {
+        matches!(self, Self::WillMiss | Self::Stalled)
+    }
+}
+
+impl fmt::Display for ForecastStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let msg = match self {
+            Self::Done => "done",
+            Self::OnTrack => "on track",
+            Self::WillMiss => "will miss",
+            Self::Stalled => "stalled",
+            Self::NoHistory => "no history",
+        };
+        write!(f, "{}", msg)
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Forecast {
+    pub name: String,
+    pub exp: i32,
+    pub due: CivilDate,
+    /// the progress of the task, oldest day first
+    pub samples: Vec<Sample>,
+    /// blocks per day
+    pub velocity: Option<f64>,
+    /// the day the remaining blocks are projected to be done
+    pub finish: Option<CivilDate>,
+    pub status: ForecastStatus,
+}
+
+impl Forecast {
+    /// the blocks done on the last day of the history
+    pub fn act(&self) -> i32 {
+        self.samples.last().map_or(0, |sample| sample.act)
+    }
+
+    pub fn remaining(&self) -> i32 {
+        (self.exp - self.act()).max(0)
+    }
+
+    /// the remaining blocks projected at `date`, from the last day of the history
+    pub fn projected_remaining(&self, date: CivilDate) -> i32 {
+        let last = match self.samples.last() {
+            Some(last) => last,
+            None => return self.exp,
+        };
+        let days = (date.to_days() - last.date.to_days()).max(0) as f64;
+        let done = (self.velocity.unwrap_or(0.0) * days).floor() as i32;
+        (self.remaining() - done).max(0)
+    }
+}
+
+impl fmt::Display for Forecast {
+    /// writes a line summing up the forecast
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.act(), self.exp)?;
+        match self.velocity {
+            Some(velocity) => write!(f, ", {:.1}/day", velocity)?,
+            None => write!(f, ", -/day")?,
+        }
+        match self.finish {
+            Some(finish) => write!(f, ", finish {}", finish)?,
+            None => write!(f, ", finish -")?,
+        }
+        write!(f, ", due {}: {}", self.due, self.status)
+    }
+}
+
+/// the (act, exp) of a task with an objective
+fn progress(task: &Task) -> Option<(i32, i32)> {
+    match task.progress_stat()? {
+        Stat::Count { act, exp: Some(exp) } => Some((act.unwrap_or(0), exp)),
+        _ => None,
+    }
+}
+
+/// forecasts a task from its `history`, oldest day first, where the last day holds the task as it
+/// is now. Short date codes are taken to be in the season of that day. Gives None for tasks that
+/// are not forecast.
+pub fn forecast(history: &[(CivilDate, &Task)], calendar: &SeasonCalendar) -> Option<Forecast> {
+    let (today, task) = *history.last()?;
+    let (_act, exp) = progress(task)?;
+    let (year, season) = calendar.season_of(today).ok()?;
+    let due = calendar.to_civil(&task.due_date()?, year, season);
+
+    let samples: Vec<Sample> = history.iter()
+        .filter_map(|(date, task)| Some(Sample { date: *date, act: progress(task)?.0 }))
+        .collect();
+    let (first, last) = (samples[0], samples[samples.len() - 1]);
+    let days = last.date.to_days() - first.date.to_days();
+    let velocity = (days > 0).then(|| (last.act - first.act) as f64 / days as f64);
+
+    let remaining = (exp - last.act).max(0);
+    let finish = match velocity {
+        _ if remaining == 0 => Some(last.date),
+        Some(velocity) if velocity > 0.0 => {
+            Some(CivilDate::from_days(last.date.to_days() + (remaining as f64 / velocity).ceil() as i64))
+        }
+        _ => None,
+    };
+    let status = match (finish, velocity) {
+        _ if remaining == 0 => ForecastStatus::Done,
+        (Some(finish), _) if finish <= due => ForecastStatus::OnTrack,
+        (Some(_), _) => ForecastStatus::WillMiss,
+        (None, Some(_)) => ForecastStatus::Stalled,
+        (None, None) => ForecastStatus::NoHistory,
+    };
+
+    Some(Forecast { name: task.name().to_string(), exp, due, samples, velocity, finish, status })
+}
+
+/// forecasts the tasks of the last of `days`, each a document of a day, oldest first. The history
+/// of a task is the same task in each day it is in, as by `ids::same_task`.
+pub fn forecast_documents(days: &[(CivilDate, Document)], calendar: &SeasonCalendar) -> Vec<Forecast> {
+    let (_today, current) = match days.last() {
+        Some(last) => last,
+        None => return vec![],
+    };
+    current.tasks().into_iter()
+        .filter_map(|task| {
+            let history: Vec<(CivilDate, &Task)> = days.iter()
+                .filter_map(|(date, document)| {
+                    document.tasks().into_iter().find(|t| ids::same_task(t, task)).map(|t| (*date, t))
+                })
+                .collect();
+            forecast(&history, calendar)
+        })
+        .collect()
+}
+
+/// draws the remaining blocks of the forecast as a bar per day of its history, followed by the
+/// projected bars at its due date and finish. Bars are `width` characters at the expected count.
+pub fn burndown(forecast: &Forecast, width: usize) -> String {
+    let bar = |remaining: i32, c: char| {
+        let len = (remaining.max(0) as usize * width + forecast.exp as usize / 2) / forecast.exp.max(1) as usize;
+        format!("{:width$}", c.to_string().repeat(len), width = width)
+    };
+
+    let mut out = format!("{}\n", forecast.name);
+    for sample in forecast.samples.iter() {
+        let remaining = forecast.exp - sample.act;
+        out.push_str(&format!("{} |{}| {}\n", sample.date, bar(remaining, '#'), remaining.max(0)));
+    }
+
+    let last = forecast.samples.last().map(|sample| sample.date);
+    let mut projected: Vec<(CivilDate, &str)> = vec![];
+    if last.is_none_or(|last| forecast.due > last) {
+        projected.push((forecast.due, "due"));
+    }
+    if let Some(finish) = forecast.finish.filter(|finish| Some(*finish) > last) {
+        projected.push((finish, "finish"));
+    }
+    projected.sort();
+    for (date, label) in projected {
+        let remaining = forecast.projected_remaining(date);
+        out.push_str(&format!("{} |{}| {} {}\n", date, bar(remaining, '.'), remaining, label));
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn day(day: u32) -> CivilDate {
+        CivilDate::new(2021, 1, day)
+    }
+
+    fn tasks(stats: &[&str]) -> Vec<Task> {
+        // W3R of Winter 2021 is 2021-01-14
+        stats.iter().map(|stat| format!("({}) Report (due: W3R)", stat).parse().unwrap()).collect()
+    }
+
+    #[test]
+    fn test_forecast() {
+        let calendar = SeasonCalendar::default();
+        let forecast_of = |stats: &[&str], days: &[u32]| {
+            let tasks = tasks(stats);
+            let history: Vec<(CivilDate, &Task)> = days.iter().map(|d| day(*d)).zip(tasks.iter()).collect();
+            forecast(&history, &calendar).unwrap()
+        };
+
+        let on_track = forecast_of(&["0/10", "4/10"], &[4, 6]);
+        assert_eq!(on_track.velocity, Some(2.0));
+        assert_eq!(on_track.finish, Some(day(9)));
+        assert_eq!(on_track.status, ForecastStatus::OnTrack);
+        assert_eq!(on_track.to_string(), "4/10, 2.0/day, finish 2021-01-09, due 2021-01-14: on track");
+
+        let missing = forecast_of(&["0/10", "2/10"], &[4, 8]);
+        assert_eq!(missing.finish, Some(day(24)));
+        assert!(missing.status.will_miss());
+        assert_eq!(missing.projected_remaining(day(14)), 5);
+
+        assert_eq!(forecast_of(&["3/10", "3/10"], &[4, 8]).status, ForecastStatus::Stalled);
+        assert_eq!(forecast_of(&["3/10"], &[4]).status, ForecastStatus::NoHistory);
+        assert_eq!(forecast_of(&["3/10", "10/10"], &[4, 8]).status, ForecastStatus::Done);
+
+        // tasks without an objective or a due date are not forecast
+        let task: Task = "(3) Report (due: W3R)".parse().unwrap();
+        assert_eq!(forecast(&[(day(4), &task)], &calendar), None);
+        let task: Task = "(3/10) Report".parse().unwrap();
+        assert_eq!(forecast(&[(day(4), &task)], &calendar), None);
+    }
+
+    #[test]
+    fn test_forecast_documents() {
+        let days: Vec<(CivilDate, Document)> = vec![
+            (day(4), "(0/10) Report (due: W3R)\n() Other\n".parse().unwrap()),
+            (day(5), "(1/8) Slides (due: W3R)\n".parse().unwrap()),
+            (day(6), "(4/10) Report (due: W3R)\n(2/8) Slides (due: W3R)\n".parse().unwrap()),
+        ];
+        let forecasts = forecast_documents(&days, &SeasonCalendar::default());
+        let names: Vec<(&str, ForecastStatus)> = forecasts.iter().map(|f| (f.name.as_str(), f.status)).collect();
+        assert_eq!(names, vec![("Report", ForecastStatus::OnTrack), ("Slides", ForecastStatus::OnTrack)]);
+        assert_eq!(forecasts[1].finish, Some(day(12)));
+
+        // a task with an id is followed through a rename
+        let days: Vec<(CivilDate, Document)> = vec![
+            (day(4), "(0/10) Report (due: W3R; #r)\n".parse().unwrap()),
+            (day(6), "(4/10) Final report (due: W3R; #r)\n".parse().unwrap()),
+        ];
+        assert_eq!(forecast_documents(&days, &SeasonCalendar::default())[0].samples.len(), 2);
+    }
+
+    #[test]
+    fn test_burndown() {
+        let tasks = tasks(&["0/10", "2/10"]);
+        let history = vec![(day(4), &tasks[0]), (day(8), &tasks[1])];
+        let forecast = forecast(&history, &SeasonCalendar::default()).unwrap();
+        assert_eq!(burndown(&forecast, 10), "\
+Report
+2021-01-04 |##########| 10
+2021-01-08 |########  | 8
+2021-01-14 |.....     | 5 due
+2021-01-24 |          | 0 finish
+");
+    }
+}
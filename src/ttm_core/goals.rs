@@ -0,0 +1,214 @@
+//! Aggregates the goal stats of tasks. A task tracks a goal with a gName suffix field, as in
+//! `(gPushups: 5/10)`, holding the day, accumulated and context stats of the goal like the stats
+//! of the task itself.
+//! Goals are summed by name across every task they appear in, over any number of documents, such
+//! as the files of several days. Each task counts its accumulated goal stat, or its day stat when it
+//! has none. Stats add to a goal as:
+//!     - Count: The actual and the expected count. Counts without an objective only add to the actual.
+//!     - Bool: 1 actual if done, and 1 expected if required.
+//!     - RequiredCount: The actual count.
+//!     - Unknown: Nothing.
+//! A goal is behind target while its actual total is short of its expected total.
+
+#![allow(dead_code)]
+
+use crate::ttm_io::document::Document;
+use crate::ttm_io::stat::Stat;
+use crate::ttm_io::task::Task;
+use crate::ttm_io::task_tree::{TaskTree, INDENT};
+use std::collections::BTreeMap;
+use std::fmt;
+
+/// the sum of the stats of a goal
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub struct GoalTotal {
+    pub act: i32,
+    pub exp: i32,
+    /// how many task stats were summed
+    pub tasks: usize,
+}
+
+impl GoalTotal {
+    fn add_stat(&mut self, stat: Stat) {
+        let (act, exp) = match stat {
+            Stat::Count { act, exp } => (act.unwrap_or(0), exp.unwrap_or(0)),
+            Stat::Bool { act, exp } => (act as i32, exp as i32),
+            Stat::RequiredCount { act, .. } => (act, 0),
+            Stat::Unknown => return,
+        };
+        self.act += act;
+        self.exp += exp;
+        self.tasks += 1;
+    }
+
+    fn add_total(&mut self, other: &GoalTotal) {
+        self.act += other.act;
+        self.exp += other.exp;
+        self.tasks += other.tasks;
+    }
+
+    /// how many units are left until the expected total is reached
+    pub fn remaining(&self) -> i32 {
+        (self.exp - self.act).max(0)
+    }
+
+    pub fn is_behind(&self) -> bool {
+        self.act < self.exp
+    }
+
+    /// the actual total in percent of the expected total, if one is expected
+    pub fn percent(&self) -> Option<i32> {
+        (self.exp > 0).then(|| (self.act.max(0) as i64 * 100 / self.exp as i64) as i32)
+    }
+}
+
+impl fmt::Display for GoalTotal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.percent() {
+            Some(percent) => write!(f, "{}/{} ({}%)", self.act, self.exp, percent),
+            None => write!(f, "{}", self.act),
+        }
+    }
+}
+
+/// goal totals by goal name
+pub type GoalTotals = BTreeMap<String, GoalTotal>;
+
+fn add_totals(totals: &mut GoalTotals, other: &GoalTotals) {
+    for (goal, total) in other.iter() {
+        totals.entry(goal.clone()).or_default().add_total(total);
+    }
+}
+
+/// the goal totals of a single task
+pub fn task_goals(task: &Task) -> GoalTotals {
+    let mut totals = GoalTotals::new();
+    for (goal, [day, accum, _context]) in task.other_stats().iter() {
+        // unknown stats add nothing, so they are not counted as stats of the goal
+        if let Some(stat) = accum.or(*day).filter(|stat| *stat != Stat::Unknown) {
+            totals.entry(goal.clone()).or_default().add_stat(stat);
+        }
+    }
+    totals
+}
+
+/// the goal totals of a task rolled up with those of all of its subtasks
+pub fn rollup(tree: &TaskTree) -> GoalTotals {
+    let mut totals = task_goals(tree.task());
+    for subtask in tree.subtasks().iter() {
+        add_totals(&mut totals, &rollup(subtask));
+    }
+    totals
+}
+
+/// the goal totals of every task in `documents`
+pub fn document_goals<'a>(documents: impl IntoIterator<Item = &'a Document>) -> GoalTotals {
+    let mut totals = GoalTotals::new();
+    for document in documents {
+        for task in document.tasks() {
+            add_totals(&mut totals, &task_goals(task));
+        }
+    }
+    totals
+}
+
+/// the goals of `totals` behind target, furthest behind first
+pub fn behind_target(totals: &GoalTotals) -> Vec<(&str, &GoalTotal)> {
+    let mut behind: Vec<(&str, &GoalTotal)> = totals.iter()
+        .filter(|(_goal, total)| total.is_behind())
+        .map(|(goal, total)| (goal.as_str(), total))
+        .collect();
+    // a stable sort keeps goals that are as far behind in order of name
+    behind.sort_by_key(|(_goal, total)| total.percent());
+    behind
+}
+
+/// writes a line per goal, with the goal names aligned
+pub fn report<'a>(goals: impl IntoIterator<Item = (&'a str, &'a GoalTotal)>) -> String {
+    let goals: Vec<(&str, &GoalTotal)> = goals.into_iter().collect();
+    let width = goals.iter().map(|(goal, _total)| goal.chars().count()).max().unwrap_or(0);
+    goals.iter()
+        .map(|(goal, total)| format!("{:width$}  {}\n", goal, total, width = width))
+        .collect()
+}
+
+/// writes the task trees of `document` with the rolled up goal totals of every task below it. Tasks
+/// without goals are left out, along with their subtasks if none of them have goals either.
+pub fn tree_report(document: &Document) -> String {
+    let mut out = String::new();
+    for tree in document.task_trees() {
+        for (depth, subtree) in tree.walk() {
+            let totals = rollup(subtree);
+            if totals.is_empty() {
+                continue;
+            }
+            let goals: Vec<String> = totals.iter().map(|(goal, total)| format!("{} {}", goal, total)).collect();
+            out.push_str(&format!("{}{}: {}\n", INDENT.repeat(depth), subtree.task().name(), goals.join("; ")));
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn total(act: i32, exp: i32, tasks: usize) -> GoalTotal {
+        GoalTotal { act, exp, tasks }
+    }
+
+    #[test]
+    fn test_task_goals() {
+        let task: Task = "() Workout (gPushups: 5/10, 20/100; gStretch: !; gRuns: 3; gSleep: ?)".parse().unwrap();
+        let totals = task_goals(&task);
+        // the accumulated stat is counted over the day stat
+        assert_eq!(totals["Pushups"], total(20, 100, 1));
+        assert_eq!(totals["Stretch"], total(1, 1, 1));
+        assert_eq!(totals["Runs"], total(3, 0, 1));
+        assert!(!totals.contains_key("Sleep"));
+    }
+
+    #[test]
+    fn test_rollup_and_days() {
+        let monday: Document = "\
+[Fitness]
+() Fitness (gPushups: 0/10)
+\t() Morning (gPushups: 10/20)
+\t() Evening (gPushups: 5/20; gReading: 30/20)
+() Other
+".parse().unwrap();
+        let tuesday: Document = "() Fitness (gPushups: 25/10)\n".parse().unwrap();
+
+        let trees = monday.task_trees();
+        let fitness = rollup(trees[0]);
+        assert_eq!(fitness["Pushups"], total(15, 50, 3));
+        assert_eq!(fitness["Reading"], total(30, 20, 1));
+        assert!(rollup(trees[1]).is_empty());
+
+        let totals = document_goals([&monday, &tuesday]);
+        assert_eq!(totals["Pushups"], total(40, 60, 4));
+        assert_eq!(behind_target(&totals), vec![("Pushups", &total(40, 60, 4))]);
+
+        assert_eq!(report(totals.iter().map(|(goal, total)| (goal.as_str(), total))), "\
+Pushups  40/60 (66%)
+Reading  30/20 (150%)
+");
+        assert_eq!(tree_report(&monday), "\
+Fitness: Pushups 15/50 (30%); Reading 30/20 (150%)
+	Morning: Pushups 10/20 (50%)
+	Evening: Pushups 5/20 (25%); Reading 30/20 (150%)
+");
+    }
+
+    #[test]
+    fn test_behind_target() {
+        let totals: GoalTotals = vec![
+            ("A".to_string(), total(5, 10, 1)),
+            ("B".to_string(), total(1, 10, 1)),
+            ("C".to_string(), total(10, 10, 1)),
+            ("D".to_string(), total(1, 0, 1)),
+        ].into_iter().collect();
+        let names: Vec<&str> = behind_target(&totals).iter().map(|(goal, _total)| *goal).collect();
+        assert_eq!(names, vec!["B", "A"]);
+    }
+}
@@ -0,0 +1,214 @@
+//! Computes habit statistics of block tracker entries over a history of weeks.
+//! Each week is a block tracker table, oldest week first. Every day stat of an entry is taken as:
+//!     - Done: `!`, a count reaching its objective, or a count above 0 without one.
+//!     - Missed: `-`, a count short of its objective, or 0 without one.
+//!     - Neutral: The day is not required, as with `/-`, `!/-` or `5/-`.
+//!     - Unknown: `?`, or a week the entry is not in.
+//! Streaks are runs of done days. Neutral and unknown days neither extend nor break a streak, so
+//! the days of the current week yet to come, written `?`, leave the current streak standing.
+//! Completion rates only count done and missed days.
+
+#![allow(dead_code)]
+
+use crate::ttm_io::block_tracker::BlockTrackerEntry;
+use crate::ttm_io::stat::Stat;
+use std::borrow::Borrow;
+use std::collections::BTreeMap;
+use std::fmt;
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum DayOutcome {
+    Done,
+    Missed,
+    Neutral,
+    Unknown,
+}
+
+impl From<Stat> for DayOutcome {
+    fn from(stat: Stat) -> Self {
+        let done = match stat {
+            Stat::Bool { exp: false, .. } | Stat::RequiredCount { exp: false, .. } => return Self::Neutral,
+            Stat::Unknown => return Self::Unknown,
+            Stat::Bool { act, .. } => act,
+            Stat::Count { act, exp: Some(exp) } => act.unwrap_or(0) >= exp,
+            Stat::Count { act, exp: None } => act.unwrap_or(0) > 0,
+            Stat::RequiredCount { act, .. } => act > 0,
+        };
+        if done { Self::Done } else { Self::Missed }
+    }
+}
+
+/// the habit statistics of an entry
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct HabitStats {
+    /// done days since the last missed day
+    pub current_streak: u32,
+    pub longest_streak: u32,
+    /// (done, done or missed) days per weekday, indexed Monday to Sunday
+    pub weekdays: [(u32, u32); 7],
+    /// weeks whose actual total reached their expected total
+    pub weeks_over_target: u32,
+    /// weeks with an expected total
+    pub weeks_with_target: u32,
+    pub unknown_days: u32,
+}
+
+impl HabitStats {
+    /// computes the statistics of an entry from its weeks, oldest first. Weeks the entry is not in
+    /// are given as None.
+    pub fn from_weeks<'a>(weeks: impl IntoIterator<Item = Option<&'a BlockTrackerEntry>>) -> Self {
+        let mut stats = Self::default();
+        for week in weeks {
+            let week_stats = match week {
+                Some(entry) => *entry.week_stats(),
+                None => [Stat::Unknown; 7],
+            };
+            for (day, stat) in week_stats.iter().enumerate() {
+                stats.add_day(day, DayOutcome::from(*stat));
+            }
+
+            let (act, exp) = week_total(&week_stats);
+            if exp > 0 {
+                stats.weeks_with_target += 1;
+                if act >= exp {
+                    stats.weeks_over_target += 1;
+                }
+            }
+        }
+        stats
+    }
+
+    fn add_day(&mut self, day: usize, outcome: DayOutcome) {
+        match outcome {
+            DayOutcome::Done => {
+                self.current_streak += 1;
+                self.longest_streak = self.longest_streak.max(self.current_streak);
+                self.weekdays[day].0 += 1;
+                self.weekdays[day].1 += 1;
+            }
+            DayOutcome::Missed => {
+                self.current_streak = 0;
+                self.weekdays[day].1 += 1;
+            }
+            DayOutcome::Neutral => {}
+            DayOutcome::Unknown => self.unknown_days += 1,
+        }
+    }
+
+    /// the completion rate of a weekday in percent, if any of its days were done or missed
+    pub fn completion_rate(&self, day: usize) -> Option<u32> {
+        let (done, counted) = self.weekdays[day];
+        (counted > 0).then(|| done * 100 / counted)
+    }
+}
+
+impl fmt::Display for HabitStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "streak {} (longest {});", self.current_streak, self.longest_streak)?;
+        for (day, name) in ["M", "T", "W", "R", "F", "S", "U"].iter().enumerate() {
+            match self.completion_rate(day) {
+                Some(rate) => write!(f, " {} {}%", name, rate)?,
+                None => write!(f, " {} -", name)?,
+            }
+        }
+        write!(f, "; {}/{} weeks over target", self.weeks_over_target, self.weeks_with_target)
+    }
+}
+
+/// the (actual, expected) total of a week, summed over the days it holds progress for
+fn week_total(week_stats: &[Stat; 7]) -> (i32, i32) {
+    week_stats.iter()
+        .filter_map(|stat| match *stat {
+            Stat::Count { act, exp: Some(exp) } => Some((act.unwrap_or(0), exp)),
+            Stat::Bool { act, exp: true } => Some((act as i32, 1)),
+            _ => None,
+        })
+        .fold((0, 0), |(act, exp), (day_act, day_exp)| (act + day_act, exp + day_exp))
+}
+
+/// computes the statistics of every entry in a history of block tracker tables, oldest week first
+pub fn history_stats<E: Borrow<BlockTrackerEntry>>(weeks: &[Vec<E>]) -> BTreeMap<String, HabitStats> {
+    let mut names: Vec<&str> = weeks.iter()
+        .flat_map(|week| week.iter().map(|entry| entry.borrow().entry_name()))
+        .collect();
+    names.sort_unstable();
+    names.dedup();
+
+    names.into_iter()
+        .map(|name| {
+            let entries = weeks.iter()
+                .map(|week| week.iter().map(|entry| entry.borrow()).find(|entry| entry.entry_name() == name));
+            (name.to_string(), HabitStats::from_weeks(entries))
+        })
+        .collect()
+}
+
+/// writes a line of statistics per entry, with the entry names aligned
+pub fn report(stats: &BTreeMap<String, HabitStats>) -> String {
+    let width = stats.keys().map(|name| name.chars().count()).max().unwrap_or(0);
+    stats.iter()
+        .map(|(name, stats)| format!("{:width$}  {}\n", name, stats, width = width))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(s: &str) -> BlockTrackerEntry {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn test_day_outcomes() {
+        let outcome = |s: &str| DayOutcome::from(s.parse::<Stat>().unwrap());
+        assert_eq!(outcome("!"), DayOutcome::Done);
+        assert_eq!(outcome("-"), DayOutcome::Missed);
+        assert_eq!(outcome("/-"), DayOutcome::Neutral);
+        assert_eq!(outcome("!/-"), DayOutcome::Neutral);
+        assert_eq!(outcome("5/-"), DayOutcome::Neutral);
+        assert_eq!(outcome("3/!"), DayOutcome::Done);
+        assert_eq!(outcome("4/4"), DayOutcome::Done);
+        assert_eq!(outcome("2/4"), DayOutcome::Missed);
+        assert_eq!(outcome("2"), DayOutcome::Done);
+        assert_eq!(outcome("0"), DayOutcome::Missed);
+        assert_eq!(outcome("?"), DayOutcome::Unknown);
+    }
+
+    #[test]
+    fn test_streaks() {
+        let weeks = [
+            entry("!  !  !  -  !  /- !  Run"),
+            entry("!  !  !  !  -  /- ?  Run"),
+            entry("!  /- ?  ?  ?  ?  ?  Run"),
+        ];
+        let stats = HabitStats::from_weeks(weeks.iter().map(Some));
+        // the neutral Saturday does not break the streak from Friday to the next Thursday
+        assert_eq!(stats.longest_streak, 6);
+        assert_eq!(stats.current_streak, 1);
+        assert_eq!(stats.weekdays[0], (3, 3));
+        assert_eq!(stats.completion_rate(3), Some(50));
+        assert_eq!(stats.completion_rate(5), None);
+        assert_eq!(stats.unknown_days, 6);
+        // only the days known so far count toward the target of the current week
+        assert_eq!((stats.weeks_over_target, stats.weeks_with_target), (1, 3));
+    }
+
+    #[test]
+    fn test_history_stats() {
+        let weeks = vec![
+            vec![entry("1/1 1/1 1/1 0/1 /- /- /- Read"), entry("! ! ! ! ! ! ! Run")],
+            vec![entry("2/1 1/1 1/1 0/1 /- /- /- Read")],
+        ];
+        let stats = history_stats(&weeks);
+        assert_eq!((stats["Read"].weeks_over_target, stats["Read"].weeks_with_target), (1, 2));
+        // Run is unknown in the second week
+        assert_eq!(stats["Run"].current_streak, 7);
+        assert_eq!(stats["Run"].unknown_days, 7);
+
+        assert_eq!(report(&stats), "\
+Read  streak 0 (longest 3); M 100% T 100% W 100% R 0% F - S - U -; 1/2 weeks over target
+Run   streak 7 (longest 7); M 100% T 100% W 100% R 100% F 100% S 100% U 100%; 1/1 weeks over target
+");
+    }
+}
@@ -0,0 +1,307 @@
+//! Checks documents for problems beyond parse errors. Every rule may be turned off on its own with
+//! a `LintConfig`:
+//!     - multiple-current: More than one task is CURRENT.
+//!     - done-unmet: A DONE task is short of its expected count, or was required and is not done.
+//!     - hard-before-due: The hard date of a task is before its due date.
+//!     - context-stat: The context stat of a task is not what its stats and the stats of its
+//!       subtasks add up to. Refer to `crate::ttm_io::task_tree::TaskTree::computed_context_stat`.
+//!     - priority-range: The priority of a task is out of 0-99.
+//!     - duplicate-name: Tasks of the same section have the same name. Tasks of nested sections are
+//!       apart.
+//!     - empty-section: A section holds nothing but blank lines.
+//!     - unknown-goal: A task tracks a goal that is not known. Known goals are those of the config,
+//!       and the names of the block tracker entries of the document, ignoring case.
+//! Problems are reported as `crate::ttm_io::diagnostic::Diagnostic`s, along with the parse errors.
+
+#![allow(dead_code)]
+
+use crate::config::Config;
+use crate::ttm_io::date::Season;
+use crate::ttm_io::diagnostic::{Diagnostic, Severity};
+use crate::ttm_io::document::{Document, DocumentParseError, Item};
+use crate::ttm_io::stat::Stat;
+use crate::ttm_io::task::{Task, TaskFlags};
+use crate::ttm_io::task_tree::TaskTree;
+use std::collections::{BTreeMap, BTreeSet};
+
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy)]
+pub enum Rule {
+    MultipleCurrent,
+    DoneUnmet,
+    HardBeforeDue,
+    ContextStat,
+    PriorityRange,
+    DuplicateName,
+    EmptySection,
+    UnknownGoal,
+}
+
+impl Rule {
+    pub const ALL: [Rule; 8] = [
+        Rule::MultipleCurrent, Rule::DoneUnmet, Rule::HardBeforeDue, Rule::ContextStat,
+        Rule::PriorityRange, Rule::DuplicateName, Rule::EmptySection, Rule::UnknownGoal,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Rule::MultipleCurrent => "multiple-current",
+            Rule::DoneUnmet => "done-unmet",
+            Rule::HardBeforeDue => "hard-before-due",
+            Rule::ContextStat => "context-stat",
+            Rule::PriorityRange => "priority-range",
+            Rule::DuplicateName => "duplicate-name",
+            Rule::EmptySection => "empty-section",
+            Rule::UnknownGoal => "unknown-goal",
+        }
+    }
+}
+
+impl std::str::FromStr for Rule {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Rule::ALL.iter().copied().find(|rule| rule.name() == s).ok_or_else(|| {
+            let names: Vec<&str> = Rule::ALL.iter().map(|rule| rule.name()).collect();
+            format!("unknown lint rule '{}', expected one of {}", s, names.join(", "))
+        })
+    }
+}
+
+/// which rules are checked, and what they take as known
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct LintConfig {
+    disabled: BTreeSet<Rule>,
+    /// goals known besides the block tracker entries of a document
+    pub goals: Vec<String>,
+}
+
+impl LintConfig {
+    pub fn is_enabled(&self, rule: Rule) -> bool {
+        !self.disabled.contains(&rule)
+    }
+
+    pub fn enable(&mut self, rule: Rule) -> &mut Self {
+        self.disabled.remove(&rule);
+        self
+    }
+
+    pub fn disable(&mut self, rule: Rule) -> &mut Self {
+        self.disabled.insert(rule);
+        self
+    }
+}
+
+/// walks a document, reporting problems on the lines its tasks and sections were read from
+struct Linter<'a> {
+    config: &'a LintConfig,
+    reference: (u32, Season),
+    known_goals: BTreeSet<String>,
+    /// the lines of the CURRENT tasks
+    current: Vec<usize>,
+    out: Vec<Diagnostic>,
+}
+
+impl<'a> Linter<'a> {
+    fn report(&mut self, line: usize, rule: Rule, message: String) {
+        if self.config.is_enabled(rule) {
+            self.out.push(Diagnostic::new(line, Severity::Warning, Some(rule.name()), &message));
+        }
+    }
+
+    fn items(&mut self, items: &[Item]) {
+        // the tasks of this section by name, along with the line of the first one
+        let mut names: BTreeMap<&str, usize> = BTreeMap::new();
+        for item in items.iter() {
+            match item {
+                Item::Section(section) => {
+                    let line = section.line();
+                    if section.items().iter().all(|item| matches!(item, Item::Text(text) if text.is_empty())) {
+                        self.report(line, Rule::EmptySection, format!("the section [{}] is empty", section.specifier()));
+                    }
+                    self.items(section.items());
+                }
+                Item::Task(tree) => {
+                    for (_depth, tree) in tree.walk() {
+                        let line = tree.line();
+                        let name = tree.task().name();
+                        match names.get(name) {
+                            Some(first) => {
+                                let first = *first;
+                                self.report(line, Rule::DuplicateName,
+                                    format!("'{}' is also the name of the task on line {}", name, first));
+                            }
+                            None => {
+                                names.insert(name, line);
+                            }
+                        }
+                        self.task(tree, line);
+                    }
+                }
+                _ => (),
+            }
+        }
+    }
+
+    fn task(&mut self, tree: &TaskTree, line: usize) {
+        let task = tree.task();
+        if task.flags().contains(TaskFlags::CURRENT) {
+            if let Some(first) = self.current.first().copied() {
+                self.report(line, Rule::MultipleCurrent,
+                    format!("'{}' is CURRENT, and so is the task on line {}", task.name(), first));
+            }
+            self.current.push(line);
+        }
+
+        if task.flags().contains(TaskFlags::DONE) {
+            if let Some(remaining) = task.remaining_blocks().filter(|remaining| *remaining > 0) {
+                self.report(line, Rule::DoneUnmet, format!("'{}' is done with {} expected blocks left", task.name(), remaining));
+            } else if let Some(Stat::Bool { act: false, exp: true }) = task.progress_stat() {
+                self.report(line, Rule::DoneUnmet, format!("'{}' is done, but its required stat is not", task.name()));
+            }
+        }
+
+        if let (Some(due), Some(hard)) = (task.due_date(), task.hard_date()) {
+            let (year, season) = self.reference;
+            if hard.ordinal_key(year, season) < due.ordinal_key(year, season) {
+                self.report(line, Rule::HardBeforeDue,
+                    format!("'{}' has its hard date {} before its due date {}", task.name(), hard, due));
+            }
+        }
+
+        if let Some(Stat::Count { act, exp }) = task.context_stat() {
+            if let Stat::Count { act: computed_act, exp: computed_exp } = tree.computed_context_stat() {
+                let exp_differs = exp.is_some() && computed_exp.is_some() && exp != computed_exp;
+                if act.unwrap_or(0) != computed_act.unwrap_or(0) || exp_differs {
+                    self.report(line, Rule::ContextStat, format!("'{}' has a context stat of {}, but its stats add up to {}",
+                        task.name(), Stat::Count { act, exp }, tree.computed_context_stat()));
+                }
+            }
+        }
+
+        if task.priority() > Task::NO_PRIORITY {
+            self.report(line, Rule::PriorityRange,
+                format!("'{}' has priority {}, out of 0-{}", task.name(), task.priority(), Task::NO_PRIORITY));
+        }
+
+        let unknown: Vec<String> = task.other_stats().keys()
+            .filter(|goal| !self.known_goals.contains(&goal.to_lowercase()))
+            .cloned()
+            .collect();
+        for goal in unknown {
+            self.report(line, Rule::UnknownGoal, format!("'{}' tracks g{}, which is not a known goal", task.name(), goal));
+        }
+    }
+}
+
+/// checks the document in `text`, parsed with the flag aliases and section payloads of `config`,
+/// giving its parse errors and the problems found by the rules enabled in `config.lint`, by line.
+/// Short date codes are taken to be in the year and season of `reference`.
+pub fn lint(text: &str, config: &Config, reference: (u32, Season)) -> Vec<Diagnostic> {
+    let (document, errors) = config.parse_document(text);
+    lint_document(&document, &errors, &config.lint, reference)
+}
+
+/// checks a document as `lint` does, given as it was parsed along with its errors. Problems are
+/// reported on the lines the tasks and sections were read from.
+pub fn lint_document(
+    document: &Document,
+    errors: &[DocumentParseError],
+    config: &LintConfig,
+    reference: (u32, Season),
+) -> Vec<Diagnostic> {
+    let known_goals: BTreeSet<String> = config.goals.iter()
+        .map(|goal| goal.to_lowercase())
+        .chain(document.block_tracker_entries().iter().map(|entry| entry.entry_name().to_lowercase()))
+        .collect();
+    let mut linter = Linter {
+        config,
+        reference,
+        known_goals,
+        current: vec![],
+        out: errors.iter().map(Diagnostic::from).collect(),
+    };
+    linter.items(document.items());
+
+    let mut out = linter.out;
+    out.sort_by_key(|diagnostic| diagnostic.line);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DOCUMENT: &str = "\
+[Block Tracker]
+\t0 0 0 0 0 0 0 PUSHUPS
+[Work]
+\t>(2/4, 5/8, 7) Write the report (due: W3R; hard: W3M; prior: 120)
+\t\t~(1/3) Outline (gPushups: 5)
+\t\t(2) Outline (gSitups: 3)
+\t>() Email
+[Empty]
+
+[Home]
+\t~(-/!) Sink
+\t() Email
+";
+
+    fn codes(diagnostics: &[Diagnostic]) -> Vec<(usize, &'static str)> {
+        diagnostics.iter().map(|diagnostic| (diagnostic.line, diagnostic.code.unwrap_or("parse"))).collect()
+    }
+
+    #[test]
+    fn test_lint() {
+        let diagnostics = lint(DOCUMENT, &Config::default(), (21, Season::Winter));
+        assert_eq!(codes(&diagnostics), vec![
+            (4, "hard-before-due"),
+            (4, "context-stat"),
+            (4, "priority-range"),
+            (5, "done-unmet"),
+            (6, "duplicate-name"),
+            (6, "unknown-goal"),
+            (7, "multiple-current"),
+            (8, "empty-section"),
+            (11, "done-unmet"),
+        ]);
+        assert_eq!(diagnostics[1].message, "'Write the report' has a context stat of 7, but its stats add up to 8/11");
+        assert_eq!(diagnostics[6].message, "'Email' is CURRENT, and so is the task on line 4");
+
+        let mut config = Config::default();
+        config.lint.disable(Rule::PriorityRange).disable(Rule::UnknownGoal).disable(Rule::DoneUnmet);
+        config.lint.enable(Rule::DoneUnmet);
+        config.lint.goals.push("situps".into());
+        let diagnostics = lint(DOCUMENT, &config, (21, Season::Winter));
+        assert!(diagnostics.iter().all(|diagnostic| diagnostic.code != Some("priority-range")));
+        assert!(diagnostics.iter().all(|diagnostic| diagnostic.code != Some("unknown-goal")));
+        assert_eq!(diagnostics.len(), 7);
+    }
+
+    #[test]
+    fn test_lines() {
+        // lines of text sections that look like tasks are not taken for the lines of tasks
+        let text = "[Journal]\n\t>(1) not a task\n\t[aside]\n\t(see below)\n[Work]\n\t*() Report\n\t>() Email\n[Empty]\n";
+        let config: Config = "[flags]\n\"*\" = \"current\"\n[sections]\nJournal = \"text\"\n".parse().unwrap();
+        let diagnostics = lint(text, &config, (21, Season::Winter));
+        assert_eq!(codes(&diagnostics), vec![(7, "multiple-current"), (8, "empty-section")]);
+        assert_eq!(diagnostics[0].message, "'Email' is CURRENT, and so is the task on line 6");
+
+        // without the config, the journal lines are tasks
+        assert_eq!(codes(&lint(text, &Config::default(), (21, Season::Winter)))[..2], [(4, "parse"), (7, "multiple-current")]);
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let diagnostics = lint("[Tasks]\n\t() Task (due: W99Z)\n\t() Task\n", &Config::default(), (21, Season::Winter));
+        assert_eq!(codes(&diagnostics), vec![(2, "parse")]);
+        assert_eq!(diagnostics[0].severity, Severity::Error);
+    }
+
+    #[test]
+    fn test_rule_names() {
+        for rule in Rule::ALL.iter() {
+            assert_eq!(rule.name().parse::<Rule>(), Ok(*rule));
+        }
+        assert!("no-such-rule".parse::<Rule>().is_err());
+    }
+}
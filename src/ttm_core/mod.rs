@@ -0,0 +1,18 @@
+//! analyzes and plans over parsed TTM elements
+pub mod deps;
+pub mod forecast;
+pub mod goals;
+pub mod habits;
+pub mod lint;
+pub mod planner;
+pub mod query;
+pub mod tags;
+pub mod timer;
+pub mod watch;
+pub mod workspace;
+
+
+#[cfg(test)]
+mod tests {
+    #[allow(unused_imports)] use super::*;
+}
@@ -0,0 +1,250 @@
+//! Plans the blocks of time of a week from the expected counts and deadlines of tasks.
+//!
+//! Every task with remaining expected blocks (a `Count {act, exp}` with act < exp) is given
+//! blocks out of the blocks available in each weekday, up to and including the day it is due.
+//! Planning is done in two passes:
+//!     - Tasks claim blocks in order of priority, then deadline. Each task claims the latest
+//!       free blocks before its deadline, leaving the earlier blocks to tasks due sooner. This
+//!       decides how many blocks each task gets, and which tasks cannot fit.
+//!     - The claimed blocks are then laid out earliest deadline first from the start of the
+//!       week, so that work is not left to the last day it could be done.
+//!
+//! The result is a proposed block tracker table, and the tasks that could not fit.
+
+// TODO remove when this is in stable development
+#![allow(dead_code)]
+
+use crate::ttm_io::block_tracker::BlockTrackerEntry;
+use crate::ttm_io::date::{Date, Season};
+use crate::ttm_io::stat::Stat;
+use crate::ttm_io::task::{Task, TaskFlags};
+
+/// the week to plan, and how many blocks of time are available in each of its days
+#[derive(Debug, Clone)]
+pub struct PlanningWeek {
+    pub year: u32,
+    pub season: Season,
+    pub week: u32,
+    /// blocks available per weekday, indexed Monday to Sunday
+    pub available: [u32; 7],
+}
+
+#[derive(Debug, PartialEq)]
+pub enum UnplannedReason {
+    /// the task is `BLOCKED` and cannot be worked on
+    Blocked,
+    /// the task was due before the planned week
+    Overdue,
+    /// not enough blocks are available before the task is due
+    InsufficientBlocks,
+}
+
+/// expected blocks of a task that did not fit in the week
+#[derive(Debug, PartialEq)]
+pub struct UnplannedTask {
+    pub name: String,
+    pub blocks: u32,
+    pub reason: UnplannedReason,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct WeekPlan {
+    /// the proposed block tracker, with an entry per planned task
+    pub entries: Vec<BlockTrackerEntry>,
+    pub unplanned: Vec<UnplannedTask>,
+}
+
+/// a task that has remaining blocks to plan
+struct Candidate<'a> {
+    task: &'a Task,
+    remaining: u32,
+    /// last weekday index that can be planned for the task
+    last_day: usize,
+}
+
+/// plans the blocks of `week` over `tasks`. Done tasks and tasks without remaining expected
+/// blocks are not planned.
+pub fn plan_week<'a, I>(week: &PlanningWeek, tasks: I) -> WeekPlan
+where
+    I: IntoIterator<Item = &'a Task>,
+{
+    let mut unplanned: Vec<UnplannedTask> = vec![];
+    let mut candidates: Vec<Candidate> = vec![];
+
+    let week_start = (week.year, week.season.index(), week.week, 0);
+    let week_end = (week.year, week.season.index(), week.week, 6);
+
+    for task in tasks {
+        if task.flags().contains(TaskFlags::DONE) {
+            continue;
+        }
+        let remaining = match task.remaining_blocks() {
+            Some(remaining) if remaining > 0 => remaining as u32,
+            _ => continue,
+        };
+
+        if task.flags().contains(TaskFlags::BLOCKED) {
+            unplanned.push(UnplannedTask {
+                name: task.name().to_string(),
+                blocks: remaining,
+                reason: UnplannedReason::Blocked,
+            });
+            continue;
+        }
+
+        // the due date is the deadline to plan for, the hard date is only used in its absence
+        let deadline: Option<Date> = task.due_date().or_else(|| task.hard_date());
+        let last_day = match deadline.map(|date| date.ordinal_key(week.year, week.season)) {
+            Some(key) if key < week_start => {
+                unplanned.push(UnplannedTask {
+                    name: task.name().to_string(),
+                    blocks: remaining,
+                    reason: UnplannedReason::Overdue,
+                });
+                continue;
+            }
+            Some(key) if key <= week_end => key.3,
+            _ => 6,
+        };
+
+        candidates.push(Candidate { task, remaining, last_day });
+    }
+
+    // first pass: claim the latest blocks before the deadline, most important tasks first
+    candidates.sort_by_key(|c| (c.task.priority(), c.last_day));
+    let mut free = week.available;
+    let mut claimed: Vec<u32> = vec![0; candidates.len()];
+    for (i, candidate) in candidates.iter().enumerate() {
+        for day in (0..=candidate.last_day).rev() {
+            let take = free[day].min(candidate.remaining - claimed[i]);
+            free[day] -= take;
+            claimed[i] += take;
+        }
+
+        if claimed[i] < candidate.remaining {
+            unplanned.push(UnplannedTask {
+                name: candidate.task.name().to_string(),
+                blocks: candidate.remaining - claimed[i],
+                reason: UnplannedReason::InsufficientBlocks,
+            });
+        }
+    }
+
+    // second pass: lay out the claimed blocks from the start of the week, earliest deadline first.
+    // This always fits, as the claimed blocks were shown to fit before their deadlines.
+    let mut order: Vec<usize> = (0..candidates.len()).collect();
+    order.sort_by_key(|&i| (candidates[i].last_day, candidates[i].task.priority()));
+    let mut free = week.available;
+    let mut entries: Vec<BlockTrackerEntry> = vec![];
+    for i in order {
+        if claimed[i] == 0 {
+            continue;
+        }
+
+        let mut planned = [0; 7];
+        let mut left = claimed[i];
+        for day in 0..=candidates[i].last_day {
+            let take = free[day].min(left);
+            free[day] -= take;
+            planned[day] = take;
+            left -= take;
+        }
+        assert_eq!(left, 0, "claimed blocks did not fit");
+
+        let mut week_stats = [Stat::from_bool(false, false); 7];
+        for (stat, &blocks) in week_stats.iter_mut().zip(planned.iter()) {
+            if blocks > 0 {
+                *stat = Stat::from_count(None, Some(blocks as i32));
+            }
+        }
+        entries.push(BlockTrackerEntry::new(&entry_name(candidates[i].task.name()), week_stats));
+    }
+
+    WeekPlan { entries, unplanned }
+}
+
+/// block tracker entry names cannot contain whitespace
+fn entry_name(task_name: &str) -> String {
+    task_name.split_whitespace().collect::<Vec<&str>>().join("_")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ttm_io::block_tracker;
+
+    fn tasks(lines: &[&str]) -> Vec<Task> {
+        lines.iter().map(|line| line.parse().unwrap()).collect()
+    }
+
+    fn week(available: [u32; 7]) -> PlanningWeek {
+        PlanningWeek { year: 21, season: Season::Winter, week: 3, available }
+    }
+
+    #[test]
+    fn test_plan_within_deadlines() {
+        let tasks = tasks(&[
+            "(,2/6) Write essay (due: W3W)",
+            "(1/3) Read chapter",
+            "(,0/2) Tax forms (due: Y21W-W3T; prior: 0)",
+        ]);
+        let plan = plan_week(&week([2, 2, 2, 2, 0, 0, 0]), &tasks);
+
+        assert_eq!(plan.unplanned, vec![]);
+        assert_eq!(block_tracker::render_table(&plan.entries),
+            "/2 /- /- /- /- /- /- Tax_forms\n\
+             /- /2 /2 /- /- /- /- Write_essay\n\
+             /- /- /- /2 /- /- /- Read_chapter\n");
+    }
+
+    #[test]
+    fn test_plan_respects_priority() {
+        let tasks = tasks(&[
+            "(,0/3) Low priority (due: W3T; prior: 50)",
+            "(,0/3) High priority (due: W3F; prior: 1)",
+        ]);
+        let plan = plan_week(&week([1, 1, 1, 1, 1, 1, 1]), &tasks);
+
+        // the high priority task takes the blocks nearest its deadline, leaving Monday and
+        // Tuesday to the earlier task
+        assert_eq!(plan.unplanned, vec![UnplannedTask {
+            name: "Low priority".into(),
+            blocks: 1,
+            reason: UnplannedReason::InsufficientBlocks,
+        }]);
+        assert_eq!(block_tracker::render_table(&plan.entries),
+            "/1 /1 /- /- /- /- /- Low_priority\n\
+             /- /- /1 /1 /1 /- /- High_priority\n");
+    }
+
+    #[test]
+    fn test_plan_unplannable_tasks() {
+        let tasks = tasks(&[
+            "B(,0/4) Waiting on review",
+            "(,1/4) Late report (due: Y21W-W2F)",
+            "~(,1/4) Already done",
+            "(,4/4) Expectation met",
+            "(3) No expectation",
+        ]);
+        let plan = plan_week(&week([4, 4, 4, 4, 4, 4, 4]), &tasks);
+
+        assert_eq!(plan.entries, vec![]);
+        assert_eq!(plan.unplanned, vec![
+            UnplannedTask { name: "Waiting on review".into(), blocks: 4, reason: UnplannedReason::Blocked },
+            UnplannedTask { name: "Late report".into(), blocks: 3, reason: UnplannedReason::Overdue },
+        ]);
+    }
+
+    #[test]
+    fn test_plan_deadlines_after_week() {
+        let tasks = tasks(&["(,0/3) Next season (hard: Y21S-W1M)"]);
+        let plan = plan_week(&week([0, 0, 0, 0, 0, 1, 1]), &tasks);
+
+        assert_eq!(plan.unplanned, vec![UnplannedTask {
+            name: "Next season".into(),
+            blocks: 1,
+            reason: UnplannedReason::InsufficientBlocks,
+        }]);
+        assert_eq!(block_tracker::render_table(&plan.entries), "/- /- /- /- /- /1 /1 Next_season\n");
+    }
+}
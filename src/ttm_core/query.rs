@@ -0,0 +1,150 @@
+//! Filters the tasks of a document by a query. A query is a list of terms, separated by spaces,
+//! all of which a task must match:
+//!     - +tag, @context: The task has the tag. Refer to `crate::ttm_io::task`.
+//!     - #Id: The task has the id.
+//!     - is:done, is:current, is:blocked, is:late: The task has the flag. is:open matches tasks
+//!       that are not done.
+//!     - Any other word: The name of the task holds the word, ignoring case.
+//! A term starting with '-' matches the tasks the rest of the term does not, as in -is:done.
+
+#![allow(dead_code)]
+
+use crate::ttm_io::document::Document;
+use crate::ttm_io::task::{self, Task, TaskFlags};
+use std::borrow::Cow;
+use std::fmt;
+
+#[derive(Debug, PartialEq)]
+pub enum QueryParseError {
+    EmptyQuery,
+    UnknownFlag(String),
+}
+
+impl fmt::Display for QueryParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::result::Result<(), std::fmt::Error> {
+        let msg: Cow<'static, str> = match self {
+            Self::EmptyQuery => "the query has no terms".into(),
+            Self::UnknownFlag(flag) => {
+                format!("unknown flag 'is:{}', expected one of done, current, blocked, late or open", flag).into()
+            }
+        };
+        write!(f, "{}", msg)?;
+        Ok(())
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+enum Term {
+    Tag(String),
+    Id(String),
+    Flag(TaskFlags),
+    Open,
+    Word(String),
+}
+
+impl Term {
+    fn matches(&self, task: &Task) -> bool {
+        match self {
+            Term::Tag(tag) => task.has_tag(tag),
+            Term::Id(id) => task.id() == Some(id.as_str()),
+            Term::Flag(flag) => task.flags().contains(*flag),
+            Term::Open => !task.flags().contains(TaskFlags::DONE),
+            Term::Word(word) => task.name().to_lowercase().contains(word.as_str()),
+        }
+    }
+}
+
+impl std::str::FromStr for Term {
+    type Err = QueryParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if task::is_tag(s) {
+            return Ok(Term::Tag(s.to_string()));
+        }
+        if let Some(id) = s.strip_prefix('#').filter(|id| !id.is_empty()) {
+            return Ok(Term::Id(id.to_string()));
+        }
+        match s.strip_prefix("is:") {
+            Some("done") => Ok(Term::Flag(TaskFlags::DONE)),
+            Some("current") => Ok(Term::Flag(TaskFlags::CURRENT)),
+            Some("blocked") => Ok(Term::Flag(TaskFlags::BLOCKED)),
+            Some("late") => Ok(Term::Flag(TaskFlags::LATE)),
+            Some("open") => Ok(Term::Open),
+            Some(flag) => Err(QueryParseError::UnknownFlag(flag.to_string())),
+            None => Ok(Term::Word(s.to_lowercase())),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Query {
+    /// the terms, each along with whether it is negated
+    terms: Vec<(bool, Term)>,
+}
+
+impl Query {
+    pub fn matches(&self, task: &Task) -> bool {
+        self.terms.iter().all(|(negated, term)| term.matches(task) != *negated)
+    }
+
+    /// the tasks of `document` matching the query, in the order they are written
+    pub fn filter<'a>(&self, document: &'a Document) -> Vec<&'a Task> {
+        document.tasks().into_iter().filter(|task| self.matches(task)).collect()
+    }
+}
+
+impl std::str::FromStr for Query {
+    type Err = QueryParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let terms = s.split_whitespace()
+            .map(|word| match word.strip_prefix('-').filter(|rest| !rest.is_empty()) {
+                Some(rest) => Ok((true, rest.parse::<Term>()?)),
+                None => Ok((false, word.parse::<Term>()?)),
+            })
+            .collect::<Result<Vec<(bool, Term)>, QueryParseError>>()?;
+        if terms.is_empty() {
+            return Err(QueryParseError::EmptyQuery);
+        }
+        Ok(Self { terms })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DOCUMENT: &str = "\
+[Work]
+\t>(1/4) Write the report +work @office (#report)
+\t~() Email the team +work
+[Home]
+\tB() Fix the sink @home (tags: chores)
+\t() Call mom @phone
+";
+
+    fn names(query: &str) -> Vec<String> {
+        let document: Document = DOCUMENT.parse().unwrap();
+        let query: Query = query.parse().unwrap();
+        query.filter(&document).iter().map(|task| task.name().to_string()).collect()
+    }
+
+    #[test]
+    fn test_filter() {
+        assert_eq!(names("+work"), vec!["Write the report +work @office", "Email the team +work"]);
+        assert_eq!(names("+work -is:done"), vec!["Write the report +work @office"]);
+        assert_eq!(names("+chores"), vec!["Fix the sink @home"]);
+        assert_eq!(names("is:open -+work"), vec!["Fix the sink @home", "Call mom @phone"]);
+        assert_eq!(names("#report is:current"), vec!["Write the report +work @office"]);
+        assert_eq!(names("MOM"), vec!["Call mom @phone"]);
+        assert!(names("@office is:blocked").is_empty());
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert_eq!("  ".parse::<Query>(), Err(QueryParseError::EmptyQuery));
+        assert_eq!("is:someday".parse::<Query>(), Err(QueryParseError::UnknownFlag("someday".into())));
+        // a lone '-' is a word
+        assert_eq!(names("-"), Vec::<String>::new());
+    }
+}
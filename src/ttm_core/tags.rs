@@ -0,0 +1,94 @@
+//! Sums the time spent on each tag per week, from the files of days. The blocks of a task on a day
+//! are the blocks of its day stat in the file of that day, as by `crate::ttm_io::charts::blocks`,
+//! and add to every tag of the task. Days are grouped into the weeks of the season calendar; days
+//! outside of any season are left out.
+
+#![allow(dead_code)]
+
+use crate::ttm_io::calendar::{CivilDate, SeasonCalendar};
+use crate::ttm_io::charts;
+use crate::ttm_io::date::Date;
+use crate::ttm_io::document::Document;
+use std::collections::{BTreeMap, BTreeSet};
+
+/// the blocks of each tag in a week
+#[derive(Debug, PartialEq, Clone)]
+pub struct TagWeek {
+    /// a `Date::WeekDateCode`
+    pub week: Date,
+    pub blocks: BTreeMap<String, i32>,
+}
+
+/// sums the blocks per tag of `days`, each a document of a day, into the weeks they are in,
+/// oldest week first
+pub fn tag_time(days: &[(CivilDate, Document)], calendar: &SeasonCalendar) -> Vec<TagWeek> {
+    let mut weeks: BTreeMap<(u32, usize, u32), TagWeek> = BTreeMap::new();
+    for (date, document) in days.iter() {
+        let (year, season, week) = match calendar.from_civil(*date) {
+            Ok(Date::DateCode { year, season, week, .. }) => (year, season, week),
+            _ => continue,
+        };
+        let tag_week = weeks.entry((year, season.index(), week))
+            .or_insert_with(|| TagWeek { week: Date::WeekDateCode { year, season, week }, blocks: BTreeMap::new() });
+
+        for task in document.tasks() {
+            let blocks = match task.day_stat().and_then(charts::blocks) {
+                Some(blocks) => blocks,
+                None => continue,
+            };
+            for tag in task.tags().iter() {
+                *tag_week.blocks.entry(tag.clone()).or_insert(0) += blocks;
+            }
+        }
+    }
+    weeks.into_values().collect()
+}
+
+/// writes a table with a row per week and a column per tag
+pub fn report(weeks: &[TagWeek]) -> String {
+    let tags: BTreeSet<&str> = weeks.iter().flat_map(|week| week.blocks.keys().map(|tag| tag.as_str())).collect();
+    let labels: Vec<String> = weeks.iter().map(|week| week.week.to_string()).collect();
+    let label_width = labels.iter().map(|label| label.len()).max().unwrap_or(0).max("week".len());
+
+    let mut out = format!("{:width$}", "week", width = label_width);
+    for tag in tags.iter() {
+        out.push_str(&format!("  {}", tag));
+    }
+    out.push('\n');
+    for (week, label) in weeks.iter().zip(labels.iter()) {
+        out.push_str(&format!("{:width$}", label, width = label_width));
+        for tag in tags.iter() {
+            let blocks = week.blocks.get(*tag).copied().unwrap_or(0);
+            out.push_str(&format!("  {:>width$}", blocks, width = tag.chars().count()));
+        }
+        out.push('\n');
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tag_time() {
+        let days: Vec<(CivilDate, Document)> = vec![
+            // 2021-01-08 is in week 2 of Winter 2021, and 2021-01-11 in week 3
+            (CivilDate::new(2021, 1, 8), "(2/4) Report +work @office\n(1) Sink @home\n() Idle +work\n".parse().unwrap()),
+            (CivilDate::new(2021, 1, 9), "(3, 5/10) Report +work @office\n(!) Call (tags: family)\n".parse().unwrap()),
+            (CivilDate::new(2021, 1, 11), "(1) Report +work @office\n".parse().unwrap()),
+        ];
+        let weeks = tag_time(&days, &SeasonCalendar::default());
+        assert_eq!(weeks.len(), 2);
+        assert_eq!(weeks[0].week.to_string(), "Y21W-W2");
+        assert_eq!(weeks[0].blocks["+work"], 5);
+        assert_eq!(weeks[0].blocks["+family"], 1);
+        assert_eq!(weeks[1].blocks.get("@home"), None);
+
+        assert_eq!(report(&weeks), "\
+week     +family  +work  @home  @office
+Y21W-W2        1      5      1        5
+Y21W-W3        0      1      0        1
+");
+    }
+}
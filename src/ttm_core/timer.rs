@@ -0,0 +1,268 @@
+//! A block timer. A timer runs a block of time on a task of a file; once the block is over, the
+//! block is ticked: the day stat of the task gets one more block, and so does the block tracker
+//! entry of the task on the day the block ended. The entry of a task is the first one named as the
+//! task or as one of its tags without the sigil, ignoring case, so +reading ticks READING.
+//!
+//! Ticking edits only the fields it changes, as by `crate::ttm_io::edit`, so the rest of the file
+//! is kept as written. Files are read as the config has them, so tasks may be written with flag
+//! aliases. A running timer is a `TimerState`, which is written to a state file so that
+//! it outlives the process that started it. Time is read from a `Clock`, so it can be faked.
+
+#![allow(dead_code)]
+
+use crate::config::Config;
+use crate::ttm_io::block_tracker::BlockTrackerEntry;
+use crate::ttm_io::calendar::CivilDate;
+use crate::ttm_io::edit;
+use crate::ttm_io::ids;
+use crate::ttm_io::stat::Stat;
+use std::borrow::Cow;
+use std::fmt;
+
+/// length of a block, in minutes, when none is given
+pub const DEFAULT_MINUTES: u32 = 25;
+
+pub trait Clock {
+    /// seconds since 1970-01-01T00:00:00Z
+    fn now(&self) -> i64;
+    fn sleep(&self, secs: i64);
+}
+
+/// the clock of the system
+#[derive(Debug, Clone, Copy)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> i64 {
+        std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs() as i64)
+    }
+
+    fn sleep(&self, secs: i64) {
+        std::thread::sleep(std::time::Duration::from_secs(secs.max(0) as u64));
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum TimerError {
+    /// the state file does not hold a timer. Holds what was wrong with it.
+    InvalidState(Cow<'static, str>),
+    TaskNotFound(String),
+}
+
+impl fmt::Display for TimerError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::result::Result<(), std::fmt::Error> {
+        let msg: Cow<'static, str> = match self {
+            Self::InvalidState(message) => format!("invalid timer state: {}", message).into(),
+            Self::TaskNotFound(task) => format!("no task '{}'", task).into(),
+        };
+        write!(f, "{}", msg)?;
+        Ok(())
+    }
+}
+
+/// a running timer
+#[derive(Debug, PartialEq, Clone)]
+pub struct TimerState {
+    /// the file of the task
+    pub file: String,
+    /// the task, by its name or as #Id
+    pub task: String,
+    /// when the block started, in seconds as by `Clock::now`
+    pub start: i64,
+    pub minutes: u32,
+}
+
+impl TimerState {
+    pub fn new(file: &str, task: &str, start: i64, minutes: u32) -> Self {
+        Self { file: file.to_string(), task: task.to_string(), start, minutes }
+    }
+
+    /// when the block is over
+    pub fn end(&self) -> i64 {
+        self.start + self.minutes as i64 * 60
+    }
+
+    /// seconds left in the block at `now`
+    pub fn remaining(&self, now: i64) -> i64 {
+        (self.end() - now).max(0)
+    }
+
+    pub fn is_over(&self, now: i64) -> bool {
+        now >= self.end()
+    }
+
+    /// the day the block ended on, in local time `utc_offset` seconds ahead of UTC
+    pub fn end_date(&self, utc_offset: i64) -> CivilDate {
+        CivilDate::from_timestamp(self.end(), utc_offset)
+    }
+}
+
+impl fmt::Display for TimerState {
+    /// writes the state as `key: value` lines
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "file: {}", self.file)?;
+        writeln!(f, "task: {}", self.task)?;
+        writeln!(f, "start: {}", self.start)?;
+        writeln!(f, "minutes: {}", self.minutes)
+    }
+}
+
+impl std::str::FromStr for TimerState {
+    type Err = TimerError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (mut file, mut task, mut start, mut minutes) = (None, None, None, None);
+        for line in s.lines().filter(|line| !line.trim().is_empty()) {
+            let (key, value) = line.split_once(": ")
+                .ok_or_else(|| TimerError::InvalidState(format!("'{}' is not a key: value line", line).into()))?;
+            let number = |value: &str| value.trim().parse::<i64>()
+                .map_err(|_| TimerError::InvalidState(format!("{} '{}' is not a number", key, value).into()));
+            match key {
+                "file" => file = Some(value.to_string()),
+                "task" => task = Some(value.to_string()),
+                "start" => start = Some(number(value)?),
+                "minutes" => minutes = Some(number(value)?),
+                _ => return Err(TimerError::InvalidState(format!("unknown key '{}'", key).into())),
+            }
+        }
+
+        let missing = |key: &str| TimerError::InvalidState(format!("no {}", key).into());
+        let minutes = minutes.ok_or_else(|| missing("minutes"))?;
+        if minutes <= 0 || minutes > u32::MAX as i64 {
+            return Err(TimerError::InvalidState(format!("{} is not a block length", minutes).into()));
+        }
+        Ok(Self {
+            file: file.ok_or_else(|| missing("file"))?,
+            task: task.ok_or_else(|| missing("task"))?,
+            start: start.ok_or_else(|| missing("start"))?,
+            minutes: minutes as u32,
+        })
+    }
+}
+
+/// what ticking a block changed
+#[derive(Debug, PartialEq)]
+pub struct Tick {
+    /// the file, with the block ticked
+    pub text: String,
+    pub day_stat: Stat,
+    /// the block tracker entry ticked, along with its new stat
+    pub entry: Option<(String, Stat)>,
+}
+
+/// `stat` with one more block
+fn add_block(stat: Option<Stat>) -> Stat {
+    match stat {
+        Some(Stat::Count { act, exp }) => Stat::Count { act: Some(act.unwrap_or(0) + 1), exp },
+        Some(Stat::RequiredCount { act, exp }) => Stat::RequiredCount { act: act + 1, exp },
+        Some(Stat::Bool { exp, .. }) => Stat::Bool { act: true, exp },
+        Some(Stat::Unknown) | None => Stat::from_count(Some(1), None),
+    }
+}
+
+/// ticks a block of the task `reference`, by its name or as #Id, in `text`, a TTM document read as
+/// `config` has it. `date` is the day the block ended on.
+pub fn tick(text: &str, reference: &str, date: CivilDate, config: &Config) -> Result<Tick, TimerError> {
+    let (document, _errors) = config.parse_document(text);
+    let tree = ids::find_tree(&document, reference).ok_or_else(|| TimerError::TaskNotFound(reference.to_string()))?;
+    let task = tree.task();
+    let names: Vec<String> = std::iter::once(task.name())
+        .chain(task.tags().iter().map(|tag| &tag[1..]))
+        .map(|name| name.to_lowercase())
+        .collect();
+    let day = date.weekday().index();
+
+    let day_stat = add_block(task.day_stat());
+    let mut ticked = task.clone();
+    ticked.build_day_stat(day_stat);
+    let mut entry: Option<(String, Stat)> = None;
+    let mut found = false;
+    let text = edit::edit_lines(text, |i, line| {
+        if i + 1 == tree.line() {
+            found = true;
+            return edit::set_task(line, task, &ticked, &config.flag_aliases);
+        }
+        if entry.is_none() {
+            let tracked = line.trim().parse::<BlockTrackerEntry>().ok()
+                .filter(|tracked| names.contains(&tracked.entry_name().to_lowercase()));
+            if let Some(tracked) = tracked {
+                let stat = add_block(Some(tracked.week_stats()[day]));
+                entry = Some((tracked.entry_name().to_string(), stat));
+                return edit::set_tracker_stat(line, day, stat);
+            }
+        }
+        None
+    });
+
+    // the task is found in the document, so it is on one of the lines
+    if !found {
+        return Err(TimerError::TaskNotFound(reference.to_string()));
+    }
+    Ok(Tick { text, day_stat, entry })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DOCUMENT: &str = "\
+[Block Tracker]
+    0  0  !  -  /- ?  4/4 PROJECT
+    1  2  3  4  5  6  7   READING
+
+[Tasks]
+    >(2/15) Write the report  (due: W3R; #report)
+    () Read a chapter +reading
+";
+
+    #[test]
+    fn test_tick() {
+        // 2021-01-08 is a Friday
+        let date = CivilDate::new(2021, 1, 8);
+        let config = Config::default();
+        let tick = super::tick(DOCUMENT, "#report", date, &config).unwrap();
+        assert_eq!(tick.day_stat, Stat::from_count(Some(3), Some(15)));
+        assert_eq!(tick.entry, None);
+        assert_eq!(tick.text, DOCUMENT.replace("(2/15)", "(3/15)"));
+
+        let tick = super::tick(DOCUMENT, "Read a chapter +reading", date, &config).unwrap();
+        assert_eq!(tick.day_stat, Stat::from_count(Some(1), None));
+        assert_eq!(tick.entry, Some(("READING".to_string(), Stat::from_count(Some(6), None))));
+        assert!(tick.text.contains("    1  2  3  4  6  6  7   READING\n"));
+        assert!(tick.text.contains("    (1) Read a chapter +reading\n"));
+
+        assert_eq!(super::tick(DOCUMENT, "Sleep", date, &config), Err(TimerError::TaskNotFound("Sleep".into())));
+
+        // tasks written with aliases are found, and keep them
+        let config: Config = "[flags]\n\"*\" = \"current\"\n".parse().unwrap();
+        let aliased = "[Tasks]\n    *(2/15) Write the report  (#report)\n";
+        let tick = super::tick(aliased, "#report", date, &config).unwrap();
+        assert_eq!(tick.text, "[Tasks]\n    *(3/15) Write the report  (#report)\n");
+    }
+
+    #[test]
+    fn test_add_block() {
+        assert_eq!(add_block(None), Stat::from_count(Some(1), None));
+        assert_eq!(add_block(Some(Stat::from_bool(false, true))), Stat::from_bool(true, true));
+        assert_eq!(add_block(Some(Stat::RequiredCount { act: 0, exp: true })), Stat::RequiredCount { act: 1, exp: true });
+        assert_eq!(add_block(Some(Stat::from_count(None, Some(4)))), Stat::from_count(Some(1), Some(4)));
+    }
+
+    #[test]
+    fn test_state() {
+        let state = TimerState::new("days/2021-01-08.ttm", "Write the report", 1_610_064_000, 30);
+        assert_eq!(state.to_string().parse::<TimerState>().unwrap(), state);
+        assert_eq!(state.remaining(state.start + 60), 29 * 60);
+        assert!(!state.is_over(state.end() - 1));
+        assert!(state.is_over(state.end()));
+        assert_eq!(state.end_date(0), CivilDate::new(2021, 1, 8));
+        // the block ends at 00:30 UTC, still the day before five hours behind
+        assert_eq!(state.end_date(-5 * 3600), CivilDate::new(2021, 1, 7));
+
+        assert!(matches!("file: a.ttm\ntask: A\nstart: 0\n".parse::<TimerState>(), Err(TimerError::InvalidState(_))));
+        assert!(matches!("file: a.ttm\ntask: A\nstart: 0\nminutes: 0\n".parse::<TimerState>(), Err(TimerError::InvalidState(_))));
+        assert!(matches!("start 0\n".parse::<TimerState>(), Err(TimerError::InvalidState(_))));
+    }
+}
@@ -0,0 +1,400 @@
+//! Keeps a workspace up to date while its files are edited, as `ttm watch` does. Refer to
+//! `crate::ttm_core::workspace`.
+//!
+//! The files of the workspace are polled for changes to their modification times and lengths. A
+//! change is taken once the files have been left alone for the debounce time, so the burst of
+//! writes an editor makes when saving is taken at once. Only the files that changed are read,
+//! parsed and linted again, and files that appear or go away join or leave the workspace.
+//!
+//! After every change, the merged document is refreshed as it would be by hand:
+//!     - Context stats are set to what the stats of their task and its subtasks add up to. Refer to
+//!       `crate::ttm_io::task_tree::TaskTree::computed_context_stat`.
+//!     - Tasks with a due date are LATE while they are open past it, or if they were done after it.
+//!     - Tasks with dependencies are BLOCKED while any of them is not done. Refer to
+//!       `crate::ttm_core::deps`.
+//! The files are never written. The refreshed document is reported along with the diagnostics of
+//! the files, and the agenda: the open tasks with a due date, soonest first.
+
+#![allow(dead_code)]
+
+use crate::config::Config;
+use crate::ttm_core::deps::{self, DepError};
+use crate::ttm_core::lint;
+use crate::ttm_core::workspace::{Workspace, WorkspaceError};
+use crate::ttm_io::calendar::CivilDate;
+use crate::ttm_io::date::{Date, Season};
+use crate::ttm_io::diagnostic::Diagnostic;
+use crate::ttm_io::document::{Document, Item};
+use crate::ttm_io::stat::Stat;
+use crate::ttm_io::task::{Task, TaskFlags, FLAG_NAMES};
+use crate::ttm_io::task_tree::TaskTree;
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+
+/// how long the files must be left alone before a change is taken
+pub const DEFAULT_DEBOUNCE: Duration = Duration::from_millis(300);
+
+#[derive(Debug)]
+pub enum WatchError {
+    Workspace(WorkspaceError),
+    /// today is not a day of the season calendar
+    NoSeason(Cow<'static, str>),
+}
+
+impl fmt::Display for WatchError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::result::Result<(), std::fmt::Error> {
+        let msg: Cow<'static, str> = match self {
+            Self::Workspace(err) => err.to_string().into(),
+            Self::NoSeason(err) => err.clone(),
+        };
+        write!(f, "{}", msg)?;
+        Ok(())
+    }
+}
+
+impl From<WorkspaceError> for WatchError {
+    fn from(err: WorkspaceError) -> Self {
+        Self::Workspace(err)
+    }
+}
+
+/// how many tasks a refresh changed, and the dependency errors found
+#[derive(Debug, PartialEq, Default)]
+pub struct Refresh {
+    pub context_stats: usize,
+    pub late: usize,
+    pub on_time: usize,
+    pub blocked: usize,
+    pub unblocked: usize,
+    pub dep_errors: Vec<DepError>,
+}
+
+/// the files a change read again or left out, along with the refresh that followed
+#[derive(Debug, PartialEq, Default)]
+pub struct Change {
+    pub read: Vec<PathBuf>,
+    pub removed: Vec<PathBuf>,
+    pub refresh: Refresh,
+}
+
+/// what a file looked like when it was last polled
+#[derive(Debug, PartialEq, Clone, Copy)]
+struct Stamp {
+    modified: Option<SystemTime>,
+    len: u64,
+}
+
+/// the day `civil` as a full date code, along with the year and season short date codes are in
+fn day_of(config: &Config, civil: CivilDate) -> Result<(Date, (u32, Season)), WatchError> {
+    match config.calendar.from_civil(civil).map_err(WatchError::NoSeason)? {
+        today @ Date::DateCode { year, season, .. } => Ok((today, (year, season))),
+        _ => unreachable!("from_civil always gives DateCodes"),
+    }
+}
+
+#[derive(Debug)]
+pub struct Watcher {
+    root: PathBuf,
+    config: Config,
+    debounce: Duration,
+    workspace: Workspace,
+    /// the diagnostics of each file, as of when it was last read
+    diagnostics: BTreeMap<PathBuf, Vec<Diagnostic>>,
+    /// the stamps of the files as they were read into the workspace
+    loaded: BTreeMap<PathBuf, Stamp>,
+    /// the stamps of the files when they were last polled
+    seen: BTreeMap<PathBuf, Stamp>,
+    /// when the files were last seen to change, while the change is yet to be taken
+    pending: Option<Instant>,
+    today: Date,
+    reference: (u32, Season),
+}
+
+impl Watcher {
+    /// loads and refreshes the workspace of `config` under `root`, as of `today`
+    pub fn new(root: &Path, config: Config, debounce: Duration, today: CivilDate) -> Result<Self, WatchError> {
+        let (today, reference) = day_of(&config, today)?;
+        let mut watcher = Self {
+            root: root.to_path_buf(),
+            workspace: Workspace::from_files(vec![], &config),
+            config,
+            debounce,
+            diagnostics: BTreeMap::new(),
+            loaded: BTreeMap::new(),
+            seen: BTreeMap::new(),
+            pending: None,
+            today,
+            reference,
+        };
+        watcher.seen = watcher.stamps()?;
+        watcher.take_change()?;
+        Ok(watcher)
+    }
+
+    pub fn workspace(&self) -> &Workspace {
+        &self.workspace
+    }
+
+    /// looks for changes to the files at `now`, taking them once the files have been left alone for
+    /// the debounce time. The workspace is refreshed when the day changes too.
+    pub fn poll(&mut self, now: Instant, today: CivilDate) -> Result<Option<Change>, WatchError> {
+        let stamps = self.stamps()?;
+        if stamps != self.seen {
+            self.seen = stamps;
+            self.pending = Some(now);
+        }
+
+        let (today, reference) = day_of(&self.config, today)?;
+        let day_changed = today != self.today;
+        self.today = today;
+        self.reference = reference;
+        match self.pending {
+            Some(changed) if now.saturating_duration_since(changed) >= self.debounce => {
+                self.pending = None;
+                self.take_change().map(Some)
+            }
+            None if day_changed => {
+                // short date codes may now be in another season
+                for (path, text) in self.workspace.note_files() {
+                    self.diagnostics.insert(path, lint::lint(&text, &self.config, self.reference));
+                }
+                Ok(Some(Change { refresh: self.refresh(), ..Change::default() }))
+            }
+            _ => Ok(None),
+        }
+    }
+
+    /// the stamps of the files of the workspace as they are now
+    fn stamps(&self) -> Result<BTreeMap<PathBuf, Stamp>, WatchError> {
+        Ok(Workspace::find_files(&self.root, &self.config.workspace)?
+            .into_iter()
+            // a file may go away between being found and being looked at
+            .filter_map(|path| {
+                let metadata = std::fs::metadata(&path).ok()?;
+                Some((path, Stamp { modified: metadata.modified().ok(), len: metadata.len() }))
+            })
+            .collect())
+    }
+
+    /// reads the files seen to change since they were loaded, and refreshes the workspace
+    fn take_change(&mut self) -> Result<Change, WatchError> {
+        let mut change = Change::default();
+        let removed: Vec<PathBuf> = self.loaded.keys().filter(|path| !self.seen.contains_key(*path)).cloned().collect();
+        for path in removed {
+            self.workspace.remove_file(&path);
+            self.diagnostics.remove(&path);
+            self.loaded.remove(&path);
+            change.removed.push(path);
+        }
+
+        let changed: Vec<(PathBuf, Stamp)> = self.seen.iter()
+            .filter(|(path, stamp)| self.loaded.get(*path) != Some(*stamp))
+            .map(|(path, stamp)| (path.clone(), *stamp))
+            .collect();
+        for (path, stamp) in changed {
+            let text = match std::fs::read_to_string(&path) {
+                Ok(text) => text,
+                // it is left out with the next poll
+                Err(err) if err.kind() == std::io::ErrorKind::NotFound => continue,
+                Err(err) => return Err(WorkspaceError::Io { path, err }.into()),
+            };
+            self.diagnostics.insert(path.clone(), lint::lint(&text, &self.config, self.reference));
+            self.workspace.set_file(&path, text, &self.config);
+            self.loaded.insert(path.clone(), stamp);
+            change.read.push(path);
+        }
+        change.refresh = self.refresh();
+        Ok(change)
+    }
+
+    fn refresh(&mut self) -> Refresh {
+        refresh(self.workspace.document_mut(), &self.today, self.reference)
+    }
+
+    /// the path of a file of the workspace relative to its root
+    fn relative<'p>(&self, path: &'p Path) -> &'p Path {
+        path.strip_prefix(&self.root).unwrap_or(path)
+    }
+
+    /// the open tasks with a due date along with their files, soonest due first
+    pub fn agenda(&self) -> Vec<(&Path, &Task)> {
+        let (year, season) = self.reference;
+        let mut out: Vec<(&Path, &Task)> = self.workspace.tasks().into_iter()
+            .filter(|(_path, task)| task.due_date().is_some() && !task.flags().contains(TaskFlags::DONE))
+            .collect();
+        out.sort_by_key(|(_path, task)| task.due_date().map(|due| due.ordinal_key(year, season)));
+        out
+    }
+
+    /// writes what `change` did, the diagnostics of the files and the agenda
+    pub fn report(&self, change: &Change) -> String {
+        let mut out = String::new();
+        let paths = |paths: &[PathBuf]| -> String {
+            paths.iter().map(|path| self.relative(path).display().to_string()).collect::<Vec<String>>().join(", ")
+        };
+        if !change.read.is_empty() {
+            out.push_str(&format!("read {}\n", paths(&change.read)));
+        }
+        if !change.removed.is_empty() {
+            out.push_str(&format!("removed {}\n", paths(&change.removed)));
+        }
+        for error in change.refresh.dep_errors.iter() {
+            out.push_str(&format!("workspace: {}\n", error));
+        }
+        for (path, diagnostics) in self.diagnostics.iter() {
+            for diagnostic in diagnostics.iter() {
+                out.push_str(&format!("{}:{}\n", self.relative(path).display(), diagnostic));
+            }
+        }
+
+        out.push_str("agenda:\n");
+        for (path, task) in self.agenda() {
+            let flags: Vec<&str> = FLAG_NAMES.iter()
+                .filter(|(flag, _name)| *flag != TaskFlags::DONE && task.flags().contains(*flag))
+                .map(|(_flag, name)| *name)
+                .collect();
+            let flags = if flags.is_empty() { String::new() } else { format!(" [{}]", flags.join(", ")) };
+            let due = task.due_date().map_or(String::new(), |due| due.to_string());
+            out.push_str(&format!("    {}  {}: {}{}\n", due, self.relative(path).display(), task.name(), flags));
+        }
+        out
+    }
+}
+
+/// sets the context stats, LATE and BLOCKED flags of the tasks in `document` from their stats,
+/// dates and dependencies. `today` is a full date code, and short date codes are taken to be in
+/// `reference`.
+pub fn refresh(document: &mut Document, today: &Date, reference: (u32, Season)) -> Refresh {
+    let mut refresh = Refresh::default();
+    let (year, season) = reference;
+    let today = today.ordinal_key(year, season);
+    visit_trees_mut(document.items_mut(), &mut |tree| {
+        if let Some(context_stat @ Stat::Count { .. }) = tree.task().context_stat() {
+            let computed = tree.computed_context_stat();
+            if computed != context_stat {
+                tree.task_mut().build_context_stat(computed);
+                refresh.context_stats += 1;
+            }
+        }
+
+        let task = tree.task_mut();
+        let due = match task.due_date() {
+            Some(due) => due.ordinal_key(year, season),
+            None => return,
+        };
+        let late = match (task.flags().contains(TaskFlags::DONE), task.done_date()) {
+            (true, Some(done)) => done.ordinal_key(year, season) > due,
+            (true, None) => return,
+            (false, _) => today > due,
+        };
+        let flags = task.flags();
+        if late && !flags.contains(TaskFlags::LATE) {
+            task.build_flags(flags | TaskFlags::LATE);
+            refresh.late += 1;
+        } else if !late && flags.contains(TaskFlags::LATE) {
+            task.build_flags(flags - TaskFlags::LATE);
+            refresh.on_time += 1;
+        }
+    });
+
+    let evaluation = deps::evaluate(document);
+    refresh.blocked = evaluation.blocked;
+    refresh.unblocked = evaluation.unblocked;
+    refresh.dep_errors = evaluation.errors;
+    refresh
+}
+
+fn visit_trees_mut(items: &mut [Item], f: &mut dyn FnMut(&mut TaskTree)) {
+    fn visit_tree(tree: &mut TaskTree, f: &mut dyn FnMut(&mut TaskTree)) {
+        f(tree);
+        for subtask in tree.subtasks_mut().iter_mut() {
+            visit_tree(subtask, f);
+        }
+    }
+
+    for item in items.iter_mut() {
+        match item {
+            Item::Section(section) => visit_trees_mut(section.items_mut(), f),
+            Item::Task(tree) => visit_tree(tree, f),
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// W4R of Y21W, in the default calendar
+    const TODAY: CivilDate = CivilDate { year: 2021, month: 1, day: 21 };
+
+    #[test]
+    fn test_refresh() {
+        let (mut document, _errors) = Document::parse_lenient("\
+[Tasks]
+\t(0/2, 1/3, 9) Report (due: W3R)
+\t\t(2/4) Outline
+\t~(1/1) Review (due: W5M; done: W4M)
+\t~L(1/1) Email (due: W4M; done: W3M)
+\t() Send (due: W6F; deps: Report)
+");
+        let today: Date = "Y21W-W4R".parse().unwrap();
+        let refresh = refresh(&mut document, &today, (21, Season::Winter));
+        assert_eq!(refresh, Refresh { context_stats: 1, late: 1, on_time: 1, blocked: 1, ..Refresh::default() });
+        assert_eq!(document.to_string(), "\
+[Tasks]
+\tL(0/2, 1/3, 3/7) Report (due: W3R)
+\t\t(2/4) Outline
+\t~(1/1) Review (due: W5M; done: W4M)
+\t~(1/1) Email (due: W4M; done: W3M)
+\tB() Send (due: W6F; deps: Report)
+");
+    }
+
+    #[test]
+    fn test_watcher() {
+        let root = std::env::temp_dir().join(format!("ttm_watch_{}", std::process::id()));
+        std::fs::create_dir_all(&root).unwrap();
+        std::fs::write(root.join("work.ttm"), "[Tasks]\n    (0/2) Report (due: W3R)\n").unwrap();
+        std::fs::write(root.join("home.ttm"), "[Errands]\n    () Groceries (due: W5M; deps: work/Tasks/Report)\n").unwrap();
+
+        let start = Instant::now();
+        let at = |millis: u64| start + Duration::from_millis(millis);
+        let mut watcher = Watcher::new(&root, Config::default(), DEFAULT_DEBOUNCE, TODAY).unwrap();
+        assert_eq!(watcher.report(&Change::default()), "\
+agenda:
+    W3R  work.ttm: Report [late]
+    W5M  home.ttm: Groceries [blocked]
+");
+        assert_eq!(watcher.poll(at(0), TODAY).unwrap(), None);
+
+        // a burst of saves is taken once the files are left alone, and only the saved file is read
+        std::fs::write(root.join("work.ttm"), "[Tasks]\n    ~(2/2) Report (due: W3R)\n").unwrap();
+        assert_eq!(watcher.poll(at(100), TODAY).unwrap(), None);
+        std::fs::write(root.join("work.ttm"), "[Tasks]\n    ~(2/2) Report (due: W3R; done: W3M)\n    (0/1) Bad (due: W99Z)\n").unwrap();
+        assert_eq!(watcher.poll(at(200), TODAY).unwrap(), None);
+        assert_eq!(watcher.poll(at(400), TODAY).unwrap(), None);
+        let change = watcher.poll(at(500), TODAY).unwrap().unwrap();
+        assert_eq!(change.read, vec![root.join("work.ttm")]);
+        assert_eq!(change.refresh, Refresh { unblocked: 1, ..Refresh::default() });
+        let report = watcher.report(&change);
+        assert!(report.starts_with("read work.ttm\nwork.ttm:3: error: invalid task: "));
+        assert!(report.ends_with("agenda:\n    W5M  home.ttm: Groceries\n"));
+
+        // files going away leave the workspace
+        std::fs::remove_file(root.join("home.ttm")).unwrap();
+        assert_eq!(watcher.poll(at(600), TODAY).unwrap(), None);
+        let change = watcher.poll(at(900), TODAY).unwrap().unwrap();
+        assert_eq!((change.read.len(), change.removed), (0, vec![root.join("home.ttm")]));
+        assert_eq!(watcher.workspace().paths(), &[root.join("work.ttm")]);
+
+        // a new day refreshes the workspace without a change to the files
+        let tomorrow = CivilDate::new(2021, 1, 22);
+        assert_eq!(watcher.poll(at(1000), tomorrow).unwrap(), Some(Change::default()));
+        assert_eq!(watcher.poll(at(1100), tomorrow).unwrap(), None);
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
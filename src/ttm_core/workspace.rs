@@ -0,0 +1,373 @@
+//! A workspace is a set of TTM files worked on together, such as a file for each area along with
+//! their archives. Its files are found under a root directory by glob patterns, as in
+//! `archive/**/*.ttm`. Refer to `crate::utils::glob`.
+//!
+//! The documents of the files are merged into one document, in which each file is a section named
+//! by the stem of its file, in the order of their paths. Whatever works on a document works across
+//! the files of a workspace this way:
+//!     - Dependencies may name the tasks of other files, as in work/Tasks/Report or #Id. Refer to
+//!       `crate::ttm_core::deps`.
+//!     - Ids generated for the merged document are unique across the files. Refer to
+//!       `crate::ttm_io::ids`.
+//!     - Queries match the tasks of every file. Refer to `crate::ttm_core::query`.
+//! The files are notes too, so note links may point into the sections of other files. Refer to
+//! `Workspace::note_files`.
+//! Tasks keep track of the file they are written in. Edits made to the merged document are written
+//! back to the files they were made in, and the other files are left as they are. Only the lines of
+//! the edited tasks are written again, so the files keep their tabbing and flag aliases. Refer to
+//! `crate::ttm_io::edit::set_task`.
+//! Files read again only parse the sections changed since they were last read. Refer to
+//! `crate::ttm_io::document::SectionCache`.
+
+#![allow(dead_code)]
+
+use crate::config::Config;
+use crate::ttm_io::document::{Document, DocumentParseError, DocumentSection, Item, SectionCache};
+use crate::ttm_io::edit;
+use crate::ttm_io::task::{Task, TaskFlags};
+use crate::ttm_io::task_tree::TaskTree;
+use crate::utils::glob;
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+/// where the files of a workspace are
+#[derive(Debug, PartialEq, Clone)]
+pub struct WorkspaceConfig {
+    /// the directory the patterns are taken from. The working directory is taken if there is none.
+    pub root: Option<PathBuf>,
+    /// the files of the workspace are those matching any of these patterns
+    pub include: Vec<String>,
+    /// and none of these
+    pub exclude: Vec<String>,
+}
+
+impl Default for WorkspaceConfig {
+    fn default() -> Self {
+        Self { root: None, include: vec!["**/*.ttm".to_string()], exclude: vec![] }
+    }
+}
+
+#[derive(Debug)]
+pub enum WorkspaceError {
+    Io { path: PathBuf, err: std::io::Error },
+}
+
+impl fmt::Display for WorkspaceError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::result::Result<(), std::fmt::Error> {
+        let msg: Cow<'static, str> = match self {
+            Self::Io { path, err } => format!("{}: {}", path.display(), err).into(),
+        };
+        write!(f, "{}", msg)?;
+        Ok(())
+    }
+}
+
+fn io_error(path: &Path) -> impl FnOnce(std::io::Error) -> WorkspaceError + '_ {
+    move |err| WorkspaceError::Io { path: path.to_path_buf(), err }
+}
+
+#[derive(Debug)]
+pub struct Workspace {
+    paths: Vec<PathBuf>,
+    texts: Vec<String>,
+    /// one section for each file, in the order of `paths`
+    document: Document,
+    /// the section of each file as it was loaded, to tell the edited ones apart
+    loaded: Vec<DocumentSection>,
+    /// the parse errors of each file, in the order of `paths`
+    errors: Vec<Vec<DocumentParseError>>,
+    /// the parsed sections of each file, in the order of `paths`
+    caches: Vec<SectionCache>,
+    /// the flag aliases of the config the files were parsed with, kept when tasks are written back
+    flag_aliases: BTreeMap<char, TaskFlags>,
+}
+
+impl Workspace {
+    /// the files under `root` matching `config`, in the order of their paths. Hidden files and
+    /// directories are skipped.
+    pub fn find_files(root: &Path, config: &WorkspaceConfig) -> Result<Vec<PathBuf>, WorkspaceError> {
+        let mut out: Vec<PathBuf> = vec![];
+        find_dir(root, root, config, &mut out)?;
+        out.sort();
+        Ok(out)
+    }
+
+    /// loads the files under `root` matching the workspace of `config`, parsing them as the config
+    /// has them. Refer to `Config::parse_document`.
+    pub fn load(root: &Path, config: &Config) -> Result<Self, WorkspaceError> {
+        let files = Self::find_files(root, &config.workspace)?
+            .into_iter()
+            .map(|path| std::fs::read_to_string(&path).map_err(io_error(&path)).map(|text| (path, text)))
+            .collect::<Result<Vec<(PathBuf, String)>, WorkspaceError>>()?;
+        Ok(Self::from_files(files, config))
+    }
+
+    /// the workspace of files given as (path, text), in that order. Lines that fail to parse are
+    /// kept as text, and their errors are kept along with the workspace.
+    pub fn from_files(files: Vec<(PathBuf, String)>, config: &Config) -> Self {
+        let mut workspace = Self {
+            paths: vec![], texts: vec![], document: Document::default(), loaded: vec![], errors: vec![], caches: vec![],
+            flag_aliases: config.flag_aliases.clone(),
+        };
+        for (path, text) in files.into_iter() {
+            let mut cache = SectionCache::new();
+            let (section, errors) = file_section(&path, &text, config, &mut cache);
+            workspace.loaded.push(section.clone());
+            workspace.document.items_mut().push(Item::Section(section));
+            workspace.errors.push(errors);
+            workspace.caches.push(cache);
+            workspace.paths.push(path);
+            workspace.texts.push(text);
+        }
+        workspace
+    }
+
+    /// parses `text` again as the file at `path`, leaving the other files as they are. A file new
+    /// to the workspace is placed in the order of the paths.
+    pub fn set_file(&mut self, path: &Path, text: String, config: &Config) {
+        match self.paths.binary_search_by(|other| other.as_path().cmp(path)) {
+            Ok(idx) => {
+                let (section, errors) = file_section(path, &text, config, &mut self.caches[idx]);
+                self.loaded[idx] = section.clone();
+                self.document.items_mut()[idx] = Item::Section(section);
+                self.errors[idx] = errors;
+                self.texts[idx] = text;
+            }
+            Err(idx) => {
+                let mut cache = SectionCache::new();
+                let (section, errors) = file_section(path, &text, config, &mut cache);
+                self.loaded.insert(idx, section.clone());
+                self.document.items_mut().insert(idx, Item::Section(section));
+                self.caches.insert(idx, cache);
+                self.errors.insert(idx, errors);
+                self.texts.insert(idx, text);
+                self.paths.insert(idx, path.to_path_buf());
+            }
+        }
+    }
+
+    /// leaves the file at `path` out of the workspace, giving whether it was in it
+    pub fn remove_file(&mut self, path: &Path) -> bool {
+        let idx = match self.paths.iter().position(|other| other == path) {
+            Some(idx) => idx,
+            None => return false,
+        };
+        self.document.items_mut().remove(idx);
+        self.errors.remove(idx);
+        self.caches.remove(idx);
+        self.loaded.remove(idx);
+        self.texts.remove(idx);
+        self.paths.remove(idx);
+        true
+    }
+
+    pub fn paths(&self) -> &[PathBuf] {
+        &self.paths
+    }
+
+    pub fn document(&self) -> &Document {
+        &self.document
+    }
+
+    /// the merged document, to be edited. Its sections of files must be kept as they are, one for
+    /// each file in the order of `paths`.
+    pub fn document_mut(&mut self) -> &mut Document {
+        &mut self.document
+    }
+
+    /// the merged document, leaving the files behind
+    pub fn into_document(self) -> Document {
+        self.document
+    }
+
+    /// the parse errors of the files, along with the file of each
+    pub fn errors(&self) -> Vec<(&Path, &DocumentParseError)> {
+        self.paths.iter()
+            .zip(self.errors.iter())
+            .flat_map(|(path, errors)| errors.iter().map(move |err| (path.as_path(), err)))
+            .collect()
+    }
+
+    /// the sections of the files, in the order of `paths`
+    fn sections(&self) -> impl Iterator<Item = &DocumentSection> {
+        self.document.items().iter().filter_map(|item| if let Item::Section(section) = item { Some(section) } else { None })
+    }
+
+    /// every task and subtask along with the file it is written in, in the order they are written
+    /// in the merged document, as in `Document::tasks`
+    pub fn tasks(&self) -> Vec<(&Path, &Task)> {
+        self.sections()
+            .zip(self.paths.iter())
+            .flat_map(|(section, path)| section.tasks().into_iter().map(move |task| (path.as_path(), task)))
+            .collect()
+    }
+
+    /// the files as notes, as (path, text), to be indexed along with other notes. Refer to
+    /// `crate::ttm_io::notes::NoteIndex::with_files`.
+    pub fn note_files(&self) -> Vec<(PathBuf, String)> {
+        self.paths.iter().cloned().zip(self.texts.iter().cloned()).collect()
+    }
+
+    /// the files edited in the merged document since they were loaded, along with their new text
+    pub fn edited(&self) -> Vec<(&Path, String)> {
+        self.sections()
+            .enumerate()
+            .filter(|(idx, section)| **section != self.loaded[*idx])
+            .map(|(idx, section)| (self.paths[idx].as_path(), self.edited_text(idx, section)))
+            .collect()
+    }
+
+    /// the text of file `idx` with the edits made to its section. Each edited task is written again
+    /// on its own line. Edits that add or remove tasks write the whole section instead.
+    fn edited_text(&self, idx: usize, section: &DocumentSection) -> String {
+        fn trees(section: &DocumentSection) -> Vec<&TaskTree> {
+            section.task_trees().into_iter().flat_map(|tree| tree.walk().into_iter().map(|(_depth, tree)| tree)).collect()
+        }
+        edit::set_tasks(&self.texts[idx], &trees(&self.loaded[idx]), &trees(section), &self.flag_aliases)
+            .unwrap_or_else(|| section.body_to_string())
+    }
+
+    /// writes the edited files back, giving the paths written
+    pub fn write_edited(&self) -> Result<Vec<&Path>, WorkspaceError> {
+        let mut out: Vec<&Path> = vec![];
+        for (path, text) in self.edited() {
+            std::fs::write(path, text).map_err(io_error(path))?;
+            out.push(path);
+        }
+        Ok(out)
+    }
+}
+
+/// the section of the file at `path`, named by its stem, along with its parse errors
+fn file_section(path: &Path, text: &str, config: &Config, cache: &mut SectionCache) -> (DocumentSection, Vec<DocumentParseError>) {
+    let (document, errors) = config.parse_document_cached(text, cache);
+    let stem = path.file_stem().map_or("".into(), |stem| stem.to_string_lossy());
+    (DocumentSection::new(&stem, document.into_items()), errors)
+}
+
+/// the path of `path` relative to `root`, with its parts separated by '/'
+fn relative_path(root: &Path, path: &Path) -> String {
+    let relative = path.strip_prefix(root).unwrap_or(path);
+    let parts: Vec<Cow<str>> = relative.components().map(|part| part.as_os_str().to_string_lossy()).collect();
+    parts.join("/")
+}
+
+fn find_dir(root: &Path, dir: &Path, config: &WorkspaceConfig, out: &mut Vec<PathBuf>) -> Result<(), WorkspaceError> {
+    for entry in std::fs::read_dir(dir).map_err(io_error(dir))? {
+        let path = entry.map_err(io_error(dir))?.path();
+        if path.file_name().is_some_and(|name| name.to_string_lossy().starts_with('.')) {
+            continue;
+        }
+        if path.is_dir() {
+            find_dir(root, &path, config, out)?;
+            continue;
+        }
+        let relative = relative_path(root, &path);
+        let matches = |patterns: &[String]| patterns.iter().any(|pattern| glob::matches(pattern, &relative));
+        if matches(&config.include) && !matches(&config.exclude) {
+            out.push(path);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ttm_core::deps::{self, DepGraph};
+    use crate::ttm_io::ids::{self, IdGenerator};
+
+    fn workspace() -> Workspace {
+        Workspace::from_files(vec![
+            (PathBuf::from("home.ttm"), "[Errands]\n    (0) Groceries (deps: work/Tasks/Report)\n".to_string()),
+            (PathBuf::from("work.ttm"), "[Tasks]\n    ~(2/2) Report\n    (0/1) Review (deps: Groceries)\n".to_string()),
+        ], &Config::default())
+    }
+
+    #[test]
+    fn test_workspace() {
+        let workspace = workspace();
+        let tasks: Vec<(&str, &str)> = workspace.tasks().iter()
+            .map(|(path, task)| (path.to_str().unwrap(), task.name()))
+            .collect();
+        assert_eq!(tasks, vec![("home.ttm", "Groceries"), ("work.ttm", "Report"), ("work.ttm", "Review")]);
+        assert!(workspace.errors().is_empty());
+        assert!(workspace.edited().is_empty());
+
+        // dependencies are resolved across the files
+        let graph = DepGraph::new(workspace.document());
+        assert!(graph.errors().is_empty());
+        assert_eq!(graph.nodes()[graph.ready()[0]].path, vec!["home", "Errands", "Groceries"]);
+    }
+
+    #[test]
+    fn test_edits_written_back() {
+        let mut workspace = workspace();
+        let evaluation = deps::evaluate(workspace.document_mut());
+        assert_eq!(evaluation.blocked, 1);
+        let edited = workspace.edited();
+        assert_eq!(edited.len(), 1);
+        // the edited line is written again as it was, save the flag
+        assert_eq!(edited[0], (Path::new("work.ttm"), "[Tasks]\n    ~(2/2) Report\n    B(0/1) Review (deps: Groceries)\n".to_string()));
+
+        let mut generator = IdGenerator::for_document(7, workspace.document());
+        assert_eq!(ids::assign_ids(workspace.document_mut(), &mut generator), 3);
+        assert_eq!(workspace.edited().len(), 2);
+    }
+
+    #[test]
+    fn test_aliases_written_back() {
+        let mut config = Config::default();
+        config.flag_aliases.insert('*', TaskFlags::CURRENT | TaskFlags::BLOCKED);
+        let text = "[Tasks]\n  *(1)  Report\n  A note\n    ~(0/1) Outline (due: W3R)\n";
+        let mut workspace = Workspace::from_files(vec![(PathBuf::from("work.ttm"), text.to_string())], &config);
+
+        let mut generator = IdGenerator::for_document(7, workspace.document());
+        assert_eq!(ids::assign_ids(workspace.document_mut(), &mut generator), 2);
+        let ids: Vec<String> = workspace.tasks().iter().map(|(_path, task)| task.id().unwrap().to_string()).collect();
+        let edited = workspace.edited();
+        assert_eq!(edited[0].1, format!("[Tasks]\n  *(1)  Report (#{})\n  A note\n    ~(0/1) Outline (due: W3R; #{})\n", ids[0], ids[1]));
+    }
+
+    #[test]
+    fn test_set_file() {
+        let mut workspace = workspace();
+        workspace.set_file(Path::new("work.ttm"), "[Tasks]\n    ~(2/2) Report\n    (0/1) Review (deps: Groceries\n".to_string(), &Config::default());
+        assert_eq!(workspace.errors().len(), 1);
+        assert_eq!(workspace.errors()[0].0, Path::new("work.ttm"));
+
+        workspace.set_file(Path::new("gym.ttm"), "[Sets]\n    (0/3) Squats\n".to_string(), &Config::default());
+        assert_eq!(workspace.paths(), &[PathBuf::from("gym.ttm"), PathBuf::from("home.ttm"), PathBuf::from("work.ttm")]);
+        assert_eq!(workspace.tasks()[0], (Path::new("gym.ttm"), &"(0/3) Squats".parse::<Task>().unwrap()));
+        assert!(workspace.edited().is_empty());
+
+        assert!(workspace.remove_file(Path::new("work.ttm")));
+        assert!(!workspace.remove_file(Path::new("work.ttm")));
+        assert!(workspace.errors().is_empty());
+        // the dependency on the removed file names no task
+        assert_eq!(DepGraph::new(workspace.document()).errors().len(), 1);
+    }
+
+    #[test]
+    fn test_find_files() {
+        let root = std::env::temp_dir().join(format!("ttm_workspace_{}", std::process::id()));
+        for file in ["work.ttm", "home.ttm", "notes.md", "archive/2021/w1.ttm", ".hidden/h.ttm"].iter() {
+            let path = root.join(file);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(&path, "[Tasks]\n    (0) Task\n").unwrap();
+        }
+        let found = |config: &WorkspaceConfig| -> Vec<String> {
+            Workspace::find_files(&root, config).unwrap().iter().map(|path| relative_path(&root, path)).collect()
+        };
+
+        assert_eq!(found(&WorkspaceConfig::default()), vec!["archive/2021/w1.ttm", "home.ttm", "work.ttm"]);
+        let config = WorkspaceConfig { exclude: vec!["archive/**".into()], ..WorkspaceConfig::default() };
+        assert_eq!(found(&config), vec!["home.ttm", "work.ttm"]);
+
+        let workspace = Workspace::load(&root, &Config::default()).unwrap();
+        assert_eq!(workspace.tasks().len(), 3);
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -1,4 +1,5 @@
 use super::stat::*;
+use std::borrow::Borrow;
 
 
 #[allow(dead_code)]
@@ -7,12 +8,52 @@ pub enum WeekDay {
     M, T, W, R, F, S, U,
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct BlockTrackerEntry {
     entry_name: String,
     week_stats: [Stat; 7],
 }
 
+impl BlockTrackerEntry {
+    pub fn new(entry_name: &str, week_stats: [Stat; 7]) -> Self {
+        Self {entry_name: entry_name.to_string(), week_stats}
+    }
+
+    pub fn entry_name(&self) -> &str {
+        &self.entry_name
+    }
+
+    /// stats of the week, indexed Monday to Sunday
+    pub fn week_stats(&self) -> &[Stat; 7] {
+        &self.week_stats
+    }
+}
+
+impl std::fmt::Display for BlockTrackerEntry {
+    /// writes the entry as a block tracker line. A width may be given to align the day columns.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let width = f.width().unwrap_or(0);
+        for stat in self.week_stats.iter() {
+            write!(f, "{:<width$} ", stat.to_string(), width = width)?;
+        }
+        write!(f, "{}", self.entry_name)
+    }
+}
+
+/// writes block tracker entries as a table, with the day columns aligned across entries
+pub fn render_table<E: Borrow<BlockTrackerEntry>>(entries: &[E]) -> String {
+    let width = entries.iter()
+        .flat_map(|entry| entry.borrow().week_stats.iter())
+        .map(|stat| stat.to_string().len())
+        .max()
+        .unwrap_or(0);
+
+    entries.iter()
+        .map(|entry| format!("{:width$}\n", entry.borrow(), width = width))
+        .collect()
+}
+
 impl std::ops::Index<WeekDay> for BlockTrackerEntry {
     type Output = Stat;
     fn index(&self, idx: WeekDay) -> &Stat {
@@ -37,7 +78,6 @@ impl std::str::FromStr for BlockTrackerEntry {
         let mut week_stats: [Stat; 7] = [Stat::Unknown; 7];
         let mut entry_count = 0;
         for (i, token) in s.split(' ').enumerate() {
-            println!("token {} {}", i, token);
             // invalid block tracker entry, they are only a stat per day and then the name.
             if i > 7 {
                 return Err(BlockTrackerEntryParseError::TooManyEntryTokens);
@@ -63,8 +103,6 @@ impl std::str::FromStr for BlockTrackerEntry {
             return Err(BlockTrackerEntryParseError::TooFewEntryTokens);
         }
 
-        println!("week_stats {:?}", week_stats);
-
         Ok(BlockTrackerEntry {entry_name, week_stats})
     }
 }
@@ -73,6 +111,22 @@ impl std::str::FromStr for BlockTrackerEntry {
 mod tests {
     
     #[allow(unused_imports)] use super::*;
+
+    #[test]
+    fn test_render_table() {
+        let entries = vec![
+            "0  0  0  0  0  0  0   PROJECT".parse::<BlockTrackerEntry>().unwrap(),
+            "?  !  !  !  !  /- 4/4 PR0JECT".parse::<BlockTrackerEntry>().unwrap(),
+        ];
+
+        let table = render_table(&entries);
+        assert_eq!(table, "0   0   0   0   0   0   0   PROJECT\n\
+                           ?   !   !   !   !   /-  4/4 PR0JECT\n");
+
+        // the rendered table parses back to the same entries
+        let parsed: Vec<BlockTrackerEntry> = table.lines().map(|line| line.parse().unwrap()).collect();
+        assert_eq!(parsed, entries);
+    }
 }
     // ------------------------------------------------------------------------------------------------------------------
     // BlockTracker Tests -----------------------------------------------------------------------------------------------
@@ -0,0 +1,278 @@
+//! Conversion between date codes and the Gregorian calendar.
+//!
+//! The season calendar defines the Gregorian day each season starts at. A season's week 1 is the
+//! Monday-to-Sunday week holding that day, and its weeks go on until the next season starts.
+//! Two digit years are taken to be in the 2000s, so Y21W-W3R is the Thursday of the third week of
+//! the 2021 Winter season.
+
+#![allow(dead_code)]
+
+use super::date::{Date, Season, Weekday};
+use std::borrow::Cow;
+
+/// a day of the Gregorian calendar
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy)]
+pub struct CivilDate {
+    pub year: i32,
+    /// 1-12
+    pub month: u32,
+    /// 1-31
+    pub day: u32,
+}
+
+impl CivilDate {
+    pub fn new(year: i32, month: u32, day: u32) -> Self {
+        Self { year, month, day }
+    }
+
+    /// days since 1970-01-01
+    pub fn to_days(self) -> i64 {
+        // Howard Hinnant's days_from_civil algorithm
+        let y = self.year as i64 - if self.month <= 2 { 1 } else { 0 };
+        let era = if y >= 0 { y } else { y - 399 } / 400;
+        let yoe = y - era * 400;
+        let m = self.month as i64;
+        let doy = (153 * (if m > 2 { m - 3 } else { m + 9 }) + 2) / 5 + self.day as i64 - 1;
+        let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+        era * 146097 + doe - 719468
+    }
+
+    /// the date `days` days since 1970-01-01
+    pub fn from_days(days: i64) -> Self {
+        // Howard Hinnant's civil_from_days algorithm
+        let z = days + 719468;
+        let era = if z >= 0 { z } else { z - 146096 } / 146097;
+        let doe = z - era * 146097;
+        let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+        let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+        let mp = (5 * doy + 2) / 153;
+        let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+        let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+        let year = (yoe + era * 400 + if month <= 2 { 1 } else { 0 }) as i32;
+        Self { year, month, day }
+    }
+
+    /// how many days month `month` of `year` has
+    pub fn days_in_month(year: i32, month: u32) -> u32 {
+        match month {
+            2 if year % 4 == 0 && (year % 100 != 0 || year % 400 == 0) => 29,
+            2 => 28,
+            4 | 6 | 9 | 11 => 30,
+            _ => 31,
+        }
+    }
+
+    /// the day of `secs`, seconds since 1970-01-01T00:00:00Z, in local time `utc_offset` seconds
+    /// ahead of UTC
+    pub fn from_timestamp(secs: i64, utc_offset: i64) -> Self {
+        Self::from_days((secs + utc_offset).div_euclid(86400))
+    }
+
+    /// today in UTC, by the system clock
+    pub fn today() -> Self {
+        let secs = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs() as i64);
+        Self::from_days(secs.div_euclid(86400))
+    }
+
+    pub fn weekday(self) -> Weekday {
+        // 1970-01-01 was a Thursday
+        Weekday::ALL[(self.to_days() + 3).rem_euclid(7) as usize]
+    }
+}
+
+impl std::fmt::Display for CivilDate {
+    /// writes the date as YYYY-MM-DD
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:04}-{:02}-{:02}", self.year, self.month, self.day)
+    }
+}
+
+impl std::str::FromStr for CivilDate {
+    type Err = Cow<'static, str>;
+
+    /// parses YYYY-MM-DD
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || -> Self::Err { format!("'{}' is not a YYYY-MM-DD date", s).into() };
+        let parts: Vec<&str> = s.split('-').collect();
+        if parts.len() != 3 || parts[0].len() != 4 || parts[1].len() != 2 || parts[2].len() != 2 {
+            return Err(invalid());
+        }
+        let year: i32 = parts[0].parse().map_err(|_| invalid())?;
+        let month: u32 = parts[1].parse().map_err(|_| invalid())?;
+        let day: u32 = parts[2].parse().map_err(|_| invalid())?;
+        if !(1..=12).contains(&month) || day < 1 || day > Self::days_in_month(year, month) {
+            return Err(format!("{} is not a day of the calendar", s).into());
+        }
+        Ok(Self { year, month, day })
+    }
+}
+
+/// the day each season starts at, as a (month, day). The seasons are in cyclic order, so the ones
+/// starting later in the Gregorian year than a following season start in the year before their
+/// own, such as a winter starting on 12-21.
+#[derive(Debug, PartialEq, Clone)]
+pub struct SeasonCalendar {
+    /// indexed by `Season::index`
+    pub season_starts: [(u32, u32); 4],
+}
+
+impl Default for SeasonCalendar {
+    /// seasons start with the quarters of the year: January, April, July and October
+    fn default() -> Self {
+        Self { season_starts: [(1, 1), (4, 1), (7, 1), (10, 1)] }
+    }
+}
+
+impl SeasonCalendar {
+    /// the Gregorian year of the year code of a date
+    pub fn gregorian_year(year: u32) -> i32 {
+        if year < 100 { 2000 + year as i32 } else { year as i32 }
+    }
+
+    /// the seasons before the first one to start earlier in the Gregorian year than the season
+    /// before it, which start in the year before their own
+    fn seasons_in_year_before(&self) -> usize {
+        let starts = &self.season_starts;
+        (1..starts.len()).find(|&idx| starts[idx - 1] >= starts[idx]).unwrap_or(0)
+    }
+
+    /// the Monday of week 1 of the season, in days since 1970-01-01
+    fn season_start_days(&self, year: u32, season: Season) -> i64 {
+        let (month, day) = self.season_starts[season.index()];
+        let mut gregorian_year = Self::gregorian_year(year);
+        if season.index() < self.seasons_in_year_before() {
+            gregorian_year -= 1;
+        }
+        let start = CivilDate::new(gregorian_year, month, day);
+        start.to_days() - start.weekday().index() as i64
+    }
+
+    /// converts a date to a Gregorian day. Short codes are taken to be in `year` and `season`,
+    /// and week codes are converted to the last day of their week. Refer to `Date::resolve`.
+    pub fn to_civil(&self, date: &Date, year: u32, season: Season) -> CivilDate {
+        match date.resolve(year, season) {
+            Date::DateCode { year, season, week, day } => {
+                let days = self.season_start_days(year, season)
+                    + (week as i64 - 1) * 7
+                    + day.index() as i64;
+                CivilDate::from_days(days)
+            }
+            _ => unreachable!("resolved dates are always DateCodes"),
+        }
+    }
+
+    /// converts a Gregorian day to a full date code
+    pub fn from_civil(&self, civil: CivilDate) -> Result<Date, Cow<'static, str>> {
+        let days = civil.to_days();
+
+        // the season holding the day is the last one to start before it. Seasons may start in
+        // the Gregorian year before their own, and the season may be of the previous year.
+        let mut found: Option<(u32, Season, i64)> = None;
+        for year in [civil.year - 1, civil.year, civil.year + 1].iter() {
+            if *year < 2000 {
+                continue;
+            }
+            let year_code = if *year < 2100 { (*year - 2000) as u32 } else { *year as u32 };
+            for season in [Season::Winter, Season::Spring, Season::Summer, Season::Fall].iter() {
+                let start = self.season_start_days(year_code, *season);
+                if start <= days {
+                    found = Some((year_code, *season, start));
+                }
+            }
+        }
+
+        match found {
+            Some((year, season, start)) => Ok(Date::DateCode {
+                year,
+                season,
+                week: ((days - start) / 7 + 1) as u32,
+                day: Weekday::ALL[((days - start) % 7) as usize],
+            }),
+            None => Err(format!("{} is before the season calendar starts", civil).into()),
+        }
+    }
+
+    /// the year and season holding a Gregorian day, which short date codes written on it are in
+    pub fn season_of(&self, civil: CivilDate) -> Result<(u32, Season), Cow<'static, str>> {
+        match self.from_civil(civil)? {
+            Date::DateCode { year, season, .. } => Ok((year, season)),
+            _ => unreachable!("from_civil always gives DateCodes"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_civil_days() {
+        assert_eq!(CivilDate::new(1970, 1, 1).to_days(), 0);
+        assert_eq!(CivilDate::new(2021, 1, 21).to_days(), 18648);
+        assert_eq!(CivilDate::from_days(18648), CivilDate::new(2021, 1, 21));
+        assert_eq!(CivilDate::from_days(-1), CivilDate::new(1969, 12, 31));
+        assert_eq!(CivilDate::from_days(CivilDate::new(2024, 2, 29).to_days() + 1), CivilDate::new(2024, 3, 1));
+        assert_eq!(CivilDate::new(2021, 1, 21).weekday(), Weekday::Thu);
+        assert_eq!(CivilDate::new(2021, 1, 21).to_string(), "2021-01-21");
+        assert_eq!("2024-02-29".parse(), Ok(CivilDate::new(2024, 2, 29)));
+        assert!("2021-02-29".parse::<CivilDate>().is_err());
+        assert!("2021-1-21".parse::<CivilDate>().is_err());
+        assert!("2021-13-01".parse::<CivilDate>().is_err());
+    }
+
+    #[test]
+    fn test_date_to_civil() {
+        let calendar = SeasonCalendar::default();
+        let civil = |s: &str| calendar.to_civil(&s.parse().unwrap(), 21, Season::Winter);
+
+        // 2021-01-01 is a Friday, so Winter 2021 starts on Monday 2020-12-28
+        assert_eq!(civil("Y21W-W1M"), CivilDate::new(2020, 12, 28));
+        assert_eq!(civil("Y21W-W3R"), CivilDate::new(2021, 1, 14));
+        assert_eq!(civil("W3R"), CivilDate::new(2021, 1, 14));
+        assert_eq!(civil("W3"), CivilDate::new(2021, 1, 17));
+        assert_eq!(civil("Y21S-W1M"), CivilDate::new(2021, 3, 29));
+        assert_eq!(civil("Y21M-WAF"), CivilDate::new(2021, 9, 3));
+    }
+
+    #[test]
+    fn test_civil_to_date() {
+        let calendar = SeasonCalendar::default();
+        let date = |year, month, day| calendar.from_civil(CivilDate::new(year, month, day)).unwrap().to_string();
+
+        assert_eq!(date(2021, 1, 14), "Y21W-W3R");
+        assert_eq!(date(2020, 12, 28), "Y21W-W1M");
+        assert_eq!(date(2020, 12, 27), "Y20F-WDU");
+        assert_eq!(date(2021, 9, 3), "Y21M-WAF");
+        assert!(calendar.from_civil(CivilDate::new(1999, 6, 1)).is_err());
+        assert_eq!(calendar.season_of(CivilDate::new(2020, 12, 28)), Ok((21, Season::Winter)));
+
+        for days in 18000..19000 {
+            let civil = CivilDate::from_days(days);
+            let date = calendar.from_civil(civil).unwrap();
+            assert_eq!(calendar.to_civil(&date, 0, Season::Winter), civil);
+        }
+    }
+
+    #[test]
+    fn test_winter_in_year_before() {
+        let calendar = SeasonCalendar { season_starts: [(12, 21), (3, 20), (6, 21), (9, 22)] };
+        let civil = |s: &str| calendar.to_civil(&s.parse().unwrap(), 24, Season::Winter);
+        let date = |year, month, day| calendar.from_civil(CivilDate::new(year, month, day)).unwrap().to_string();
+
+        // 2023-12-21 is a Thursday, so Winter 2024 starts on Monday 2023-12-18
+        assert_eq!(civil("Y24W-W1M"), CivilDate::new(2023, 12, 18));
+        assert_eq!(civil("Y24S-W1M"), CivilDate::new(2024, 3, 18));
+        assert_eq!(civil("Y23F-WDU"), CivilDate::new(2023, 12, 17));
+        assert_eq!(date(2023, 12, 18), "Y24W-W1M");
+        assert_eq!(date(2023, 12, 17), "Y23F-WDU");
+        assert_eq!(date(2024, 1, 1), "Y24W-W3M");
+
+        for days in 18000..19000 {
+            let civil = CivilDate::from_days(days);
+            let date = calendar.from_civil(civil).unwrap();
+            assert_eq!(calendar.to_civil(&date, 0, Season::Winter), civil);
+        }
+    }
+}
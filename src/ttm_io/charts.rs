@@ -0,0 +1,191 @@
+//! Renders tracked blocks as text charts for terminals:
+//!     - Heatmap: A row per weekday and a column per week, shaded by the blocks of each day, like
+//!       the contribution graph of GitHub.
+//!     - Sparkline: A line of bars, as the weekly totals of blocks.
+//! Charts are drawn with Unicode block elements, or with ASCII characters for terminals without
+//! them. Days are read from the stats of block tracker entries or the day stats of tasks, taking
+//! counts as their actual count and `!` as one block. Unknown and missing days are left blank.
+
+#![allow(dead_code)]
+
+use super::block_tracker::BlockTrackerEntry;
+use super::calendar::CivilDate;
+use super::stat::Stat;
+use super::task::Task;
+
+/// the characters charts are drawn with
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Glyphs {
+    Unicode,
+    Ascii,
+}
+
+impl Glyphs {
+    /// the shades of a heatmap cell, from no blocks to the most blocks
+    fn shades(self) -> &'static [char] {
+        match self {
+            Glyphs::Unicode => &['·', '░', '▒', '▓', '█'],
+            Glyphs::Ascii => &['.', ':', '+', '*', '#'],
+        }
+    }
+
+    /// the bars of a sparkline, from lowest to highest
+    fn bars(self) -> &'static [char] {
+        match self {
+            Glyphs::Unicode => &['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'],
+            Glyphs::Ascii => &['_', '.', '-', '~', '=', '+', '*', '#'],
+        }
+    }
+}
+
+/// the blocks of a day, if they are known
+pub fn blocks(stat: Stat) -> Option<i32> {
+    match stat {
+        Stat::Count { act, .. } => Some(act.unwrap_or(0)),
+        Stat::Bool { act, .. } => Some(act as i32),
+        Stat::RequiredCount { act, .. } => Some(act),
+        Stat::Unknown => None,
+    }
+}
+
+/// the blocks of every day of a date range
+#[derive(Debug, PartialEq, Clone)]
+pub struct DaySeries {
+    pub start: CivilDate,
+    /// the blocks of each day from `start` on
+    pub days: Vec<Option<i32>>,
+}
+
+impl DaySeries {
+    /// the days from `start` to `end`, taking the blocks of each day from `stats`. Days without a
+    /// stat are unknown, and stats outside of the range are ignored.
+    pub fn from_stats(start: CivilDate, end: CivilDate, stats: impl IntoIterator<Item = (CivilDate, Stat)>) -> Self {
+        let len = (end.to_days() - start.to_days() + 1).max(0) as usize;
+        let mut days: Vec<Option<i32>> = vec![None; len];
+        for (date, stat) in stats {
+            let idx = date.to_days() - start.to_days();
+            if (0..len as i64).contains(&idx) {
+                days[idx as usize] = blocks(stat);
+            }
+        }
+        Self { start, days }
+    }
+
+    /// the days from `start` to `end` of a task, taken from its day stat in the file of each day
+    pub fn from_task_days<'a>(start: CivilDate, end: CivilDate, days: impl IntoIterator<Item = (CivilDate, &'a Task)>) -> Self {
+        Self::from_stats(start, end, days.into_iter().filter_map(|(date, task)| Some((date, task.day_stat()?))))
+    }
+
+    /// the days of the weeks of an entry, where `monday` starts the first week. Weeks the entry is
+    /// not in are given as None.
+    pub fn from_entry_weeks<'a>(monday: CivilDate, weeks: impl IntoIterator<Item = Option<&'a BlockTrackerEntry>>) -> Self {
+        let days = weeks.into_iter()
+            .flat_map(|week| match week {
+                Some(entry) => entry.week_stats().map(blocks),
+                None => [None; 7],
+            })
+            .collect();
+        Self { start: monday, days }
+    }
+
+    /// the total blocks of each Monday to Sunday week the series overlaps, counting unknown days as 0
+    pub fn weekly_totals(&self) -> Vec<i32> {
+        let offset = self.start.weekday().index();
+        let mut totals: Vec<i32> = vec![0; (offset + self.days.len()).div_ceil(7)];
+        for (idx, blocks) in self.days.iter().enumerate() {
+            totals[(offset + idx) / 7] += blocks.unwrap_or(0);
+        }
+        totals
+    }
+}
+
+/// the shade of `blocks` out of `max`, 0 for no blocks and rising in even steps to the last shade
+fn shade(blocks: i32, max: i32, shades: &[char]) -> char {
+    if blocks <= 0 || max <= 0 {
+        return shades[0];
+    }
+    let steps = (shades.len() - 1) as i64;
+    let level = (blocks as i64 * steps + max as i64 - 1) / max as i64;
+    shades[level.clamp(1, steps) as usize]
+}
+
+/// draws `series` as a heatmap, with a row per weekday from Monday, labeled with the block tracker
+/// day letters. Each column is a week, the first holding the start of the series.
+pub fn heatmap(series: &DaySeries, glyphs: Glyphs) -> String {
+    let offset = series.start.weekday().index();
+    let weeks = (offset + series.days.len()).div_ceil(7);
+    let max = series.days.iter().flatten().copied().max().unwrap_or(0);
+
+    let mut out = String::new();
+    for (day, label) in ["M", "T", "W", "R", "F", "S", "U"].iter().enumerate() {
+        let mut row = format!("{} ", label);
+        for week in 0..weeks {
+            let cell = (week * 7 + day).checked_sub(offset).and_then(|idx| series.days.get(idx).copied().flatten());
+            row.push(cell.map_or(' ', |blocks| shade(blocks, max, glyphs.shades())));
+        }
+        out.push_str(row.trim_end());
+        out.push('\n');
+    }
+    out
+}
+
+/// draws `values` as a sparkline, scaled from 0 to the highest value
+pub fn sparkline(values: &[i32], glyphs: Glyphs) -> String {
+    let bars = glyphs.bars();
+    let max = values.iter().copied().max().unwrap_or(0);
+    values.iter()
+        .map(|value| {
+            if max <= 0 || *value <= 0 {
+                return bars[0];
+            }
+            let level = *value as i64 * (bars.len() - 1) as i64 / max as i64;
+            bars[level as usize]
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_heatmap() {
+        let weeks: Vec<BlockTrackerEntry> = vec![
+            "0 1 2 3 4 ! /- Run".parse().unwrap(),
+            "4/4 ? ? ? ? ? ? Run".parse().unwrap(),
+        ];
+        // 2021-01-11 is a Monday
+        let series = DaySeries::from_entry_weeks(CivilDate::new(2021, 1, 11), weeks.iter().map(Some));
+        assert_eq!(series.weekly_totals(), vec![11, 4]);
+
+        assert_eq!(heatmap(&series, Glyphs::Ascii), "M .#\nT :\nW +\nR *\nF #\nS :\nU .\n");
+        assert_eq!(heatmap(&series, Glyphs::Unicode), "M ·█\nT ░\nW ▒\nR ▓\nF █\nS ░\nU ·\n");
+    }
+
+    #[test]
+    fn test_from_stats() {
+        // a range starting on a Wednesday leaves Monday and Tuesday of the first week blank
+        let start = CivilDate::new(2021, 1, 13);
+        let stats = vec![
+            (CivilDate::new(2021, 1, 13), "2".parse().unwrap()),
+            (CivilDate::new(2021, 1, 18), "1/3".parse().unwrap()),
+            (CivilDate::new(2021, 2, 1), "5".parse().unwrap()),
+        ];
+        let series = DaySeries::from_stats(start, CivilDate::new(2021, 1, 18), stats);
+        assert_eq!(series.days, vec![Some(2), None, None, None, None, Some(1)]);
+        assert_eq!(series.weekly_totals(), vec![2, 1]);
+        assert_eq!(heatmap(&series, Glyphs::Ascii), "M  +\nT\nW #\nR\nF\nS\nU\n");
+
+        let tasks: Vec<Task> = vec!["(3/4) Run".parse().unwrap(), "() Run".parse().unwrap()];
+        let days = vec![(CivilDate::new(2021, 1, 13), &tasks[0]), (CivilDate::new(2021, 1, 14), &tasks[1])];
+        assert_eq!(DaySeries::from_task_days(start, CivilDate::new(2021, 1, 14), days).days, vec![Some(3), None]);
+    }
+
+    #[test]
+    fn test_sparkline() {
+        assert_eq!(sparkline(&[0, 1, 2, 3, 4, 5, 6, 7], Glyphs::Unicode), "▁▂▃▄▅▆▇█");
+        assert_eq!(sparkline(&[0, 7, 14], Glyphs::Ascii), "_~#");
+        assert_eq!(sparkline(&[0, 0], Glyphs::Ascii), "__");
+        assert_eq!(sparkline(&[], Glyphs::Ascii), "");
+    }
+}
@@ -6,9 +6,11 @@
 //! Example: Y21W-W3R is Year 2021 (W)inter, Week 3 Thursday.
 
 use std::borrow::Cow;
+use std::str::FromStr;
 use regex::Regex;
 use super::common_regex;
 use super::regex_utils;
+use crate::utils::scanner::{FromNext, ScanError, StrScanner};
 
 pub mod date_regex {
     use super::*;
@@ -16,13 +18,13 @@ pub mod date_regex {
 
     lazy_static! {
         pub static ref SHORT_DATE_CODE: String = format!(r"((?x) ^(
-            W(?P<Week>{INTEGER}|[A-Fa-f])(?P<Day>[MTWRSU])
+            W(?P<Week>{INTEGER}|[A-Fa-f])(?P<Day>[MTWRFSU])
         )$)", INTEGER=common_regex::INTEGER);
         pub static ref SHORT_WEEK_DATE_CODE: String = format!(r"((?x) ^(
             W(?P<Week>{INTEGER}|[A-Fa-f])
         )$)", INTEGER=common_regex::INTEGER);
         pub static ref LONG_DATE_CODE: String = format!(r"((?x) ^(
-            Y(?P<Year>{INTEGER})(?P<Season>[MFWS])-W(?P<Week>{INTEGER}|[A-Fa-f])(?P<Day>[MTWRSU])
+            Y(?P<Year>{INTEGER})(?P<Season>[MFWS])-W(?P<Week>{INTEGER}|[A-Fa-f])(?P<Day>[MTWRFSU])
         )$)", INTEGER=common_regex::INTEGER);
         pub static ref LONG_WEEK_DATE_CODE: String = format!(r"((?x) ^(
             Y(?P<Year>{INTEGER})(?P<Season>[MFWS])-W(?P<Week>{INTEGER}|[A-Fa-f])
@@ -34,14 +36,58 @@ pub mod date_regex {
         pub static ref LONG_DATE_CODE_RE: Regex = Regex::new(&LONG_DATE_CODE).unwrap();
         pub static ref LONG_WEEK_DATE_CODE_RE: Regex = Regex::new(&LONG_WEEK_DATE_CODE).unwrap();
     }
+
+    /// parses a date code by trying each form's regex in turn. `Date::next` has replaced this,
+    /// which is kept to benchmark the two against each other.
+    pub fn parse(s: &str) -> Result<Date, Cow<'static, str>> {
+        let s = s.trim();
+
+        if let Some(cap) = SHORT_DATE_CODE_RE.captures(s) {
+            return Ok(Date::ShortDateCode {
+                week: regex_utils::capture_parse_u32(&cap, "Week"), 
+                day: regex_utils::capture_parse(&cap, "Day")});
+        } 
+        else if let Some(cap) = SHORT_WEEK_DATE_CODE_RE.captures(s) {
+            return Ok(Date::ShortWeekDateCode {
+                week: regex_utils::capture_parse_u32(&cap, "Week")});
+        }
+        else if let Some(cap) = LONG_DATE_CODE_RE.captures(s) {
+            return Ok(Date::DateCode {
+                year: regex_utils::capture_parse_u32(&cap, "Year"), 
+                season: regex_utils::capture_parse(&cap, "Season"), 
+                week: regex_utils::capture_parse_u32(&cap, "Week"), 
+                day: regex_utils::capture_parse(&cap, "Day")});
+        }
+        else if let Some(cap) = LONG_WEEK_DATE_CODE_RE.captures(s) {
+            return Ok(Date::WeekDateCode {
+                year: regex_utils::capture_parse_u32(&cap, "Year"), 
+                season: regex_utils::capture_parse(&cap, "Season"), 
+                week: regex_utils::capture_parse_u32(&cap, "Week")});
+        }
+        else {return Err(format!("could not parse {} as a DateCode", s).into());}
+    }
 }
 
 
-#[derive(PartialEq, Debug)]
+#[derive(PartialEq, Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
 pub enum Season {
     Summer, Fall, Winter, Spring,
 }
 
+impl Season {
+    /// the position of the season within its year, starting from Winter
+    pub fn index(self) -> usize {
+        match self {
+        Season::Winter => 0,
+        Season::Spring => 1,
+        Season::Summer => 2,
+        Season::Fall => 3,
+        }
+    }
+}
+
 impl std::str::FromStr for Season {
     type Err = Cow<'static, str>;
 
@@ -56,11 +102,38 @@ impl std::str::FromStr for Season {
     }
 }
 
-#[derive(PartialEq, Debug)]
+impl std::fmt::Display for Season {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let code = match self {
+        Season::Summer => "M",
+        Season::Fall => "F",
+        Season::Winter => "W",
+        Season::Spring => "S",
+        };
+        write!(f, "{}", code)
+    }
+}
+
+#[derive(PartialEq, Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
 pub enum Weekday {
     Mon, Tue, Wed, Thu, Fri, Sat, Sun,
 }
 
+impl Weekday {
+    /// all weekdays, in the order of the block tracker columns
+    pub const ALL: [Weekday; 7] = [
+        Weekday::Mon, Weekday::Tue, Weekday::Wed, Weekday::Thu,
+        Weekday::Fri, Weekday::Sat, Weekday::Sun,
+    ];
+
+    /// the position of the day within its week, starting from Monday
+    pub fn index(self) -> usize {
+        self as usize
+    }
+}
+
 impl std::str::FromStr for Weekday {
     type Err = Cow<'static, str>;
 
@@ -78,7 +151,24 @@ impl std::str::FromStr for Weekday {
     }
 }
 
-#[derive(PartialEq, Debug)]
+impl std::fmt::Display for Weekday {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let code = match self {
+        Weekday::Mon => "M",
+        Weekday::Tue => "T",
+        Weekday::Wed => "W",
+        Weekday::Thu => "R",
+        Weekday::Fri => "F",
+        Weekday::Sat => "S",
+        Weekday::Sun => "U",
+        };
+        write!(f, "{}", code)
+    }
+}
+
+#[derive(PartialEq, Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(tag = "kind", rename_all = "snake_case"))]
 pub enum Date {
     DateCode {year: u32, season: Season, week: u32, day: Weekday},
     WeekDateCode {year: u32, season: Season, week: u32},
@@ -86,6 +176,94 @@ pub enum Date {
     ShortWeekDateCode {week: u32},
 }
 
+impl Date {
+    /// resolves the date into a full `DateCode`. Short codes are taken to be in `year` and
+    /// `season`, and week codes resolve to the last day of the week, as that is when a week
+    /// deadline is met.
+    pub fn resolve(&self, year: u32, season: Season) -> Date {
+        match *self {
+        Date::DateCode {..} => *self,
+        Date::WeekDateCode {year, season, week} => Date::DateCode {year, season, week, day: Weekday::Sun},
+        Date::ShortDateCode {week, day} => Date::DateCode {year, season, week, day},
+        Date::ShortWeekDateCode {week} => Date::DateCode {year, season, week, day: Weekday::Sun},
+        }
+    }
+
+    /// key that orders dates chronologically once resolved against `year` and `season`.
+    /// Refer to `Date::resolve`.
+    pub fn ordinal_key(&self, year: u32, season: Season) -> (u32, usize, u32, usize) {
+        match self.resolve(year, season) {
+        Date::DateCode {year, season, week, day} => (year, season.index(), week, day.index()),
+        _ => unreachable!("resolved dates are always DateCodes"),
+        }
+    }
+}
+
+impl FromNext for Date {
+    type Err = ScanError;
+
+    /// parses a date code off the start of `s`, of the forms parsed by `Date::from_str`
+    fn next(s: &str) -> Result<(usize, Self), Self::Err> {
+        /// parses the next character as a `T`, if it is one
+        fn peek_letter<T: FromStr>(scan: &StrScanner) -> Option<T> {
+            scan.stream.get(scan.cur..scan.cur + 1).and_then(|c| c.parse().ok())
+        }
+        fn next_letter<T: FromStr>(scan: &mut StrScanner) -> Option<T> {
+            let out = peek_letter(scan)?;
+            scan.advance(1);
+            Some(out)
+        }
+        /// scans a year or week number followed by a `T`. A hex number may run into it, as in
+        /// W0xAF for week 10 Friday, so its last digit is given back when no `T` follows.
+        fn next_number<T: FromStr>(scan: &mut StrScanner, what: &'static str) -> Result<u32, ScanError> {
+            let start = scan.cur;
+            let num = scan.next_integer().map_err(|_| ScanError::new(start, what))?;
+            let digits = &scan.stream[start..scan.cur];
+            if digits.starts_with("0x") && digits.len() > 3 && peek_letter::<T>(scan).is_none() {
+                scan.rewind(1);
+                if peek_letter::<T>(scan).is_some() {
+                    return Ok(num >> 4);
+                }
+                scan.advance(1);
+            }
+            Ok(num)
+        }
+
+        let mut scan = StrScanner::create(s);
+        let year_season = match scan.match_next("Y") {
+            Ok(()) => {
+                let year = next_number::<Season>(&mut scan, "a year")?;
+                let season = next_letter(&mut scan).ok_or(ScanError::new(scan.cur, "a season: M, F, W or S"))?;
+                scan.expect("-")?;
+                scan.expect("W")?;
+                Some((year, season))
+            }
+            Err(()) => {
+                scan.expect("W").or(Err(ScanError::new(scan.cur, "'Y' or 'W'")))?;
+                None
+            }
+        };
+
+        // weeks 10-15 may be written as a single hex digit
+        let week = match scan.peek_char() {
+            Ok((_, c)) if c.is_ascii_digit() => next_number::<Weekday>(&mut scan, "a week")?,
+            Ok((_, c)) if c.is_ascii_hexdigit() => {
+                scan.advance(1);
+                c.to_digit(16).unwrap()
+            }
+            _ => return Err(ScanError::new(scan.cur, "a week")),
+        };
+
+        let date = match (year_season, next_letter(&mut scan)) {
+            (Some((year, season)), Some(day)) => Date::DateCode {year, season, week, day},
+            (Some((year, season)), None) => Date::WeekDateCode {year, season, week},
+            (None, Some(day)) => Date::ShortDateCode {week, day},
+            (None, None) => Date::ShortWeekDateCode {week},
+        };
+        Ok((scan.cur, date))
+    }
+}
+
 impl std::str::FromStr for Date {
     type Err = Cow<'static, str>;
 
@@ -94,30 +272,27 @@ impl std::str::FromStr for Date {
     /// - Y<num><season>-W<num><day> like Y20S-W8M for Year (20)20, Week 8 Monday
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         let s = s.trim();
+        let mut scan = StrScanner::create(s);
+        scan.next::<Date>()
+            .and_then(|date| if scan.is_done() {Ok(date)} else {Err(ScanError::new(scan.cur, "the end of the date"))})
+            .map_err(|err| format!("could not parse {} as a DateCode: {}", s, err).into())
+    }
+}
 
-        if let Some(cap) = date_regex::SHORT_DATE_CODE_RE.captures(s) {
-            return Ok(Date::ShortDateCode {
-                week: regex_utils::capture_parse_u32(&cap, "Week"), 
-                day: regex_utils::capture_parse(&cap, "Day")});
-        } 
-        else if let Some(cap) = date_regex::SHORT_WEEK_DATE_CODE_RE.captures(s) {
-            return Ok(Date::ShortWeekDateCode {
-                week: regex_utils::capture_parse_u32(&cap, "Week")});
+impl std::fmt::Display for Date {
+    /// writes the date code in the same format it is parsed from. Weeks 10-15 are written as a
+    /// single hex digit.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        fn week_code(week: u32) -> String {
+            if (10..=15).contains(&week) {format!("{:X}", week)} else {week.to_string()}
         }
-        else if let Some(cap) = date_regex::LONG_DATE_CODE_RE.captures(s) {
-            return Ok(Date::DateCode {
-                year: regex_utils::capture_parse_u32(&cap, "Year"), 
-                season: regex_utils::capture_parse(&cap, "Season"), 
-                week: regex_utils::capture_parse_u32(&cap, "Week"), 
-                day: regex_utils::capture_parse(&cap, "Day")});
-        }
-        else if let Some(cap) = date_regex::LONG_WEEK_DATE_CODE_RE.captures(s) {
-            return Ok(Date::WeekDateCode {
-                year: regex_utils::capture_parse_u32(&cap, "Year"), 
-                season: regex_utils::capture_parse(&cap, "Season"), 
-                week: regex_utils::capture_parse_u32(&cap, "Week")});
+
+        match self {
+        Date::DateCode {year, season, week, day} => write!(f, "Y{}{}-W{}{}", year, season, week_code(*week), day),
+        Date::WeekDateCode {year, season, week} => write!(f, "Y{}{}-W{}", year, season, week_code(*week)),
+        Date::ShortDateCode {week, day} => write!(f, "W{}{}", week_code(*week), day),
+        Date::ShortWeekDateCode {week} => write!(f, "W{}", week_code(*week)),
         }
-        else {return Err(format!("could not parse {} as a DateCode", s).into());}
     }
 }
 
@@ -134,10 +309,68 @@ mod tests {
         }
 
         assert_parsing("W8T", Date::ShortDateCode {week: 8, day: Weekday::Tue});
+        assert_parsing("W8F", Date::ShortDateCode {week: 8, day: Weekday::Fri});
+        assert_parsing("WAF", Date::ShortDateCode {week: 0xA, day: Weekday::Fri});
+        assert_parsing("WF", Date::ShortWeekDateCode {week: 0xF});
         assert_parsing("W333", Date::ShortWeekDateCode {week: 333});
         assert_parsing("Y20S-W8M", Date::DateCode {year: 20, season: Season::Spring, week: 8, day: Weekday::Mon});
         assert_parsing("Y22M-W1", Date::WeekDateCode {year: 22, season: Season::Summer, week: 1});
         assert_parsing("Y20M-WF", Date::WeekDateCode {year: 20, season: Season::Summer, week: 0xF});
         assert_parsing("Y20S-WAU", Date::DateCode {year: 20, season: Season::Spring, week: 0xA, day: Weekday::Sun});
     }
+
+    #[test]
+    fn test_next() {
+        assert_eq!(Date::next("W8T)"), Ok((3, Date::ShortDateCode {week: 8, day: Weekday::Tue})));
+        assert_eq!(Date::next("Y20S-W8; done"), Ok((7, Date::WeekDateCode {year: 20, season: Season::Spring, week: 8})));
+        // a hex week or year gives its last digit back to the day or season after it
+        assert_eq!(Date::next("W0xAF"), Ok((5, Date::ShortDateCode {week: 0xA, day: Weekday::Fri})));
+        assert_eq!(Date::next("W0xAFM"), Ok((6, Date::ShortDateCode {week: 0xAF, day: Weekday::Mon})));
+        assert_eq!(Date::next("Y0x15F-W1"), Ok((9, Date::WeekDateCode {year: 0x15, season: Season::Fall, week: 1})));
+
+        assert_eq!(Date::next("due"), Err(ScanError::new(0, "'Y' or 'W'")));
+        assert_eq!(Date::next("W"), Err(ScanError::new(1, "a week")));
+        assert_eq!(Date::next("Y20X-W1"), Err(ScanError::new(3, "a season: M, F, W or S")));
+        assert_eq!(Date::next("Y20S-X1"), Err(ScanError::new(5, "'W'")));
+        assert_eq!(&"W8TT".parse::<Date>().unwrap_err(), "could not parse W8TT as a DateCode: expected the end of the date at 3");
+    }
+
+    #[test]
+    fn test_parse_as_regex() {
+        for s in &["W8T", "WAF", "WF", "Wf", "W333", " Y20S-W8M ", "Y22M-W1", "Y20S-WAU", "W0xAF", "W0xAFM", "W0xF", "W0b1F",
+                   "Y0x15F-W1", "Y0x1FF-W0x1FF", "W1A", "W0x", "WS", "Y20-W1", "Y20S-W", "W8TT", "", "W-1"] {
+            assert_eq!(s.parse::<Date>().ok(), date_regex::parse(s).ok(), "{}", s);
+        }
+    }
+
+    #[test]
+    fn test_display() {
+        for s in &["W8T", "W333", "Y20S-W8M", "Y22M-W1", "Y20M-WF", "Y20S-WAU", "Y21F-WCR"] {
+            let date: Date = s.parse().unwrap();
+            assert_eq!(&date.to_string(), s);
+        }
+        assert_eq!("W0xAF".parse::<Date>().unwrap().to_string(), "WAF");
+        assert_eq!("Y21W-W10".parse::<Date>().unwrap().to_string(), "Y21W-WA");
+    }
+
+    #[test]
+    fn test_resolve() {
+        fn assert_resolves(s: &str, year: u32, season: Season, exp: &str) {
+            let date: Date = s.parse().unwrap();
+            assert_eq!(date.resolve(year, season), exp.parse::<Date>().unwrap());
+        }
+
+        assert_resolves("W8T", 21, Season::Winter, "Y21W-W8T");
+        assert_resolves("W3", 21, Season::Fall, "Y21F-W3U");
+        assert_resolves("Y20S-W8M", 21, Season::Winter, "Y20S-W8M");
+        assert_resolves("Y20S-W8", 21, Season::Winter, "Y20S-W8U");
+
+        let key = |s: &str| s.parse::<Date>().unwrap().ordinal_key(21, Season::Spring);
+        assert!(key("Y21W-W9U") < key("W1M"));
+        assert!(key("W1M") < key("W1T"));
+        assert!(key("W1T") < key("W1"));
+        assert!(key("W1") < key("W2M"));
+        assert!(key("Y21F-W1M") > key("W9U"));
+        assert!(key("Y20F-W1M") < key("W1M"));
+    }
 }
@@ -0,0 +1,78 @@
+//! Problems found in a TTM file, by line. Parse errors are diagnostics, and so are the findings of
+//! checks over parsed documents, such as `crate::ttm_core::lint`, so they can be reported alike.
+
+#![allow(dead_code)]
+
+use super::document::DocumentParseError;
+use std::fmt;
+
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Severity::Error => write!(f, "error"),
+            Severity::Warning => write!(f, "warning"),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Diagnostic {
+    /// the line the problem is on, starting from 1
+    pub line: usize,
+    pub severity: Severity,
+    /// what found the problem, as the name of a lint rule. Parse errors have none.
+    pub code: Option<&'static str>,
+    pub message: String,
+}
+
+impl Diagnostic {
+    pub fn new(line: usize, severity: Severity, code: Option<&'static str>, message: &str) -> Self {
+        Self { line, severity, code, message: message.to_string() }
+    }
+}
+
+impl From<&DocumentParseError> for Diagnostic {
+    fn from(err: &DocumentParseError) -> Self {
+        // the line is given apart, so it is left out of the message
+        let message = match err {
+            DocumentParseError::InvalidTask { err, .. } => format!("invalid task: {}", err),
+            DocumentParseError::InvalidSection { err, .. } => format!("invalid section: {}", err),
+        };
+        Self::new(err.line(), Severity::Error, None, &message)
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    /// writes the diagnostic as `line: severity: message [code]`
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}: {}", self.line, self.severity, self.message)?;
+        if let Some(code) = self.code {
+            write!(f, " [{}]", code)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ttm_io::document::Document;
+
+    #[test]
+    fn test_diagnostic() {
+        let (_document, errors) = Document::parse_lenient("[Tasks]\n\t() Task (due: W99Z)\n");
+        let diagnostic = Diagnostic::from(&errors[0]);
+        assert_eq!(diagnostic.line, 2);
+        assert_eq!(diagnostic.severity, Severity::Error);
+        assert!(diagnostic.to_string().starts_with("2: error: invalid task: "));
+
+        let diagnostic = Diagnostic::new(3, Severity::Warning, Some("empty-section"), "the section is empty");
+        assert_eq!(diagnostic.to_string(), "3: warning: the section is empty [empty-section]");
+    }
+}
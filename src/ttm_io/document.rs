@@ -0,0 +1,779 @@
+//! A document is a whole TTM file. It holds sections, which may nest other sections, task trees,
+//! block tracker entries and free text. Refer to `super::section::Section` for how a section's
+//! body is delimited.
+//!
+//! Each line of a body is parsed as:
+//!     - [Specifier]: A nested section, along with its own body.
+//!     - A task, if the text before its first parenthesis parses as `TaskFlags`. The task is read
+//!       along with its subtasks. Refer to `super::task_tree::TaskTree`.
+//!     - A block tracker entry, if the line parses as one.
+//!     - Free text otherwise. This is kept as is.
+//! A section may be given a `Payload` by its specifier, which limits what its body is read as.
+//! Nested sections take the payload of their parent unless given their own.
+//!
+//! A written document is tabbed canonically: the body of a section is one `INDENT` deeper than
+//! its specifier, so parsing a written document gives back the same document. Text and task trees
+//! tabbed deeper than the body they are in keep how many levels deeper they are, and are written
+//! back as deep. Refer to `crate::utils::common::StrUtils::tab_level`.
+//!
+//! Documents parsed again and again as they are edited may keep their parsed sections in a
+//! `SectionCache`. A section is delimited by its tab level, so a section whose lines are unchanged
+//! parses the same wherever it is, and is taken from the cache. An edit then only parses the
+//! section it is in again, along with the sections holding it. Refer to `Document::parse_cached`.
+
+#![allow(dead_code)]
+
+use super::block_tracker::{self, BlockTrackerEntry};
+use super::section::{Section, SectionParseError};
+use super::task::{Task, TaskFlags, TaskParseError};
+use super::task_tree::{TaskTree, INDENT};
+use crate::utils::common::StrUtils;
+use crate::utils::scanner::StrScanner;
+use std::borrow::Cow;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::hash::{Hash, Hasher};
+
+#[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum Item {
+    Section(DocumentSection),
+    Task(TaskTree),
+    BlockTrackerEntry(BlockTrackerEntry),
+    Text(TextLine),
+}
+
+/// a line that is not a TTM element, without its tabbing
+#[derive(Debug, PartialEq, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(from = "JsonTextLine", into = "JsonTextLine"))]
+pub struct TextLine {
+    pub text: String,
+    /// how many levels the line is tabbed deeper than the items around it
+    pub depth: usize,
+}
+
+impl TextLine {
+    pub fn new(text: &str, depth: usize) -> Self {
+        Self { text: text.to_string(), depth }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.text.is_empty()
+    }
+}
+
+impl From<&str> for TextLine {
+    fn from(text: &str) -> Self {
+        Self::new(text, 0)
+    }
+}
+
+impl From<String> for TextLine {
+    fn from(text: String) -> Self {
+        Self { text, depth: 0 }
+    }
+}
+
+/// text lines as written in JSON: a string, unless the line is tabbed deeper
+#[cfg(feature = "serde")]
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(untagged)]
+enum JsonTextLine {
+    Text(String),
+    Tabbed { text: String, depth: usize },
+}
+
+#[cfg(feature = "serde")]
+impl From<JsonTextLine> for TextLine {
+    fn from(line: JsonTextLine) -> Self {
+        match line {
+            JsonTextLine::Text(text) => text.into(),
+            JsonTextLine::Tabbed { text, depth } => Self { text, depth },
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl From<TextLine> for JsonTextLine {
+    fn from(line: TextLine) -> Self {
+        match line.depth {
+            0 => JsonTextLine::Text(line.text),
+            depth => JsonTextLine::Tabbed { text: line.text, depth },
+        }
+    }
+}
+
+/// the 1-based line of the document an item was read from, or 0 if it was not read from one. It
+/// is left out of comparisons, so that documents compare by what they hold.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SourceLine(pub usize);
+
+impl PartialEq for SourceLine {
+    fn eq(&self, _other: &Self) -> bool {
+        true
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DocumentSection {
+    specifier: String,
+    items: Vec<Item>,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    line: SourceLine,
+}
+
+impl DocumentSection {
+    pub fn new(specifier: &str, items: Vec<Item>) -> Self {
+        Self { specifier: specifier.to_string(), items, line: SourceLine::default() }
+    }
+
+    pub fn specifier(&self) -> &str {
+        &self.specifier
+    }
+
+    /// the line of the specifier, or 0 if the section was not read from a document
+    pub fn line(&self) -> usize {
+        self.line.0
+    }
+
+    /// moves the lines of the section and its items read from line `from` on to line `to` on
+    fn move_lines(&mut self, from: usize, to: usize) {
+        self.line.0 = self.line.0 + to - from;
+        move_lines(&mut self.items, from, to);
+    }
+
+    pub fn items(&self) -> &[Item] {
+        &self.items
+    }
+
+    pub fn items_mut(&mut self) -> &mut Vec<Item> {
+        &mut self.items
+    }
+
+    /// lists every task tree in the section, in the order they are written
+    pub fn task_trees(&self) -> Vec<&TaskTree> {
+        task_trees_of(&self.items)
+    }
+
+    /// lists every task and subtask in the section, in the order they are written
+    pub fn tasks(&self) -> Vec<&Task> {
+        tasks_of(&self.items)
+    }
+
+    /// writes the items of the section as a document of their own, without the specifier
+    pub fn body_to_string(&self) -> String {
+        let mut out = String::new();
+        render_items(&self.items, "", &mut out);
+        out
+    }
+}
+
+/// what the body of a section is read as. Sections without a payload may hold any item.
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+pub enum Payload {
+    /// task trees and free text
+    Tasks,
+    /// block tracker entries and free text
+    BlockTracker,
+    /// free text only, such as notes written in brackets and parentheses
+    Text,
+}
+
+impl Payload {
+    pub fn name(self) -> &'static str {
+        match self {
+            Payload::Tasks => "tasks",
+            Payload::BlockTracker => "block-tracker",
+            Payload::Text => "text",
+        }
+    }
+}
+
+impl std::str::FromStr for Payload {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        [Payload::Tasks, Payload::BlockTracker, Payload::Text].iter().copied()
+            .find(|payload| payload.name() == s)
+            .ok_or_else(|| format!("unknown payload '{}', expected one of tasks, block-tracker, text", s))
+    }
+}
+
+/// the payloads of sections, by specifier
+pub type Payloads = BTreeMap<String, Payload>;
+
+#[derive(Debug, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Document {
+    items: Vec<Item>,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum DocumentParseError {
+    /// the line looks like a task, but does not parse as one
+    InvalidTask { line: usize, err: TaskParseError },
+    InvalidSection { line: usize, err: SectionParseError },
+}
+
+impl fmt::Display for DocumentParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::result::Result<(), std::fmt::Error> {
+        let msg: Cow<'static, str> = match self {
+            Self::InvalidTask { line, err } => format!("line {}: invalid task: {}", line, err).into(),
+            Self::InvalidSection { line, err } => {
+                format!("line {}: invalid section: {}", line, err).into()
+            }
+        };
+        write!(f, "{}", msg)?;
+        Ok(())
+    }
+}
+
+impl DocumentParseError {
+    /// the 1-based line the error was found in
+    pub fn line(&self) -> usize {
+        match self {
+            Self::InvalidTask { line, .. } => *line,
+            Self::InvalidSection { line, .. } => *line,
+        }
+    }
+
+    /// the error, found in `line` instead
+    fn at_line(&self, line: usize) -> Self {
+        match self {
+            Self::InvalidTask { err, .. } => Self::InvalidTask { line, err: err.clone() },
+            Self::InvalidSection { err, .. } => Self::InvalidSection { line, err: err.clone() },
+        }
+    }
+}
+
+/// a section by the hash and length of its lines, from its specifier to the end of its tab level,
+/// and the payload it is read as
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+struct SectionKey {
+    hash: u64,
+    len: usize,
+    payload: Option<Payload>,
+}
+
+impl SectionKey {
+    fn new(span: &str, payload: Option<Payload>) -> Self {
+        let mut hasher = DefaultHasher::new();
+        span.hash(&mut hasher);
+        Self { hash: hasher.finish(), len: span.len(), payload }
+    }
+}
+
+#[derive(Debug)]
+struct CachedSection {
+    section: DocumentSection,
+    /// the parse errors of the section, with lines counted from its specifier line, as are the
+    /// lines of the section
+    errors: Vec<DocumentParseError>,
+    /// the sections nested in the section, kept for as long as it is
+    children: Vec<SectionKey>,
+    /// whether the last parse came across the section
+    used: bool,
+}
+
+/// the sections of a document parsed so far, to be taken as they are when parsing the document
+/// again. Sections the last parse did not come across are let go of.
+#[derive(Debug, Default)]
+pub struct SectionCache {
+    /// the payloads the sections were read with
+    payloads: Payloads,
+    sections: HashMap<SectionKey, CachedSection>,
+    hits: usize,
+    misses: usize,
+}
+
+impl SectionCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// how many sections the last parse took from the cache, and how many it parsed
+    pub fn stats(&self) -> (usize, usize) {
+        (self.hits, self.misses)
+    }
+
+    pub fn len(&self) -> usize {
+        self.sections.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sections.is_empty()
+    }
+
+    /// starts a parse with `payloads`. Sections read with other payloads may parse differently.
+    fn begin(&mut self, payloads: &Payloads) {
+        if *payloads != self.payloads {
+            self.sections.clear();
+            self.payloads = payloads.clone();
+        }
+        self.hits = 0;
+        self.misses = 0;
+    }
+
+    /// lets go of the sections the parse did not come across
+    fn end(&mut self) {
+        self.sections.retain(|_key, cached| cached.used);
+        for cached in self.sections.values_mut() {
+            cached.used = false;
+        }
+    }
+
+    /// the section of `key` along with its errors, if it is cached, as if its specifier were in
+    /// `line`. The sections nested in it are kept too.
+    fn get(&mut self, key: &SectionKey, line: usize) -> Option<(DocumentSection, Vec<DocumentParseError>)> {
+        let cached = self.sections.get(key)?;
+        let mut section = cached.section.clone();
+        section.move_lines(0, line);
+        let out = (section, cached.errors.iter().map(|err| err.at_line(err.line() + line)).collect());
+        let mut used = vec![*key];
+        while let Some(key) = used.pop() {
+            if let Some(cached) = self.sections.get_mut(&key) {
+                cached.used = true;
+                used.extend(cached.children.iter().copied());
+            }
+        }
+        self.hits += 1;
+        Some(out)
+    }
+
+    /// caches `section`, whose specifier is in `line`
+    fn insert(&mut self, key: SectionKey, section: &DocumentSection, errors: &[DocumentParseError], line: usize, children: Vec<SectionKey>) {
+        let errors = errors.iter().map(|err| err.at_line(err.line() - line)).collect();
+        let mut section = section.clone();
+        section.move_lines(line, 0);
+        self.sections.insert(key, CachedSection { section, errors, children, used: true });
+        self.misses += 1;
+    }
+}
+
+impl Document {
+    pub fn new(items: Vec<Item>) -> Self {
+        Self { items }
+    }
+
+    pub fn items(&self) -> &[Item] {
+        &self.items
+    }
+
+    pub fn items_mut(&mut self) -> &mut Vec<Item> {
+        &mut self.items
+    }
+
+    pub fn into_items(self) -> Vec<Item> {
+        self.items
+    }
+
+    /// parses the document, keeping any lines that fail to parse as text. The errors found
+    /// are returned along with the document.
+    pub fn parse_lenient(s: &str) -> (Self, Vec<DocumentParseError>) {
+        Self::parse_with(s, &Payloads::new())
+    }
+
+    /// parses the document as `parse_lenient` does, reading the sections named in `payloads` as
+    /// their payload
+    pub fn parse_with(s: &str, payloads: &Payloads) -> (Self, Vec<DocumentParseError>) {
+        let mut parser = Parser { payloads, cache: None, errors: vec![], children: vec![] };
+        let items = parser.items(s, 1, 0, None);
+        (Self { items }, parser.errors)
+    }
+
+    /// parses the document as `parse_with` does, taking the sections that are unchanged since the
+    /// last parse from `cache`
+    pub fn parse_cached(s: &str, payloads: &Payloads, cache: &mut SectionCache) -> (Self, Vec<DocumentParseError>) {
+        cache.begin(payloads);
+        let mut parser = Parser { payloads, cache: Some(cache), errors: vec![], children: vec![] };
+        let items = parser.items(s, 1, 0, None);
+        let errors = parser.errors;
+        cache.end();
+        (Self { items }, errors)
+    }
+
+    /// lists every task tree in the document, in the order they are written
+    pub fn task_trees(&self) -> Vec<&TaskTree> {
+        task_trees_of(&self.items)
+    }
+
+    /// lists every task tree in the document along with all of their subtrees, in the order they
+    /// are written
+    pub fn walk_trees(&self) -> Vec<&TaskTree> {
+        self.task_trees().into_iter().flat_map(|tree| tree.walk().into_iter().map(|(_depth, tree)| tree)).collect()
+    }
+
+    /// lists every task and subtask in the document, in the order they are written
+    pub fn tasks(&self) -> Vec<&Task> {
+        tasks_of(&self.items)
+    }
+
+    /// lists every block tracker entry in the document, in the order they are written
+    pub fn block_tracker_entries(&self) -> Vec<&BlockTrackerEntry> {
+        let mut out: Vec<&BlockTrackerEntry> = vec![];
+        visit_items(&self.items, &mut |item| {
+            if let Item::BlockTrackerEntry(entry) = item {
+                out.push(entry);
+            }
+        });
+        out
+    }
+}
+
+fn task_trees_of(items: &[Item]) -> Vec<&TaskTree> {
+    let mut out: Vec<&TaskTree> = vec![];
+    visit_items(items, &mut |item| {
+        if let Item::Task(tree) = item {
+            out.push(tree);
+        }
+    });
+    out
+}
+
+fn tasks_of(items: &[Item]) -> Vec<&Task> {
+    task_trees_of(items)
+        .into_iter()
+        .flat_map(|tree| tree.walk().into_iter().map(|(_depth, tree)| tree.task()))
+        .collect()
+}
+
+/// calls `f` on every item, including the items nested in sections
+fn visit_items<'a>(items: &'a [Item], f: &mut dyn FnMut(&'a Item)) {
+    for item in items.iter() {
+        f(item);
+        if let Item::Section(section) = item {
+            visit_items(&section.items, f);
+        }
+    }
+}
+
+/// a line is meant to be a task if the text before its first parenthesis are task flags
+pub(crate) fn is_task_line(line: &str) -> bool {
+    let mut scan = StrScanner::create(line);
+    scan.skip_whitespace();
+    let _ = scan.next::<TaskFlags>();
+    scan.skip_whitespace();
+    scan.match_next("(").is_ok()
+}
+
+pub(crate) fn is_specifier_line(line: &str) -> bool {
+    let line = line.trim();
+    line.starts_with('[') && line.ends_with(']')
+}
+
+/// parses section bodies, taking sections from `cache` if there is one
+struct Parser<'a> {
+    payloads: &'a Payloads,
+    cache: Option<&'a mut SectionCache>,
+    errors: Vec<DocumentParseError>,
+    /// the sections come across in the body being parsed
+    children: Vec<SectionKey>,
+}
+
+impl Parser<'_> {
+    /// parses the items of a section body `s`, whose first line is line `first_line` of the
+    /// document and whose items are tabbed `level` levels deep, as `payload` if the section has one
+    fn items(&mut self, s: &str, first_line: usize, level: usize, payload: Option<Payload>) -> Vec<Item> {
+        let mut items: Vec<Item> = vec![];
+        let mut scan = StrScanner::create(s);
+        let line_at = |cur: usize| first_line + s[..cur].matches('\n').count();
+
+        while let Ok((len, line)) = scan.peek_line() {
+            let line_no = line_at(scan.cur);
+            let trimmed = line.trim();
+            let depth = StrUtils(&line).tab_level().saturating_sub(level);
+
+            if payload == Some(Payload::Text) {
+                // text sections are kept whole, nested sections included
+            } else if is_specifier_line(trimmed) {
+                let start = scan.cur;
+                match scan.next::<Section>() {
+                    Ok(section) => {
+                        let section_payload = self.payloads.get(section.specifier()).copied().or(payload);
+                        let section = self.section(&section, &s[start..scan.cur], line_no, section_payload);
+                        items.push(Item::Section(section));
+                        continue;
+                    }
+                    Err(err) => self.errors.push(DocumentParseError::InvalidSection { line: line_no, err }),
+                }
+            } else if payload != Some(Payload::BlockTracker) && is_task_line(trimmed) {
+                match scan.next::<TaskTree>() {
+                    Ok(mut tree) => {
+                        tree.set_depth(depth);
+                        tree.move_lines(1, line_no);
+                        items.push(Item::Task(tree));
+                        continue;
+                    }
+                    Err(err) => self.errors.push(DocumentParseError::InvalidTask { line: line_no, err }),
+                }
+            } else if let Some(entry) = trimmed.parse::<BlockTrackerEntry>().ok().filter(|_| payload != Some(Payload::Tasks)) {
+                items.push(Item::BlockTrackerEntry(entry));
+                scan.advance(len);
+                continue;
+            }
+
+            items.push(Item::Text(TextLine::new(trimmed, depth)));
+            scan.advance(len);
+        }
+
+        items
+    }
+
+    /// parses `section`, written as `span` from line `line` of the document, unless it is cached
+    fn section(&mut self, section: &Section, span: &str, line: usize, payload: Option<Payload>) -> DocumentSection {
+        let key = match self.cache.as_deref_mut() {
+            Some(cache) => {
+                let key = SectionKey::new(span, payload);
+                if let Some((section, errors)) = cache.get(&key, line) {
+                    self.errors.extend(errors);
+                    self.children.push(key);
+                    return section;
+                }
+                Some(key)
+            }
+            None => None,
+        };
+
+        let errors = std::mem::take(&mut self.errors);
+        let children = std::mem::take(&mut self.children);
+        let level = StrUtils(section.tab()).tab_level() + 1;
+        let items = self.items(section.body(), line + 1, level, payload);
+        let mut parsed = DocumentSection::new(section.specifier(), items);
+        parsed.line = SourceLine(line);
+        let section_errors = std::mem::replace(&mut self.errors, errors);
+        let section_children = std::mem::replace(&mut self.children, children);
+
+        if let (Some(key), Some(cache)) = (key, self.cache.as_deref_mut()) {
+            cache.insert(key, &parsed, &section_errors, line, section_children);
+            self.children.push(key);
+        }
+        self.errors.extend(section_errors);
+        parsed
+    }
+}
+
+/// moves the lines of `items` read from line `from` on to line `to` on
+fn move_lines(items: &mut [Item], from: usize, to: usize) {
+    for item in items.iter_mut() {
+        match item {
+            Item::Section(section) => section.move_lines(from, to),
+            Item::Task(tree) => tree.move_lines(from, to),
+            _ => (),
+        }
+    }
+}
+
+/// writes `items` with every line tabbed by `tab`
+fn render_items(items: &[Item], tab: &str, out: &mut String) {
+    let mut i = 0;
+    while i < items.len() {
+        match &items[i] {
+            Item::Section(section) => {
+                out.push_str(&format!("{}[{}]\n", tab, section.specifier));
+                render_items(&section.items, &format!("{}{}", tab, INDENT), out);
+            }
+            Item::Task(tree) => out.push_str(&tree.render(tab)),
+            Item::BlockTrackerEntry(_) => {
+                // consecutive entries make up a table, so their columns are aligned together
+                let mut entries: Vec<&BlockTrackerEntry> = vec![];
+                while let Some(Item::BlockTrackerEntry(entry)) = items.get(i) {
+                    entries.push(entry);
+                    i += 1;
+                }
+                for line in block_tracker::render_table(&entries).lines() {
+                    out.push_str(&format!("{}{}\n", tab, line));
+                }
+                continue;
+            }
+            Item::Text(line) => render_text(line, tab, out),
+        }
+        i += 1;
+    }
+}
+
+/// writes `line` tabbed by `tab` and its depth. Blank lines are written without tabbing.
+pub(crate) fn render_text(line: &TextLine, tab: &str, out: &mut String) {
+    if line.is_empty() {
+        out.push('\n');
+    } else {
+        out.push_str(&format!("{}{}{}\n", tab, INDENT.repeat(line.depth), line.text));
+    }
+}
+
+impl std::str::FromStr for Document {
+    type Err = DocumentParseError;
+
+    /// parses the document, failing on the first line that does not parse
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (document, mut errors) = Self::parse_lenient(s);
+        if !errors.is_empty() {
+            return Err(errors.remove(0));
+        }
+        Ok(document)
+    }
+}
+
+impl fmt::Display for Document {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut out = String::new();
+        render_items(&self.items, "", &mut out);
+        write!(f, "{}", out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DOCUMENT: &str = "\
+Notes before any section
+
+[Block Tracker]
+    0  0  !  -  /- ?  4/4 PROJECT
+    1  2  3  4  5  6  7   READING
+
+[Tasks]
+    >(2/15) Write the report (due: W3R)
+        ~(2/2) Outline
+        (0/2) Draft
+    A note on the tasks
+    [Errands]
+        (0) Groceries
+";
+
+    #[test]
+    fn test_parse_document() {
+        let document: Document = DOCUMENT.parse().unwrap();
+
+        let specifiers: Vec<&str> = document.items().iter()
+            .filter_map(|item| if let Item::Section(section) = item {Some(section.specifier())} else {None})
+            .collect();
+        assert_eq!(specifiers, vec!["Block Tracker", "Tasks"]);
+        assert_eq!(document.items()[0], Item::Text("Notes before any section".into()));
+
+        let names: Vec<&str> = document.tasks().iter().map(|task| task.name()).collect();
+        assert_eq!(names, vec!["Write the report", "Outline", "Draft", "Groceries"]);
+        assert_eq!(document.task_trees().len(), 2);
+
+        let entries: Vec<&str> = document.block_tracker_entries().iter().map(|entry| entry.entry_name()).collect();
+        assert_eq!(entries, vec!["PROJECT", "READING"]);
+    }
+
+    #[test]
+    fn test_render_document() {
+        let document: Document = DOCUMENT.parse().unwrap();
+        let rendered = document.to_string();
+
+        assert_eq!(rendered, "\
+Notes before any section
+
+[Block Tracker]
+\t0   0   !   -   /-  ?   4/4 PROJECT
+\t1   2   3   4   5   6   7   READING
+
+[Tasks]
+\t>(2/15) Write the report (due: W3R)
+\t\t~(2/2) Outline
+\t\t(0/2) Draft
+\tA note on the tasks
+\t[Errands]
+\t\t(0) Groceries
+");
+        assert_eq!(rendered.parse::<Document>().unwrap(), document);
+    }
+
+    #[test]
+    fn test_round_trip_tabbing() {
+        // text and blank lines between a task and its subtasks keep them nested
+        let s = "[T]\n\t() a\n\t\tnote\n\t\t() child\n";
+        let document: Document = s.parse().unwrap();
+        assert_eq!(document.tasks().len(), 2);
+        assert_eq!(document.task_trees().len(), 1);
+        assert_eq!(document.to_string(), s);
+
+        let s = "[T]\n\t() a\n\n\t\t() child\n";
+        let document: Document = s.parse().unwrap();
+        assert_eq!(document.task_trees()[0].subtasks().len(), 1);
+        assert_eq!(document.to_string(), s);
+
+        // text and task trees tabbed deeper than their place are written back as deep
+        let s = "\t\t() deep\n() top\n[T]\n\tnote\n\t\t\tdeeper note\n\t() a\n\t\t\t() child\n";
+        let document: Document = s.parse().unwrap();
+        assert_eq!(document.task_trees()[0].depth(), 2);
+        assert_eq!(document.to_string(), s);
+        assert_eq!(document.to_string().parse::<Document>().unwrap(), document);
+
+        // tabbing is written canonically
+        let document: Document = "[T]\n    () a\n            () child\n        note\n".parse().unwrap();
+        assert_eq!(document.to_string(), "[T]\n\t() a\n\t\t\t() child\n\t\tnote\n");
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let s = "[Tasks]\n    (0/x) Broken task\n    (0) Fine task\n    Just text (with parenthesis)\n";
+        let (document, errors) = Document::parse_lenient(s);
+
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].line(), 2);
+        assert!(matches!(errors[0], DocumentParseError::InvalidTask {..}));
+        assert_eq!(document.tasks().len(), 1);
+        assert!(s.parse::<Document>().is_err());
+    }
+
+    #[test]
+    fn test_section_cache() {
+        let s = "[Work]\n    [Report]\n        (0/2) Draft\n    [Review]\n        (0/1) Read\n[Home]\n    (0/x) Broken\n";
+        // the lines of the tasks and sections, which are left out of comparisons
+        fn lines(document: &Document) -> (Vec<usize>, Vec<usize>) {
+            let mut sections = vec![];
+            visit_items(document.items(), &mut |item| if let Item::Section(section) = item { sections.push(section.line()) });
+            let tasks = document.task_trees().iter().flat_map(|tree| tree.walk()).map(|(_depth, tree)| tree.line()).collect();
+            (tasks, sections)
+        }
+
+        let mut cache = SectionCache::new();
+        let parsed = Document::parse_cached(s, &Payloads::new(), &mut cache);
+        assert_eq!(parsed, Document::parse_lenient(s));
+        assert_eq!(lines(&parsed.0), (vec![3, 5], vec![1, 2, 4, 6]));
+        assert_eq!((cache.stats(), cache.len()), ((0, 4), 4));
+
+        // unchanged sections are taken whole, along with the sections nested in them
+        Document::parse_cached(s, &Payloads::new(), &mut cache);
+        assert_eq!((cache.stats(), cache.len()), ((2, 0), 4));
+
+        // an edit parses its section and the sections holding it, and errors after it move along
+        let edited = s.replace("(0/2) Draft", "(1/2) Draft\n        (0/1) Proofread");
+        let parsed = Document::parse_cached(&edited, &Payloads::new(), &mut cache);
+        assert_eq!(parsed, Document::parse_lenient(&edited));
+        assert_eq!(parsed.1[0].line(), 8);
+        assert_eq!(lines(&parsed.0), (vec![3, 4, 6], vec![1, 2, 5, 7]));
+        assert_eq!(cache.stats(), (2, 2));
+        // the sections as they were before the edit are let go of
+        assert_eq!(cache.len(), 4);
+
+        let payloads: Payloads = vec![("Home".to_string(), Payload::Text)].into_iter().collect();
+        let parsed = Document::parse_cached(&edited, &payloads, &mut cache);
+        assert_eq!(parsed, Document::parse_with(&edited, &payloads));
+        assert_eq!(cache.stats(), (0, 4));
+    }
+
+    #[test]
+    fn test_payloads() {
+        let s = "[Journal]\n    (1) Not a task\n    [Aside]\n[Tasks]\n    1 2 3 4 5 6 7 NOT_AN_ENTRY\n    [Errands]\n        (0) Groceries\n";
+        let payloads: Payloads = vec![("Journal".to_string(), Payload::Text), ("Tasks".to_string(), Payload::Tasks)]
+            .into_iter().collect();
+        let (document, errors) = Document::parse_with(s, &payloads);
+
+        assert!(errors.is_empty());
+        let names: Vec<&str> = document.tasks().iter().map(|task| task.name()).collect();
+        assert_eq!(names, vec!["Groceries"]);
+        assert!(document.block_tracker_entries().is_empty());
+        assert_eq!(Document::parse_lenient(s).0.tasks().len(), 2);
+        assert_eq!("block-tracker".parse::<Payload>(), Ok(Payload::BlockTracker));
+        assert!("notes".parse::<Payload>().is_err());
+    }
+}
@@ -0,0 +1,250 @@
+//! Edits TTM text in place. Writing a parsed `super::document::Document` gives its canonical form,
+//! which retabs the file and realigns its tables. These edits change a single field of a line and
+//! leave everything else as it was written, so a file can be updated without reformatting it.
+
+#![allow(dead_code)]
+
+use super::block_tracker::BlockTrackerEntry;
+use super::format::keep_dates;
+use super::stat::Stat;
+use super::task::{self, Task, TaskFlags};
+use super::task_tree::TaskTree;
+use std::collections::{BTreeMap, HashMap};
+
+/// edits the lines of `text` with `f`, called with the index and the text of each line without its
+/// line ending. `f` gives the new text of the line, or None to keep it. Line endings are kept.
+pub fn edit_lines(text: &str, mut f: impl FnMut(usize, &str) -> Option<String>) -> String {
+    let mut out = String::with_capacity(text.len());
+    for (i, line) in text.split_inclusive('\n').enumerate() {
+        let content = line.trim_end_matches(['\n', '\r']);
+        match f(i, content) {
+            Some(edited) => {
+                out.push_str(&edited);
+                out.push_str(&line[content.len()..]);
+            }
+            None => out.push_str(line),
+        }
+    }
+    out
+}
+
+/// replaces the flags of the task written on `line`, keeping its tabbing. Gives None if the line is
+/// not a task.
+pub fn set_flags(line: &str, flags: TaskFlags) -> Option<String> {
+    let open = line.find('(')?;
+    line[..open].trim().parse::<TaskFlags>().ok()?;
+    let tab = line.len() - line.trim_start().len();
+    Some(format!("{}{}{}", &line[..tab], flags, &line[open..]))
+}
+
+/// replaces the day stat of the task written on `line`, which is the first stat of its prefix
+/// descriptor. Gives None if the line is not a task.
+pub fn set_day_stat(line: &str, stat: Stat) -> Option<String> {
+    let open = line.find('(')?;
+    line[..open].trim().parse::<TaskFlags>().ok()?;
+    let close = open + line[open..].find(')')?;
+    let end = line[open + 1..close].find(',').map_or(close, |idx| open + 1 + idx);
+
+    // keep the spacing around the stat
+    let field = &line[open + 1..end];
+    let start = open + 1 + (field.len() - field.trim_start().len());
+    let end = start.max(open + 1 + field.trim_end().len());
+    Some(format!("{}{}{}", &line[..start], stat, &line[end..]))
+}
+
+/// rewrites the task written on `line`, read as `old`, into `new`. Only the parts that changed are
+/// written again: flag aliases from `aliases` are kept while their flags are set, a changed day
+/// stat or a new id is edited in place, and other changes rewrite the prefix, name or suffix
+/// descriptor in canonical form, keeping the spelling of dates. Gives None if the line is not a
+/// task.
+pub fn set_task(line: &str, old: &Task, new: &Task, aliases: &BTreeMap<char, TaskFlags>) -> Option<String> {
+    let tab = line.len() - line.trim_start().len();
+    let open = line.find('(')?;
+    let close = open + line[open..].find(')')?;
+    // names hold no parentheses, so any after the prefix open the suffix
+    let suffix = line[close..].rfind('(').map(|idx| close + idx);
+
+    let mut flags = line[tab..open].trim_end().to_string();
+    if old.flags() != new.flags() {
+        flags = edited_flags(&flags, new.flags(), aliases)?;
+    }
+
+    let mut prefix = line[open..=close].to_string();
+    match new.day_stat() {
+        _ if old.accum_stat() != new.accum_stat() || old.context_stat() != new.context_stat() => {
+            let stats = [new.day_stat(), new.accum_stat(), new.context_stat()];
+            prefix = format!("({})", task::format_stat_tuple(&stats));
+        }
+        Some(stat) if old.day_stat() != Some(stat) => prefix = set_day_stat(&prefix, stat)?,
+        None if old.day_stat().is_some() => {
+            prefix = format!("({})", task::format_stat_tuple(&[None, new.accum_stat(), new.context_stat()]));
+        }
+        _ => {}
+    }
+
+    let mut name = line[close + 1..suffix.unwrap_or(line.len())].to_string();
+    if old.name() != new.name() {
+        let space = if suffix.is_some() { " " } else { "" };
+        name = format!(" {}{}", new.name(), space);
+    }
+
+    let mut rest = suffix.map_or("", |idx| &line[idx..]).to_string();
+    let (old_fields, new_fields) = (old.suffix_fields(), new.suffix_fields());
+    if old_fields != new_fields {
+        let added_id = old.id().is_none() && new.id().is_some() && new_fields[..new_fields.len() - 1] == old_fields[..];
+        rest = match rest.rfind(')') {
+            Some(end) if added_id && !old_fields.is_empty() => {
+                format!("{}; {}{}", &rest[..end], new_fields.last()?, &rest[end..])
+            }
+            _ if new_fields.is_empty() => String::new(),
+            _ => keep_dates(&format!("({})", new_fields.join("; ")), &rest),
+        };
+        if suffix.is_none() && !rest.is_empty() {
+            rest.insert(0, ' ');
+        } else if rest.is_empty() {
+            name.truncate(name.trim_end().len());
+        }
+    }
+    Some(format!("{}{}{}{}{}", &line[..tab], flags, prefix, name, rest))
+}
+
+/// `written`, the flags of a task, edited to be `flags`. Written characters are kept while the
+/// flags they stand for are set, and the other flags are appended.
+fn edited_flags(written: &str, flags: TaskFlags, aliases: &BTreeMap<char, TaskFlags>) -> Option<String> {
+    let mut kept = String::new();
+    let mut covered = TaskFlags::empty();
+    for c in written.chars() {
+        let stands_for = match aliases.get(&c) {
+            Some(&alias) => alias,
+            None => c.to_string().parse::<TaskFlags>().ok()?,
+        };
+        if flags.contains(stands_for) {
+            kept.push(c);
+            covered |= stands_for;
+        }
+    }
+    Some(format!("{}{}", kept, flags - covered))
+}
+
+/// rewrites the tasks of `text`, read as the trees of `loaded`, into the trees of `edited`, both
+/// walked in the order they are written, with `set_task`. Only the lines of the tasks that changed
+/// are written again. Gives None if the trees were added or removed, or a line cannot be edited.
+pub fn set_tasks(text: &str, loaded: &[&TaskTree], edited: &[&TaskTree], aliases: &BTreeMap<char, TaskFlags>) -> Option<String> {
+    if loaded.len() != edited.len() || loaded.iter().any(|tree| tree.line() == 0) {
+        return None;
+    }
+
+    // the loaded and edited versions of each edited task, by line number
+    let tasks: HashMap<usize, (&Task, &Task)> = loaded.iter()
+        .zip(edited.iter())
+        .filter(|(loaded, edited)| loaded.task() != edited.task())
+        .map(|(loaded, edited)| (loaded.line(), (loaded.task(), edited.task())))
+        .collect();
+    let mut failed = false;
+    let text = edit_lines(text, |i, line| {
+        let (old, new) = tasks.get(&(i + 1))?;
+        let edited = set_task(line, old, new, aliases);
+        failed |= edited.is_none();
+        edited
+    });
+    if failed { None } else { Some(text) }
+}
+
+/// replaces the stat of day `day`, 0 being Monday, of the block tracker entry written on `line`.
+/// The other columns stay where they are: a shorter stat is padded, and a longer one takes the
+/// spaces after it, as long as one is left. Gives None if the line is not an entry.
+pub fn set_tracker_stat(line: &str, day: usize, stat: Stat) -> Option<String> {
+    if day >= 7 || line.trim().parse::<BlockTrackerEntry>().is_err() {
+        return None;
+    }
+    let (start, end) = token_spans(line).nth(day)?;
+    let stat = stat.to_string();
+
+    let mut rest = &line[end..];
+    let mut written = stat.clone();
+    let old_len = end - start;
+    if stat.len() < old_len {
+        written.push_str(&" ".repeat(old_len - stat.len()));
+    } else {
+        let spaces = rest.len() - rest.trim_start_matches(' ').len();
+        let taken = (stat.len() - old_len).min(spaces.saturating_sub(1));
+        rest = &rest[taken..];
+    }
+    Some(format!("{}{}{}", &line[..start], written, rest))
+}
+
+/// the byte spans of the words of `line`
+fn token_spans(line: &str) -> impl Iterator<Item = (usize, usize)> + '_ {
+    let mut cur = 0;
+    std::iter::from_fn(move || {
+        let rest = &line[cur..];
+        let start = cur + rest.find(|c: char| !c.is_whitespace())?;
+        let end = start + line[start..].find(char::is_whitespace).unwrap_or(line.len() - start);
+        cur = end;
+        Some((start, end))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_set_flags() {
+        assert_eq!(set_flags("\t\t~B(1) Report  (due: W3R)", TaskFlags::CURRENT).unwrap(), "\t\t>(1) Report  (due: W3R)");
+        assert_eq!(set_flags(">() Report", TaskFlags::empty()).unwrap(), "() Report");
+        assert_eq!(set_flags("Notes (on the report)", TaskFlags::DONE), None);
+    }
+
+    #[test]
+    fn test_set_day_stat() {
+        let count = Stat::from_count(Some(3), Some(4));
+        assert_eq!(set_day_stat("\t>(2/4, 5) Report (due: W3R)", count).unwrap(), "\t>(3/4, 5) Report (due: W3R)");
+        assert_eq!(set_day_stat("( 2/4 ,5) Report", count).unwrap(), "( 3/4 ,5) Report");
+        assert_eq!(set_day_stat("~() Report", count).unwrap(), "~(3/4) Report");
+        assert_eq!(set_day_stat("(, 5) Report", count).unwrap(), "(3/4, 5) Report");
+        assert_eq!(set_day_stat("Notes (on the report)", count), None);
+    }
+
+    #[test]
+    fn test_set_task() {
+        let aliases: BTreeMap<char, TaskFlags> = vec![('*', TaskFlags::CURRENT | TaskFlags::BLOCKED)].into_iter().collect();
+        let edit = |line: &str, written: &str, f: &dyn Fn(&mut Task)| {
+            let old = written.parse::<Task>().unwrap();
+            let mut new = old.clone();
+            f(&mut new);
+            set_task(line, &old, &new, &aliases).unwrap()
+        };
+
+        let line = "    *(1)  Report   (due: W3R;prior: 2)";
+        let written = ">B(1) Report (due: W3R; prior: 2)";
+        assert_eq!(edit(line, written, &|_task| {}), line);
+        assert_eq!(edit(line, written, &|task| { task.build_id("report"); }),
+            "    *(1)  Report   (due: W3R;prior: 2; #report)");
+        assert_eq!(edit(line, written, &|task| { task.build_flags(TaskFlags::CURRENT | TaskFlags::BLOCKED | TaskFlags::LATE); }),
+            "    *L(1)  Report   (due: W3R;prior: 2)");
+        assert_eq!(edit(line, written, &|task| { task.build_flags(TaskFlags::CURRENT); }),
+            "    >(1)  Report   (due: W3R;prior: 2)");
+        assert_eq!(edit(line, written, &|task| { task.build_day_stat(Stat::from_count(Some(2), None)); }),
+            "    *(2)  Report   (due: W3R;prior: 2)");
+        assert_eq!(edit("\t() Report", "() Report", &|task| { task.build_id("report"); }), "\t() Report (#report)");
+    }
+
+    #[test]
+    fn test_set_tracker_stat() {
+        let line = "    0  0  !  -  /- ?  4/4 PROJECT";
+        assert_eq!(set_tracker_stat(line, 0, Stat::from_count(Some(1), None)).unwrap(),
+            "    1  0  !  -  /- ?  4/4 PROJECT");
+        assert_eq!(set_tracker_stat(line, 5, Stat::from_count(Some(12), None)).unwrap(),
+            "    0  0  !  -  /- 12 4/4 PROJECT");
+        assert_eq!(set_tracker_stat(line, 1, Stat::from_count(Some(10), Some(12))).unwrap(),
+            "    0  10/12 !  -  /- ?  4/4 PROJECT");
+        assert_eq!(set_tracker_stat("Notes on the week", 0, Stat::Unknown), None);
+    }
+
+    #[test]
+    fn test_edit_lines() {
+        let text = "a\r\nb\nc";
+        assert_eq!(edit_lines(text, |i, line| if i > 0 { Some(line.to_uppercase()) } else { None }), "a\r\nB\nC");
+    }
+}
@@ -0,0 +1,233 @@
+//! Formats whole documents canonically, as written by `super::document::Document`:
+//!     - Sections and task trees are tabbed by their depth, one `INDENT` per level.
+//!     - Stat tuples are separated by ", ", as in (2/15, 10, 40).
+//!     - Suffix fields are written in one order, separated by "; ".
+//!     - The columns of consecutive block tracker entries are aligned.
+//!     - Free text is tabbed by its depth, and loses its trailing whitespace.
+//! Date codes are kept as they are written, as in W0xA, unless `FormatOptions::canonical_dates`
+//! is set, which writes them as `super::date::Date` does, as in WA. Flag aliases are kept as they
+//! are written. Documents are parsed with the flag aliases and section payloads of the config,
+//! and the formatted text is only given back if it parses back into the same document.
+
+#![allow(dead_code)]
+
+use super::date::Date;
+use super::document::{self, DocumentParseError};
+use super::task::Task;
+use crate::config::Config;
+use crate::utils::common::StrUtils;
+use std::borrow::Cow;
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+
+/// the suffix fields holding date codes
+const DATE_KEYS: [&str; 3] = ["due", "hard", "done"];
+
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub struct FormatOptions {
+    /// write date codes canonically, rather than as written
+    pub canonical_dates: bool,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum FormatError {
+    Parse(DocumentParseError),
+    /// the formatted text does not parse back into the document, so it is not given back
+    Changed,
+}
+
+impl fmt::Display for FormatError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let msg: Cow<'static, str> = match self {
+            Self::Parse(err) => err.to_string().into(),
+            Self::Changed => "the formatted document does not parse back into the document".into(),
+        };
+        write!(f, "{}", msg)
+    }
+}
+
+/// formats the document in `text`, parsed with the flag aliases and section payloads of `config`
+pub fn format(text: &str, config: &Config, options: &FormatOptions) -> Result<String, FormatError> {
+    let (document, mut errors) = config.parse_document(text);
+    if !errors.is_empty() {
+        return Err(FormatError::Parse(errors.remove(0)));
+    }
+    let formatted = document.to_string();
+
+    // the tasks of the formatted text are paired with the tasks written in the text by their
+    // identity, so that lines that only look like tasks are not paired
+    let mut written = task_lines(text, config);
+    let mut out = String::with_capacity(formatted.len());
+    let mut path = TaskPath::default();
+    for line in formatted.lines() {
+        let kept = path.identity(line, config)
+            .and_then(|identity| written.get_mut(&identity)?.pop_front())
+            .map(|written| keep_written(line, written, config, options));
+        out.push_str(kept.as_deref().unwrap_or(line));
+        out.push('\n');
+    }
+
+    let (reparsed, errors) = config.parse_document(&out);
+    if !errors.is_empty() || reparsed != document {
+        return Err(FormatError::Changed);
+    }
+    Ok(out)
+}
+
+/// the task lines of `text` by their identity, in the order they are written
+fn task_lines<'a>(text: &'a str, config: &Config) -> HashMap<String, VecDeque<&'a str>> {
+    let mut out: HashMap<String, VecDeque<&str>> = HashMap::new();
+    let mut path = TaskPath::default();
+    for line in text.lines() {
+        if let Some(identity) = path.identity(line, config) {
+            out.entry(identity).or_default().push_back(line);
+        }
+    }
+    out
+}
+
+/// the sections and tasks a line is nested in, by their tab columns
+#[derive(Default)]
+struct TaskPath {
+    path: Vec<(usize, String)>,
+}
+
+impl TaskPath {
+    /// takes in `line`, giving back how the task on it is identified, if it is a task: #Id if it
+    /// has one, or its path of section specifiers and task names otherwise
+    fn identity(&mut self, line: &str, config: &Config) -> Option<String> {
+        let task = if document::is_specifier_line(line) {
+            None
+        } else {
+            Some(config.unalias_flags(line).trim().parse::<Task>().ok()?)
+        };
+        let name = match &task {
+            Some(task) => task.name().to_string(),
+            None => line.trim().trim_start_matches('[').trim_end_matches(']').trim().to_string(),
+        };
+        let columns = StrUtils(line).tab_columns();
+        while self.path.last().is_some_and(|(tab, _name)| *tab >= columns) {
+            self.path.pop();
+        }
+        self.path.push((columns, name));
+
+        Some(task?.id().map_or_else(
+            || self.path.iter().map(|(_tab, name)| name.as_str()).collect::<Vec<&str>>().join("/"),
+            |id| format!("#{}", id),
+        ))
+    }
+}
+
+/// `line`, a formatted task, with its flag aliases and date codes as in `written`, the task as
+/// written. Dates are written canonically if `options.canonical_dates` is set.
+fn keep_written(line: &str, written: &str, config: &Config, options: &FormatOptions) -> String {
+    let mut line = if options.canonical_dates { line.to_string() } else { keep_dates(line, written) };
+
+    let flags = |line: &str| line.trim_start().find('(').map(|open| line.trim_start()[..open].to_string());
+    if let (Some(canonical), Some(aliased)) = (flags(&line), flags(written)) {
+        if aliased.chars().any(|c| config.flag_aliases.contains_key(&c)) {
+            let tab = StrUtils(&line).tabs().to_string();
+            line = format!("{}{}{}", tab, aliased, &line[tab.len() + canonical.len()..]);
+        }
+    }
+    line
+}
+
+/// the value of suffix field `key` of the task written on `line`, as written
+fn field_value<'a>(line: &'a str, key: &str) -> Option<&'a str> {
+    let suffix = &line[line.rfind('(')? + 1..line.rfind(')')?];
+    suffix.split(';')
+        .filter_map(|field| field.split_once(':'))
+        .find(|(field_key, _value)| field_key.trim() == key)
+        .map(|(_key, value)| value.trim())
+}
+
+/// `line`, a formatted task, with its date codes spelled as in `written`, the task as written
+pub(super) fn keep_dates(line: &str, written: &str) -> String {
+    let mut line = line.to_string();
+    for key in DATE_KEYS.iter() {
+        let (canonical, spelled) = match (field_value(&line, key), field_value(written, key)) {
+            (Some(canonical), Some(spelled)) => (canonical.to_string(), spelled),
+            _ => continue,
+        };
+        // only spellings of the same date are kept
+        if canonical != spelled && spelled.parse::<Date>().ok() == canonical.parse::<Date>().ok() {
+            line = line.replacen(&format!("{}: {}", key, canonical), &format!("{}: {}", key, spelled), 1);
+        }
+    }
+    line
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MESSY: &str = "\
+[Block Tracker]
+  0 0 ! - /- ? 4/4 PROJECT
+  10/12 2  3  4  5  6  7 READING
+[Tasks]
+\t>(2/15,10 ,40) Write the report  (#report;due:W0xAR ; prior: 3)
+\t\t~(2/2) Outline   (done: W3M;due: W3R)
+    A note on the tasks
+";
+
+    fn format(text: &str, options: &FormatOptions) -> Result<String, FormatError> {
+        super::format(text, &Config::default(), options)
+    }
+
+    #[test]
+    fn test_format() {
+        let formatted = format(MESSY, &FormatOptions::default()).unwrap();
+        assert_eq!(formatted, "\
+[Block Tracker]
+\t0     0     !     -     /-    ?     4/4   PROJECT
+\t10/12 2     3     4     5     6     7     READING
+[Tasks]
+\t>(2/15, 10, 40) Write the report (due: W0xAR; prior: 3; #report)
+\t\t~(2/2) Outline (due: W3R; done: W3M)
+\tA note on the tasks
+");
+        assert_eq!(format(&formatted, &FormatOptions::default()).unwrap(), formatted);
+
+        let canonical = format(MESSY, &FormatOptions { canonical_dates: true }).unwrap();
+        assert!(canonical.contains("(due: WAR; prior: 3; #report)"));
+        assert!(format("(1) Task (due: W99Z)\n", &FormatOptions::default()).is_err());
+    }
+
+    #[test]
+    fn test_format_config() {
+        let config: Config = "[flags]\n\"*\" = \"current\"\n[sections]\nJournal = \"text\"\n".parse().unwrap();
+        let text = "\
+[Journal]
+\t(1) looks like a task (due: W0x3R)
+[Tasks]
+\t*(1) Write (#write;due: W0x3R)
+\t\t(0/1) Outline ( due:W0x4R )
+\t(2) Write (due: W0x5R)
+";
+        let formatted = super::format(text, &config, &FormatOptions::default()).unwrap();
+        assert_eq!(formatted, "\
+[Journal]
+\t(1) looks like a task (due: W0x3R)
+[Tasks]
+\t*(1) Write (due: W0x3R; #write)
+\t\t(0/1) Outline (due: W0x4R)
+\t(2) Write (due: W0x5R)
+");
+        let canonical = super::format(text, &config, &FormatOptions { canonical_dates: true }).unwrap();
+        assert!(canonical.contains("\t*(1) Write (due: W3R; #write)\n"));
+
+        // without the config, the journal entry is a task, and the aliased task is text
+        assert!(format(text, &FormatOptions::default()).unwrap().contains("\t(1) looks like a task (due: W0x3R)\n"));
+    }
+
+    #[test]
+    fn test_keep_written() {
+        let config = Config::default();
+        let options = FormatOptions::default();
+        assert_eq!(keep_written("\t() A (due: WAR)", "  ()   A (due:W0xAR)", &config, &options), "\t() A (due: W0xAR)");
+        // a different date is not kept
+        assert_eq!(keep_written("\t() A (due: WAR)", "() A (due: W0xBR)", &config, &options), "\t() A (due: WAR)");
+    }
+}
@@ -0,0 +1,276 @@
+//! iCalendar (RFC 5545) export of the deadlines of tasks.
+//!
+//! Every task with a due or hard date is written as a VTODO, due at its due date, or its hard date
+//! if it has no due date. Tasks with a hard date also get an all-day VEVENT on that day, so that hard
+//! deadlines show on the calendar itself. Dates are converted through the season calendar. Refer to
+//! `super::calendar::SeasonCalendar`.
+//!     - PRIORITY: Priorities 0-98 are spread over the iCalendar priorities 1-9. No priority (99)
+//!       is written as 0, which is undefined in iCalendar.
+//!     - STATUS: COMPLETED for DONE tasks, NEEDS-ACTION otherwise.
+//!     - PERCENT-COMPLETE: From the task's count, if it has an expected count.
+//!     - RRULE: From rept. Refer to `super::task::Repeat`.
+//!     - UID: From the task's #Id, so that it is kept as the task is renamed or moved. Tasks without
+//!       an id get a hash of their name, numbered by the order tasks of that name are written in.
+
+#![allow(dead_code)]
+
+use super::calendar::{CivilDate, SeasonCalendar};
+use super::date::{Date, Season};
+use super::document::Document;
+use super::stat::Stat;
+use super::task::{Repeat, Task, TaskFlags};
+use std::collections::HashMap;
+
+/// iCalendar lines may not be longer than this many bytes, and are folded otherwise
+const MAX_LINE_LEN: usize = 75;
+
+pub struct IcsOptions<'a> {
+    pub calendar: &'a SeasonCalendar,
+    /// the year short date codes are in
+    pub year: u32,
+    /// the season short date codes are in
+    pub season: Season,
+    /// the time the calendar is written at, in seconds since 1970-01-01 UTC
+    pub timestamp: i64,
+}
+
+/// writes the deadlines of the tasks in `document` as an iCalendar file
+pub fn to_ics(document: &Document, options: &IcsOptions) -> String {
+    let mut lines: Vec<String> = vec![
+        "BEGIN:VCALENDAR".into(),
+        "VERSION:2.0".into(),
+        "PRODID:-//ttm//ttm//EN".into(),
+        "CALSCALE:GREGORIAN".into(),
+    ];
+
+    // tasks of the same name get a different uid by the order they are written in
+    let mut name_counts: HashMap<&str, usize> = HashMap::new();
+    for task in document.tasks() {
+        let deadline = match task.due_date().or_else(|| task.hard_date()) {
+            Some(deadline) => deadline,
+            None => continue,
+        };
+        let uid = match task.id() {
+            Some(id) => format!("id-{}", id),
+            None => {
+                let count = name_counts.entry(task.name()).or_insert(0);
+                *count += 1;
+                format!("{:016x}-{}", fnv1a(task.name()), count)
+            }
+        };
+
+        lines.push("BEGIN:VTODO".into());
+        lines.push(format!("UID:{}-due@ttm", uid));
+        lines.push(format!("DTSTAMP:{}", format_timestamp(options.timestamp)));
+        lines.push(format!("SUMMARY:{}", escape_text(task.name())));
+        if !task.note_link().is_empty() {
+            lines.push(format!("DESCRIPTION:{}", escape_text(&format!("Note: {}", task.note_link()))));
+        }
+        lines.push(format!("DUE;VALUE=DATE:{}", format_date(&deadline, options)));
+        lines.push(format!("PRIORITY:{}", ics_priority(task.priority())));
+        if task.flags().contains(TaskFlags::DONE) {
+            lines.push("STATUS:COMPLETED".into());
+            if let Some(done_date) = task.done_date() {
+                // COMPLETED must be a UTC date-time, so tasks are taken to be done at the start of the day
+                lines.push(format!("COMPLETED:{}T000000Z", format_date(&done_date, options)));
+            }
+        } else {
+            lines.push("STATUS:NEEDS-ACTION".into());
+        }
+        if let Some(percent) = percent_complete(task) {
+            lines.push(format!("PERCENT-COMPLETE:{}", percent));
+        }
+        if let Some(repeat) = task.repeat() {
+            lines.push(format!("RRULE:{}", rrule(repeat)));
+        }
+        lines.push("END:VTODO".into());
+
+        if let Some(hard_date) = task.hard_date() {
+            let day = options.calendar.to_civil(&hard_date, options.year, options.season);
+            lines.push("BEGIN:VEVENT".into());
+            lines.push(format!("UID:{}-hard@ttm", uid));
+            lines.push(format!("DTSTAMP:{}", format_timestamp(options.timestamp)));
+            lines.push(format!("SUMMARY:{}", escape_text(&format!("Hard deadline: {}", task.name()))));
+            lines.push(format!("DTSTART;VALUE=DATE:{}", format_civil(day)));
+            lines.push(format!("DTEND;VALUE=DATE:{}", format_civil(CivilDate::from_days(day.to_days() + 1))));
+            lines.push("TRANSP:TRANSPARENT".into());
+            lines.push("END:VEVENT".into());
+        }
+    }
+    lines.push("END:VCALENDAR".into());
+
+    lines.iter().map(|line| fold_line(line)).collect()
+}
+
+/// spreads priorities 0-98 over 1-9, where 1 is the highest in both
+fn ics_priority(priority: usize) -> usize {
+    if priority >= Task::NO_PRIORITY {
+        0
+    } else {
+        1 + priority * 9 / Task::NO_PRIORITY
+    }
+}
+
+/// progress of the task's count towards its expected count
+fn percent_complete(task: &Task) -> Option<i32> {
+    match task.progress_stat()? {
+        Stat::Count { act, exp: Some(exp) } if exp > 0 => Some((act.unwrap_or(0) * 100 / exp).min(100)),
+        _ => None,
+    }
+}
+
+fn rrule(repeat: Repeat) -> String {
+    match repeat {
+        Repeat::Daily(interval) => format!("FREQ=DAILY;INTERVAL={}", interval),
+        Repeat::Weekly(interval) => format!("FREQ=WEEKLY;INTERVAL={}", interval),
+    }
+}
+
+fn format_civil(day: CivilDate) -> String {
+    format!("{:04}{:02}{:02}", day.year, day.month, day.day)
+}
+
+fn format_date(date: &Date, options: &IcsOptions) -> String {
+    format_civil(options.calendar.to_civil(date, options.year, options.season))
+}
+
+/// writes a UTC timestamp as YYYYMMDDTHHMMSSZ
+fn format_timestamp(timestamp: i64) -> String {
+    let day = CivilDate::from_days(timestamp.div_euclid(86400));
+    let secs = timestamp.rem_euclid(86400);
+    format!("{}T{:02}{:02}{:02}Z", format_civil(day), secs / 3600, secs / 60 % 60, secs % 60)
+}
+
+/// escapes the characters that are special in iCalendar text values
+fn escape_text(s: &str) -> String {
+    s.replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace('\n', "\\n")
+}
+
+/// ends the line with CRLF, folding it into continuation lines that start with a space if it is
+/// too long. Lines are only split between characters.
+fn fold_line(line: &str) -> String {
+    let mut out = String::new();
+    let mut line_len = 0;
+    for c in line.chars() {
+        if line_len + c.len_utf8() > MAX_LINE_LEN {
+            out.push_str("\r\n ");
+            line_len = 1;
+        }
+        out.push(c);
+        line_len += c.len_utf8();
+    }
+    out.push_str("\r\n");
+    out
+}
+
+/// FNV-1a hash, used for uids as it is stable across builds
+fn fnv1a(s: &str) -> u64 {
+    s.bytes().fold(0xcbf29ce484222325, |hash, b| (hash ^ b as u64).wrapping_mul(0x100000001b3))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ics(document: &str) -> String {
+        let calendar = SeasonCalendar::default();
+        let options = IcsOptions {
+            calendar: &calendar,
+            year: 21,
+            season: Season::Winter,
+            // 2021-01-11 12:30:05 UTC
+            timestamp: 1610368205,
+        };
+        to_ics(&document.parse().unwrap(), &options)
+    }
+
+    #[test]
+    fn test_to_ics() {
+        let out = ics("[Tasks]\n\
+                       \t(2/8) Write report, draft (due: W3R; hard: Y21W-W4M; prior: 0; rept: W2; *P[Report])\n\
+                       \t~() Done task (due: W2; done: W2F)\n\
+                       \t() No deadline\n");
+        let uid = format!("{:016x}", fnv1a("Write report, draft"));
+        let done_uid = format!("{:016x}", fnv1a("Done task"));
+
+        assert_eq!(out, format!("\
+BEGIN:VCALENDAR\r
+VERSION:2.0\r
+PRODID:-//ttm//ttm//EN\r
+CALSCALE:GREGORIAN\r
+BEGIN:VTODO\r
+UID:{uid}-1-due@ttm\r
+DTSTAMP:20210111T123005Z\r
+SUMMARY:Write report\\, draft\r
+DESCRIPTION:Note: P[Report]\r
+DUE;VALUE=DATE:20210114\r
+PRIORITY:1\r
+STATUS:NEEDS-ACTION\r
+PERCENT-COMPLETE:25\r
+RRULE:FREQ=WEEKLY;INTERVAL=2\r
+END:VTODO\r
+BEGIN:VEVENT\r
+UID:{uid}-1-hard@ttm\r
+DTSTAMP:20210111T123005Z\r
+SUMMARY:Hard deadline: Write report\\, draft\r
+DTSTART;VALUE=DATE:20210118\r
+DTEND;VALUE=DATE:20210119\r
+TRANSP:TRANSPARENT\r
+END:VEVENT\r
+BEGIN:VTODO\r
+UID:{done_uid}-1-due@ttm\r
+DTSTAMP:20210111T123005Z\r
+SUMMARY:Done task\r
+DUE;VALUE=DATE:20210110\r
+PRIORITY:0\r
+STATUS:COMPLETED\r
+COMPLETED:20210108T000000Z\r
+END:VTODO\r
+END:VCALENDAR\r
+", uid = uid, done_uid = done_uid));
+    }
+
+    #[test]
+    fn test_duplicate_names() {
+        let out = ics("() Same (due: W1M)\n() Same (due: W2M)\n");
+        assert!(out.contains("-1-due@ttm"));
+        assert!(out.contains("-2-due@ttm"));
+    }
+
+    #[test]
+    fn test_id_uids() {
+        let uids = |document: &str| -> Vec<String> {
+            ics(document).lines().filter(|line| line.starts_with("UID:")).map(|line| line.to_string()).collect()
+        };
+
+        let before = uids("() Report (due: W1M; hard: W2M; #report)\n");
+        assert_eq!(before, vec!["UID:id-report-due@ttm", "UID:id-report-hard@ttm"]);
+        // renamed, and with a task of the same name written before it
+        assert_eq!(uids("() Draft (due: W1M)\n() Draft (due: W1M; hard: W2M; #report)\n")[1..], before[..]);
+    }
+
+    #[test]
+    fn test_ics_priority() {
+        assert_eq!(ics_priority(0), 1);
+        assert_eq!(ics_priority(10), 1);
+        assert_eq!(ics_priority(11), 2);
+        assert_eq!(ics_priority(50), 5);
+        assert_eq!(ics_priority(98), 9);
+        assert_eq!(ics_priority(99), 0);
+    }
+
+    #[test]
+    fn test_fold_line() {
+        let line = "SUMMARY:".to_string() + &"é".repeat(40);
+        let folded = fold_line(&line);
+
+        for part in folded.split("\r\n") {
+            assert!(part.len() <= MAX_LINE_LEN);
+        }
+        assert_eq!(folded.replace("\r\n ", ""), line + "\r\n");
+        assert_eq!(fold_line("SHORT"), "SHORT\r\n");
+    }
+}
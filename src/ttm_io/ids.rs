@@ -0,0 +1,236 @@
+//! Stable identifiers of tasks. A task is given an id with its #Id suffix field, as in
+//! `() Write the report (due: W3R; #k3x9qa)`. Names change and collide, but an id stays with its
+//! task, so dependencies, note links and the history of a task across files can follow it.
+//! Ids are letters, digits, '-' and '_', and must be unique within a document. Generated ids are
+//! `ID_LEN` lowercase letters and digits.
+//! Tasks are not given ids as they are written or imported. Ids are only generated by `assign_ids`,
+//! as run by `ttm ids`, and by the Add id action of the language server, so a task without one is
+//! followed by its name, and loses its history when it is renamed.
+
+#![allow(dead_code)]
+
+use super::document::{Document, Item};
+use super::task::Task;
+use super::task_tree::TaskTree;
+use std::collections::{BTreeMap, HashSet};
+
+/// length of generated ids
+pub const ID_LEN: usize = 6;
+
+/// the characters of generated ids
+const ALPHABET: &[u8] = b"0123456789abcdefghijklmnopqrstuvwxyz";
+
+/// the longest id accepted
+const MAX_ID_LEN: usize = 32;
+
+pub fn is_valid_id(s: &str) -> bool {
+    !s.is_empty() && s.len() <= MAX_ID_LEN && s.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+/// generates ids that are not taken yet. The ids generated from a seed are always the same, so
+/// the seed should differ between runs, as with the current time.
+#[derive(Debug)]
+pub struct IdGenerator {
+    state: u64,
+    taken: HashSet<String>,
+}
+
+impl IdGenerator {
+    pub fn new(seed: u64) -> Self {
+        Self { state: seed, taken: HashSet::new() }
+    }
+
+    /// a generator that does not give any of the ids in `document`
+    pub fn for_document(seed: u64, document: &Document) -> Self {
+        let mut generator = Self::new(seed);
+        generator.taken.extend(document.tasks().into_iter().filter_map(|task| task.id()).map(|id| id.to_string()));
+        generator
+    }
+
+    /// splitmix64, which gives well spread values from consecutive states
+    fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    pub fn next_id(&mut self) -> String {
+        loop {
+            let mut value = self.next_u64();
+            let id: String = (0..ID_LEN)
+                .map(|_| {
+                    let c = ALPHABET[(value % ALPHABET.len() as u64) as usize] as char;
+                    value /= ALPHABET.len() as u64;
+                    c
+                })
+                .collect();
+            if self.taken.insert(id.clone()) {
+                return id;
+            }
+        }
+    }
+}
+
+/// gives every task of `document` without an id a generated one, and gets how many were given
+pub fn assign_ids(document: &mut Document, generator: &mut IdGenerator) -> usize {
+    fn assign_tree(tree: &mut TaskTree, generator: &mut IdGenerator) -> usize {
+        let mut assigned = 0;
+        if tree.task().id().is_none() {
+            tree.task_mut().build_id(&generator.next_id());
+            assigned += 1;
+        }
+        for subtask in tree.subtasks_mut().iter_mut() {
+            assigned += assign_tree(subtask, generator);
+        }
+        assigned
+    }
+
+    fn assign_items(items: &mut [Item], generator: &mut IdGenerator) -> usize {
+        items.iter_mut()
+            .map(|item| match item {
+                Item::Section(section) => assign_items(section.items_mut(), generator),
+                Item::Task(tree) => assign_tree(tree, generator),
+                _ => 0,
+            })
+            .sum()
+    }
+
+    assign_items(document.items_mut(), generator)
+}
+
+/// the tasks of a document by their ids
+#[derive(Debug)]
+pub struct TaskIndex<'a> {
+    tasks: BTreeMap<&'a str, &'a Task>,
+    /// ids given to more than one task, in the order they are first repeated
+    duplicates: Vec<&'a str>,
+}
+
+impl<'a> TaskIndex<'a> {
+    /// indexes the tasks of `document` with ids. A repeated id indexes the first task with it.
+    pub fn new(document: &'a Document) -> Self {
+        let mut tasks: BTreeMap<&'a str, &'a Task> = BTreeMap::new();
+        let mut duplicates: Vec<&'a str> = vec![];
+        for task in document.tasks() {
+            if let Some(id) = task.id() {
+                if tasks.contains_key(id) {
+                    if !duplicates.contains(&id) {
+                        duplicates.push(id);
+                    }
+                } else {
+                    tasks.insert(id, task);
+                }
+            }
+        }
+        Self { tasks, duplicates }
+    }
+
+    pub fn get(&self, id: &str) -> Option<&'a Task> {
+        self.tasks.get(id).copied()
+    }
+
+    pub fn duplicates(&self) -> &[&'a str] {
+        &self.duplicates
+    }
+
+    pub fn len(&self) -> usize {
+        self.tasks.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tasks.is_empty()
+    }
+}
+
+/// whether `task` is named by `reference`: #Id for the task with that id, or its name
+pub fn is_named_by(task: &Task, reference: &str) -> bool {
+    match reference.strip_prefix('#') {
+        Some(id) => task.id() == Some(id),
+        None => task.name() == reference,
+    }
+}
+
+/// how `task` is best referred to: #Id if it has one, or its name otherwise
+pub fn reference(task: &Task) -> String {
+    task.id().map_or_else(|| task.name().to_string(), |id| format!("#{}", id))
+}
+
+/// finds the first task of `document` named by `reference`, as by `is_named_by`
+pub fn find_task<'a>(document: &'a Document, reference: &str) -> Option<&'a Task> {
+    find_tree(document, reference).map(TaskTree::task)
+}
+
+/// finds the tree of the first task of `document` named by `reference`, as by `is_named_by`. Its
+/// line is that of the task in the text the document was read from.
+pub fn find_tree<'a>(document: &'a Document, reference: &str) -> Option<&'a TaskTree> {
+    document.walk_trees().into_iter().find(|tree| is_named_by(tree.task(), reference))
+}
+
+/// whether `a` and `b` are the same task, as in files of different days. Tasks with ids are the
+/// same if their ids are, so they are followed through a rename. Otherwise they are compared by name.
+pub fn same_task(a: &Task, b: &Task) -> bool {
+    match (a.id(), b.id()) {
+        (Some(a), Some(b)) => a == b,
+        _ => a.name() == b.name(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_generate() {
+        let mut generator = IdGenerator::new(7);
+        let ids: Vec<String> = (0..100).map(|_| generator.next_id()).collect();
+        assert!(ids.iter().all(|id| id.len() == ID_LEN && is_valid_id(id)));
+        assert_eq!(ids.iter().collect::<HashSet<&String>>().len(), 100);
+        // the same seed gives the same ids
+        assert_eq!(IdGenerator::new(7).next_id(), ids[0]);
+
+        let document: Document = format!("() Task (#{})\n", ids[0]).parse().unwrap();
+        assert_ne!(IdGenerator::for_document(7, &document).next_id(), ids[0]);
+    }
+
+    #[test]
+    fn test_assign_and_index() {
+        let mut document: Document = "\
+[Work]
+\t() Report (#report)
+\t\t() Outline
+() Errand
+".parse().unwrap();
+        let mut generator = IdGenerator::for_document(1, &document);
+        assert_eq!(assign_ids(&mut document, &mut generator), 2);
+        assert!(document.tasks().iter().all(|task| task.id().is_some()));
+        assert_eq!(document.tasks()[0].id(), Some("report"));
+
+        let index = TaskIndex::new(&document);
+        assert_eq!(index.len(), 3);
+        assert_eq!(index.get("report").map(|task| task.name()), Some("Report"));
+        assert!(index.duplicates().is_empty());
+
+        let outline_id = format!("#{}", document.tasks()[1].id().unwrap());
+        assert_eq!(find_task(&document, &outline_id).map(|task| task.name()), Some("Outline"));
+        assert_eq!(find_task(&document, "Errand").map(|task| task.name()), Some("Errand"));
+        assert_eq!(find_task(&document, "#missing"), None);
+    }
+
+    #[test]
+    fn test_duplicates_and_renames() {
+        let document: Document = "() A (#x)\n() B (#x)\n() C (#y)\n".parse().unwrap();
+        let index = TaskIndex::new(&document);
+        assert_eq!(index.duplicates(), &["x"]);
+        assert_eq!(index.get("x").map(|task| task.name()), Some("A"));
+
+        let task = |s: &str| s.parse::<Task>().unwrap();
+        assert!(same_task(&task("() Draft (#x)"), &task("() Final draft (#x)")));
+        assert!(!same_task(&task("() Draft (#x)"), &task("() Draft (#y)")));
+        assert!(same_task(&task("() Draft"), &task("() Draft (#x)")));
+
+        assert_eq!(reference(&task("() Draft (#x)")), "#x");
+        assert_eq!(reference(&task("() Draft")), "Draft");
+    }
+}
@@ -0,0 +1,155 @@
+//! Shared parts of the importers of other task formats. Refer to `super::todotxt` and `super::org`.
+//! These formats hold information TTM has no place for, so an importer maps what it can into a
+//! document and reports the rest as warnings, rather than failing.
+
+#![allow(dead_code)]
+
+use super::calendar::{CivilDate, SeasonCalendar};
+use super::block_tracker::BlockTrackerEntry;
+use super::date::Date;
+use super::document::{self, Document};
+use super::task::Repeat;
+use std::borrow::Cow;
+use std::fmt;
+
+/// something in the imported file that could not be represented, and was dropped or changed
+#[derive(Debug, PartialEq)]
+pub struct ImportWarning {
+    /// the 1-based line it was found in
+    pub line: usize,
+    pub message: Cow<'static, str>,
+}
+
+impl fmt::Display for ImportWarning {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::result::Result<(), std::fmt::Error> {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Import {
+    pub document: Document,
+    pub warnings: Vec<ImportWarning>,
+}
+
+/// collects the warnings of an import
+#[derive(Debug, Default)]
+pub(crate) struct Warnings(pub Vec<ImportWarning>);
+
+impl Warnings {
+    pub fn warn(&mut self, line: usize, message: impl Into<Cow<'static, str>>) {
+        self.0.push(ImportWarning { line, message: message.into() });
+    }
+}
+
+/// makes `name` usable as a task name. Parentheses would be read as the suffix of the task, so
+/// they are replaced by brackets.
+pub(crate) fn task_name(name: &str, line: usize, warnings: &mut Warnings) -> String {
+    if !name.contains(['(', ')']) {
+        return name.to_string();
+    }
+    warnings.warn(line, format!("replaced the parentheses in '{}' with brackets", name));
+    name.replace('(', "[").replace(')', "]")
+}
+
+/// makes `name` usable as a section specifier. A closing bracket would end the specifier, so
+/// brackets are replaced by parentheses.
+pub(crate) fn section_name(name: &str, line: usize, warnings: &mut Warnings) -> String {
+    if !name.contains(['[', ']']) {
+        return name.to_string();
+    }
+    warnings.warn(line, format!("replaced the brackets in '{}' with parentheses", name));
+    name.replace('[', "(").replace(']', ")")
+}
+
+/// makes `text` read back as a line of text. Lines that would be read as a section specifier, a
+/// task or a block tracker entry are escaped with a leading "- ".
+pub(crate) fn text_line(text: &str, line: usize, warnings: &mut Warnings) -> String {
+    let misread = document::is_specifier_line(text) || document::is_task_line(text)
+        || text.parse::<BlockTrackerEntry>().is_ok();
+    if !misread {
+        return text.to_string();
+    }
+    warnings.warn(line, format!("escaped '{}' with a leading '-', as it would not be read as text", text));
+    format!("- {}", text)
+}
+
+/// converts a YYYY-MM-DD date to a full date code through `calendar`
+pub(crate) fn date_code(s: &str, calendar: &SeasonCalendar, line: usize, warnings: &mut Warnings) -> Option<Date> {
+    match s.parse::<CivilDate>().and_then(|civil| calendar.from_civil(civil)) {
+        Ok(date) => Some(date),
+        Err(e) => {
+            warnings.warn(line, format!("dropped date: {}", e));
+            None
+        }
+    }
+}
+
+/// converts a repeat of every `interval` units, where units are d(ays), w(eeks), m(onths) or
+/// y(ears). TTM tasks only repeat by days or weeks.
+pub(crate) fn repeat(n: &str, unit: &str, line: usize, warnings: &mut Warnings) -> Option<Repeat> {
+    let interval: u32 = if n.is_empty() { 1 } else { n.parse().unwrap_or(0) };
+    match unit {
+        _ if interval == 0 => warnings.warn(line, format!("dropped repeat of invalid interval '{}{}'", n, unit)),
+        "d" => return Some(Repeat::Daily(interval)),
+        "w" => return Some(Repeat::Weekly(interval)),
+        _ => warnings.warn(line, format!("dropped repeat every {}{}, only days and weeks are supported", interval, unit)),
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_task_name() {
+        let mut warnings = Warnings::default();
+        assert_eq!(task_name("Call mom", 1, &mut warnings), "Call mom");
+        assert!(warnings.0.is_empty());
+        assert_eq!(task_name("Call mom (again)", 2, &mut warnings), "Call mom [again]");
+        assert_eq!(warnings.0.len(), 1);
+        assert_eq!(warnings.0[0].line, 2);
+    }
+
+    #[test]
+    fn test_section_name() {
+        let mut warnings = Warnings::default();
+        assert_eq!(section_name("Work (home)", 1, &mut warnings), "Work (home)");
+        assert_eq!(section_name("Work [stuff]", 2, &mut warnings), "Work (stuff)");
+        assert_eq!(warnings.0.iter().map(|w| w.line).collect::<Vec<_>>(), vec![2]);
+    }
+
+    #[test]
+    fn test_text_line() {
+        let mut warnings = Warnings::default();
+        assert_eq!(text_line("Notes about work", 1, &mut warnings), "Notes about work");
+        assert_eq!(text_line("- a list item", 1, &mut warnings), "- a list item");
+        assert!(warnings.0.is_empty());
+        assert_eq!(text_line("(see below)", 2, &mut warnings), "- (see below)");
+        assert_eq!(text_line("[links]", 3, &mut warnings), "- [links]");
+        assert_eq!(text_line("1 2 3 4 5 6 7 pages", 4, &mut warnings), "- 1 2 3 4 5 6 7 pages");
+        assert_eq!(warnings.0.iter().map(|w| w.line).collect::<Vec<_>>(), vec![2, 3, 4]);
+    }
+
+    #[test]
+    fn test_date_code() {
+        let calendar = SeasonCalendar::default();
+        let mut warnings = Warnings::default();
+        assert_eq!(date_code("2021-01-14", &calendar, 1, &mut warnings).unwrap().to_string(), "Y21W-W3R");
+        assert_eq!(date_code("2021-02-30", &calendar, 2, &mut warnings), None);
+        assert_eq!(date_code("1999-01-14", &calendar, 3, &mut warnings), None);
+        assert_eq!(warnings.0.iter().map(|w| w.line).collect::<Vec<_>>(), vec![2, 3]);
+    }
+
+    #[test]
+    fn test_repeat() {
+        let mut warnings = Warnings::default();
+        assert_eq!(repeat("", "d", 1, &mut warnings), Some(Repeat::Daily(1)));
+        assert_eq!(repeat("2", "w", 1, &mut warnings), Some(Repeat::Weekly(2)));
+        assert!(warnings.0.is_empty());
+        assert_eq!(repeat("1", "m", 1, &mut warnings), None);
+        assert_eq!(repeat("0", "d", 1, &mut warnings), None);
+        assert_eq!(warnings.0.len(), 2);
+    }
+}
@@ -0,0 +1,270 @@
+//! JSON import and export of documents, available with the `serde` feature.
+//! The schema is described in docs/wiki/json-schema.txt. A document is wrapped with the version
+//! of the schema it was written in:
+//!     {"version": 1, "document": {"items": [...]}}
+
+use super::block_tracker::BlockTrackerEntry;
+use super::document::{Document, Item, TextLine};
+use super::task::Task;
+use super::task_tree::TaskTree;
+use std::borrow::Cow;
+use std::fmt;
+
+/// version of the JSON schema written by `to_json`
+pub const SCHEMA_VERSION: u32 = 1;
+
+#[derive(serde::Serialize)]
+struct JsonDocumentRef<'a> {
+    version: u32,
+    document: &'a Document,
+}
+
+#[derive(serde::Deserialize)]
+struct JsonDocument {
+    version: u32,
+    document: Document,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum JsonError {
+    /// the JSON does not conform to the schema
+    InvalidSchema(Cow<'static, str>),
+    UnsupportedVersion(u32),
+    InvalidPriority { task: String, priority: usize },
+    /// an item that would not read back the same once written as TTM text, along with why
+    Unwritable { item: String, reason: Cow<'static, str> },
+}
+
+impl fmt::Display for JsonError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::result::Result<(), std::fmt::Error> {
+        let msg: Cow<'static, str> = match self {
+            Self::InvalidSchema(message) => message.clone(),
+            Self::UnsupportedVersion(version) => format!(
+                "unsupported schema version {}, expected {}", version, SCHEMA_VERSION).into(),
+            Self::InvalidPriority { task, priority } => format!(
+                "priority {} of task '{}' exceeds max value of {}", priority, task, Task::NO_PRIORITY).into(),
+            Self::Unwritable { item, reason } => format!("'{}' cannot be written as TTM: {}", item, reason).into(),
+        };
+        write!(f, "{}", msg)?;
+        Ok(())
+    }
+}
+
+/// writes `document` as pretty printed JSON
+pub fn to_json(document: &Document) -> String {
+    let json = JsonDocumentRef { version: SCHEMA_VERSION, document };
+    serde_json::to_string_pretty(&json).expect("documents always serialize")
+}
+
+/// reads a document from JSON, validating what the schema alone cannot express: every item must
+/// read back the same once written as TTM text, so names hold no parentheses, entry names are one
+/// word, and so on. The tags written in the names of tasks are added to their tags, whether or not
+/// the JSON lists them.
+pub fn from_json(s: &str) -> Result<Document, JsonError> {
+    let mut json: JsonDocument = serde_json::from_str(s)
+        .map_err(|e| JsonError::InvalidSchema(e.to_string().into()))?;
+    if json.version != SCHEMA_VERSION {
+        return Err(JsonError::UnsupportedVersion(json.version));
+    }
+
+    for task in json.document.tasks() {
+        if task.priority() > Task::NO_PRIORITY {
+            return Err(JsonError::InvalidPriority {
+                task: task.name().to_string(),
+                priority: task.priority(),
+            });
+        }
+    }
+
+    add_name_tags(json.document.items_mut());
+    validate_items(json.document.items())?;
+    Ok(json.document)
+}
+
+/// checks that each item reads back the same once written
+fn validate_items(items: &[Item]) -> Result<(), JsonError> {
+    let unwritable = |item: &str, reason: Cow<'static, str>| Err(JsonError::Unwritable { item: item.to_string(), reason });
+    for item in items.iter() {
+        match item {
+            Item::Section(section) => {
+                let (written, _errors) = Document::parse_lenient(&format!("[{}]\n", section.specifier()));
+                match written.items() {
+                    [Item::Section(read)] if read.specifier() == section.specifier() => {}
+                    _ => return unwritable(section.specifier(), "specifiers are one line without surrounding whitespace".into()),
+                }
+                validate_items(section.items())?;
+            }
+            Item::Task(tree) => {
+                for (_depth, tree) in tree.walk() {
+                    let task = tree.task();
+                    match task.to_string().parse::<Task>() {
+                        Ok(read) if read == *task => {}
+                        Ok(read) => return unwritable(task.name(), format!("it reads back as '{}'", read).into()),
+                        Err(e) => return unwritable(task.name(), e.to_string().into()),
+                    }
+                    validate_text(tree.notes().iter().map(|note| &note.line))?;
+                }
+            }
+            Item::BlockTrackerEntry(entry) => {
+                if entry.to_string().parse::<BlockTrackerEntry>().ok().as_ref() != Some(entry) {
+                    return unwritable(entry.entry_name(), "entry names are one word".into());
+                }
+            }
+            Item::Text(line) => validate_text(std::iter::once(line))?,
+        }
+    }
+    Ok(())
+}
+
+fn validate_text<'a>(mut lines: impl Iterator<Item = &'a TextLine>) -> Result<(), JsonError> {
+    match lines.find(|line| line.text.contains(['\n', '\r'])) {
+        Some(line) => Err(JsonError::Unwritable { item: line.text.clone(), reason: "text lines hold no line breaks".into() }),
+        None => Ok(()),
+    }
+}
+
+fn add_name_tags(items: &mut [Item]) {
+    fn add_to_tree(tree: &mut TaskTree) {
+        tree.task_mut().build_name_tags();
+        tree.subtasks_mut().iter_mut().for_each(add_to_tree);
+    }
+
+    for item in items.iter_mut() {
+        match item {
+            Item::Section(section) => add_name_tags(section.items_mut()),
+            Item::Task(tree) => add_to_tree(tree),
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DOCUMENT: &str = "\
+[Block Tracker]
+\t0   !   /-  ?   4/4 -   0   PROJECT
+
+[Tasks]
+\t>B(2/15, 10) Write the report (due: Y21W-W3R; hard: W4; prior: 3; gPages: 0/10; *P[Report])
+\t\t~(2/2) Outline
+\t\tA note on the report
+\tSome text
+\t\t\tSome deeper text
+";
+
+    #[test]
+    fn test_json_round_trip() {
+        let document: Document = DOCUMENT.parse().unwrap();
+        let json = to_json(&document);
+        let imported = from_json(&json).unwrap();
+
+        assert_eq!(imported, document);
+        assert_eq!(imported.to_string(), DOCUMENT);
+    }
+
+    #[test]
+    fn test_json_schema() {
+        let document: Document = "() Task (due: W3R)\n".parse().unwrap();
+        let json: serde_json::Value = serde_json::from_str(&to_json(&document)).unwrap();
+
+        assert_eq!(json, serde_json::json!({
+            "version": 1,
+            "document": {"items": [{"task": {
+                "flags": [],
+                "day_stat": null,
+                "accum_stat": null,
+                "context_stat": null,
+                "name": "Task",
+                "note_link": "",
+                "priority": 99,
+                "due_date": {"kind": "short_date_code", "week": 3, "day": "thu"},
+                "hard_date": null,
+                "done_date": null,
+                "repeat": null,
+                "id": null,
+                "tags": [],
+                "deps": [],
+                "other_stats": {},
+                "subtasks": [],
+                "notes": [],
+                "depth": 0,
+            }}]},
+        }));
+    }
+
+    #[test]
+    fn test_json_minimal_task() {
+        let json = r#"{"version": 1, "document": {"items": [
+            {"section": {"specifier": "Tasks", "items": [
+                {"task": {"name": "Minimal", "flags": ["done"],
+                          "day_stat": {"kind": "count", "act": 1, "exp": 2}}},
+                {"block_tracker_entry": {"entry_name": "READ", "week_stats": [
+                    {"kind": "bool", "act": true, "exp": true}, {"kind": "unknown"},
+                    {"kind": "unknown"}, {"kind": "unknown"}, {"kind": "unknown"},
+                    {"kind": "unknown"}, {"kind": "required_count", "act": 2, "exp": false}]}}
+            ]}}
+        ]}}"#;
+
+        assert_eq!(from_json(json).unwrap().to_string(),
+            "[Tasks]\n\t~(1/2) Minimal\n\t!   ?   ?   ?   ?   ?   2/- READ\n");
+    }
+
+    #[test]
+    fn test_json_name_tags() {
+        let json = r#"{"version": 1, "document": {"items": [
+            {"task": {"name": "Call +work @phone", "subtasks": [{"name": "Notes +work", "tags": ["+misc"]}]}}
+        ]}}"#;
+        let document = from_json(json).unwrap();
+
+        let tags: Vec<Vec<&str>> = document.tasks().iter()
+            .map(|task| task.tags().iter().map(|tag| tag.as_str()).collect())
+            .collect();
+        assert_eq!(tags, vec![vec!["+work", "@phone"], vec!["+misc", "+work"]]);
+        assert_eq!(document, "() Call +work @phone\n\t() Notes +work (tags: +misc)\n".parse().unwrap());
+    }
+
+    #[test]
+    fn test_json_errors() {
+        assert!(matches!(from_json("{}"), Err(JsonError::InvalidSchema(_))));
+        assert_eq!(from_json(r#"{"version": 2, "document": {"items": []}}"#),
+            Err(JsonError::UnsupportedVersion(2)));
+        assert!(matches!(
+            from_json(r#"{"version": 1, "document": {"items": [{"task": {"name": "T", "flags": ["urgent"]}}]}}"#),
+            Err(JsonError::InvalidSchema(_))));
+        assert_eq!(
+            from_json(r#"{"version": 1, "document": {"items": [{"task": {"name": "T", "priority": 100}}]}}"#),
+            Err(JsonError::InvalidPriority { task: "T".into(), priority: 100 }));
+
+        // items that would not read back the same once written
+        let unwritable = |items: &str| match from_json(&format!(r#"{{"version": 1, "document": {{"items": [{}]}}}}"#, items)) {
+            Err(JsonError::Unwritable { item, .. }) => item,
+            other => panic!("{:?}", other),
+        };
+        assert_eq!(unwritable(r#"{"task": {"name": "Call (maybe)"}}"#), "Call (maybe)");
+        assert_eq!(unwritable(r#"{"task": {"name": "T", "deps": ["A; B"]}}"#), "T");
+        assert_eq!(unwritable(r#"{"task": {"name": "T", "tags": ["work"]}}"#), "T");
+        assert_eq!(unwritable(r#"{"task": {"name": "T", "note_link": "a)b"}}"#), "T");
+        assert_eq!(unwritable(r#"{"task": {"name": "T", "id": "an id"}}"#), "T");
+        assert_eq!(unwritable(r#"{"section": {"specifier": "A\nB", "items": []}}"#), "A\nB");
+        assert_eq!(unwritable(r#"{"text": "one\ntwo"}"#), "one\ntwo");
+        let week = r#"[{"kind": "unknown"}, {"kind": "unknown"}, {"kind": "unknown"}, {"kind": "unknown"},
+            {"kind": "unknown"}, {"kind": "unknown"}, {"kind": "unknown"}]"#;
+        assert_eq!(unwritable(&format!(r#"{{"block_tracker_entry": {{"entry_name": "TWO WORDS", "week_stats": {}}}}}"#, week)),
+            "TWO WORDS");
+    }
+
+    #[test]
+    fn test_json_round_trip_validated() {
+        // what is imported writes back as TTM text that reads as the same document
+        let json = r#"{"version": 1, "document": {"items": [
+            {"section": {"specifier": "Tasks", "items": [
+                {"task": {"name": "Call :)", "deps": ["Tasks/Plan"], "tags": ["+work"], "id": "call"}},
+                {"text": "a note"}
+            ]}}
+        ]}}"#;
+        let document = from_json(json).unwrap();
+        assert_eq!(document.to_string().parse::<Document>().unwrap(), document);
+    }
+}
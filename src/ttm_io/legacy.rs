@@ -0,0 +1,209 @@
+//! Parses tasks in the format used before 07-Jan-21, and migrates files to the current format.
+//! Legacy tasks held all of their information in the prefix, before the name:
+//!     [TaskFlags]([DayStat][, AccStat]; DoneDateCode; [due|hard: DateCode;] [g[Name]: Stat[,AccStat];]
+//!     [rept: (D|W)[<N>];]) TaskName
+//!         - TaskFlags: As in the current format. A digit gives the priority of the task.
+//!         - DoneDateCode: When the task was done. It may be left empty, or left out.
+//!         - The rest are written to the suffix of the current format, with the done date as done:.
+//! Refer to `super::task` for the current format.
+//! A migration rewrites every legacy task of a file and leaves all other lines as they are, so
+//! sections, block trackers and tasks already in the current format are kept.
+
+#![allow(dead_code)]
+
+use super::date::Date;
+use super::import::{self, ImportWarning, Warnings};
+use super::stat::Stat;
+use super::task::{self, Repeat, Task, TaskFlags};
+use std::borrow::Cow;
+use std::fmt;
+
+#[derive(Debug, PartialEq)]
+pub enum LegacyParseError {
+    /// the line has no (...) prefix after its flags
+    NoPrefix,
+    InvalidTaskFlags(Cow<'static, str>),
+    InvalidStats(Cow<'static, str>),
+    InvalidField { field: String, message: Cow<'static, str> },
+}
+
+impl fmt::Display for LegacyParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::result::Result<(), std::fmt::Error> {
+        let msg: Cow<'static, str> = match self {
+            Self::NoPrefix => "a legacy task needs a (...) prefix after its flags".into(),
+            Self::InvalidTaskFlags(message) => message.clone(),
+            Self::InvalidStats(message) => format!("invalid stats: {}", message).into(),
+            Self::InvalidField { field, message } => format!("invalid field '{}': {}", field, message).into(),
+        };
+        write!(f, "{}", msg)?;
+        Ok(())
+    }
+}
+
+/// splits a task line into its flags, prefix and name, if it has a prefix
+fn split_prefix(s: &str) -> Option<(&str, &str, &str)> {
+    let open = s.find('(')?;
+    let close = open + s[open..].find(')')?;
+    Some((&s[..open], &s[open + 1..close], s[close + 1..].trim()))
+}
+
+/// whether `s` is a task in the legacy format. The formats only differ by the fields after the
+/// stats of the prefix, so tasks without them are taken to be current.
+pub fn is_legacy(s: &str) -> bool {
+    match split_prefix(s.trim()) {
+        Some((flags, prefix, _name)) => {
+            flags.chars().all(|c| "~>BL".contains(c) || c.is_ascii_digit()) && prefix.contains(';')
+        }
+        None => false,
+    }
+}
+
+fn parse_field<T>(field: &str, value: &str, parse: impl FnOnce(&str) -> Result<T, Cow<'static, str>>) -> Result<T, LegacyParseError> {
+    parse(value.trim()).map_err(|message| LegacyParseError::InvalidField { field: field.to_string(), message })
+}
+
+/// parses a legacy task. Parentheses in its name are replaced by brackets, with a warning given
+/// for `line`.
+pub(crate) fn parse_task(s: &str, line: usize, warnings: &mut Warnings) -> Result<Task, LegacyParseError> {
+    let (flags, prefix, name) = split_prefix(s.trim()).ok_or(LegacyParseError::NoPrefix)?;
+
+    let mut fields = prefix.split(';');
+    let stats = task::parse_stat_tuple(fields.next().unwrap_or("")).map_err(LegacyParseError::InvalidStats)?;
+    if stats[2].is_some() {
+        return Err(LegacyParseError::InvalidStats("legacy tasks only hold a DayStat and an AccStat".into()));
+    }
+    let mut task = Task::from_name_and_stats(&import::task_name(name, line, warnings), (stats[0], stats[1], None));
+
+    let digits: String = flags.chars().filter(|c| c.is_ascii_digit()).collect();
+    let flags: String = flags.chars().filter(|c| !c.is_ascii_digit()).collect();
+    task.build_flags(flags.parse::<TaskFlags>().map_err(LegacyParseError::InvalidTaskFlags)?);
+    if let Ok(priority) = digits.parse::<usize>() {
+        task.build_priority(priority);
+    }
+
+    for (idx, field) in fields.enumerate() {
+        let field = field.trim();
+        let (key, value) = match field.find(':') {
+            Some(colon) => (field[..colon].trim(), &field[colon + 1..]),
+            // the done date is the only field without a key, and comes right after the stats
+            None if idx == 0 && !field.is_empty() => {
+                task.build_done_date(parse_field(field, field, |v| v.parse::<Date>())?);
+                continue;
+            }
+            None if field.is_empty() => continue,
+            None => return Err(LegacyParseError::InvalidField { field: field.to_string(), message: "expected key: value".into() }),
+        };
+
+        match key {
+            "due" => task.build_due_date(parse_field(field, value, |v| v.parse::<Date>())?),
+            "hard" => task.build_hard_date(parse_field(field, value, |v| v.parse::<Date>())?),
+            "rept" => task.build_repeat(parse_field(field, value, |v| v.parse::<Repeat>())?),
+            goal if goal.len() > 1 && goal.starts_with('g') => {
+                let stats: [Option<Stat>; 3] = parse_field(field, value, task::parse_stat_tuple)?;
+                task.build_other_stat(&goal[1..], [stats[0], stats[1], None])
+            }
+            _ => return Err(LegacyParseError::InvalidField { field: field.to_string(), message: "unsupported key".into() }),
+        };
+    }
+    Ok(task)
+}
+
+/// a file migrated to the current format
+#[derive(Debug, PartialEq)]
+pub struct Migration {
+    pub text: String,
+    /// how many tasks were rewritten
+    pub migrated: usize,
+    /// legacy tasks that could not be parsed, and were left as they were, along with changes made
+    /// to task names
+    pub warnings: Vec<ImportWarning>,
+}
+
+/// rewrites the legacy tasks of `s` in the current format, keeping their indentation
+pub fn migrate(s: &str) -> Migration {
+    let mut warnings = Warnings::default();
+    let mut text = String::with_capacity(s.len());
+    let mut migrated = 0;
+
+    for (idx, line) in s.split_inclusive('\n').enumerate() {
+        let content = line.trim_end_matches(['\n', '\r']);
+        let ending = &line[content.len()..];
+        if !is_legacy(content) {
+            text.push_str(line);
+            continue;
+        }
+
+        let tab = &content[..content.len() - content.trim_start().len()];
+        match parse_task(content, idx + 1, &mut warnings) {
+            Ok(task) => {
+                text.push_str(&format!("{}{}{}", tab, task, ending));
+                migrated += 1;
+            }
+            Err(e) => {
+                warnings.warn(idx + 1, format!("left the task as it was: {}", e));
+                text.push_str(line);
+            }
+        }
+    }
+
+    Migration { text, migrated, warnings: warnings.0 }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(s: &str) -> Result<String, LegacyParseError> {
+        parse_task(s, 1, &mut Warnings::default()).map(|task| task.to_string())
+    }
+
+    #[test]
+    fn test_parse_task() {
+        assert_eq!(parse("~(1, 5; Y21W-W1T; due: Y21W-W1U;) Old task").unwrap(),
+            "~(1, 5) Old task (due: Y21W-W1U; done: Y21W-W1T)");
+        assert_eq!(parse(">2(0/4; ; hard: W3R; gPushups: 10/20, 40; rept: D2;) Exercise").unwrap(),
+            ">(0/4) Exercise (hard: W3R; prior: 2; rept: D2; gPushups: 10/20, 40)");
+        assert_eq!(parse("(; W2M) No stats").unwrap(), "() No stats (done: W2M)");
+        assert_eq!(parse("B(2) Call (mom)").unwrap(), "B(2) Call [mom]");
+
+        assert!(matches!(parse("(1, 2, 3; ) Task"), Err(LegacyParseError::InvalidStats(_))));
+        assert!(matches!(parse("(; W2M; prior: 3) Task"), Err(LegacyParseError::InvalidField { .. })));
+        assert!(matches!(parse("(; someday) Task"), Err(LegacyParseError::InvalidField { .. })));
+        assert!(matches!(parse("X(; ) Task"), Err(LegacyParseError::InvalidTaskFlags(_))));
+    }
+
+    #[test]
+    fn test_is_legacy() {
+        assert!(is_legacy("\t~(1; W2M) Task"));
+        assert!(!is_legacy("\t~(1) Task (due: W2M)"));
+        assert!(!is_legacy("[Section; with a semicolon]"));
+        assert!(!is_legacy("! ! ! ! ! ! ! Entry"));
+    }
+
+    #[test]
+    fn test_migrate() {
+        let migration = migrate("\
+[Tasks]
+\t~(2; W1M) Done task
+\t\t(0/3; ; due: W2F) Subtask
+\t() Current task (due: W2F)
+\t(; someday) Broken
+! ! ! ! ! ! ! Entry
+");
+        assert_eq!(migration.text, "\
+[Tasks]
+\t~(2) Done task (done: W1M)
+\t\t(0/3) Subtask (due: W2F)
+\t() Current task (due: W2F)
+\t(; someday) Broken
+! ! ! ! ! ! ! Entry
+");
+        assert_eq!(migration.migrated, 2);
+        assert_eq!(migration.warnings.len(), 1);
+        assert_eq!(migration.warnings[0].line, 5);
+
+        // the migrated file parses in the current format
+        assert!(migrate("~(2; W1M) Done\r\n").text.parse::<super::super::document::Document>().is_ok());
+        assert_eq!(migrate("~(2; W1M) Done\r\n").text, "~(2) Done (done: W1M)\r\n");
+    }
+}
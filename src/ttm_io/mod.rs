@@ -2,11 +2,28 @@
 pub mod common_regex; 
 pub mod regex_utils;
 
-mod block_tracker; 
-mod stat; 
-mod task; 
-mod date;
-mod section;
+pub mod block_tracker; 
+pub mod stat; 
+pub mod task; 
+pub mod ids;
+pub mod date;
+pub mod calendar;
+pub mod section;
+pub mod task_tree;
+pub mod document;
+pub mod diagnostic;
+pub mod edit;
+pub mod format;
+#[cfg(feature = "serde")]
+pub mod json;
+pub mod ics;
+pub mod import;
+pub mod todotxt;
+pub mod org;
+pub mod report;
+pub mod charts;
+pub mod notes;
+pub mod legacy;
 
 
 #[cfg(test)]
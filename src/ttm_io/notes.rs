@@ -0,0 +1,321 @@
+//! Resolves the note links of tasks into a directory of notes.
+//! A task links to a note with its *Link suffix field. Links are of the form P[Pattern], where the
+//! pattern names a place in the notes, found by comparing the trimmed pattern case-insensitively to:
+//!     - Section specifiers: A [Pattern] line, as in TTM files.
+//!     - Headings: Markdown (# Pattern) and Org-mode (* Pattern) headings.
+//!     - File names: The name of a note, with or without its extension.
+//! Sections come first, then headings, then file names. Notes are searched in the order of their
+//! paths, so the first match of a link is stable.
+//! A task with an id and no link is linked to the places named by its id, if there are any, as in a
+//! note named <Id>.md. Notes named this way stay linked as the task is renamed.
+
+#![allow(dead_code)]
+
+use super::document::Document;
+use super::task::Task;
+use std::borrow::Cow;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct NoteLink {
+    pattern: String,
+}
+
+impl NoteLink {
+    pub fn pattern(&self) -> &str {
+        &self.pattern
+    }
+}
+
+impl std::str::FromStr for NoteLink {
+    type Err = Cow<'static, str>;
+
+    /// parses P[Pattern]
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        match s.strip_prefix("P[").and_then(|rest| rest.strip_suffix(']')) {
+            Some(pattern) if !pattern.trim().is_empty() => Ok(Self { pattern: pattern.trim().to_string() }),
+            _ => Err(format!("'{}' is not a link of the form P[Pattern]", s).into()),
+        }
+    }
+}
+
+impl fmt::Display for NoteLink {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "P[{}]", self.pattern)
+    }
+}
+
+/// what part of a note a link matched, in the order they are searched
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy)]
+pub enum TargetKind {
+    Section,
+    Heading,
+    FileName,
+}
+
+/// a place in the notes a link may point to
+#[derive(Debug, PartialEq, Clone)]
+pub struct NoteLocation {
+    pub path: PathBuf,
+    /// 1-based, the first line for file names
+    pub line: usize,
+    pub kind: TargetKind,
+}
+
+impl fmt::Display for NoteLocation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.path.display(), self.line)
+    }
+}
+
+/// the places of a note that links may point to
+#[derive(Debug)]
+struct Note {
+    path: PathBuf,
+    /// (kind, name, line)
+    targets: Vec<(TargetKind, String, usize)>,
+}
+
+impl Note {
+    fn new(path: PathBuf, text: &str) -> Self {
+        let mut targets: Vec<(TargetKind, String, usize)> = vec![];
+        for (idx, line) in text.lines().enumerate() {
+            let trimmed = line.trim();
+            if trimmed.len() > 2 && trimmed.starts_with('[') && trimmed.ends_with(']') {
+                targets.push((TargetKind::Section, trimmed[1..trimmed.len() - 1].to_string(), idx + 1));
+            } else if let Some(heading) = heading(line) {
+                targets.push((TargetKind::Heading, heading.to_string(), idx + 1));
+            }
+        }
+        if let Some(stem) = path.file_stem() {
+            targets.push((TargetKind::FileName, stem.to_string_lossy().to_string(), 1));
+        }
+        if let Some(name) = path.file_name() {
+            targets.push((TargetKind::FileName, name.to_string_lossy().to_string(), 1));
+        }
+        Self { path, targets }
+    }
+}
+
+/// the title of a Markdown or Org-mode heading line
+fn heading(line: &str) -> Option<&str> {
+    let marker = line.chars().next().filter(|c| *c == '#' || *c == '*')?;
+    let title = line.trim_start_matches(marker);
+    if !title.starts_with(' ') {
+        return None;
+    }
+    // Markdown headings may be closed by #s
+    let title = if marker == '#' { title.trim_end().trim_end_matches('#') } else { title };
+    Some(title.trim()).filter(|title| !title.is_empty())
+}
+
+/// the notes in a directory, indexed for resolving links
+#[derive(Debug, Default)]
+pub struct NoteIndex {
+    notes: Vec<Note>,
+}
+
+impl NoteIndex {
+    /// indexes every text file in `dir` and its subdirectories. Hidden files and directories are
+    /// skipped, as are files that are not UTF-8 text.
+    pub fn scan(dir: &Path) -> std::io::Result<Self> {
+        let mut files: Vec<(PathBuf, String)> = vec![];
+        scan_dir(dir, &mut files)?;
+        Ok(Self::from_files(files))
+    }
+
+    /// indexes notes given as (path, text)
+    pub fn from_files(files: Vec<(PathBuf, String)>) -> Self {
+        let mut notes: Vec<Note> = files.into_iter().map(|(path, text)| Note::new(path, &text)).collect();
+        notes.sort_by(|a, b| a.path.cmp(&b.path));
+        Self { notes }
+    }
+
+    /// the index along with the notes given as (path, text)
+    pub fn with_files(self, files: Vec<(PathBuf, String)>) -> Self {
+        let mut notes = self.notes;
+        notes.extend(files.into_iter().map(|(path, text)| Note::new(path, &text)));
+        notes.sort_by(|a, b| a.path.cmp(&b.path));
+        Self { notes }
+    }
+
+    /// every place `link` points to, best match first
+    pub fn resolve(&self, link: &NoteLink) -> Vec<NoteLocation> {
+        let pattern = link.pattern.to_lowercase();
+        let mut out: Vec<NoteLocation> = self.notes.iter()
+            .flat_map(|note| note.targets.iter()
+                .filter(|(_kind, name, _line)| name.to_lowercase() == pattern)
+                .map(move |(kind, _name, line)| NoteLocation { path: note.path.clone(), line: *line, kind: *kind }))
+            .collect();
+        out.sort_by_key(|location| location.kind);
+        // the stem and the full name of a file may both match
+        out.dedup();
+        out
+    }
+}
+
+fn scan_dir(dir: &Path, files: &mut Vec<(PathBuf, String)>) -> std::io::Result<()> {
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.file_name().is_some_and(|name| name.to_string_lossy().starts_with('.')) {
+            continue;
+        }
+        if path.is_dir() {
+            scan_dir(&path, files)?;
+        } else if let Ok(text) = std::fs::read_to_string(&path) {
+            files.push((path, text));
+        }
+    }
+    Ok(())
+}
+
+/// a note link of a task that does not resolve
+#[derive(Debug, PartialEq)]
+pub struct BrokenLink<'a> {
+    pub task: &'a Task,
+    pub reason: Cow<'static, str>,
+}
+
+impl fmt::Display for BrokenLink<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "task '{}': *{}: {}", self.task.name(), self.task.note_link(), self.reason)
+    }
+}
+
+/// resolves the note link of `task`, if it has one, or the notes named by its id otherwise
+pub fn resolve_task(task: &Task, index: &NoteIndex) -> Option<Result<Vec<NoteLocation>, Cow<'static, str>>> {
+    if task.note_link().is_empty() {
+        let locations = index.resolve(&NoteLink { pattern: task.id()?.to_string() });
+        return (!locations.is_empty()).then_some(Ok(locations));
+    }
+    Some(task.note_link().parse::<NoteLink>().map(|link| index.resolve(&link)))
+}
+
+/// lists the tasks of `document` whose note links are malformed or point nowhere
+pub fn broken_links<'a>(document: &'a Document, index: &NoteIndex) -> Vec<BrokenLink<'a>> {
+    document.tasks().into_iter()
+        .filter_map(|task| match resolve_task(task, index)? {
+            Ok(locations) if locations.is_empty() => Some(BrokenLink { task, reason: "no note matches".into() }),
+            Ok(_) => None,
+            Err(reason) => Some(BrokenLink { task, reason }),
+        })
+        .collect()
+}
+
+/// lists the tasks of `document` that link into the note at `path`
+pub fn backlinks<'a>(document: &'a Document, index: &NoteIndex, path: &Path) -> Vec<&'a Task> {
+    document.tasks().into_iter()
+        .filter(|task| match resolve_task(task, index) {
+            Some(Ok(locations)) => locations.iter().any(|location| location.path == path),
+            _ => false,
+        })
+        .collect()
+}
+
+/// a command opening `location` in `editor`. Editors are given the line as +<line>, which is
+/// understood by vi, emacs, nano and most others.
+pub fn open_command(location: &NoteLocation, editor: &str) -> std::process::Command {
+    let mut words = editor.split_whitespace();
+    let mut command = std::process::Command::new(words.next().unwrap_or("vi"));
+    command.args(words).arg(format!("+{}", location.line)).arg(&location.path);
+    command
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn index() -> NoteIndex {
+        NoteIndex::from_files(vec![
+            (PathBuf::from("notes/work.md"), "# Work\n\n## Report outline ##\n\nText\n".into()),
+            (PathBuf::from("notes/exercise.ttm"), "[My note!]\n\tPushups\n".into()),
+            (PathBuf::from("notes/Reading.org"), "* Books\n** Dune\n".into()),
+        ])
+    }
+
+    fn location(path: &str, line: usize, kind: TargetKind) -> NoteLocation {
+        NoteLocation { path: PathBuf::from(path), line, kind }
+    }
+
+    #[test]
+    fn test_parse_link() {
+        assert_eq!("P[My note!]".parse::<NoteLink>().unwrap().pattern(), "My note!");
+        assert_eq!("P[My note!]".parse::<NoteLink>().unwrap().to_string(), "P[My note!]");
+        assert!("My note!".parse::<NoteLink>().is_err());
+        assert!("P[ ]".parse::<NoteLink>().is_err());
+    }
+
+    #[test]
+    fn test_resolve() {
+        let index = index();
+        let resolve = |s: &str| index.resolve(&s.parse().unwrap());
+
+        assert_eq!(resolve("P[My note!]"), vec![location("notes/exercise.ttm", 1, TargetKind::Section)]);
+        assert_eq!(resolve("P[report outline]"), vec![location("notes/work.md", 3, TargetKind::Heading)]);
+        assert_eq!(resolve("P[Dune]"), vec![location("notes/Reading.org", 2, TargetKind::Heading)]);
+        assert_eq!(resolve("P[reading]"), vec![location("notes/Reading.org", 1, TargetKind::FileName)]);
+        assert_eq!(resolve("P[work.md]"), vec![location("notes/work.md", 1, TargetKind::FileName)]);
+        // the heading comes before the file name
+        assert_eq!(resolve("P[Work]"), vec![
+            location("notes/work.md", 1, TargetKind::Heading),
+            location("notes/work.md", 1, TargetKind::FileName),
+        ]);
+        assert!(resolve("P[Missing]").is_empty());
+    }
+
+    #[test]
+    fn test_broken_links_and_backlinks() {
+        let index = index();
+        let document: Document = "\
+() Pushups (*P[My note!])
+() Outline (*P[Report outline])
+() Lost (*P[Missing])
+() Malformed (*Missing)
+() Unlinked
+() Novel (#dune)
+() No note (#k3x9qa)
+".parse().unwrap();
+
+        let broken: Vec<String> = broken_links(&document, &index).iter().map(|b| b.to_string()).collect();
+        assert_eq!(broken, vec![
+            "task 'Lost': *P[Missing]: no note matches",
+            "task 'Malformed': *Missing: 'Missing' is not a link of the form P[Pattern]",
+        ]);
+
+        let names: Vec<&str> = backlinks(&document, &index, Path::new("notes/work.md")).iter()
+            .map(|task| task.name())
+            .collect();
+        assert_eq!(names, vec!["Outline"]);
+
+        // tasks without links are linked by their ids
+        let tasks = document.tasks();
+        assert_eq!(resolve_task(tasks[5], &index), Some(Ok(vec![location("notes/Reading.org", 2, TargetKind::Heading)])));
+        assert_eq!(resolve_task(tasks[6], &index), None);
+        assert_eq!(resolve_task(tasks[4], &index), None);
+    }
+
+    #[test]
+    fn test_scan() {
+        let dir = std::env::temp_dir().join(format!("ttm_notes_{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("sub")).unwrap();
+        std::fs::create_dir_all(dir.join(".hidden")).unwrap();
+        std::fs::write(dir.join("sub/plans.md"), "# Trip\n").unwrap();
+        std::fs::write(dir.join(".hidden/plans.md"), "# Trip\n").unwrap();
+
+        let index = NoteIndex::scan(&dir).unwrap();
+        assert_eq!(index.resolve(&"P[Trip]".parse().unwrap()), vec![
+            NoteLocation { path: dir.join("sub/plans.md"), line: 1, kind: TargetKind::Heading },
+        ]);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_open_command() {
+        let command = open_command(&location("notes/work.md", 3, TargetKind::Heading), "code --wait");
+        assert_eq!(command.get_program(), "code");
+        let args: Vec<&std::ffi::OsStr> = command.get_args().collect();
+        assert_eq!(args, vec!["--wait", "+3", "notes/work.md"]);
+    }
+}
@@ -0,0 +1,287 @@
+//! Imports Org-mode files. Headings are mapped by their TODO keyword:
+//!     * Work                                          A section, as it has no keyword
+//!     ** TODO [#A] Write the report [1/3] :writing:   A task
+//!        DEADLINE: <2021-01-14 Thu +1w>
+//!     *** DONE Outline                                A subtask
+//!     - Headings with a keyword are tasks, and so is every heading nested in a task. Other
+//!       headings are sections, nesting the headings under them.
+//!     - Keywords: DONE and CANCELLED make the task done, WAITING and HOLD block it, and NEXT and
+//!       STARTED make it current. Any other keyword, like TODO, only marks a task.
+//!     - [#A]: Priorities A-Z are mapped to TTM priorities 0-25.
+//!     - [N/M]: The statistics cookie is read as the task's count.
+//!     - :tag1:tag2: Tags are kept in the name of the task as +tag1 +tag2.
+//!     - DEADLINE: The due date, converted to a date code through the season calendar. Its repeater
+//!       is read as how often the task repeats. Refer to `super::import::repeat`.
+//!     - Lines under a section are kept as text. SCHEDULED and CLOSED dates, property drawers and
+//!       text under a task are reported and dropped.
+//!     - Names are changed so that they read back the same, which is reported: parentheses in task
+//!       names and brackets in section names are swapped, and text lines that would be read as
+//!       other items are escaped. Refer to `super::import`.
+
+#![allow(dead_code)]
+
+use super::calendar::SeasonCalendar;
+use super::document::{Document, DocumentSection, Item};
+use super::import::{self, Import, Warnings};
+use super::stat::Stat;
+use super::task::{Task, TaskFlags};
+use super::task_tree::TaskTree;
+use regex::Regex;
+
+lazy_static! {
+    static ref HEADING_RE: Regex = Regex::new(r"^(\*+)\s+(.*)$").unwrap();
+    static ref PRIORITY_RE: Regex = Regex::new(r"^\[#([A-Z])\]\s*").unwrap();
+    static ref TAGS_RE: Regex = Regex::new(r"\s+:([\w@#%:]+):\s*$").unwrap();
+    static ref COOKIE_RE: Regex = Regex::new(r"\s*\[(\d*)(/(\d*)|%)\]").unwrap();
+    static ref PLANNING_RE: Regex = Regex::new(
+        r"(DEADLINE|SCHEDULED|CLOSED):\s*[<\[](\d{4}-\d{2}-\d{2})[^>\]]*?(\.?\+\+?(\d*)([dwmy]))?[>\]]").unwrap();
+}
+
+/// keywords of task headings, along with the flags they give
+const KEYWORDS: [(&str, TaskFlags); 8] = [
+    ("TODO", TaskFlags::empty()),
+    ("DONE", TaskFlags::DONE),
+    ("CANCELLED", TaskFlags::DONE),
+    ("CANCELED", TaskFlags::DONE),
+    ("WAITING", TaskFlags::BLOCKED),
+    ("HOLD", TaskFlags::BLOCKED),
+    ("NEXT", TaskFlags::CURRENT),
+    ("STARTED", TaskFlags::CURRENT),
+];
+
+/// a line of the file that matters to the document
+enum Entry {
+    Heading { line: usize, level: usize, is_task: bool, task: Box<Task> },
+    Text { line: usize, text: String },
+}
+
+/// imports the Org-mode file `s`, converting its dates through `calendar`
+pub fn import(s: &str, calendar: &SeasonCalendar) -> Import {
+    let mut warnings = Warnings::default();
+    let entries = parse_entries(s, calendar, &mut warnings);
+
+    let mut idx = 0;
+    let items = build_items(&entries, &mut idx, 0, &mut warnings);
+    warnings.0.sort_by_key(|warning| warning.line);
+    Import { document: Document::new(items), warnings: warnings.0 }
+}
+
+/// reads the headings and text of the file. Planning lines are read into the heading they follow.
+fn parse_entries(s: &str, calendar: &SeasonCalendar, warnings: &mut Warnings) -> Vec<Entry> {
+    let mut entries: Vec<Entry> = vec![];
+    let mut in_drawer = false;
+
+    for (idx, line) in s.lines().enumerate() {
+        let line_no = idx + 1;
+        let trimmed = line.trim();
+
+        if let Some(caps) = HEADING_RE.captures(line) {
+            in_drawer = false;
+            let (is_task, task) = parse_heading(&caps[2], line_no, warnings);
+            entries.push(Entry::Heading { line: line_no, level: caps[1].len(), is_task, task: Box::new(task) });
+            continue;
+        }
+        if in_drawer {
+            in_drawer = trimmed != ":END:";
+            continue;
+        }
+        if trimmed.is_empty() {
+            continue;
+        }
+
+        let heading_task = match entries.last_mut() {
+            Some(Entry::Heading { task, .. }) => Some(task),
+            _ => None,
+        };
+        match heading_task {
+            Some(task) if PLANNING_RE.is_match(trimmed) => {
+                for caps in PLANNING_RE.captures_iter(trimmed) {
+                    match &caps[1] {
+                        "DEADLINE" => {
+                            if let Some(date) = import::date_code(&caps[2], calendar, line_no, warnings) {
+                                task.build_due_date(date);
+                            }
+                            if let Some(unit) = caps.get(5) {
+                                if let Some(repeat) = import::repeat(&caps[4], unit.as_str(), line_no, warnings) {
+                                    task.build_repeat(repeat);
+                                }
+                            }
+                        }
+                        kind => warnings.warn(line_no, format!("dropped {} date {}", kind, &caps[2])),
+                    }
+                }
+            }
+            Some(_) if trimmed.starts_with(':') && trimmed.ends_with(':') && trimmed.len() > 1 => {
+                warnings.warn(line_no, format!("dropped drawer {}", trimmed));
+                in_drawer = trimmed != ":END:";
+            }
+            _ => entries.push(Entry::Text { line: line_no, text: trimmed.to_string() }),
+        }
+    }
+
+    entries
+}
+
+/// parses the title of a heading into a task, and whether the heading is a task on its own
+fn parse_heading(title: &str, line_no: usize, warnings: &mut Warnings) -> (bool, Task) {
+    let mut title = title.trim();
+    let mut flags = TaskFlags::empty();
+    let mut is_task = false;
+    let keyword = title.split_whitespace().next().unwrap_or("");
+    if let Some((_, keyword_flags)) = KEYWORDS.iter().find(|(name, _)| *name == keyword) {
+        flags = *keyword_flags;
+        is_task = true;
+        title = title[keyword.len()..].trim_start();
+    }
+
+    let mut priority: Option<usize> = None;
+    if let Some(caps) = PRIORITY_RE.captures(title) {
+        priority = Some((caps[1].as_bytes()[0] - b'A') as usize);
+        title = &title[caps[0].len()..];
+    }
+
+    let mut name = title.to_string();
+    if let Some(caps) = TAGS_RE.captures(title) {
+        let tags: Vec<String> = caps[1].split(':').filter(|tag| !tag.is_empty())
+            .map(|tag| format!("+{}", tag))
+            .collect();
+        name = format!("{} {}", &title[..caps.get(0).unwrap().start()], tags.join(" "));
+    }
+
+    let mut count: Option<Stat> = None;
+    if let Some(caps) = COOKIE_RE.captures(&name) {
+        match caps.get(3) {
+            Some(exp) => count = Some(Stat::from_count(caps[1].parse().ok(), exp.as_str().parse().ok())),
+            None => warnings.warn(line_no, format!("dropped percent cookie [{}%]", &caps[1])),
+        }
+        name = COOKIE_RE.replace(&name, "").to_string();
+    }
+
+    // the name is made usable once it is known whether the heading is a task or a section
+    let mut task = Task::from_name_and_stats(name.trim(), (count, None, None));
+    task.build_flags(flags);
+    if let Some(priority) = priority {
+        task.build_priority(priority);
+    }
+    (is_task, task)
+}
+
+/// builds the items from `entries[*idx]` on, until a heading of `level` or higher
+fn build_items(entries: &[Entry], idx: &mut usize, level: usize, warnings: &mut Warnings) -> Vec<Item> {
+    let mut items: Vec<Item> = vec![];
+    while let Some(entry) = entries.get(*idx) {
+        match entry {
+            Entry::Heading { level: heading_level, .. } if *heading_level <= level => break,
+            Entry::Heading { is_task: true, .. } => items.push(Item::Task(build_tree(entries, idx, warnings))),
+            Entry::Heading { line, level: heading_level, task, .. } => {
+                *idx += 1;
+                let name = import::section_name(task.name(), *line, warnings);
+                let section_items = build_items(entries, idx, *heading_level, warnings);
+                items.push(Item::Section(DocumentSection::new(&name, section_items)));
+            }
+            Entry::Text { line, text } => {
+                items.push(Item::Text(import::text_line(text, *line, warnings).into()));
+                *idx += 1;
+            }
+        }
+    }
+    items
+}
+
+/// builds the task tree of the heading at `entries[*idx]`. Every heading nested in it is a subtask.
+fn build_tree(entries: &[Entry], idx: &mut usize, warnings: &mut Warnings) -> TaskTree {
+    let (level, task) = match &entries[*idx] {
+        Entry::Heading { line, level, task, .. } => {
+            let mut task = task.as_ref().clone();
+            task.build_name(&import::task_name(task.name(), *line, warnings));
+            (*level, task)
+        }
+        Entry::Text { .. } => unreachable!("task trees start at a heading"),
+    };
+    *idx += 1;
+
+    let mut subtasks: Vec<TaskTree> = vec![];
+    while let Some(entry) = entries.get(*idx) {
+        match entry {
+            Entry::Heading { level: heading_level, .. } if *heading_level <= level => break,
+            Entry::Heading { .. } => subtasks.push(build_tree(entries, idx, warnings)),
+            Entry::Text { line, text } => {
+                warnings.warn(*line, format!("dropped text under task '{}': {}", task.name(), text));
+                *idx += 1;
+            }
+        }
+    }
+    TaskTree::new(task, subtasks)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ORG: &str = "\
+#+TITLE: Tasks
+* Work
+Notes about work
+** TODO [#A] Write the report [1/3] :writing:urgent:
+   DEADLINE: <2021-01-14 Thu +1w> SCHEDULED: <2021-01-11 Mon>
+   :PROPERTIES:
+   :EFFORT: 2h
+   :END:
+   Some details
+*** DONE Outline
+*** Draft (first pass)
+** WAITING Review [50%]
+* Home
+** NEXT Groceries
+";
+
+    #[test]
+    fn test_import() {
+        let import = import(ORG, &SeasonCalendar::default());
+
+        assert_eq!(import.document.to_string(), "\
+#+TITLE: Tasks
+[Work]
+\tNotes about work
+\t(1/3) Write the report +writing +urgent (due: Y21W-W3R; prior: 0; rept: W)
+\t\t~() Outline
+\t\t() Draft [first pass]
+\tB() Review
+[Home]
+\t>() Groceries
+");
+        let warnings: Vec<(usize, String)> = import.warnings.iter()
+            .map(|w| (w.line, w.message.to_string()))
+            .collect();
+        assert_eq!(warnings, vec![
+            (5, "dropped SCHEDULED date 2021-01-11".to_string()),
+            (6, "dropped drawer :PROPERTIES:".to_string()),
+            (9, "dropped text under task 'Write the report +writing +urgent': Some details".to_string()),
+            (11, "replaced the parentheses in 'Draft (first pass)' with brackets".to_string()),
+            (12, "dropped percent cookie [50%]".to_string()),
+        ]);
+    }
+
+    #[test]
+    fn test_import_round_trips() {
+        let import = import(ORG, &SeasonCalendar::default());
+        let rendered = import.document.to_string();
+        assert_eq!(rendered.parse::<Document>().unwrap(), import.document);
+    }
+
+    #[test]
+    fn test_unreadable_names() {
+        let org = "* Work [stuff]\n(see below)\n[1/2]\n** TODO Call (maybe)\n";
+        let import = import(org, &SeasonCalendar::default());
+        assert_eq!(import.document.to_string(), "[Work (stuff)]\n\t- (see below)\n\t- [1/2]\n\t() Call [maybe]\n");
+        assert_eq!(import.warnings.iter().map(|w| w.line).collect::<Vec<usize>>(), vec![1, 2, 3, 4]);
+        assert_eq!(import.document.to_string().parse::<Document>().unwrap(), import.document);
+    }
+
+    #[test]
+    fn test_nested_sections() {
+        let import = import("* A\n** B\n*** TODO Task\n* C\n", &SeasonCalendar::default());
+        assert_eq!(import.document.to_string(), "[A]\n\t[B]\n\t\t() Task\n[C]\n");
+        assert!(import.warnings.is_empty());
+    }
+}
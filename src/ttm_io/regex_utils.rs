@@ -37,7 +37,6 @@ pub fn filter_inner_capture_group_names(regex: &str) -> String {
 /// Assumes that the capture is well-defined and parses successfully
 pub fn capture_parse_i32(cap: &regex::Captures<'_>, field: &str) -> i32 {
     let out = cap.name(field).map(|m| m.as_str()).unwrap();
-    println!("{}", out);
     let out = common::parse_integer_auto(out).unwrap();
 
     out
@@ -0,0 +1,317 @@
+//! Renders documents as reports to share progress with, in Markdown or standalone HTML.
+//! Reports are written from the parsed document:
+//!     - Sections become headings, nesting deeper the deeper the section is.
+//!     - Task trees become checklists. A task shows the progress of its lifetime count, or of its
+//!       count for today if it has none (refer to `Task::progress_stat`), its deadlines, and
+//!       whether it is current, blocked or late. HTML reports also draw a progress bar of the
+//!       count, if it has an objective.
+//!     - Consecutive block tracker entries become a table, with a column for each day of the week.
+//!     - Free text is kept as is.
+
+#![allow(dead_code)]
+
+use super::block_tracker::BlockTrackerEntry;
+use super::date::Weekday;
+use super::document::{Document, Item};
+use super::task::{Task, TaskFlags};
+use super::task_tree::TaskTree;
+
+/// headings go no deeper than this, as neither format has deeper ones
+const MAX_HEADING_LEVEL: usize = 6;
+
+/// labels for the flags of a task, other than being done
+const FLAG_LABELS: [(TaskFlags, &str); 3] = [
+    (TaskFlags::CURRENT, "current"),
+    (TaskFlags::BLOCKED, "blocked"),
+    (TaskFlags::LATE, "late"),
+];
+
+/// the progress, deadlines and flags of a task, like "2/15, due W3R, current"
+fn task_details(task: &Task) -> Vec<String> {
+    let mut details: Vec<String> = vec![];
+    if let Some(stat) = task.progress_stat() {
+        details.push(stat.to_string());
+    }
+    if let Some(due_date) = task.due_date() {
+        details.push(format!("due {}", due_date));
+    }
+    if let Some(hard_date) = task.hard_date() {
+        details.push(format!("hard {}", hard_date));
+    }
+    if let Some(done_date) = task.done_date() {
+        details.push(format!("done {}", done_date));
+    }
+    for (flag, label) in FLAG_LABELS.iter() {
+        if task.flags().contains(*flag) {
+            details.push(label.to_string());
+        }
+    }
+    details
+}
+
+/// calls `f` on every run of items, where consecutive block tracker entries are gathered into one
+/// run so they can be written as a table
+fn group_entries<'a>(items: &'a [Item], mut f: impl FnMut(Group<'a>)) {
+    let mut i = 0;
+    while i < items.len() {
+        if let Item::BlockTrackerEntry(_) = &items[i] {
+            let mut entries: Vec<&BlockTrackerEntry> = vec![];
+            while let Some(Item::BlockTrackerEntry(entry)) = items.get(i) {
+                entries.push(entry);
+                i += 1;
+            }
+            f(Group::Table(entries));
+        } else {
+            f(Group::Item(&items[i]));
+            i += 1;
+        }
+    }
+}
+
+enum Group<'a> {
+    Item(&'a Item),
+    Table(Vec<&'a BlockTrackerEntry>),
+}
+
+/// escapes the characters Markdown would read as formatting
+fn escape_markdown(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        if "\\`*_[]<>|#".contains(c) {
+            out.push('\\');
+        }
+        out.push(c);
+    }
+    out
+}
+
+/// writes `document` as Markdown
+pub fn to_markdown(document: &Document) -> String {
+    let mut out = String::new();
+    markdown_items(document.items(), 1, &mut out);
+
+    // headings and tables are set apart by blank lines, which may pile up between them
+    let mut collapsed = String::with_capacity(out.len());
+    for line in out.trim_start_matches('\n').split_inclusive('\n') {
+        if !(line == "\n" && (collapsed.is_empty() || collapsed.ends_with("\n\n"))) {
+            collapsed.push_str(line);
+        }
+    }
+    collapsed
+}
+
+fn markdown_items(items: &[Item], level: usize, out: &mut String) {
+    group_entries(items, |group| match group {
+        Group::Item(Item::Section(section)) => {
+            out.push_str(&format!("\n{} {}\n\n", "#".repeat(level.min(MAX_HEADING_LEVEL)),
+                escape_markdown(section.specifier())));
+            markdown_items(section.items(), level + 1, out);
+        }
+        Group::Item(Item::Task(tree)) => markdown_task(tree, 0, out),
+        Group::Item(Item::Text(line)) => out.push_str(&format!("{}\n", escape_markdown(&line.text))),
+        Group::Item(Item::BlockTrackerEntry(_)) => unreachable!("entries are grouped into tables"),
+        Group::Table(entries) => {
+            out.push_str("\n| Entry |");
+            for day in Weekday::ALL.iter() {
+                out.push_str(&format!(" {} |", day));
+            }
+            out.push_str("\n|---|");
+            out.push_str(&"---|".repeat(Weekday::ALL.len()));
+            out.push('\n');
+            for entry in entries {
+                out.push_str(&format!("| {} |", escape_markdown(entry.entry_name())));
+                for stat in entry.week_stats().iter() {
+                    out.push_str(&format!(" {} |", escape_markdown(&stat.to_string())));
+                }
+                out.push('\n');
+            }
+            out.push('\n');
+        }
+    });
+}
+
+fn markdown_task(tree: &TaskTree, depth: usize, out: &mut String) {
+    let task = tree.task();
+    let check = if task.flags().contains(TaskFlags::DONE) { "x" } else { " " };
+    out.push_str(&format!("{}- [{}] {}", "  ".repeat(depth), check, escape_markdown(task.name())));
+    let details = task_details(task);
+    if !details.is_empty() {
+        out.push_str(&format!(" ({})", escape_markdown(&details.join(", "))));
+    }
+    out.push('\n');
+    for subtask in tree.subtasks() {
+        markdown_task(subtask, depth + 1, out);
+    }
+}
+
+/// escapes the characters HTML would read as markup
+fn escape_html(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+}
+
+const HTML_STYLE: &str = "\
+body { font-family: sans-serif; max-width: 50em; margin: auto; }
+ul.tasks { list-style: none; padding-left: 1.5em; }
+li.done > .name { text-decoration: line-through; color: gray; }
+li.blocked > .name { color: firebrick; }
+li.current > .name { font-weight: bold; }
+progress { vertical-align: middle; margin-left: 0.5em; }
+.details { color: gray; font-size: smaller; margin-left: 0.5em; }
+table.block-tracker { border-collapse: collapse; }
+table.block-tracker td, table.block-tracker th { border: 1px solid lightgray; padding: 0.2em 0.5em; }";
+
+/// writes `document` as a standalone HTML page titled `title`
+pub fn to_html(document: &Document, title: &str) -> String {
+    let mut body = String::new();
+    html_items(document.items(), 1, &mut body);
+    format!("<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{title}</title>\n\
+             <style>\n{style}\n</style>\n</head>\n<body>\n<h1>{title}</h1>\n{body}</body>\n</html>\n",
+        title = escape_html(title), style = HTML_STYLE, body = body)
+}
+
+fn html_items(items: &[Item], level: usize, out: &mut String) {
+    // consecutive task trees share a list
+    let mut in_list = false;
+    group_entries(items, |group| {
+        let is_task = matches!(group, Group::Item(Item::Task(_)));
+        if is_task && !in_list {
+            out.push_str("<ul class=\"tasks\">\n");
+        } else if !is_task && in_list {
+            out.push_str("</ul>\n");
+        }
+        in_list = is_task;
+
+        match group {
+            Group::Item(Item::Section(section)) => {
+                // the page title is the only h1
+                let heading = (level + 1).min(MAX_HEADING_LEVEL);
+                out.push_str(&format!("<section>\n<h{h}>{}</h{h}>\n", escape_html(section.specifier()), h = heading));
+                html_items(section.items(), level + 1, out);
+                out.push_str("</section>\n");
+            }
+            Group::Item(Item::Task(tree)) => html_task(tree, out),
+            Group::Item(Item::Text(text)) if text.is_empty() => {}
+            Group::Item(Item::Text(line)) => out.push_str(&format!("<p>{}</p>\n", escape_html(&line.text))),
+            Group::Item(Item::BlockTrackerEntry(_)) => unreachable!("entries are grouped into tables"),
+            Group::Table(entries) => {
+                out.push_str("<table class=\"block-tracker\">\n<tr><th>Entry</th>");
+                for day in Weekday::ALL.iter() {
+                    out.push_str(&format!("<th>{}</th>", day));
+                }
+                out.push_str("</tr>\n");
+                for entry in entries {
+                    out.push_str(&format!("<tr><td>{}</td>", escape_html(entry.entry_name())));
+                    for stat in entry.week_stats().iter() {
+                        out.push_str(&format!("<td>{}</td>", escape_html(&stat.to_string())));
+                    }
+                    out.push_str("</tr>\n");
+                }
+                out.push_str("</table>\n");
+            }
+        }
+    });
+    if in_list {
+        out.push_str("</ul>\n");
+    }
+}
+
+fn html_task(tree: &TaskTree, out: &mut String) {
+    let task = tree.task();
+    let mut classes = vec!["task"];
+    if task.flags().contains(TaskFlags::DONE) {
+        classes.push("done");
+    }
+    for (flag, label) in FLAG_LABELS.iter() {
+        if task.flags().contains(*flag) {
+            classes.push(label);
+        }
+    }
+
+    let checked = if task.flags().contains(TaskFlags::DONE) { " checked" } else { "" };
+    out.push_str(&format!("<li class=\"{}\"><input type=\"checkbox\" disabled{}> <span class=\"name\">{}</span>",
+        classes.join(" "), checked, escape_html(task.name())));
+    if let Some((done, total)) = task.progress_stat().and_then(|stat| stat.progress()) {
+        out.push_str(&format!("<progress value=\"{}\" max=\"{}\"></progress>", done, total));
+    }
+    let details = task_details(task);
+    if !details.is_empty() {
+        out.push_str(&format!("<span class=\"details\">{}</span>", escape_html(&details.join(", "))));
+    }
+
+    if !tree.subtasks().is_empty() {
+        out.push_str("\n<ul class=\"tasks\">\n");
+        for subtask in tree.subtasks() {
+            html_task(subtask, out);
+        }
+        out.push_str("</ul>\n");
+    }
+    out.push_str("</li>\n");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DOCUMENT: &str = "\
+Weekly progress
+[Block Tracker]
+\t0   !   /-  ?   4/4 -   0   PROJECT
+\t1   2   3   4   5   6   7   READING
+[Tasks]
+\t>(2/15) Write the report (due: W3R; hard: W4)
+\t\t~(2/2) Outline (done: W3M)
+\t\t(!) Draft <v2>
+\t[Errands]
+\t\tB() Groceries
+";
+
+    #[test]
+    fn test_to_markdown() {
+        let document: Document = DOCUMENT.parse().unwrap();
+
+        assert_eq!(to_markdown(&document), "\
+Weekly progress
+
+# Block Tracker
+
+| Entry | M | T | W | R | F | S | U |
+|---|---|---|---|---|---|---|---|
+| PROJECT | 0 | ! | /- | ? | 4/4 | - | 0 |
+| READING | 1 | 2 | 3 | 4 | 5 | 6 | 7 |
+
+# Tasks
+
+- [ ] Write the report (2/15, due W3R, hard W4, current)
+  - [x] Outline (2/2, done W3M)
+  - [ ] Draft \\<v2\\> (!)
+
+## Errands
+
+- [ ] Groceries (blocked)
+");
+    }
+
+    #[test]
+    fn test_to_html() {
+        let document: Document = DOCUMENT.parse().unwrap();
+        let html = to_html(&document, "Week 3 & 4");
+
+        assert!(html.starts_with("<!DOCTYPE html>\n"));
+        assert!(html.contains("<title>Week 3 &amp; 4</title>"));
+        assert!(html.contains("<section>\n<h2>Tasks</h2>\n<ul class=\"tasks\">\n\
+            <li class=\"task current\"><input type=\"checkbox\" disabled> <span class=\"name\">Write the report</span>\
+            <progress value=\"2\" max=\"15\"></progress><span class=\"details\">2/15, due W3R, hard W4, current</span>\n\
+            <ul class=\"tasks\">\n\
+            <li class=\"task done\"><input type=\"checkbox\" disabled checked> <span class=\"name\">Outline</span>\
+            <progress value=\"2\" max=\"2\"></progress><span class=\"details\">2/2, done W3M</span></li>\n"));
+        assert!(html.contains("<span class=\"name\">Draft &lt;v2&gt;</span><progress value=\"1\" max=\"1\"></progress>"));
+        assert!(html.contains("<section>\n<h3>Errands</h3>\n<ul class=\"tasks\">\n<li class=\"task blocked\">"));
+        assert!(html.contains("<tr><td>PROJECT</td><td>0</td><td>!</td><td>/-</td><td>?</td><td>4/4</td><td>-</td><td>0</td></tr>"));
+        assert!(html.contains("<p>Weekly progress</p>"));
+        assert_eq!(html.matches("<ul").count(), html.matches("</ul>").count());
+    }
+}
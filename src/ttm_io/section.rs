@@ -18,15 +18,30 @@ use std::borrow::Cow;
 /// |-tab-| Content...
 ///
 #[derive(Debug, PartialEq)]
-struct Section {
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Section {
     /// initial tabbing found in the Specifier line
     tab: String,
     specifier: String,
     body: String,
 }
 
-#[derive(Debug, PartialEq)]
-enum SectionParseError {
+impl Section {
+    pub fn tab(&self) -> &str {
+        &self.tab
+    }
+
+    pub fn specifier(&self) -> &str {
+        &self.specifier
+    }
+
+    pub fn body(&self) -> &str {
+        &self.body
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum SectionParseError {
     /// error message of generic parsing error
     /// meant to be reported for diagnostics, not handled
     Generic(Cow<'static, str>),
@@ -3,10 +3,15 @@
 use super::common_regex;
 use super::regex_utils;
 use crate::utils::common;
+use crate::utils::scanner::{FromNext, ScanError, StrScanner};
 use regex::Regex;
 use std::borrow::Cow;
+use std::convert::TryFrom;
+use std::fmt;
 
 #[derive(Debug, PartialEq, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(tag = "kind", rename_all = "snake_case"))]
 pub enum Stat {
     Count { act: Option<i32>, exp: Option<i32> }, // actual count out of expected count or objective. ex. 2/5 reads "Done 2 out of 5."
     Bool { act: bool, exp: bool }, // -: not done (but implicitly required!), !: done, -/!: explicit default of "-", !/-: done, wasn't required, /-: not required.
@@ -24,6 +29,47 @@ impl Stat {
     pub fn from_bool(act: bool, exp: bool) -> Self {
         Self::Bool { act, exp }
     }
+
+    /// how many units are left until the expected count is reached. Only counts with an
+    /// objective have a remainder.
+    pub fn remaining(&self) -> Option<i32> {
+        match *self {
+            Stat::Count { act, exp: Some(exp) } => Some((exp - act.unwrap_or(0)).max(0)),
+            _ => None,
+        }
+    }
+
+    /// how far along the stat is, as (done, total). Counts without an objective and unknown
+    /// stats have no progress to show.
+    pub fn progress(&self) -> Option<(i32, i32)> {
+        match *self {
+            Stat::Count { act, exp: Some(exp) } if exp > 0 => Some((act.unwrap_or(0).clamp(0, exp), exp)),
+            Stat::Bool { act, .. } => Some((act as i32, 1)),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Stat {
+    /// writes the stat in the same format it is parsed from
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fn custom_bool(b: bool) -> &'static str {
+            if b { "!" } else { "-" }
+        }
+
+        match *self {
+            Stat::Count { act: Some(act), exp: Some(exp) } => write!(f, "{}/{}", act, exp),
+            Stat::Count { act: Some(act), exp: None } => write!(f, "{}", act),
+            Stat::Count { act: None, exp: Some(exp) } => write!(f, "/{}", exp),
+            Stat::Count { act: None, exp: None } => write!(f, "0"),
+            // act defaults to '-' and exp defaults to '!', so they may be omitted
+            Stat::Bool { act: false, exp: false } => write!(f, "/-"),
+            Stat::Bool { act, exp: true } => write!(f, "{}", custom_bool(act)),
+            Stat::Bool { act: true, exp: false } => write!(f, "!/-"),
+            Stat::RequiredCount { act, exp } => write!(f, "{}/{}", act, custom_bool(exp)),
+            Stat::Unknown => write!(f, "?"),
+        }
+    }
 }
 
 #[allow(dead_code)]
@@ -61,12 +107,10 @@ pub mod stat_parser_regex {
         pub static ref UNKNOWN_RE: Regex = Regex::new(&UNKNOWN).unwrap();
         pub static ref STAT_RE: Regex = Regex::new(&STAT).unwrap();
     }
-}
 
-impl std::str::FromStr for Stat {
-    type Err = Cow<'static, str>;
-
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
+    /// parses a stat by trying each variant's regex in turn. `Stat::next` has replaced this, which
+    /// is kept to benchmark the two against each other.
+    pub fn parse(s: &str) -> Result<Stat, Cow<'static, str>> {
         fn parse_custom_bool(s: &str) -> Result<bool, ()> {
             match s {
                 "!" => Ok(true),
@@ -75,7 +119,7 @@ impl std::str::FromStr for Stat {
             }
         }
 
-        if let Some(cap) = stat_parser_regex::COUNT_RE.captures(s) {
+        if let Some(cap) = COUNT_RE.captures(s) {
             // captures the tokens from the regex depending on which variant they end up on
             let act_cap = cap
                 .name("ACT0")
@@ -97,7 +141,7 @@ impl std::str::FromStr for Stat {
                 None
             };
             Ok(Stat::Count { act, exp })
-        } else if let Some(cap) = stat_parser_regex::BOOL_RE.captures(s) {
+        } else if let Some(cap) = BOOL_RE.captures(s) {
             // captures the tokens from the regex depending on which variant they end up on
             let act_cap = cap
                 .name("ACT0")
@@ -119,7 +163,7 @@ impl std::str::FromStr for Stat {
                 true
             };
             Ok(Stat::Bool { act, exp })
-        } else if let Some(cap) = stat_parser_regex::REQUIRED_COUNT_RE.captures(s) {
+        } else if let Some(cap) = REQUIRED_COUNT_RE.captures(s) {
             // captures the tokens from the regex depending on which variant they end up on
             let act_cap = cap.name("ACT").map(|m| m.as_str());
             let exp_cap = cap.name("EXP").map(|m| m.as_str());
@@ -127,7 +171,7 @@ impl std::str::FromStr for Stat {
             let act = common::parse_integer_auto(act_cap.unwrap()).unwrap();
             let exp = parse_custom_bool(exp_cap.unwrap()).unwrap();
             Ok(Stat::RequiredCount { act, exp })
-        } else if let Some(_cap) = stat_parser_regex::UNKNOWN_RE.captures(s) {
+        } else if let Some(_cap) = UNKNOWN_RE.captures(s) {
             Ok(Stat::Unknown)
         } else {
             Err(format!("No variant of Stat is satisfied by '{}'", s).into())
@@ -135,6 +179,90 @@ impl std::str::FromStr for Stat {
     }
 }
 
+impl FromNext for Stat {
+    type Err = ScanError;
+
+    /// parses a stat off the start of `s`. Whitespace is allowed around the '/' only.
+    fn next(s: &str) -> Result<(usize, Self), Self::Err> {
+        fn next_bool(scan: &mut StrScanner) -> Option<bool> {
+            if scan.match_next("!").is_ok() {
+                Some(true)
+            } else if scan.match_next("-").is_ok() {
+                Some(false)
+            } else {
+                None
+            }
+        }
+        fn next_count(scan: &mut StrScanner) -> Result<Option<i32>, ScanError> {
+            let at = scan.cur;
+            match scan.next_integer() {
+                Ok(num) => i32::try_from(num).map(Some).or(Err(ScanError::new(at, "a count that fits in 32 bits"))),
+                Err(_) => Ok(None),
+            }
+        }
+        /// matches the '/' after an ACT, leaving the cursor where it was if there is none
+        fn next_slash(scan: &mut StrScanner) -> bool {
+            let cur = scan.cur;
+            scan.skip_whitespace();
+            if scan.match_next("/").is_ok() {
+                scan.skip_whitespace();
+                return true;
+            }
+            scan.cur = cur;
+            false
+        }
+
+        let mut scan = StrScanner::create(s);
+        if scan.match_next("?").is_ok() {
+            return Ok((scan.cur, Stat::Unknown));
+        }
+
+        // act defaults to false (not done) and exp defaults to true (required to be done)
+        if let Some(act) = next_bool(&mut scan) {
+            if !next_slash(&mut scan) {
+                return Ok((scan.cur, Stat::Bool { act, exp: true }));
+            }
+            let exp = next_bool(&mut scan).ok_or(ScanError::new(scan.cur, "'-' or '!'"))?;
+            return Ok((scan.cur, Stat::Bool { act, exp }));
+        }
+
+        let act = next_count(&mut scan)?;
+        let has_slash = match act {
+            Some(_) => next_slash(&mut scan),
+            None => {
+                scan.expect("/").or(Err(ScanError::new(scan.cur, "a stat")))?;
+                scan.skip_whitespace();
+                true
+            }
+        };
+        if !has_slash {
+            return Ok((scan.cur, Stat::Count { act, exp: None }));
+        }
+
+        match (act, next_bool(&mut scan)) {
+            (Some(act), Some(exp)) => return Ok((scan.cur, Stat::RequiredCount { act, exp })),
+            (None, Some(exp)) => return Ok((scan.cur, Stat::Bool { act: false, exp })),
+            (_, None) => {}
+        }
+        match next_count(&mut scan)? {
+            Some(exp) => Ok((scan.cur, Stat::Count { act, exp: Some(exp) })),
+            None if act.is_some() => Err(ScanError::new(scan.cur, "a count, '-' or '!'")),
+            None => Err(ScanError::new(scan.cur, "a count")),
+        }
+    }
+}
+
+impl std::str::FromStr for Stat {
+    type Err = Cow<'static, str>;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut scan = StrScanner::create(s);
+        scan.next::<Stat>()
+            .and_then(|stat| if scan.is_done() { Ok(stat) } else { Err(ScanError::new(scan.cur, "the end of the stat")) })
+            .map_err(|err| format!("No variant of Stat is satisfied by '{}': {}", s, err).into())
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -191,4 +319,54 @@ mod tests {
         assert_fails("(999)");
         assert_fails("999 // beep boop");
     }
+
+    #[test]
+    fn test_stat_next() {
+        assert_eq!(Stat::next("2/5, 3"), Ok((3, Stat::Count {act: Some(2), exp: Some(5)})));
+        assert_eq!(Stat::next("2 ,3"), Ok((1, Stat::Count {act: Some(2), exp: None})));
+        assert_eq!(Stat::next("!/- done"), Ok((3, Stat::Bool {act: true, exp: false})));
+        assert_eq!(Stat::next("1 / !)"), Ok((5, Stat::RequiredCount {act: 1, exp: true})));
+        assert_eq!(Stat::next("?)"), Ok((1, Stat::Unknown)));
+
+        assert_eq!(Stat::next(""), Err(ScanError::new(0, "a stat")));
+        assert_eq!(Stat::next("!/"), Err(ScanError::new(2, "'-' or '!'")));
+        assert_eq!(Stat::next("2 / x"), Err(ScanError::new(4, "a count, '-' or '!'")));
+        assert_eq!(Stat::next("/ 0x"), Ok((3, Stat::Count {act: None, exp: Some(0)})));
+        assert_eq!(Stat::next("0xFFFFFFFF"), Err(ScanError::new(0, "a count that fits in 32 bits")));
+        assert_eq!(&"2 // 3".parse::<Stat>().unwrap_err(), "No variant of Stat is satisfied by '2 // 3': expected a count, '-' or '!' at 3");
+    }
+
+    #[test]
+    fn test_stat_parse_as_regex() {
+        for s in &["0/5", "/0xFF", "0b1/0o7", "5 /  5", "3", "/-", "-", "!/ -", "0/-", "?", "", "/", "0x", "5 ", "-/3", "??", "1/!/"] {
+            assert_eq!(s.parse::<Stat>().ok(), stat_parser_regex::parse(s).ok(), "{}", s);
+        }
+    }
+
+    #[test]
+    fn test_stat_display() {
+        for s in &["0/5", "/15", "3", "/-", "-", "!/-", "!", "0/-", "1/!", "?"] {
+            assert_eq!(&s.parse::<Stat>().unwrap().to_string(), s);
+        }
+        assert_eq!(Stat::Bool {act: false, exp: true}.to_string(), "-");
+    }
+
+    #[test]
+    fn test_stat_remaining() {
+        assert_eq!(Stat::from_count(Some(2), Some(15)).remaining(), Some(13));
+        assert_eq!(Stat::from_count(None, Some(4)).remaining(), Some(4));
+        assert_eq!(Stat::from_count(Some(6), Some(4)).remaining(), Some(0));
+        assert_eq!(Stat::from_count(Some(6), None).remaining(), None);
+        assert_eq!(Stat::RequiredCount {act: 1, exp: true}.remaining(), None);
+    }
+
+    #[test]
+    fn test_stat_progress() {
+        assert_eq!(Stat::from_count(Some(2), Some(15)).progress(), Some((2, 15)));
+        assert_eq!(Stat::from_count(Some(6), Some(4)).progress(), Some((4, 4)));
+        assert_eq!(Stat::from_count(None, Some(0)).progress(), None);
+        assert_eq!(Stat::from_count(Some(6), None).progress(), None);
+        assert_eq!(Stat::from_bool(true, false).progress(), Some((1, 1)));
+        assert_eq!(Stat::Unknown.progress(), None);
+    }
 }
@@ -2,14 +2,13 @@
 #![allow(dead_code)]
 // #![allow(unused_imports)]
 // TODO Tasks:
-//  - Rewrite the REGEX implementation to conform to the new Task description at
-//  [*ttm_io/tasks/TaskRegex]
 //  - Thoroughly test the requirements of the parsing
 
 //! Implementation of the Task token text format.
 //! The Task token is described in the following regex: [^ttm_io/tasks/TaskRegex]
 //!     [TaskFlags]\([Daystat][, AccStat][, ContextStat]\) TaskName [\([; due: DateCode]\
-//!     [; prior: uint][; *Link]\)]
+//!     [; done: DateCode][; prior: uint][; rept: (D|W)[<N>]][; deps: Ref[, Ref]*][; tags: Tag[, Tag]*]\
+//!     [; *Link][; #Id]\)]
 //!         - TaskFlags: Specifies the state of the Task. Refer to struct `TaskFlags.`
 //!         - \([Daystat][, AccStat][, ContextStat]\):
 //!             - Refer to `super::stat::Stat`. DayStat specifies the count for today,
@@ -23,12 +22,25 @@
 //!               - ContextStat = accum(AccStat) + accum(child.AccStat) for each child
 //!         - TaskName: Arbitrary text. Can include any arbitrary symbols except for parenthesis.
 //!         That would count as the suffix descriptor. [^1].
+//!             - Words of the form +tag and @context are tags of the task, as in todo.txt. A tag
+//!               is a letter followed by letters, digits, '_' and '-'.
 //!         - [; due: Datecode]: When is this Task due? Refer to `super::date::Date.`
 //!             - Keep in mind that the ';' is optional if this is the first entry in the task
 //!             post-meta.
+//!         - [; done: DateCode]: When the Task was done.
 //!         - [; prior: uint]: Specifies priority of the task. 0-99, where 0 is most important and
 //!                           99 is no priority.
+//!         - [; rept: (D|W)[<N>]]: The task repeats every N days (D) or weeks (W). N defaults to 1.
+//!         - [; deps: Ref[, Ref]*]: The tasks that must be done before this one can be worked on.
+//!           Each is referenced by its path, the names of its sections and parent tasks down to
+//!           its own joined with '/', as in "Report/Outline". Any trailing part of the path will
+//!           do, so long as it names a single task. A task with an id may be referenced as #Id.
+//!           Refer to `crate::ttm_core::deps`.
+//!         - [; tags: Tag[, Tag]*]: Tags of the task that are not written in its name. Tags
+//!           without a + or @ are taken as +tags.
 //!         - [; *Link]: Links this task to a note.
+//!         - [; #Id]: A stable identifier of the task, kept as its name changes. Refer to
+//!           `super::ids`.
 //!
 //! Previous Iterations of Task metainformation
 //!     07-Jan-21
@@ -36,55 +48,26 @@
 //!         - Used to include a lot of information prefix to the task, but this really hurt
 //!           readability. I also didn't find use for "hard" deadlines, custom stats, or
 //!           specifying that a task will repeat as of now
+//!         - Files in this format are rewritten to the current one by `super::legacy`.
 //!
 //! Footnotes
 //! [^1]: [*ttm_io/tasks/SuffixDescriptor]   Information at the end of the task.
 
 use super::date;
 use super::stat;
-use regex::Regex;
+use crate::utils::scanner::{FromNext, ScanError, StrScanner};
 use std::borrow::Cow;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, BTreeSet};
 use std::fmt;
 
 use stat::Stat;
 
-/// implementation of the task token regex. Must conform to the requirements of
-/// [*ttm_io/tasks_TaskRegex].
-pub mod task_parser_regex {
-    use super::*;
-    lazy_static! {
-        pub static ref STAT_PAIR: String = format!(r"((?x) ^ (      # [Stat][,Stat]
-            ((?P<Left0> {STAT}))
-            | (,(?P<Right1> {STAT}))
-            | ((?P<Left2> {STAT})\s* ,(?P<Right2> {STAT}))
-        )$)", STAT=stat::stat_parser_regex::STAT.as_str());
-        pub static ref TASK: String = format!(r"((?x) ^ (
-            (?P<EntryFlags> [>~BL0-9])                              # Flags about the task
-            \(
-            (?P<DayAccStats> {STAT_PAIR})                           # [DayStat][,AccStat]
-            (; (?P<DoneDateCode> {DATE_CODE} )?                     # [; DoneDateCode]
-            (; (?P<KeyValuePairs> ((due|hard): {DATE_CODE})*) )?    # [; key:val]*
-            (; (?P<KeyValuePairs> (\w+: .*)*) )?                    # [; key:val]*
-            ;?\)
-            (?P<Name> .*)
-        )$)", /*STAT=stat::stat_parser_regex::STAT.as_str(),*/
-              DATE_CODE=r".*",
-              STAT_PAIR=STAT_PAIR.as_str(),
-        );
-
-        // compiled regex
-        pub static ref TASK_RE: Regex = Regex::new(&TASK).unwrap();
-        pub static ref STAT_PAIR_RE: Regex = Regex::new(&STAT_PAIR).unwrap();
-    }
-}
-
 bitflags! {
-    struct TaskFlags: u32 {
+    pub struct TaskFlags: u32 {
         const BLOCKED = 0b00000001;
         const CURRENT = 0b00000010;
-        const LATE    = 0b00000011;
         const DONE    = 0b00000100;
+        const LATE    = 0b00001000;
     }
 }
 
@@ -110,6 +93,151 @@ impl std::str::FromStr for TaskFlags {
     }
 }
 
+impl FromNext for TaskFlags {
+    type Err = ScanError;
+
+    /// parses the flags at the start of `s`, stopping at the first character that is not one
+    fn next(s: &str) -> Result<(usize, Self), Self::Err> {
+        let mut out = Self::empty();
+        for (i, c) in s.char_indices() {
+            match c {
+                'B' => out.insert(TaskFlags::BLOCKED),
+                '>' => out.insert(TaskFlags::CURRENT),
+                'L' => out.insert(TaskFlags::LATE),
+                '~' => out.insert(TaskFlags::DONE),
+                _ => return Ok((i, out)),
+            }
+        }
+        Ok((s.len(), out))
+    }
+}
+
+/// the start of a task: its flags and the stats in parentheses after them, as in ">(1/4, 2/8)"
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub struct TaskPrefix {
+    pub flags: TaskFlags,
+    /// DayStat, AccStat and ContextStat
+    pub stats: [Option<Stat>; 3],
+}
+
+impl FromNext for TaskPrefix {
+    type Err = ScanError;
+
+    fn next(s: &str) -> Result<(usize, Self), Self::Err> {
+        let mut scan = StrScanner::create(s);
+        scan.skip_whitespace();
+        let flags = scan.next::<TaskFlags>()?;
+        scan.skip_whitespace();
+        scan.expect("(").or(Err(ScanError::new(scan.cur, "a task flag or '('")))?;
+        let stats = next_stat_tuple(&mut scan)?;
+        scan.expect(")")?;
+        Ok((scan.cur, TaskPrefix { flags, stats }))
+    }
+}
+
+/// names of the flags in serialized formats such as JSON, and in `crate::config`
+pub const FLAG_NAMES: [(TaskFlags, &str); 4] = [
+    (TaskFlags::BLOCKED, "blocked"),
+    (TaskFlags::CURRENT, "current"),
+    (TaskFlags::DONE, "done"),
+    (TaskFlags::LATE, "late"),
+];
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for TaskFlags {
+    /// serializes the flags as a list of flag names
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let names: Vec<&str> = FLAG_NAMES
+            .iter()
+            .filter(|(flag, _name)| self.contains(*flag))
+            .map(|(_flag, name)| *name)
+            .collect();
+        serde::Serialize::serialize(&names, serializer)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for TaskFlags {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let names: Vec<String> = serde::Deserialize::deserialize(deserializer)?;
+        let mut out = TaskFlags::empty();
+        for name in names.iter() {
+            match FLAG_NAMES.iter().find(|(_flag, flag_name)| flag_name == name) {
+                Some((flag, _name)) => out.insert(*flag),
+                None => {
+                    return Err(serde::de::Error::custom(format!("unknown task flag '{}'", name)))
+                }
+            }
+        }
+        Ok(out)
+    }
+}
+
+impl fmt::Display for TaskFlags {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let flag_chars = [
+            (TaskFlags::DONE, '~'),
+            (TaskFlags::CURRENT, '>'),
+            (TaskFlags::BLOCKED, 'B'),
+            (TaskFlags::LATE, 'L'),
+        ];
+        for &(flag, c) in flag_chars.iter() {
+            if self.contains(flag) {
+                write!(f, "{}", c)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// how often a task repeats
+#[derive(Debug, PartialEq, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
+pub enum Repeat {
+    /// every N days
+    Daily(u32),
+    /// every N weeks
+    Weekly(u32),
+}
+
+impl std::str::FromStr for Repeat {
+    type Err = Cow<'static, str>;
+
+    /// parses (D|W)[<N>], like D for every day or W2 for every other week
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let interval = match &s.get(1..) {
+            Some("") => 1,
+            Some(n) => match n.parse::<u32>() {
+                Ok(n) if n > 0 => n,
+                _ => return Err(format!("invalid repeat interval '{}'", n).into()),
+            },
+            None => return Err("a repeat must start with D or W".into()),
+        };
+
+        match &s[..1] {
+            "D" => Ok(Repeat::Daily(interval)),
+            "W" => Ok(Repeat::Weekly(interval)),
+            _ => Err(format!("a repeat must start with D or W, found '{}'", s).into()),
+        }
+    }
+}
+
+impl fmt::Display for Repeat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (unit, interval) = match *self {
+            Repeat::Daily(interval) => ("D", interval),
+            Repeat::Weekly(interval) => ("W", interval),
+        };
+        if interval == 1 {
+            write!(f, "{}", unit)
+        } else {
+            write!(f, "{}{}", unit, interval)
+        }
+    }
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum TaskParseError {
     NoTaskDescriptorsFound,
@@ -120,6 +248,11 @@ pub enum TaskParseError {
     InvalidPriorityValue,
     InvalidDueDate(Cow<'static, str>),
     InvalidHardDate(Cow<'static, str>),
+    InvalidDoneDate(Cow<'static, str>),
+    InvalidRepeat(Cow<'static, str>),
+    InvalidDeps(Cow<'static, str>),
+    InvalidId(Cow<'static, str>),
+    InvalidTags(Cow<'static, str>),
     UnsupportedDescriptorKey {
         key: Cow<'static, str>,
         field: Cow<'static, str>,
@@ -144,6 +277,21 @@ impl fmt::Display for TaskParseError {
             Self::InvalidHardDate(message) => {
                 format!("Failed to parse hard date descriptor field: {}", message).into()
             }
+            Self::InvalidDoneDate(message) => {
+                format!("Failed to parse done date descriptor field: {}", message).into()
+            }
+            Self::InvalidRepeat(message) => {
+                format!("Failed to parse rept descriptor field: {}", message).into()
+            }
+            Self::InvalidDeps(message) => {
+                format!("Failed to parse deps descriptor field: {}", message).into()
+            }
+            Self::InvalidId(field) => {
+                format!("{} is not an id: ids are letters, digits, '-' and '_'", field).into()
+            }
+            Self::InvalidTags(message) => {
+                format!("Failed to parse tags descriptor field: {}", message).into()
+            }
             Self::UnsupportedDescriptorKey { key, field } => {
                 format!("unsupported keyword argument {} in {}", key, field).into()
             }
@@ -154,9 +302,11 @@ impl fmt::Display for TaskParseError {
     }
 }
 
-#[derive(PartialEq, Debug)]
+#[derive(PartialEq, Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Task {
     /// describes the current state of the task
+    #[cfg_attr(feature = "serde", serde(default = "TaskFlags::empty"))]
     flags: TaskFlags,
     /// how many units of time were accomplished in this task today
     day_stat: Option<Stat>,
@@ -167,22 +317,38 @@ pub struct Task {
     /// describes the task to be accomplished
     name: String,
     /// a pattern that could be searched to a note
+    #[cfg_attr(feature = "serde", serde(default))]
     note_link: String,
     /// allows tasks to be sorted by importance
+    #[cfg_attr(feature = "serde", serde(default = "Task::no_priority"))]
     priority: usize,
     /// the task's deadline, when it is expected to be done
     due_date: Option<date::Date>,
     /// hard deadline, could be bad to miss
     hard_date: Option<date::Date>,
+    /// when the task was done
+    done_date: Option<date::Date>,
+    /// how often the task repeats, if it does
+    repeat: Option<Repeat>,
+    /// stable identifier of the task, unique within its document
+    #[cfg_attr(feature = "serde", serde(default))]
+    id: Option<String>,
+    /// the +tags and @contexts of the task, from its name and its tags field
+    #[cfg_attr(feature = "serde", serde(default))]
+    tags: BTreeSet<String>,
+    /// references to the tasks this task depends on, as written
+    #[cfg_attr(feature = "serde", serde(default))]
+    deps: Vec<String>,
     /// custom counters used in the task to track progress
-    other_stats: HashMap<String, [Option<Stat>; 3]>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    other_stats: BTreeMap<String, [Option<Stat>; 3]>,
 }
 
 impl Task {
     /// maximum value allowed for task priority. Lower is more important.
-    const NO_PRIORITY: usize = 99;
+    pub const NO_PRIORITY: usize = 99;
 
-    fn from_name_and_stats(name: &str, stats: (Option<Stat>, Option<Stat>, Option<Stat>)) -> Self {
+    pub fn from_name_and_stats(name: &str, stats: (Option<Stat>, Option<Stat>, Option<Stat>)) -> Self {
         Self {
             flags: TaskFlags::empty(),
             day_stat: stats.0,
@@ -193,29 +359,161 @@ impl Task {
             priority: Task::NO_PRIORITY,
             due_date: None,
             hard_date: None,
-            other_stats: HashMap::new(),
+            done_date: None,
+            repeat: None,
+            id: None,
+            tags: name_tags(name).map(|tag| tag.to_string()).collect(),
+            deps: vec![],
+            other_stats: BTreeMap::new(),
         }
     }
 
-    fn from_name(name: &str) -> Self {
+    pub fn from_name(name: &str) -> Self {
         Self::from_name_and_stats(name, (None, None, None))
     }
 
-    fn build(self) -> Self {
+    fn no_priority() -> usize {
+        Task::NO_PRIORITY
+    }
+
+    pub fn flags(&self) -> TaskFlags {
+        self.flags
+    }
+
+    pub fn day_stat(&self) -> Option<Stat> {
+        self.day_stat
+    }
+
+    pub fn accum_stat(&self) -> Option<Stat> {
+        self.accum_stat
+    }
+
+    pub fn context_stat(&self) -> Option<Stat> {
+        self.context_stat
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn note_link(&self) -> &str {
+        &self.note_link
+    }
+
+    pub fn priority(&self) -> usize {
+        self.priority
+    }
+
+    pub fn due_date(&self) -> Option<date::Date> {
+        self.due_date
+    }
+
+    pub fn hard_date(&self) -> Option<date::Date> {
+        self.hard_date
+    }
+
+    pub fn done_date(&self) -> Option<date::Date> {
+        self.done_date
+    }
+
+    pub fn repeat(&self) -> Option<Repeat> {
+        self.repeat
+    }
+
+    pub fn id(&self) -> Option<&str> {
+        self.id.as_deref()
+    }
+
+    /// the +tags and @contexts of the task, written with their + or @
+    pub fn tags(&self) -> &BTreeSet<String> {
+        &self.tags
+    }
+
+    pub fn has_tag(&self, tag: &str) -> bool {
+        self.tags.contains(tag)
+    }
+
+    pub fn deps(&self) -> &[String] {
+        &self.deps
+    }
+
+    pub fn other_stats(&self) -> &BTreeMap<String, [Option<Stat>; 3]> {
+        &self.other_stats
+    }
+
+    /// the stat tracking the progress of the task. The lifetime count is used when present,
+    /// otherwise the count for today.
+    pub fn progress_stat(&self) -> Option<Stat> {
+        self.accum_stat.or(self.day_stat)
+    }
+
+    /// how many expected blocks of time are left in the task. The lifetime count is used when
+    /// present, otherwise the count for today.
+    pub fn remaining_blocks(&self) -> Option<i32> {
+        self.accum_stat
+            .and_then(|stat| stat.remaining())
+            .or_else(|| self.day_stat.and_then(|stat| stat.remaining()))
+    }
+
+    /// sets the flags of the task. A task becoming DONE is stamped with `today` as its done date,
+    /// unless it has one, and a task that is no longer DONE loses its done date.
+    pub fn set_flags(&mut self, flags: TaskFlags, today: date::Date) {
+        if flags.contains(TaskFlags::DONE) && self.done_date.is_none() {
+            self.done_date = Some(today);
+        } else if !flags.contains(TaskFlags::DONE) {
+            self.done_date = None;
+        }
+        self.flags = flags;
+    }
+
+    /// marks the task DONE on `today`. Done tasks are no longer CURRENT.
+    pub fn mark_done(&mut self, today: date::Date) {
+        self.set_flags((self.flags | TaskFlags::DONE) - TaskFlags::CURRENT, today);
+    }
+
+    /// checks that a done date is only given to DONE tasks, and is not after the due date. Short
+    /// date codes are taken to be in `year` and `season`.
+    pub fn check_done_date(&self, year: u32, season: date::Season) -> Result<(), Cow<'static, str>> {
+        let done_date = match self.done_date {
+            Some(done_date) => done_date,
+            None => return Ok(()),
+        };
+        if !self.flags.contains(TaskFlags::DONE) {
+            return Err(format!("'{}' has a done date but is not done", self.name).into());
+        }
+        match self.due_date {
+            Some(due_date) if done_date.ordinal_key(year, season) > due_date.ordinal_key(year, season) => {
+                Err(format!("'{}' was done on {}, after its due date {}", self.name, done_date, due_date).into())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    pub fn build(self) -> Self {
         self
     }
 
-    fn build_flags(&mut self, flags: TaskFlags) -> &mut Self {
+    pub fn build_flags(&mut self, flags: TaskFlags) -> &mut Self {
         self.flags = flags;
         self
     }
 
-    fn build_note_link(&mut self, note_link: &str) -> &mut Self {
+    pub fn build_day_stat(&mut self, day_stat: Stat) -> &mut Self {
+        self.day_stat = Some(day_stat);
+        self
+    }
+
+    pub fn build_context_stat(&mut self, context_stat: Stat) -> &mut Self {
+        self.context_stat = Some(context_stat);
+        self
+    }
+
+    pub fn build_note_link(&mut self, note_link: &str) -> &mut Self {
         self.note_link = note_link.to_string();
         self
     }
 
-    fn build_priority(&mut self, priority: usize) -> &mut Self {
+    pub fn build_priority(&mut self, priority: usize) -> &mut Self {
         if priority > Task::NO_PRIORITY {
             panic!(format!(
                 "Task Priority cannot exceed max value of {}",
@@ -226,17 +524,54 @@ impl Task {
         self
     }
 
-    fn build_due_date(&mut self, due_date: date::Date) -> &mut Self {
+    pub fn build_due_date(&mut self, due_date: date::Date) -> &mut Self {
         self.due_date = Some(due_date);
         self
     }
 
-    fn build_hard_date(&mut self, hard_date: date::Date) -> &mut Self {
+    pub fn build_hard_date(&mut self, hard_date: date::Date) -> &mut Self {
         self.hard_date = Some(hard_date);
         self
     }
 
-    fn build_other_stat(&mut self, goal: &str, stats: [Option<Stat>; 3]) -> &mut Self {
+    pub fn build_done_date(&mut self, done_date: date::Date) -> &mut Self {
+        self.done_date = Some(done_date);
+        self
+    }
+
+    pub fn build_repeat(&mut self, repeat: Repeat) -> &mut Self {
+        self.repeat = Some(repeat);
+        self
+    }
+
+    pub fn build_name(&mut self, name: &str) -> &mut Self {
+        self.name = name.to_string();
+        self
+    }
+
+    pub fn build_id(&mut self, id: &str) -> &mut Self {
+        self.id = Some(id.to_string());
+        self
+    }
+
+    /// adds `tag`, written with its + or @
+    pub fn build_tag(&mut self, tag: &str) -> &mut Self {
+        self.tags.insert(tag.to_string());
+        self
+    }
+
+    /// adds the tags written in the name of the task, as parsing the task does
+    pub fn build_name_tags(&mut self) -> &mut Self {
+        self.tags.extend(name_tags(&self.name).map(|tag| tag.to_string()));
+        self
+    }
+
+    pub fn build_dep(&mut self, dep: &str) -> &mut Self {
+        self.deps.push(dep.to_string());
+        self
+    }
+
+    pub fn build_other_stat(&mut self, goal: &str, stats: [Option<Stat>; 3]) -> &mut Self {
         self.other_stats.insert(goal.to_string(), stats);
         self
     }
@@ -247,21 +582,29 @@ impl std::str::FromStr for Task {
 
     /// parses a Task entry of the form specified in [*ttm_io/tasks/TaskRegex]
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        // identify prefix descriptor range
-        let idx_prefix_open_paren: Option<usize> = s.find('(');
-        let idx_prefix_closed_paren: Option<usize> = s.find(')');
-        if !(idx_prefix_open_paren.is_some() && idx_prefix_closed_paren.is_some()) {
-            // All valid tasks must contain metainformation, even if it's empty
-            return Err(TaskParseError::NoTaskDescriptorsFound);
-        }
-        let idx_prefix_open_paren: usize = idx_prefix_open_paren.unwrap();
-        let idx_prefix_closed_paren: usize = idx_prefix_closed_paren.unwrap();
+        // All valid tasks must contain metainformation, even if it's empty
+        let idx_prefix_open_paren: usize = match (s.find('('), s.find(')')) {
+            (Some(open), Some(_)) => open,
+            _ => return Err(TaskParseError::NoTaskDescriptorsFound),
+        };
+
+        // parse task flags and prefix descriptor
+        let mut scan = StrScanner::create(s);
+        let prefix = match scan.next::<TaskPrefix>() {
+            Ok(prefix) => prefix,
+            Err(e) if e.at < idx_prefix_open_paren => return Err(TaskParseError::InvalidTaskFlags(e.to_string().into())),
+            Err(e) => return Err(TaskParseError::InvalidPrefixDescriptor(e.to_string().into())),
+        };
 
-        let s_after_prefix: &str = &s[idx_prefix_closed_paren + 1..].trim();
+        let s_after_prefix: &str = s[scan.cur..].trim();
 
-        // identify suffix descriptor range, if found
+        // identify suffix descriptor range, if found. The name may hold a ')' before it, as in an
+        // emoticon, so the closing parenthesis is looked for after the opening one.
         let idx_suffix_open_paren: Option<usize> = s_after_prefix.find('(');
-        let idx_suffix_close_paren: Option<usize> = s_after_prefix.find(')');
+        let idx_suffix_close_paren: Option<usize> = match idx_suffix_open_paren {
+            Some(open) => s_after_prefix[open..].find(')').map(|idx| open + idx),
+            None => s_after_prefix.find(')'),
+        };
 
         if !(idx_suffix_open_paren.is_some() && idx_suffix_close_paren.is_some())
             && !(idx_suffix_open_paren.is_none() && idx_suffix_close_paren.is_none())
@@ -280,23 +623,10 @@ impl std::str::FromStr for Task {
 
         // create a task state to populate
         let mut res = Task::from_name(task_name);
-
-        // parse task flags
-        let task_flags: TaskFlags = match (&s[..idx_prefix_open_paren]).parse() {
-            Ok(res) => res,
-            Err(e) => return Err(TaskParseError::InvalidTaskFlags(e)),
-        };
-        res.flags = task_flags;
-
-        // parse prefix descriptor
-        let prefix_stats =
-            match parse_stat_tuple(&s[idx_prefix_open_paren + 1..idx_prefix_closed_paren]) {
-                Ok(stats) => stats,
-                Err(msg) => return Err(TaskParseError::InvalidPrefixDescriptor(msg)),
-            };
-        res.day_stat = prefix_stats[0];
-        res.accum_stat = prefix_stats[1];
-        res.context_stat = prefix_stats[2];
+        res.flags = prefix.flags;
+        res.day_stat = prefix.stats[0];
+        res.accum_stat = prefix.stats[1];
+        res.context_stat = prefix.stats[2];
 
         // parse suffix descriptor
         if idx_suffix_open_paren.is_some() {
@@ -320,11 +650,16 @@ impl std::str::FromStr for Task {
                     if field.starts_with('*') {
                         // parse note link, skip '*'
                         res.note_link = (&field[1..]).to_string();
+                    } else if let Some(id) = field.strip_prefix('#') {
+                        if !super::ids::is_valid_id(id) {
+                            return Err(TaskParseError::InvalidId(field.to_owned().into()));
+                        }
+                        res.id = Some(id.to_string());
                     }
                 } else {
                     // "key: value" fields
                     let key = (&field[..idx_colon.unwrap()]).to_string();
-                    let val = (&field[idx_colon.unwrap() + 1..]).to_string();
+                    let val = (&field[idx_colon.unwrap() + 1..]).trim().to_string();
                     // println!("key {}, val {}", key, val);
 
                     if key == "due" {
@@ -337,13 +672,34 @@ impl std::str::FromStr for Task {
                             Ok(date) => date,
                             Err(msg) => return Err(TaskParseError::InvalidHardDate(msg)),
                         });
+                    } else if key == "done" {
+                        res.done_date = Some(match val.parse::<date::Date>() {
+                            Ok(date) => date,
+                            Err(msg) => return Err(TaskParseError::InvalidDoneDate(msg)),
+                        });
                     } else if key == "prior" {
                         res.priority = match val.parse::<usize>() {
                             Ok(prior) => prior,
                             Err(_e) => return Err(TaskParseError::InvalidPriorityValue),
                         };
                     } else if key == "rept" {
-                        unimplemented!("rept kwarg not supported yet");
+                        res.repeat = Some(match val.parse::<Repeat>() {
+                            Ok(repeat) => repeat,
+                            Err(msg) => return Err(TaskParseError::InvalidRepeat(msg)),
+                        });
+                    } else if key == "deps" {
+                        res.deps = match parse_deps(&val) {
+                            Ok(deps) => deps,
+                            Err(msg) => return Err(TaskParseError::InvalidDeps(msg)),
+                        };
+                    } else if key == "tags" {
+                        for tag in val.split(|c: char| c == ',' || c.is_whitespace()).filter(|tag| !tag.is_empty()) {
+                            let tag = if is_tag(tag) { tag.to_string() } else { format!("+{}", tag) };
+                            if !is_tag(&tag) {
+                                return Err(TaskParseError::InvalidTags(format!("'{}' is not a tag", tag).into()));
+                            }
+                            res.tags.insert(tag);
+                        }
                     } else if key.starts_with("g") {
                         let other_stats = match parse_stat_tuple(&val.trim()) {
                             Ok(stats) => stats,
@@ -366,6 +722,94 @@ impl std::str::FromStr for Task {
     }
 }
 
+impl fmt::Display for Task {
+    /// writes the task in the format specified in [*ttm_io/tasks/TaskRegex]. Empty stats and
+    /// suffix fields are omitted.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let stats = [self.day_stat, self.accum_stat, self.context_stat];
+        write!(f, "{}({}) {}", self.flags, format_stat_tuple(&stats), self.name)?;
+
+        let fields = self.suffix_fields();
+        if !fields.is_empty() {
+            write!(f, " ({})", fields.join("; "))?;
+        }
+        Ok(())
+    }
+}
+
+impl Task {
+    /// the fields of the suffix descriptor, in the order they are written
+    pub(crate) fn suffix_fields(&self) -> Vec<String> {
+        let mut fields: Vec<String> = vec![];
+        if let Some(due_date) = self.due_date {
+            fields.push(format!("due: {}", due_date));
+        }
+        if let Some(hard_date) = self.hard_date {
+            fields.push(format!("hard: {}", hard_date));
+        }
+        if let Some(done_date) = self.done_date {
+            fields.push(format!("done: {}", done_date));
+        }
+        if self.priority != Task::NO_PRIORITY {
+            fields.push(format!("prior: {}", self.priority));
+        }
+        if let Some(repeat) = self.repeat {
+            fields.push(format!("rept: {}", repeat));
+        }
+        if !self.deps.is_empty() {
+            fields.push(format!("deps: {}", self.deps.join(", ")));
+        }
+        // tags written in the name are not repeated
+        let in_name: BTreeSet<&str> = name_tags(&self.name).collect();
+        let tags: Vec<&str> = self.tags.iter().map(|tag| tag.as_str()).filter(|tag| !in_name.contains(tag)).collect();
+        if !tags.is_empty() {
+            fields.push(format!("tags: {}", tags.join(", ")));
+        }
+        for (goal, stats) in self.other_stats.iter() {
+            fields.push(format!("g{}: {}", goal, format_stat_tuple(stats)));
+        }
+        if !self.note_link.is_empty() {
+            fields.push(format!("*{}", self.note_link));
+        }
+        if let Some(id) = &self.id {
+            fields.push(format!("#{}", id));
+        }
+        fields
+    }
+}
+
+/// whether `s` is a +tag or an @context
+pub fn is_tag(s: &str) -> bool {
+    let mut chars = s.chars();
+    matches!(chars.next(), Some('+') | Some('@'))
+        && chars.next().is_some_and(|c| c.is_alphabetic())
+        && chars.all(|c| c.is_alphanumeric() || c == '_' || c == '-')
+}
+
+/// the tags written in the name of a task
+fn name_tags(name: &str) -> impl Iterator<Item = &str> {
+    name.split_whitespace().filter(|word| is_tag(word))
+}
+
+/// parses the task references of a deps field, separated by commas. Spaces around each part of
+/// a path are dropped.
+fn parse_deps(s: &str) -> Result<Vec<String>, Cow<'static, str>> {
+    s.split(',')
+        .map(|dep| {
+            let parts: Vec<&str> = dep.split('/').map(|part| part.trim()).collect();
+            if let Some(id) = dep.trim().strip_prefix('#') {
+                if !super::ids::is_valid_id(id) {
+                    return Err(format!("'{}' is not an id", dep.trim()).into());
+                }
+            }
+            if parts.iter().any(|part| part.is_empty()) {
+                return Err(format!("'{}' does not reference a task", dep.trim()).into());
+            }
+            Ok(parts.join("/"))
+        })
+        .collect()
+}
+
 /// takes a string of the format (A; B; C; ...; K1: V1; K2; V2)
 /// and extracts it into a list of the diffrent fields [A, B, C, K1: V1, K2: V2]
 fn parse_tuple_arguments(tup: &str) -> Result<Vec<String>, &'static str> {
@@ -414,48 +858,59 @@ fn parse_tuple_arguments(tup: &str) -> Result<Vec<String>, &'static str> {
 ///
 /// # Examples
 ///
-/// ```
-/// assert_eq!(parse_stat_tuple("0,0,0", Ok((
-///     Some(&Stat::Count {act: Some(0), exp: None}),
-///     Some(&Stat::Count {act: Some(0), exp: None}),
-///     Some(&Stat::Count {act: Some(0), exp: None}),
-/// )));
-/// assert_eq!(parse_stat_tuple("0/15", Ok((
-///     Some(&Stat::Count {act: Some(0), exp: Some(15)}),
-///     None,
-///     None,
-/// )));
-/// assert_eq!(parse_stat_tuple("", Ok((
-///     None,
-///     None,
-///     None,
-/// )));
+/// ```ignore
+/// assert_eq!(parse_stat_tuple("0,0,0"), Ok([Some(Stat::from_count(Some(0), None)); 3]));
+/// assert_eq!(parse_stat_tuple("0/15"), Ok([Some(Stat::from_count(Some(0), Some(15))), None, None]));
+/// assert_eq!(parse_stat_tuple(""), Ok([None, None, None]));
 /// ```
 ///
-fn parse_stat_tuple(s: &str) -> Result<[Option<Stat>; 3], Cow<'static, str>> {
-    let mut stats: [Option<Stat>; 3] = [None; 3];
+pub(crate) fn parse_stat_tuple(s: &str) -> Result<[Option<Stat>; 3], Cow<'static, str>> {
+    let mut scan = StrScanner::create(s);
+    let stats = next_stat_tuple(&mut scan).map_err(|e| e.to_string())?;
+    if !scan.is_done() {
+        return Err(ScanError::new(scan.cur, "',' or the end of the stats").to_string().into());
+    }
+    Ok(stats)
+}
 
-    for (i, token) in s.split(",").enumerate() {
-        let token = token.trim();
+/// scans a stat tuple as parsed by `parse_stat_tuple`, up to the first character that cannot
+/// follow a stat
+fn next_stat_tuple(scan: &mut StrScanner) -> Result<[Option<Stat>; 3], ScanError> {
+    let mut stats: [Option<Stat>; 3] = [None; 3];
 
-        // if no tokens were found, it iterates over "" once
-        if token == "" {
-            continue;
+    let mut i = 0;
+    loop {
+        scan.skip_whitespace();
+        let at = scan.cur;
+        // stats may be left empty
+        if !matches!(scan.peek_char(), Ok((_, ',')) | Ok((_, ')')) | Err(())) {
+            if i > 2 {
+                // tuple should be of max size 3
+                return Err(ScanError::new(at, "at most 3 stats"));
+            }
+            stats[i] = Some(scan.next::<Stat>().map_err(|e| e.after(at))?);
+            scan.skip_whitespace();
         }
-
-        if i > 2 {
-            // tuple should be of max size 3
-            return Err("Exceeded number of Stats allowed".into());
+        if scan.match_next(",").is_err() {
+            break;
         }
-        stats[i] = match token.parse() {
-            Ok(stat) => Some(stat),
-            Err(msg) => return Err(msg),
-        };
+        i += 1;
     }
 
     Ok(stats)
 }
 
+/// writes a stat tuple in the format parsed by `parse_stat_tuple`. Trailing empty stats are
+/// omitted.
+pub(crate) fn format_stat_tuple(stats: &[Option<Stat>; 3]) -> String {
+    let len = stats.iter().rposition(|stat| stat.is_some()).map_or(0, |i| i + 1);
+    stats[..len]
+        .iter()
+        .map(|stat| stat.map_or("".to_string(), |stat| stat.to_string()))
+        .collect::<Vec<String>>()
+        .join(", ")
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -547,6 +1002,11 @@ mod tests {
             Task::from_name_and_stats("Very Complete... Very Late!", (None, None, None))
                 .build_flags(TaskFlags::DONE | TaskFlags::LATE),
         );
+
+        let flags: TaskFlags = ">B".parse().unwrap();
+        assert!(!flags.contains(TaskFlags::LATE));
+        let flags: TaskFlags = "L".parse().unwrap();
+        assert!(!flags.intersects(TaskFlags::BLOCKED | TaskFlags::CURRENT));
     }
 
     fn test_parse_suffix_deadlines() {
@@ -565,6 +1025,124 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_parse_suffix_priority() {
+        assert_parses_as(
+            "(0/3) Important Task (prior: 0; due: W2F)",
+            Task::from_name_and_stats(
+                "Important Task",
+                (Some(Stat::from_count(Some(0), Some(3))), None, None),
+            )
+            .build_priority(0)
+            .build_due_date("W2F".parse().unwrap()),
+        );
+        assert_fails_to_parse_as(
+            "(0/3) Important Task (prior: high)",
+            &TaskParseError::InvalidPriorityValue,
+        );
+    }
+
+    #[test]
+    fn test_parse_suffix_repeat() {
+        assert_parses_as(
+            "(0/1) Water plants (rept: D3)",
+            Task::from_name_and_stats("Water plants", (Some(Stat::from_count(Some(0), Some(1))), None, None))
+                .build_repeat(Repeat::Daily(3)),
+        );
+        assert_parses_as(
+            "() Weekly review (rept: W; due: W2U)",
+            Task::from_name("Weekly review")
+                .build_repeat(Repeat::Weekly(1))
+                .build_due_date("W2U".parse().unwrap()),
+        );
+        assert_fails_to_parse_as("() Task (rept: M)", &TaskParseError::InvalidRepeat("".into()));
+        assert_fails_to_parse_as("() Task (rept: D0)", &TaskParseError::InvalidRepeat("".into()));
+        assert_fails_to_parse_as("() Task (rept: )", &TaskParseError::InvalidRepeat("".into()));
+        assert_fails_to_parse_as("() Task (done: yesterday)", &TaskParseError::InvalidDoneDate("".into()));
+        assert_eq!(Repeat::Weekly(2).to_string(), "W2");
+        assert_eq!(Repeat::Daily(1).to_string(), "D");
+    }
+
+    #[test]
+    fn test_parse_suffix_deps() {
+        assert_parses_as(
+            "(0/2) Draft (deps: Outline, Report / Sources; due: W3R)",
+            Task::from_name_and_stats("Draft", (Some(Stat::from_count(Some(0), Some(2))), None, None))
+                .build_dep("Outline")
+                .build_dep("Report/Sources")
+                .build_due_date("W3R".parse().unwrap()),
+        );
+        assert_fails_to_parse_as("() Task (deps: A,, B)", &TaskParseError::InvalidDeps("".into()));
+        assert_fails_to_parse_as("() Task (deps: Report/)", &TaskParseError::InvalidDeps("".into()));
+        let task: Task = "() Draft (*Notes; deps: Outline; rept: D)".parse().unwrap();
+        assert_eq!(task.to_string(), "() Draft (rept: D; deps: Outline; *Notes)");
+    }
+
+    #[test]
+    fn test_tags() {
+        let task: Task = "() Call mom @phone +Family (tags: errand, @home)".parse().unwrap();
+        let tags: Vec<&str> = task.tags().iter().map(|tag| tag.as_str()).collect();
+        assert_eq!(tags, vec!["+Family", "+errand", "@home", "@phone"]);
+        assert!(task.has_tag("@phone"));
+        assert_eq!(task.to_string(), "() Call mom @phone +Family (tags: +errand, @home)");
+
+        // words that only start like tags are kept as they are
+        let task: Task = "() Do +1 more @ 5pm, email@work".parse().unwrap();
+        assert!(task.tags().is_empty());
+        assert_fails_to_parse_as("() Task (tags: +)", &TaskParseError::InvalidTags("".into()));
+        assert_fails_to_parse_as("() Task (tags: 1st)", &TaskParseError::InvalidTags("".into()));
+    }
+
+    #[test]
+    fn test_parse_suffix_id() {
+        assert_parses_as(
+            "() Draft (#k3x9qa; deps: #a1b2c3)",
+            Task::from_name("Draft").build_dep("#a1b2c3").build_id("k3x9qa"),
+        );
+        assert_fails_to_parse_as("() Task (#)", &TaskParseError::InvalidId("".into()));
+        assert_fails_to_parse_as("() Task (#a b)", &TaskParseError::InvalidId("".into()));
+        let task: Task = "() Draft (#k3x9qa; *P[Draft]; due: W3R)".parse().unwrap();
+        assert_eq!(task.id(), Some("k3x9qa"));
+        assert_eq!(task.to_string(), "() Draft (due: W3R; *P[Draft]; #k3x9qa)");
+    }
+
+    #[test]
+    fn test_parse_paren_in_name() {
+        assert_parses_as(
+            "() call Bob :) (due: W3R)",
+            Task::from_name("call Bob :)").build_due_date("W3R".parse().unwrap()),
+        );
+        let invalid = TaskParseError::InvalidSuffixDescriptor("".into());
+        assert_fails_to_parse_as("() call Bob :)", &invalid);
+        assert_fails_to_parse_as("() call Bob :) (due: W3R", &invalid);
+        assert_fails_to_parse_as("() a) b (", &invalid);
+
+        let (document, errors) = crate::ttm_io::document::Document::parse_lenient("() x) y (due: W3R)\n() z) (\n");
+        assert_eq!(errors.len(), 1);
+        assert_eq!(document.tasks().iter().map(|task| task.name()).collect::<Vec<_>>(), vec!["x) y"]);
+    }
+
+    #[test]
+    fn test_done_date() {
+        let today: date::Date = "Y21W-W3T".parse().unwrap();
+        let mut task: Task = ">(1/2) Task (due: W3R)".parse().unwrap();
+        task.mark_done(today);
+        assert_eq!(task.to_string(), "~(1/2) Task (due: W3R; done: Y21W-W3T)");
+        assert_eq!(task.check_done_date(21, date::Season::Winter), Ok(()));
+
+        // a task that is done already keeps its done date
+        task.set_flags(TaskFlags::DONE | TaskFlags::LATE, "Y21W-W4M".parse().unwrap());
+        assert_eq!(task.done_date(), Some(today));
+        task.set_flags(TaskFlags::empty(), today);
+        assert_eq!(task.done_date(), None);
+
+        let late: Task = "~() Task (due: W3R; done: W4M)".parse().unwrap();
+        assert_eq!(late.check_done_date(21, date::Season::Winter).unwrap_err(),
+            "'Task' was done on W4M, after its due date W3R");
+        let not_done: Task = "() Task (done: W2M)".parse().unwrap();
+        assert!(not_done.check_done_date(21, date::Season::Winter).is_err());
+    }
+
     #[test]
     fn test_parse_general() {
         assert_parses_as(
@@ -591,6 +1169,32 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_display() {
+        fn assert_displays_as(s: &str, exp: &str) {
+            let task: Task = s.parse().unwrap();
+            assert_eq!(task.to_string(), exp);
+            assert_parses_as(exp, &task);
+        }
+
+        assert_displays_as("() Task", "() Task");
+        assert_displays_as(">B(2/15) Current Blocked Task", ">B(2/15) Current Blocked Task");
+        assert_displays_as("~~LL(,,11/12) Complete!", "~L(, , 11/12) Complete!");
+        assert_displays_as("(2/15,10 ,40) Spacing", "(2/15, 10, 40) Spacing");
+        assert_displays_as(
+            ">(2/15) My Exercise Task! (*P[My note!]; gPushups: 0/10; due: W2M; gPlancks: -,0,0;)",
+            ">(2/15) My Exercise Task! (due: W2M; gPlancks: -, 0, 0; gPushups: 0/10; *P[My note!])",
+        );
+        assert_displays_as(
+            "() Deadlines (prior: 3; hard: Y21W-W4T; due: Y21W-W3U; rept: W2)",
+            "() Deadlines (due: Y21W-W3U; hard: Y21W-W4T; prior: 3; rept: W2)",
+        );
+        assert_displays_as(
+            "~() Finished (done: Y21W-W3T; due: Y21W-W3U)",
+            "~() Finished (due: Y21W-W3U; done: Y21W-W3T)",
+        );
+    }
+
     #[test]
     fn test_fn_parse_stat_pair() {
         assert_eq!(
@@ -632,6 +1236,28 @@ mod tests {
                 None
             ]
         );
+        assert_eq!(parse_stat_tuple(",, 1,").unwrap(), [None, None, Some(Stat::from_count(Some(1), None))]);
+        assert_eq!(parse_stat_tuple("1, 2, 3, 4").unwrap_err(), "expected at most 3 stats at 9");
+        assert_eq!(parse_stat_tuple("1; 2").unwrap_err(), "expected ',' or the end of the stats at 1");
+        assert_eq!(parse_stat_tuple("1, 2/x").unwrap_err(), "expected a count, '-' or '!' at 5");
+    }
+
+    #[test]
+    fn test_next_task_prefix() {
+        let s = ">B(1/4, , 3) Write the report (due: W3F)";
+        let (len, prefix) = TaskPrefix::next(s).unwrap();
+        assert_eq!(&s[len..], " Write the report (due: W3F)");
+        assert_eq!(prefix, TaskPrefix {
+            flags: TaskFlags::CURRENT | TaskFlags::BLOCKED,
+            stats: [Some(Stat::from_count(Some(1), Some(4))), None, Some(Stat::from_count(Some(3), None))],
+        });
+        assert_eq!(TaskPrefix::next("~ ()").unwrap(), (4, TaskPrefix { flags: TaskFlags::DONE, stats: [None; 3] }));
+
+        assert_eq!(TaskPrefix::next("X(1)"), Err(ScanError::new(0, "a task flag or '('")));
+        assert_eq!(TaskPrefix::next("(1, ?"), Err(ScanError::new(5, "')'")));
+        assert_eq!(TaskPrefix::next("(1, 2, /)"), Err(ScanError::new(8, "a count")));
+        assert_fails_to_parse_as("X(1) Task", &TaskParseError::InvalidTaskFlags("".into()));
+        assert_fails_to_parse_as("(1, 2/x) Task", &TaskParseError::InvalidPrefixDescriptor("".into()));
     }
 
     #[test]
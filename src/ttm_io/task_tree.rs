@@ -1,3 +1,313 @@
 //! developed by Mohammed Alzakariya (lanhikarixx@gmail.com)
-//! This module parses a tree of tasks 
-//! TODO implement module
+//! This module parses a tree of tasks
+//! A task tree is a task line followed by its subtasks, which are the task lines tabbed deeper
+//! than it. Subtasks may have their own subtasks:
+//!     (0/4) Write the report
+//!         ~(2/2) Outline
+//!         (0/2) Draft
+//!             (0) Introduction
+//! Lines of text and blank lines among the subtasks are kept in the tree as notes, so long as the
+//! tree goes on after them. The tree ends at the first line that is not tabbed deeper than its
+//! task, or that is another TTM element such as a section.
+
+#![allow(dead_code)]
+
+use super::block_tracker::BlockTrackerEntry;
+use super::document::{self, SourceLine, TextLine};
+use super::stat::Stat;
+use super::task::{Task, TaskParseError};
+use crate::utils::common::StrUtils;
+use crate::utils::scanner::{self, StrScanner};
+
+/// tabbing written for every level of a task tree or section
+pub const INDENT: &str = "\t";
+
+#[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TaskTree {
+    #[cfg_attr(feature = "serde", serde(flatten))]
+    task: Task,
+    #[cfg_attr(feature = "serde", serde(default))]
+    subtasks: Vec<TaskTree>,
+    /// lines of text among the subtasks
+    #[cfg_attr(feature = "serde", serde(default))]
+    notes: Vec<TreeNote>,
+    /// how many levels the task is tabbed deeper than its place, which is one level deeper than
+    /// its parent task
+    #[cfg_attr(feature = "serde", serde(default))]
+    depth: usize,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    line: SourceLine,
+}
+
+/// a line of text or a blank line among the subtasks of a task, tabbed `line.depth` levels deeper
+/// than the subtasks
+#[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TreeNote {
+    /// how many subtasks are written before the note
+    pub after: usize,
+    pub line: TextLine,
+}
+
+impl TaskTree {
+    pub fn new(task: Task, subtasks: Vec<TaskTree>) -> Self {
+        Self { task, subtasks, notes: vec![], depth: 0, line: SourceLine::default() }
+    }
+
+    pub fn task(&self) -> &Task {
+        &self.task
+    }
+
+    pub fn task_mut(&mut self) -> &mut Task {
+        &mut self.task
+    }
+
+    pub fn subtasks(&self) -> &[TaskTree] {
+        &self.subtasks
+    }
+
+    pub fn subtasks_mut(&mut self) -> &mut Vec<TaskTree> {
+        &mut self.subtasks
+    }
+
+    pub fn notes(&self) -> &[TreeNote] {
+        &self.notes
+    }
+
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    pub fn set_depth(&mut self, depth: usize) {
+        self.depth = depth;
+    }
+
+    /// the line of the task, or 0 if the tree was not read from a document. Trees read on their
+    /// own start at line 1.
+    pub fn line(&self) -> usize {
+        self.line.0
+    }
+
+    /// moves the lines of the tree read from line `from` on to line `to` on
+    pub(crate) fn move_lines(&mut self, from: usize, to: usize) {
+        self.line.0 = self.line.0 + to - from;
+        for subtask in self.subtasks.iter_mut() {
+            subtask.move_lines(from, to);
+        }
+    }
+
+    /// lists this tree and all of its subtrees in the order they are written, along with their
+    /// depth in the tree
+    pub fn walk(&self) -> Vec<(usize, &TaskTree)> {
+        let mut out = vec![(0, self)];
+        for subtask in self.subtasks.iter() {
+            out.extend(subtask.walk().into_iter().map(|(depth, tree)| (depth + 1, tree)));
+        }
+        out
+    }
+
+    /// computes the context count of the task: its lifetime count along with the context counts of
+    /// its subtasks. Tasks without a lifetime count use their count for today. Refer to
+    /// `super::task` for how ContextStat is defined.
+    pub fn computed_context_stat(&self) -> Stat {
+        let own = match self.task.accum_stat().or_else(|| self.task.day_stat()) {
+            Some(Stat::Count { act, exp }) => (act.unwrap_or(0), exp),
+            _ => (0, None),
+        };
+        let (act, exp) = self.subtasks.iter()
+            .map(|subtask| match subtask.computed_context_stat() {
+                Stat::Count { act, exp } => (act.unwrap_or(0), exp),
+                _ => (0, None),
+            })
+            .fold(own, |(act, exp), (sub_act, sub_exp)| {
+                (act + sub_act, match (exp, sub_exp) {
+                    (None, None) => None,
+                    (exp, sub_exp) => Some(exp.unwrap_or(0) + sub_exp.unwrap_or(0)),
+                })
+            });
+        Stat::from_count(Some(act), exp)
+    }
+
+    /// writes the tree with every line tabbed by `tab` and its own depth, and subtasks and notes
+    /// tabbed one `INDENT` deeper
+    pub fn render(&self, tab: &str) -> String {
+        let tab = format!("{}{}", tab, INDENT.repeat(self.depth));
+        let mut out = format!("{}{}\n", tab, self.task);
+        let subtask_tab = format!("{}{}", tab, INDENT);
+        let mut notes = self.notes.iter().peekable();
+        for (i, subtask) in self.subtasks.iter().enumerate() {
+            while let Some(note) = notes.next_if(|note| note.after <= i) {
+                document::render_text(&note.line, &subtask_tab, &mut out);
+            }
+            out.push_str(&subtask.render(&subtask_tab));
+        }
+        for note in notes {
+            document::render_text(&note.line, &subtask_tab, &mut out);
+        }
+        out
+    }
+
+    /// takes the subtasks and notes of `task`, whose line is tabbed by `tab`, from the lines that
+    /// follow it. Each line is parsed once.
+    fn with_subtasks(scan: &mut StrScanner, tab: &str, task: Task) -> Self {
+        let mut tree = Self::new(task, vec![]);
+        let level = StrUtils(tab).tab_level() + 1;
+        loop {
+            // blank lines are kept only if the tree goes on after them
+            let start = scan.cur;
+            let mut blanks = 0;
+            while let Ok((len, line)) = scan.peek_line() {
+                if !line.trim().is_empty() {
+                    break;
+                }
+                scan.advance(len);
+                blanks += 1;
+            }
+
+            let (len, line) = match scan.peek_line() {
+                Ok((len, line)) if StrUtils(&line).tab_columns() > StrUtils(tab).tab_columns() => (len, line),
+                _ => {
+                    scan.cur = start;
+                    break;
+                }
+            };
+            let subtask = line.trim().parse::<Task>();
+            if subtask.is_err() && !Self::is_note(line.trim()) {
+                scan.cur = start;
+                break;
+            }
+            for _ in 0..blanks {
+                tree.notes.push(TreeNote { after: tree.subtasks.len(), line: TextLine::default() });
+            }
+
+            let line_tab = StrUtils(&line).tabs();
+            let depth = StrUtils(line_tab).tab_level().saturating_sub(level);
+            let line_no = scan.stream[..scan.cur].matches('\n').count() + 1;
+            scan.advance(len);
+            match subtask {
+                Ok(subtask) => {
+                    let mut subtask = Self::with_subtasks(scan, line_tab, subtask);
+                    subtask.depth = depth;
+                    subtask.line = SourceLine(line_no);
+                    tree.subtasks.push(subtask);
+                }
+                Err(_) => tree.notes.push(TreeNote { after: tree.subtasks.len(), line: TextLine::new(line.trim(), depth) }),
+            }
+        }
+
+        tree
+    }
+
+    /// whether a line that is not a task is text a tree may hold. Other TTM elements end the tree,
+    /// and so do lines meant to be tasks that do not parse, so that they are reported where they
+    /// are.
+    fn is_note(trimmed: &str) -> bool {
+        !document::is_specifier_line(trimmed)
+            && !document::is_task_line(trimmed)
+            && trimmed.parse::<BlockTrackerEntry>().is_err()
+    }
+}
+
+impl scanner::FromNext for TaskTree {
+    type Err = TaskParseError;
+
+    /// parses a task line and all of the subtasks following it out of `s`
+    fn next(s: &str) -> Result<(usize, Self), Self::Err> {
+        let mut scan = StrScanner::create(s);
+        let task_line = scan.next_line().or(Err(TaskParseError::InvalidMatch))?;
+        let task: Task = task_line.trim().parse()?;
+        let mut tree = Self::with_subtasks(&mut scan, StrUtils(&task_line).tabs(), task);
+        tree.line = SourceLine(1);
+
+        Ok((scan.cur, tree))
+    }
+}
+
+impl std::fmt::Display for TaskTree {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.render(""))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::scanner::FromNext;
+
+    #[test]
+    fn test_parse_task_tree() {
+        let s = "(0/4) Write the report\n\
+                 \t~(2/2) Outline\n\
+                 \t(0/2) Draft\n\
+                 \t    (0) Introduction\n\
+                 \t(0) Review\n\
+                 (1) Not a subtask\n";
+        let (len, tree) = TaskTree::next(s).unwrap();
+
+        assert_eq!(&s[len..], "(1) Not a subtask\n");
+        let names: Vec<(usize, &str)> = tree.walk().iter()
+            .map(|(depth, tree)| (*depth, tree.task().name()))
+            .collect();
+        assert_eq!(names, vec![
+            (0, "Write the report"),
+            (1, "Outline"),
+            (1, "Draft"),
+            (2, "Introduction"),
+            (1, "Review"),
+        ]);
+    }
+
+    #[test]
+    fn test_tree_ends_at_non_task_line() {
+        let s = "(0) Parent\n\
+                 \t(1) Child\n\
+                 \tSome note about the child\n\
+                 \n\
+                 \t\t(2) Grandchild\n\
+                 \n\
+                 Not in the tree\n\
+                 \t(3) Not reached\n";
+        let (len, tree) = TaskTree::next(s).unwrap();
+
+        assert_eq!(tree.subtasks().len(), 2);
+        assert_eq!(tree.notes(), &[TreeNote { after: 1, line: "Some note about the child".into() }, TreeNote { after: 1, line: "".into() }]);
+        assert_eq!(tree.subtasks()[1].depth(), 1);
+        let lines: Vec<usize> = tree.walk().iter().map(|(_depth, tree)| tree.line()).collect();
+        assert_eq!(lines, vec![1, 2, 5]);
+        assert_eq!(&s[len..], "\nNot in the tree\n\t(3) Not reached\n");
+
+        // sections and lines meant to be tasks end the tree
+        for end in &["\t[Section]\n", "\t(1, 2, 3, 4) Not a task\n"] {
+            let s = format!("(0) Parent\n\tA note\n{}\t(1) Not reached\n", end);
+            let (len, tree) = TaskTree::next(&s).unwrap();
+            assert_eq!((tree.subtasks().len(), tree.notes().len()), (0, 1));
+            assert!(s[len..].starts_with(end));
+        }
+    }
+
+    #[test]
+    fn test_render() {
+        let s = "(0/4) Write the report\n    ~(2/2) Outline\n    (0/2) Draft\n        (0) Introduction\n";
+        let (_len, tree) = TaskTree::next(s).unwrap();
+
+        let rendered = tree.render("");
+        assert_eq!(rendered, "(0/4) Write the report\n\t~(2/2) Outline\n\t(0/2) Draft\n\t\t(0) Introduction\n");
+        assert_eq!(TaskTree::next(&rendered).unwrap().1, tree);
+    }
+
+    #[test]
+    fn test_computed_context_stat() {
+        let s = "(1, 2/10) Write the report\n\t(0/3) Outline\n\t\t(1) Notes\n\t(!) Review\n";
+        let (_len, tree) = TaskTree::next(s).unwrap();
+
+        assert_eq!(tree.computed_context_stat(), Stat::from_count(Some(3), Some(13)));
+        assert_eq!(tree.subtasks()[1].computed_context_stat(), Stat::from_count(Some(0), None));
+    }
+
+    #[test]
+    fn test_invalid_task() {
+        assert!(TaskTree::next("Not a task").is_err());
+        assert!(TaskTree::next("").is_err());
+    }
+}
@@ -0,0 +1,197 @@
+//! Imports todo.txt files. Each line of the file is a task:
+//!     x (A) 2021-01-14 2021-01-10 Call mom @phone +Family due:2021-01-20 rec:1w
+//!     - x: The task is done.
+//!     - (A): Priorities A-Z are mapped to TTM priorities 0-25. pri:A is read the same way, as done
+//!       tasks keep their priority in it.
+//!     - Dates: The completion date of a done task, then the date the task was created. TTM has no
+//!       place for either, so they are reported.
+//!     - @context and +project words are kept in the name of the task. Tasks are grouped into a
+//!       section for the first project they name, in the order projects are first found. Tasks
+//!       without a project come before the sections. Brackets in project names are replaced by
+//!       parentheses in the names of their sections, and parentheses in task names by brackets.
+//!     - due:YYYY-MM-DD: The due date, converted to a date code through the season calendar.
+//!     - rec:[+][N](d|w|m|y): How often the task repeats. Refer to `super::import::repeat`.
+//!     - Other key:value pairs are reported and dropped.
+
+#![allow(dead_code)]
+
+use super::calendar::SeasonCalendar;
+use super::date::Date;
+use super::document::{Document, DocumentSection, Item};
+use super::import::{self, Import, Warnings};
+use super::task::{Repeat, Task, TaskFlags};
+use super::task_tree::TaskTree;
+use regex::Regex;
+
+lazy_static! {
+    static ref DATE_RE: Regex = Regex::new(r"^\d{4}-\d{2}-\d{2}$").unwrap();
+    static ref PRIORITY_RE: Regex = Regex::new(r"^\(([A-Z])\)$").unwrap();
+    static ref KEY_VALUE_RE: Regex = Regex::new(r"^([^\s:]+):([^\s:/][^\s:]*)$").unwrap();
+    static ref RECURRENCE_RE: Regex = Regex::new(r"^\+?(\d*)([dwmy])$").unwrap();
+}
+
+/// imports the todo.txt file `s`, converting its dates through `calendar`
+pub fn import(s: &str, calendar: &SeasonCalendar) -> Import {
+    let mut warnings = Warnings::default();
+    let mut items: Vec<Item> = vec![];
+    // the name of each project, the line it is first found on and its tasks
+    let mut projects: Vec<(String, usize, Vec<Item>)> = vec![];
+
+    for (idx, line) in s.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let (task, project) = parse_task(line.trim(), idx + 1, calendar, &mut warnings);
+        let item = Item::Task(TaskTree::new(task, vec![]));
+        match project {
+            Some(project) => match projects.iter_mut().find(|(name, _line, _items)| *name == project) {
+                Some((_name, _line, project_items)) => project_items.push(item),
+                None => projects.push((project, idx + 1, vec![item])),
+            },
+            None => items.push(item),
+        }
+    }
+
+    for (name, line, project_items) in projects.into_iter() {
+        let name = import::section_name(&name, line, &mut warnings);
+        items.push(Item::Section(DocumentSection::new(&name, project_items)));
+    }
+    warnings.0.sort_by_key(|warning| warning.line);
+    Import { document: Document::new(items), warnings: warnings.0 }
+}
+
+fn priority_of(letter: &str) -> usize {
+    (letter.as_bytes()[0] - b'A') as usize
+}
+
+/// parses a todo.txt line into a task, along with the first project it names
+fn parse_task(line: &str, line_no: usize, calendar: &SeasonCalendar, warnings: &mut Warnings) -> (Task, Option<String>) {
+    let mut words = line.split_whitespace().peekable();
+    let mut flags = TaskFlags::empty();
+    let mut priority: Option<usize> = None;
+
+    if words.peek() == Some(&"x") {
+        flags.insert(TaskFlags::DONE);
+        words.next();
+    }
+    if let Some(caps) = words.peek().and_then(|word| PRIORITY_RE.captures(word)) {
+        priority = Some(priority_of(&caps[1]));
+        words.next();
+    }
+
+    // done tasks may have a completion date before the creation date
+    let mut date_kinds = if flags.contains(TaskFlags::DONE) { vec!["creation", "completion"] } else { vec!["creation"] };
+    while let Some(&word) = words.peek() {
+        if !DATE_RE.is_match(word) {
+            break;
+        }
+        match date_kinds.pop() {
+            Some(kind) => warnings.warn(line_no, format!("dropped {} date {}", kind, word)),
+            None => break,
+        }
+        words.next();
+    }
+
+    let mut name_words: Vec<&str> = vec![];
+    let mut project: Option<String> = None;
+    let mut due_date: Option<Date> = None;
+    let mut repeat: Option<Repeat> = None;
+    for word in words {
+        let caps = match KEY_VALUE_RE.captures(word) {
+            Some(caps) => caps,
+            None => {
+                if project.is_none() && word.len() > 1 && word.starts_with('+') {
+                    project = Some(word[1..].to_string());
+                }
+                name_words.push(word);
+                continue;
+            }
+        };
+
+        match (&caps[1], &caps[2]) {
+            ("due", value) => {
+                due_date = import::date_code(value, calendar, line_no, warnings).or(due_date);
+            }
+            ("rec", value) => match RECURRENCE_RE.captures(value) {
+                Some(rec) => repeat = import::repeat(&rec[1], &rec[2], line_no, warnings).or(repeat),
+                None => warnings.warn(line_no, format!("dropped invalid recurrence '{}'", word)),
+            },
+            ("pri", value) if value.len() == 1 && value.chars().all(|c| c.is_ascii_uppercase()) => {
+                priority = Some(priority_of(value));
+            }
+            _ => warnings.warn(line_no, format!("dropped '{}'", word)),
+        }
+    }
+
+    let mut task = Task::from_name(&import::task_name(&name_words.join(" "), line_no, warnings));
+    task.build_flags(flags);
+    if let Some(priority) = priority {
+        task.build_priority(priority);
+    }
+    if let Some(due_date) = due_date {
+        task.build_due_date(due_date);
+    }
+    if let Some(repeat) = repeat {
+        task.build_repeat(repeat);
+    }
+    (task, project)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TODO_TXT: &str = "\
+(A) 2021-01-10 Call mom @phone +Family due:2021-01-14
+Buy milk @store
+x 2021-01-12 2021-01-10 Pay rent +Home pri:B rec:1m
+(C) Plan the trip (with Sam) +Family rec:+2w t:2021-01-11
+";
+
+    #[test]
+    fn test_import() {
+        let import = import(TODO_TXT, &SeasonCalendar::default());
+
+        assert_eq!(import.document.to_string(), "\
+() Buy milk @store
+[Family]
+\t() Call mom @phone +Family (due: Y21W-W3R; prior: 0)
+\t() Plan the trip [with Sam] +Family (prior: 2; rept: W2)
+[Home]
+\t~() Pay rent +Home (prior: 1)
+");
+        let warnings: Vec<(usize, String)> = import.warnings.iter()
+            .map(|w| (w.line, w.message.to_string()))
+            .collect();
+        assert_eq!(warnings, vec![
+            (1, "dropped creation date 2021-01-10".to_string()),
+            (3, "dropped completion date 2021-01-12".to_string()),
+            (3, "dropped creation date 2021-01-10".to_string()),
+            (3, "dropped repeat every 1m, only days and weeks are supported".to_string()),
+            (4, "dropped 't:2021-01-11'".to_string()),
+            (4, "replaced the parentheses in 'Plan the trip (with Sam) +Family' with brackets".to_string()),
+        ]);
+    }
+
+    #[test]
+    fn test_import_round_trips() {
+        let import = import(TODO_TXT, &SeasonCalendar::default());
+        let rendered = import.document.to_string();
+        assert_eq!(rendered.parse::<Document>().unwrap(), import.document);
+    }
+
+    #[test]
+    fn test_unreadable_names() {
+        let import = import("Plan +trip[2021] (again)\n", &SeasonCalendar::default());
+        assert_eq!(import.document.to_string(), "[trip(2021)]\n\t() Plan +trip[2021] [again]\n");
+        assert_eq!(import.warnings.len(), 2);
+        assert_eq!(import.document.to_string().parse::<Document>().unwrap(), import.document);
+    }
+
+    #[test]
+    fn test_not_key_values() {
+        let import = import("Read http://example.com x:\n", &SeasonCalendar::default());
+        assert!(import.warnings.is_empty());
+        assert_eq!(import.document.tasks()[0].name(), "Read http://example.com x:");
+    }
+}
@@ -0,0 +1,439 @@
+//! The language features of the server. Each works on the text of a file, and positions are
+//! converted from the UTF-16 columns used by LSP.
+
+use super::LspOptions;
+use crate::ttm_core::deps::{DepError, DepGraph};
+use crate::ttm_core::lint;
+use crate::ttm_io::calendar::CivilDate;
+use crate::ttm_io::date::Date;
+use crate::ttm_io::diagnostic::Severity;
+use crate::ttm_io::document::{Document, SectionCache};
+use crate::ttm_io::edit;
+use crate::ttm_io::ids::{IdGenerator, TaskIndex};
+use crate::ttm_io::stat::Stat;
+use crate::ttm_io::task::{Task, TaskFlags};
+use crate::ttm_io::task_tree::TaskTree;
+use crate::utils::scanner::FromNext;
+use lsp_types::{
+    CodeAction, CodeActionKind, CodeActionOrCommand, CompletionItem, CompletionItemKind, Diagnostic,
+    DiagnosticSeverity, Hover, HoverContents, MarkupContent, MarkupKind, NumberOrString, Position, Range, TextEdit, Url,
+    WorkspaceEdit,
+};
+use std::collections::{BTreeSet, HashMap};
+
+/// how many days ahead date codes are completed for
+const DATE_COMPLETION_DAYS: i64 = 14;
+
+const FLAGS: [(char, &str); 4] = [
+    ('~', "done"),
+    ('>', "current"),
+    ('B', "blocked"),
+    ('L', "late"),
+];
+
+const SUFFIX_KEYS: [(&str, &str); 8] = [
+    ("due: ", "the task's deadline"),
+    ("hard: ", "hard deadline, could be bad to miss"),
+    ("done: ", "when the task was done"),
+    ("prior: ", "priority, 0-99 where 0 is the most important"),
+    ("rept: ", "repeats every N days (D<N>) or weeks (W<N>)"),
+    ("deps: ", "the tasks that must be done first, by their paths"),
+    ("tags: ", "tags and contexts beyond those in the name, as +tag or @context"),
+    ("*", "links the task to a note"),
+];
+
+/// indexed by `Weekday::index`
+const WEEKDAY_NAMES: [&str; 7] = ["Monday", "Tuesday", "Wednesday", "Thursday", "Friday", "Saturday", "Sunday"];
+
+/// the byte index in `line` of the UTF-16 column `character`
+pub fn byte_index(line: &str, character: u32) -> usize {
+    let mut units = 0;
+    for (idx, c) in line.char_indices() {
+        if units >= character as usize {
+            return idx;
+        }
+        units += c.len_utf16();
+    }
+    line.len()
+}
+
+/// the UTF-16 column of the byte index `idx` in `line`
+pub fn utf16_column(line: &str, idx: usize) -> u32 {
+    line[..idx].encode_utf16().count() as u32
+}
+
+fn line_at(text: &str, line: u32) -> &str {
+    text.lines().nth(line as usize).unwrap_or("")
+}
+
+fn line_range(line_no: u32, line: &str) -> Range {
+    Range::new(Position::new(line_no, 0), Position::new(line_no, utf16_column(line, line.len())))
+}
+
+/// every task and subtask of `document` along with its line, counted from 0 as in LSP
+fn task_lines(document: &Document) -> Vec<(u32, &Task)> {
+    document.walk_trees().into_iter().map(|tree| (tree.line() as u32 - 1, tree.task())).collect()
+}
+
+/// the lines of the file that fail to parse, warnings for tasks with done dates that do not fit
+/// them, ids given to more than one task, and the dependency errors of tasks. Refer to
+/// `Task::check_done_date` and `crate::ttm_core::deps`. The sections of the file are kept in
+/// `cache` between edits.
+pub fn diagnostics(text: &str, options: &LspOptions, cache: &mut SectionCache) -> Vec<Diagnostic> {
+    let (document, errors) = options.config.parse_document_cached(text, cache);
+    let diagnostic = |line_no: u32, severity: DiagnosticSeverity, message: String| Diagnostic {
+        range: line_range(line_no, line_at(text, line_no)),
+        severity: Some(severity),
+        source: Some("ttm".to_string()),
+        message,
+        ..Diagnostic::default()
+    };
+
+    // parse errors come along with the problems found by the lint rules
+    let (year, season) = options.reference();
+    let mut out: Vec<Diagnostic> = lint::lint_document(&document, &errors, &options.config.lint, (year, season)).into_iter()
+        .map(|found| {
+            let severity = match found.severity {
+                Severity::Error => DiagnosticSeverity::ERROR,
+                Severity::Warning => DiagnosticSeverity::WARNING,
+            };
+            Diagnostic {
+                code: found.code.map(|code| NumberOrString::String(code.to_string())),
+                ..diagnostic(found.line as u32 - 1, severity, found.message)
+            }
+        })
+        .collect();
+    let tasks = task_lines(&document);
+    for (line_no, task) in tasks.iter() {
+        if let Err(message) = task.check_done_date(year, season) {
+            out.push(diagnostic(*line_no, DiagnosticSeverity::WARNING, message.into_owned()));
+        }
+    }
+
+    for id in TaskIndex::new(&document).duplicates() {
+        for (line_no, _task) in tasks.iter().filter(|(_line_no, task)| task.id() == Some(*id)) {
+            out.push(diagnostic(*line_no, DiagnosticSeverity::ERROR, format!("the id #{} is given to more than one task", id)));
+        }
+    }
+
+    // dependency errors are shown on the line of the first task named by them
+    for error in DepGraph::new(&document).errors() {
+        let (name, severity) = match &error {
+            DepError::Unresolved { task, .. } | DepError::Ambiguous { task, .. } => (task, DiagnosticSeverity::WARNING),
+            DepError::Cycle(tasks) => (&tasks[0], DiagnosticSeverity::ERROR),
+        };
+        if let Some((line_no, _task)) = tasks.iter().find(|(_line_no, task)| task.name() == name) {
+            out.push(diagnostic(*line_no, severity, error.to_string()));
+        }
+    }
+    out
+}
+
+/// the field of a task's suffix being written, if `before` ends within a suffix
+fn suffix_field(before: &str) -> Option<&str> {
+    let close = before.find(')')?;
+    let open = close + before[close..].find('(')?;
+    let suffix = &before[open + 1..];
+    if suffix.contains(')') {
+        return None;
+    }
+    suffix.rsplit(';').next().map(|field| field.trim_start())
+}
+
+/// completes task flags at the start of a line, keys within a task's suffix, and date codes
+/// after due:, hard: or done:
+pub fn completions(text: &str, position: Position, options: &LspOptions) -> Vec<CompletionItem> {
+    let line = line_at(text, position.line);
+    let before = &line[..byte_index(line, position.character)];
+    let written = before.trim_start();
+
+    if written.chars().all(|c| FLAGS.iter().any(|(flag, _)| *flag == c)) {
+        return FLAGS.iter()
+            .filter(|(flag, _)| !written.contains(*flag))
+            .map(|(flag, doc)| CompletionItem {
+                label: flag.to_string(),
+                kind: Some(CompletionItemKind::ENUM_MEMBER),
+                detail: Some(format!("task flag: {}", doc)),
+                ..CompletionItem::default()
+            })
+            .collect();
+    }
+
+    match suffix_field(before) {
+        Some(field) if ["due:", "hard:", "done:"].iter().any(|key| field.starts_with(key)) => date_completions(options),
+        Some(_) => key_completions(text, options),
+        None => vec![],
+    }
+}
+
+fn key_completions(text: &str, options: &LspOptions) -> Vec<CompletionItem> {
+    let mut items: Vec<CompletionItem> = SUFFIX_KEYS.iter()
+        .map(|(key, doc)| CompletionItem {
+            label: key.trim().to_string(),
+            kind: Some(CompletionItemKind::PROPERTY),
+            detail: Some(doc.to_string()),
+            insert_text: Some(key.to_string()),
+            ..CompletionItem::default()
+        })
+        .collect();
+
+    // goals already tracked in the file
+    let (document, _errors) = options.config.parse_document(text);
+    let goals: BTreeSet<&str> = document.tasks().iter()
+        .flat_map(|task| task.other_stats().keys().map(|goal| goal.as_str()))
+        .collect();
+    items.extend(goals.into_iter().map(|goal| CompletionItem {
+        label: format!("g{}:", goal),
+        kind: Some(CompletionItemKind::PROPERTY),
+        detail: Some(format!("goal stat {}", goal)),
+        insert_text: Some(format!("g{}: ", goal)),
+        ..CompletionItem::default()
+    }));
+    items
+}
+
+/// date codes of the days from today on. Days in the current season are written as short codes.
+fn date_completions(options: &LspOptions) -> Vec<CompletionItem> {
+    let (year, season) = options.reference();
+    let today = options.today().to_days();
+    (0..DATE_COMPLETION_DAYS)
+        .filter_map(|offset| {
+            let day = CivilDate::from_days(today + offset);
+            let code = match options.config.calendar.from_civil(day).ok()? {
+                Date::DateCode { year: y, season: s, week, day } if y == year && s == season => {
+                    Date::ShortDateCode { week, day }
+                }
+                code => code,
+            };
+            Some(CompletionItem {
+                label: code.to_string(),
+                kind: Some(CompletionItemKind::VALUE),
+                detail: Some(format!("{} {}", WEEKDAY_NAMES[day.weekday().index()], day)),
+                sort_text: Some(format!("{:02}", offset)),
+                ..CompletionItem::default()
+            })
+        })
+        .collect()
+}
+
+/// the word around byte index `idx` of `line`, along with its byte range
+fn word_at(line: &str, idx: usize) -> (usize, usize, &str) {
+    let is_separator = |c: char| c.is_whitespace() || "();,".contains(c);
+    let start = line[..idx].rfind(is_separator).map_or(0, |i| i + 1);
+    let end = line[idx..].find(is_separator).map_or(line.len(), |i| idx + i);
+    (start, end, &line[start..end])
+}
+
+/// the byte offset of the start of line `line_no` in `text`
+fn line_offset(text: &str, line_no: u32) -> usize {
+    text.split_inclusive('\n').take(line_no as usize).map(|line| line.len()).sum()
+}
+
+/// shows the calendar day of a date code, or the computed context stat of a task
+pub fn hover(text: &str, position: Position, options: &LspOptions) -> Option<Hover> {
+    let line = line_at(text, position.line);
+    let (start, end, word) = word_at(line, byte_index(line, position.character));
+
+    let (value, range) = if let Ok(date) = word.parse::<Date>() {
+        let (year, season) = options.reference();
+        let day = options.config.calendar.to_civil(&date, year, season);
+        let range = Range::new(
+            Position::new(position.line, utf16_column(line, start)),
+            Position::new(position.line, utf16_column(line, end)));
+        (format!("**{}**: {} {}", word, WEEKDAY_NAMES[day.weekday().index()], day), range)
+    } else {
+        let text = options.config.unalias_flags(text);
+        let (_len, tree) = TaskTree::next(&text[line_offset(&text, position.line)..]).ok()?;
+        let mut value = format!("**{}**\n\ncontext: {}", tree.task().name(), tree.computed_context_stat());
+        if let Some(remaining) = tree.task().remaining_blocks() {
+            value.push_str(&format!(", {} blocks remaining", remaining));
+        }
+        (value, line_range(position.line, line))
+    };
+
+    Some(Hover {
+        contents: HoverContents::Markup(MarkupContent { kind: MarkupKind::Markdown, value }),
+        range: Some(range),
+    })
+}
+
+/// actions rewriting the task at the start of `range`: marking it done, incrementing its day
+/// stat, and giving it an id if it has none. Only the changed parts of the line are written again,
+/// as in `crate::ttm_io::edit::set_task`.
+pub fn code_actions(uri: &Url, text: &str, range: Range, options: &LspOptions) -> Vec<CodeActionOrCommand> {
+    let line = line_at(text, range.start.line);
+    let (document, _errors) = options.config.parse_document(text);
+    let task: Task = match task_lines(&document).into_iter().find(|(line_no, _task)| *line_no == range.start.line) {
+        Some((_line_no, task)) => task.clone(),
+        None => return vec![],
+    };
+
+    let rewrite = |title: &str, edited: &Task| {
+        let new_text = edit::set_task(line, &task, edited, &options.config.flag_aliases)?;
+        let edit = TextEdit::new(line_range(range.start.line, line), new_text);
+        Some(CodeActionOrCommand::CodeAction(CodeAction {
+            title: title.to_string(),
+            kind: Some(CodeActionKind::REFACTOR_REWRITE),
+            edit: Some(WorkspaceEdit {
+                changes: Some(HashMap::from([(uri.clone(), vec![edit])])),
+                ..WorkspaceEdit::default()
+            }),
+            ..CodeAction::default()
+        }))
+    };
+
+    let mut actions: Vec<CodeActionOrCommand> = vec![];
+    if !task.flags().contains(TaskFlags::DONE) {
+        let mut done = task.clone();
+        if let Ok(today) = options.config.calendar.from_civil(options.today()) {
+            done.mark_done(today);
+            actions.extend(rewrite("Mark done", &done));
+        }
+    }
+    let incremented = match task.day_stat() {
+        Some(Stat::Count { act, exp }) => Some(Stat::from_count(Some(act.unwrap_or(0) + 1), exp)),
+        None => Some(Stat::from_count(Some(1), None)),
+        Some(_) => None,
+    };
+    if let Some(day_stat) = incremented {
+        let mut task = task.clone();
+        task.build_day_stat(day_stat);
+        actions.extend(rewrite("Increment day stat", &task));
+    }
+    if task.id().is_none() {
+        // the line and the day seed the id, so that ids given on the same day differ
+        let seed = (options.today().to_days() as u64) << 32 | range.start.line as u64;
+        let mut task = task.clone();
+        task.build_id(&IdGenerator::for_document(seed, &document).next_id());
+        actions.extend(rewrite("Add id", &task));
+    }
+    actions
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn options() -> LspOptions {
+        // a Monday of Winter 2021
+        LspOptions { today: Some(CivilDate::new(2021, 1, 11)), ..LspOptions::default() }
+    }
+
+    const TEXT: &str = "[Tasks]\n\t>(1, 2/10) Write the report (due: W3R; gPages: 0/5)\n\t\t(0/3) Outline\n\t(0/x) Broken\n";
+
+    fn labels(items: &[CompletionItem]) -> Vec<&str> {
+        items.iter().map(|item| item.label.as_str()).collect()
+    }
+
+    #[test]
+    fn test_positions() {
+        assert_eq!(byte_index("é𝄞x", 1), 2);
+        assert_eq!(byte_index("é𝄞x", 3), 6);
+        assert_eq!(byte_index("é𝄞x", 10), 7);
+        assert_eq!(utf16_column("é𝄞x", 6), 3);
+    }
+
+    #[test]
+    fn test_diagnostics() {
+        let diagnostics = diagnostics(TEXT, &options(), &mut SectionCache::new());
+        assert_eq!(diagnostics.len(), 2);
+        assert_eq!(diagnostics[0].code, Some(NumberOrString::String("unknown-goal".into())));
+        assert_eq!(diagnostics[0].severity, Some(DiagnosticSeverity::WARNING));
+        assert_eq!(diagnostics[1].range, Range::new(Position::new(3, 0), Position::new(3, 13)));
+        assert_eq!(diagnostics[1].severity, Some(DiagnosticSeverity::ERROR));
+
+        let late = super::diagnostics("~() Task (due: W3R; done: W4M)\n", &options(), &mut SectionCache::new());
+        assert_eq!(late[0].severity, Some(DiagnosticSeverity::WARNING));
+        assert_eq!(late[0].message, "'Task' was done on W4M, after its due date W3R");
+
+        let cycle = super::diagnostics("() A (deps: B)\n() B (deps: A; due: W3R)\n() C (deps: D)\n", &options(), &mut SectionCache::new());
+        let messages: Vec<(u32, &str)> = cycle.iter().map(|d| (d.range.start.line, d.message.as_str())).collect();
+        assert_eq!(messages, vec![
+            (2, "'C' depends on 'D', which names no task"),
+            (0, "dependency cycle: A -> B -> A"),
+        ]);
+
+        let duplicates = super::diagnostics("() A (#x)\n() B (#x)\n", &options(), &mut SectionCache::new());
+        assert_eq!(duplicates.iter().map(|d| d.range.start.line).collect::<Vec<u32>>(), vec![0, 1]);
+    }
+
+    #[test]
+    fn test_config() {
+        let config = "[flags]\n\"*\" = \"current\"\n[sections]\nJournal = \"text\"\n".parse().unwrap();
+        let configured = LspOptions { config, ..options() };
+        let text = "[Tasks]\n\t*() Task (due: W3R)\n[Journal]\n\t(0/x) not a task\n";
+        assert!(diagnostics(text, &configured, &mut SectionCache::new()).is_empty());
+        // without the config, the journal entry is a task that fails to parse
+        let unconfigured = diagnostics(text, &options(), &mut SectionCache::new());
+        assert_eq!(unconfigured.iter().map(|d| d.range.start.line).collect::<Vec<u32>>(), vec![3]);
+
+        let uri = Url::parse("file:///tasks.ttm").unwrap();
+        let actions = code_actions(&uri, text, Range::new(Position::new(1, 0), Position::new(1, 0)), &configured);
+        assert_eq!(actions.len(), 3);
+        // the alias is kept in the rewritten line
+        match &actions[1] {
+            CodeActionOrCommand::CodeAction(action) => {
+                let changes = action.edit.as_ref().unwrap().changes.as_ref().unwrap();
+                assert_eq!(changes[&uri][0].new_text, "\t*(1) Task (due: W3R)");
+            }
+            _ => unreachable!(),
+        }
+        assert!(code_actions(&uri, text, Range::new(Position::new(3, 0), Position::new(3, 0)), &configured).is_empty());
+
+        // tasks are found on their own lines, past the text sections
+        let journal = "[Journal]\n\t() not a task (done: W4M; #x)\n[Tasks]\n\t~() Task (due: W3R; done: W4M; #x)\n";
+        let found = diagnostics(journal, &configured, &mut SectionCache::new());
+        assert_eq!(found.iter().map(|d| d.range.start.line).collect::<Vec<u32>>(), vec![3]);
+    }
+
+    #[test]
+    fn test_completions() {
+        let options = options();
+        assert_eq!(labels(&completions("\t>", Position::new(0, 2), &options)), vec!["~", "B", "L"]);
+        assert_eq!(labels(&completions("\t() Task (pri", Position::new(0, 13), &options)),
+            vec!["due:", "hard:", "done:", "prior:", "rept:", "deps:", "tags:", "*"]);
+        assert_eq!(labels(&completions(TEXT, Position::new(1, 50), &options))[8..], ["gPages:"]);
+        assert!(completions("\t() Task", Position::new(0, 8), &options).is_empty());
+
+        let dates = completions("() Task (prior: 1; due: ", Position::new(0, 24), &options);
+        assert_eq!(dates.len(), 14);
+        assert_eq!(dates[0].label, "W3M");
+        assert_eq!(dates[0].detail.as_deref(), Some("Monday 2021-01-11"));
+    }
+
+    #[test]
+    fn test_hover() {
+        let options = options();
+        let value = |hover: Option<Hover>| match hover.unwrap().contents {
+            HoverContents::Markup(markup) => markup.value,
+            _ => unreachable!(),
+        };
+
+        assert_eq!(value(hover(TEXT, Position::new(1, 35), &options)), "**W3R**: Thursday 2021-01-14");
+        assert_eq!(value(hover(TEXT, Position::new(1, 15), &options)),
+            "**Write the report**\n\ncontext: 2/13, 8 blocks remaining");
+        assert!(hover(TEXT, Position::new(0, 2), &options).is_none());
+    }
+
+    #[test]
+    fn test_code_actions() {
+        let uri = Url::parse("file:///tasks.ttm").unwrap();
+        let edits = |action: &CodeActionOrCommand| match action {
+            CodeActionOrCommand::CodeAction(action) => {
+                let changes = action.edit.as_ref().unwrap().changes.as_ref().unwrap();
+                changes[&uri].iter().map(|edit| edit.new_text.clone()).collect::<Vec<String>>()
+            }
+            _ => unreachable!(),
+        };
+
+        let actions = code_actions(&uri, TEXT, Range::new(Position::new(2, 3), Position::new(2, 3)), &options());
+        assert_eq!(actions.len(), 3);
+        assert_eq!(edits(&actions[0]), vec!["\t\t~(0/3) Outline (done: Y21W-W3M)"]);
+        assert_eq!(edits(&actions[1]), vec!["\t\t(1/3) Outline"]);
+        let with_id = edits(&actions[2]).remove(0);
+        assert!(with_id.parse::<Task>().is_ok_and(|task| task.id().is_some()), "{}", with_id);
+        let text = "() Task (#abc)\n";
+        assert_eq!(code_actions(&uri, text, Range::new(Position::new(0, 0), Position::new(0, 0)), &options()).len(), 2);
+        assert!(code_actions(&uri, TEXT, Range::new(Position::new(0, 0), Position::new(0, 0)), &options()).is_empty());
+    }
+}
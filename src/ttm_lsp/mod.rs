@@ -0,0 +1,311 @@
+//! Language server for TTM files, run by the ttm-lsp binary over stdio. Available with the `lsp`
+//! feature.
+//! The server keeps the text of every open file, synced in full, and provides:
+//!     - Diagnostics: The lines that fail to parse, the problems found by the lint rules, tasks done
+//!       after their due date, ids given to more than one task, and dependencies that name no task or
+//!       form a cycle, published whenever a file changes. Refer to `crate::ttm_core::lint`.
+//!     - Completion: Task flags at the start of a line, the keys of a task's suffix along with the
+//!       goals used in the file, and the date codes of the coming days after due:, hard: or done:.
+//!     - Hover: The calendar day of a date code, and the computed context stat of a task.
+//!     - Code actions: Marking a task done on today, incrementing its day stat, and giving it an id.
+//! Files are parsed as ttm parses them, with the flag aliases and section payloads of the config.
+//! Refer to `Config::parse_document`.
+
+pub mod features;
+
+use crate::config::Config;
+use crate::ttm_io::calendar::CivilDate;
+use crate::ttm_io::date::{Date, Season};
+use crate::ttm_io::document::SectionCache;
+use lsp_server::{Connection, ErrorCode, Message, Notification, Request, Response};
+use lsp_types::notification::{
+    DidChangeTextDocument, DidCloseTextDocument, DidOpenTextDocument, Notification as _, PublishDiagnostics,
+};
+use lsp_types::request::{CodeActionRequest, Completion, HoverRequest, Request as _};
+use lsp_types::{
+    CodeActionParams, CodeActionProviderCapability, CompletionOptions, CompletionParams, HoverParams,
+    HoverProviderCapability, PublishDiagnosticsParams, ServerCapabilities, TextDocumentSyncCapability,
+    TextDocumentSyncKind, Url,
+};
+use std::collections::HashMap;
+use std::error::Error;
+
+#[derive(Default)]
+pub struct LspOptions {
+    /// the calendar, lint rules, flag aliases and section payloads
+    pub config: Config,
+    /// the day taken as today, fixed for tests. None for the day of each request, so that a
+    /// server left running past midnight moves on to the next day.
+    pub today: Option<CivilDate>,
+}
+
+impl LspOptions {
+    pub fn from_config(config: &Config) -> Self {
+        Self { config: config.clone(), today: None }
+    }
+
+    /// short date codes are taken to be in the season of today
+    pub fn today(&self) -> CivilDate {
+        self.today.unwrap_or_else(CivilDate::today)
+    }
+
+    /// the year and season short date codes are in
+    pub fn reference(&self) -> (u32, Season) {
+        match self.config.calendar.from_civil(self.today()) {
+            Ok(Date::DateCode { year, season, .. }) => (year, season),
+            _ => (0, Season::Winter),
+        }
+    }
+}
+
+pub fn capabilities() -> ServerCapabilities {
+    ServerCapabilities {
+        text_document_sync: Some(TextDocumentSyncCapability::Kind(TextDocumentSyncKind::FULL)),
+        completion_provider: Some(CompletionOptions {
+            trigger_characters: Some(vec!["(".into(), ";".into(), " ".into()]),
+            ..CompletionOptions::default()
+        }),
+        hover_provider: Some(HoverProviderCapability::Simple(true)),
+        code_action_provider: Some(CodeActionProviderCapability::Simple(true)),
+        ..ServerCapabilities::default()
+    }
+}
+
+/// serves the client on `connection` until it shuts the server down
+pub fn run(connection: &Connection, options: LspOptions) -> Result<(), Box<dyn Error + Sync + Send>> {
+    connection.initialize(serde_json::to_value(capabilities())?)?;
+    let mut server = Server { options, files: HashMap::new(), caches: HashMap::new() };
+
+    for message in &connection.receiver {
+        match message {
+            Message::Request(request) => {
+                if connection.handle_shutdown(&request)? {
+                    return Ok(());
+                }
+                connection.sender.send(Message::Response(server.handle_request(request)))?;
+            }
+            Message::Notification(notification) => {
+                if let Some(out) = server.handle_notification(notification) {
+                    connection.sender.send(Message::Notification(out))?;
+                }
+            }
+            Message::Response(_) => {}
+        }
+    }
+    Ok(())
+}
+
+struct Server {
+    options: LspOptions,
+    /// the text of every open file
+    files: HashMap<Url, String>,
+    /// the parsed sections of every open file, so an edit only parses the sections it touches
+    caches: HashMap<Url, SectionCache>,
+}
+
+/// answers `request` with `f` of its params
+fn respond<P, R>(request: Request, f: impl FnOnce(P) -> R) -> Response
+where
+    P: serde::de::DeserializeOwned,
+    R: serde::Serialize,
+{
+    match serde_json::from_value::<P>(request.params) {
+        Ok(params) => Response::new_ok(request.id, f(params)),
+        Err(e) => Response::new_err(request.id, ErrorCode::InvalidParams as i32, e.to_string()),
+    }
+}
+
+impl Server {
+    fn text(&self, uri: &Url) -> &str {
+        self.files.get(uri).map_or("", |text| text.as_str())
+    }
+
+    fn handle_request(&self, request: Request) -> Response {
+        match request.method.as_str() {
+            Completion::METHOD => respond(request, |params: CompletionParams| {
+                let position = params.text_document_position;
+                features::completions(self.text(&position.text_document.uri), position.position, &self.options)
+            }),
+            HoverRequest::METHOD => respond(request, |params: HoverParams| {
+                let position = params.text_document_position_params;
+                features::hover(self.text(&position.text_document.uri), position.position, &self.options)
+            }),
+            CodeActionRequest::METHOD => respond(request, |params: CodeActionParams| {
+                let uri = params.text_document.uri;
+                features::code_actions(&uri, self.text(&uri), params.range, &self.options)
+            }),
+            method => Response::new_err(request.id, ErrorCode::MethodNotFound as i32,
+                format!("unsupported request {}", method)),
+        }
+    }
+
+    /// updates the open files, giving back the diagnostics to publish if a file changed
+    fn handle_notification(&mut self, notification: Notification) -> Option<Notification> {
+        let uri = match notification.method.as_str() {
+            DidOpenTextDocument::METHOD => {
+                let params: lsp_types::DidOpenTextDocumentParams = serde_json::from_value(notification.params).ok()?;
+                self.files.insert(params.text_document.uri.clone(), params.text_document.text);
+                params.text_document.uri
+            }
+            DidChangeTextDocument::METHOD => {
+                let params: lsp_types::DidChangeTextDocumentParams = serde_json::from_value(notification.params).ok()?;
+                // files are synced in full, so the last change holds the whole text
+                let text = params.content_changes.into_iter().last()?.text;
+                self.files.insert(params.text_document.uri.clone(), text);
+                params.text_document.uri
+            }
+            DidCloseTextDocument::METHOD => {
+                let params: lsp_types::DidCloseTextDocumentParams = serde_json::from_value(notification.params).ok()?;
+                self.files.remove(&params.text_document.uri);
+                self.caches.remove(&params.text_document.uri);
+                params.text_document.uri
+            }
+            _ => return None,
+        };
+
+        let diagnostics = match self.files.get(&uri) {
+            Some(text) => features::diagnostics(text, &self.options, self.caches.entry(uri.clone()).or_default()),
+            None => vec![],
+        };
+        Some(Notification::new(
+            PublishDiagnostics::METHOD.to_string(),
+            PublishDiagnosticsParams { uri, diagnostics, version: None },
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lsp_server::RequestId;
+    use lsp_types::notification::{Exit, Initialized};
+    use lsp_types::request::{Initialize, Shutdown};
+    use lsp_types::*;
+    use std::time::Duration;
+
+    /// an in-process client of a server run on another thread
+    struct Client {
+        connection: Connection,
+        server: Option<std::thread::JoinHandle<()>>,
+        next_id: i32,
+    }
+
+    impl Client {
+        fn start() -> Self {
+            let (server, connection) = Connection::memory();
+            let options = LspOptions { today: Some(CivilDate::new(2021, 1, 11)), ..LspOptions::default() };
+            let server = std::thread::spawn(move || run(&server, options).unwrap());
+            let mut client = Self { connection, server: Some(server), next_id: 0 };
+
+            let result = client.request(Initialize::METHOD, InitializeParams::default());
+            assert!(result["capabilities"]["hoverProvider"].as_bool().unwrap());
+            client.notify(Initialized::METHOD, InitializedParams {});
+            client
+        }
+
+        fn recv(&self) -> Message {
+            self.connection.receiver.recv_timeout(Duration::from_secs(5)).unwrap()
+        }
+
+        fn request(&mut self, method: &str, params: impl serde::Serialize) -> serde_json::Value {
+            self.next_id += 1;
+            let id = RequestId::from(self.next_id);
+            let request = Request::new(id.clone(), method.to_string(), params);
+            self.connection.sender.send(Message::Request(request)).unwrap();
+            match self.recv() {
+                Message::Response(response) if response.id == id => response.result.unwrap(),
+                message => panic!("expected a response to {}, got {:?}", method, message),
+            }
+        }
+
+        fn notify(&self, method: &str, params: impl serde::Serialize) {
+            let notification = Notification::new(method.to_string(), params);
+            self.connection.sender.send(Message::Notification(notification)).unwrap();
+        }
+
+        fn diagnostics(&self) -> PublishDiagnosticsParams {
+            match self.recv() {
+                Message::Notification(n) if n.method == PublishDiagnostics::METHOD => {
+                    serde_json::from_value(n.params).unwrap()
+                }
+                message => panic!("expected diagnostics, got {:?}", message),
+            }
+        }
+
+        fn shutdown(mut self) {
+            self.request(Shutdown::METHOD, ());
+            self.notify(Exit::METHOD, ());
+            self.server.take().unwrap().join().unwrap();
+        }
+    }
+
+    fn uri() -> Url {
+        Url::parse("file:///tasks.ttm").unwrap()
+    }
+
+    fn position(line: u32, character: u32) -> TextDocumentPositionParams {
+        TextDocumentPositionParams::new(TextDocumentIdentifier::new(uri()), Position::new(line, character))
+    }
+
+    #[test]
+    fn test_today() {
+        let options = LspOptions::from_config(&Config::default());
+        assert_eq!(options.today(), CivilDate::today());
+        let fixed = LspOptions { today: Some(CivilDate::new(2021, 1, 11)), ..options };
+        assert_eq!(fixed.reference(), (21, Season::Winter));
+    }
+
+    #[test]
+    fn test_session() {
+        let mut client = Client::start();
+
+        client.notify(DidOpenTextDocument::METHOD, DidOpenTextDocumentParams {
+            text_document: TextDocumentItem::new(uri(), "ttm".into(), 1, "() Task (due: W3R)\n(0/x) Broken\n".into()),
+        });
+        let published = client.diagnostics();
+        assert_eq!(published.uri, uri());
+        assert_eq!(published.diagnostics.len(), 1);
+        assert_eq!(published.diagnostics[0].range.start.line, 1);
+
+        client.notify(DidChangeTextDocument::METHOD, DidChangeTextDocumentParams {
+            text_document: VersionedTextDocumentIdentifier::new(uri(), 2),
+            content_changes: vec![TextDocumentContentChangeEvent {
+                range: None,
+                range_length: None,
+                text: "() Task (due: W3R)\n".into(),
+            }],
+        });
+        assert!(client.diagnostics().diagnostics.is_empty());
+
+        let hover = client.request(HoverRequest::METHOD, HoverParams {
+            text_document_position_params: position(0, 15),
+            work_done_progress_params: WorkDoneProgressParams::default(),
+        });
+        assert_eq!(hover["contents"]["value"], "**W3R**: Thursday 2021-01-14");
+
+        let completions = client.request(Completion::METHOD, CompletionParams {
+            text_document_position: position(0, 9),
+            work_done_progress_params: WorkDoneProgressParams::default(),
+            partial_result_params: PartialResultParams::default(),
+            context: None,
+        });
+        assert_eq!(completions[0]["label"], "due:");
+
+        let actions = client.request(CodeActionRequest::METHOD, CodeActionParams {
+            text_document: TextDocumentIdentifier::new(uri()),
+            range: Range::new(Position::new(0, 0), Position::new(0, 0)),
+            context: CodeActionContext::default(),
+            work_done_progress_params: WorkDoneProgressParams::default(),
+            partial_result_params: PartialResultParams::default(),
+        });
+        assert_eq!(actions[0]["title"], "Mark done");
+        assert_eq!(actions[0]["edit"]["changes"][uri().as_str()][0]["newText"], "~() Task (due: W3R; done: Y21W-W3M)");
+
+        client.notify(DidCloseTextDocument::METHOD, DidCloseTextDocumentParams {
+            text_document: TextDocumentIdentifier::new(uri()),
+        });
+        assert!(client.diagnostics().diagnostics.is_empty());
+
+        client.shutdown();
+    }
+}
@@ -11,6 +11,7 @@ impl<'a> StrUtils<'a> {
     /// 
     /// # Examples
     /// ```
+    /// # use ttm::utils::common::StrUtils;
     /// assert_eq!(StrUtils("0xDeadFeed").contains_any("ABCDEFabcdef"), true);
     /// assert_eq!(StrUtils("456").contains_any("123"), false);
     /// ```
@@ -25,6 +26,7 @@ impl<'a> StrUtils<'a> {
     /// 
     /// # Examples
     /// ```
+    /// # use ttm::utils::common::StrUtils;
     /// assert_eq!(StrUtils("[ContainsBrackets]").contains_all("[]"), true);
     /// assert_eq!(StrUtils("[01]").contains_all("01"), true);
     /// assert_eq!(StrUtils("[1]").contains_all("01"), false);
@@ -40,6 +42,7 @@ impl<'a> StrUtils<'a> {
     /// 
     /// # Examples
     /// ```
+    /// # use ttm::utils::common::StrUtils;
     /// assert_eq!(StrUtils("OK").in_any(&vec!["OK", "PASS"]), true);
     /// assert_eq!(StrUtils("NO").in_any(&vec!["OK", "PASS"]), false);
     /// ```
@@ -55,8 +58,9 @@ impl<'a> StrUtils<'a> {
     /// gets the tab characters of the string
     ///
     /// ### Examples
-    /// ````
-    /// assert_eq!(StrUtils(" \t \tTest").tabs(), " \t \t".to_string());
+    /// ```
+    /// # use ttm::utils::common::StrUtils;
+    /// assert_eq!(StrUtils(" \t \tTest").tabs(), " \t \t");
     /// ```
     pub fn tabs(&self) -> &'a str {
         let mut idx = 0;
@@ -67,6 +71,16 @@ impl<'a> StrUtils<'a> {
         &self.0[..idx]
     }
 
+    /// how many columns the tabbing of the string takes up, a tab being 4 columns
+    pub fn tab_columns(&self) -> usize {
+        self.tabs().chars().map(|c| if c == '\t' {4} else {1}).sum()
+    }
+
+    /// how many levels of tabbing the string starts with. A tab, or up to 4 spaces, is a level.
+    pub fn tab_level(&self) -> usize {
+        self.tab_columns().div_ceil(4)
+    }
+
     // untabs every line `n` tabs
     pub fn untab(&self, _n: usize) -> String {
         let mut _out = String::new();
@@ -102,6 +116,7 @@ impl CharUtils {
     ///
     /// ### Examples 
     /// ```
+    /// # use ttm::utils::common::CharUtils;
     /// assert_eq!(CharUtils('1').is_in("0-9"), true);
     /// assert_eq!(CharUtils('a').is_in("0-9"), false);
     /// assert_eq!(CharUtils('a').is_in("0-9a"), true);
@@ -109,7 +124,6 @@ impl CharUtils {
     /// ```
     pub fn is_in(&self, pat: &str) -> bool {
         let mut chars: Vec<char> = vec![];
-        println!("\n{}, {}", self.0, pat);
 
         // tokenize pattern input into matchers and ranges
         for token in TokenIter(pat.chars(), None) {
@@ -0,0 +1,206 @@
+#![allow(dead_code)]
+
+// -----------------------------------------------------------------
+// -----------------------------------------------------------------
+/// a line of a diff
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum DiffLine<'a> {
+    Same(&'a str),
+    Removed(&'a str),
+    Added(&'a str),
+}
+
+/// diffs the lines of `old` and `new`, keeping a longest common subsequence of lines. Takes space
+/// linear in the number of lines.
+pub fn diff_lines<'a>(old: &'a str, new: &'a str) -> Vec<DiffLine<'a>> {
+    let old: Vec<&str> = old.lines().collect();
+    let new: Vec<&str> = new.lines().collect();
+    let mut out: Vec<DiffLine> = vec![];
+    diff_slices(&old, &new, &mut out);
+    out
+}
+
+/// diffs `old` and `new` into `out`. The common prefix and suffix are kept as they are, and the
+/// lines between them are split at the middle snake of a shortest edit script, each half being
+/// diffed on its own. Refer to Myers, "An O(ND) Difference Algorithm and Its Variations".
+fn diff_slices<'a>(old: &[&'a str], new: &[&'a str], out: &mut Vec<DiffLine<'a>>) {
+    let prefix = old.iter().zip(new.iter()).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..].iter().rev().zip(new[prefix..].iter().rev()).take_while(|(a, b)| a == b).count();
+    out.extend(old[..prefix].iter().map(|line| DiffLine::Same(line)));
+
+    let (old_mid, new_mid) = (&old[prefix..old.len() - suffix], &new[prefix..new.len() - suffix]);
+    if old_mid.is_empty() || new_mid.is_empty() {
+        out.extend(old_mid.iter().map(|line| DiffLine::Removed(line)));
+        out.extend(new_mid.iter().map(|line| DiffLine::Added(line)));
+    } else {
+        // the ends of both differ, so at least two edits are left and each half has fewer
+        let (x, y, u, v) = middle_snake(old_mid, new_mid);
+        diff_slices(&old_mid[..x], &new_mid[..y], out);
+        out.extend(old_mid[x..u].iter().map(|line| DiffLine::Same(line)));
+        diff_slices(&old_mid[u..], &new_mid[v..], out);
+    }
+    out.extend(old[old.len() - suffix..].iter().map(|line| DiffLine::Same(line)));
+}
+
+/// the middle snake of a shortest edit script from `old` to `new`, as (x, y, u, v): the lines
+/// old[x..u] are the same as new[y..v]. Searches from both ends at once until the paths overlap.
+fn middle_snake(old: &[&str], new: &[&str]) -> (usize, usize, usize, usize) {
+    let (n, m) = (old.len() as isize, new.len() as isize);
+    let delta = n - m;
+    let max = (n + m + 1) / 2;
+    // furthest x reached on each diagonal k = x - y, forward and from the ends backward
+    let at = |k: isize| (k + max + 1) as usize;
+    let mut forward = vec![0isize; 2 * max as usize + 3];
+    let mut backward = vec![0isize; 2 * max as usize + 3];
+
+    for d in 0..=max {
+        for k in (-d..=d).step_by(2) {
+            let mut x = if k == -d || (k != d && forward[at(k - 1)] < forward[at(k + 1)]) {
+                forward[at(k + 1)]
+            } else {
+                forward[at(k - 1)] + 1
+            };
+            let (x0, y0) = (x, x - k);
+            let mut y = y0;
+            while x < n && y < m && old[x as usize] == new[y as usize] {
+                x += 1;
+                y += 1;
+            }
+            forward[at(k)] = x;
+            // the backward paths have taken d - 1 edits
+            if delta % 2 != 0 && (delta - k).abs() < d && x + backward[at(delta - k)] >= n {
+                return (x0 as usize, y0 as usize, x as usize, y as usize);
+            }
+        }
+        for k in (-d..=d).step_by(2) {
+            let mut x = if k == -d || (k != d && backward[at(k - 1)] < backward[at(k + 1)]) {
+                backward[at(k + 1)]
+            } else {
+                backward[at(k - 1)] + 1
+            };
+            let (x0, y0) = (x, x - k);
+            let mut y = y0;
+            while x < n && y < m && old[(n - 1 - x) as usize] == new[(m - 1 - y) as usize] {
+                x += 1;
+                y += 1;
+            }
+            backward[at(k)] = x;
+            // backward diagonal k is forward diagonal delta - k, and the forward paths have taken d edits
+            if delta % 2 == 0 && (delta - k).abs() <= d && x + forward[at(delta - k)] >= n {
+                return ((n - x) as usize, (m - y) as usize, (n - x0) as usize, (m - y0) as usize);
+            }
+        }
+    }
+    unreachable!("the paths overlap within (n + m + 1) / 2 edits")
+}
+
+/// writes the changes from `old` to `new` as a unified diff, with `context` unchanged lines around
+/// each change. Gives an empty string if the texts have the same lines.
+///
+/// # Examples
+/// ```
+/// use ttm::utils::diff::unified_diff;
+/// assert_eq!(unified_diff("a\nb\nc\n", "a\nB\nc\n", 1), "@@ -1,3 +1,3 @@\n a\n-b\n+B\n c\n");
+/// ```
+pub fn unified_diff(old: &str, new: &str, context: usize) -> String {
+    let lines = diff_lines(old, new);
+    let changed: Vec<usize> = (0..lines.len()).filter(|&i| !matches!(lines[i], DiffLine::Same(_))).collect();
+
+    // group the changes whose context touches into hunks
+    let mut hunks: Vec<(usize, usize)> = vec![];
+    for &i in changed.iter() {
+        let (start, end) = (i.saturating_sub(context), (i + context + 1).min(lines.len()));
+        match hunks.last_mut() {
+            Some(last) if start <= last.1 => last.1 = end,
+            _ => hunks.push((start, end)),
+        }
+    }
+
+    let mut out = String::new();
+    for (start, end) in hunks {
+        // line numbers of the hunk in the old and new texts, starting from 1
+        let count = |lines: &[DiffLine], old: bool| lines.iter()
+            .filter(|line| match line {
+                DiffLine::Same(_) => true,
+                DiffLine::Removed(_) => old,
+                DiffLine::Added(_) => !old,
+            })
+            .count();
+        let old_start = count(&lines[..start], true) + 1;
+        let new_start = count(&lines[..start], false) + 1;
+        out.push_str(&format!("@@ -{},{} +{},{} @@\n",
+            old_start, count(&lines[start..end], true), new_start, count(&lines[start..end], false)));
+        for line in lines[start..end].iter() {
+            match line {
+                DiffLine::Same(s) => out.push_str(&format!(" {}\n", s)),
+                DiffLine::Removed(s) => out.push_str(&format!("-{}\n", s)),
+                DiffLine::Added(s) => out.push_str(&format!("+{}\n", s)),
+            }
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_diff_lines() {
+        assert_eq!(diff_lines("a\nb\nc", "a\nc\nd"), vec![
+            DiffLine::Same("a"), DiffLine::Removed("b"), DiffLine::Same("c"), DiffLine::Added("d")]);
+        assert_eq!(diff_lines("", "a"), vec![DiffLine::Added("a")]);
+        assert_eq!(diff_lines("a\nb", "a\nb"), vec![DiffLine::Same("a"), DiffLine::Same("b")]);
+    }
+
+    #[test]
+    fn test_longest_common_subsequence() {
+        // each diff keeps as many lines as the longest common subsequence, and turns old into new
+        let texts = ["a\nb\nc\na\nb\nb\na", "c\nb\na\nb\na\nc", "x\na\ny\nb\nz", "b\na\nb", "", "q\nq\nq"];
+        for old in texts.iter() {
+            for new in texts.iter() {
+                let lines = diff_lines(old, new);
+                let kept = |keep: fn(&DiffLine) -> bool| -> Vec<&str> {
+                    lines.iter().filter(|line| keep(line)).map(|line| match line {
+                        DiffLine::Same(s) | DiffLine::Removed(s) | DiffLine::Added(s) => *s,
+                    }).collect()
+                };
+                assert_eq!(kept(|line| !matches!(line, DiffLine::Added(_))), old.lines().collect::<Vec<&str>>());
+                assert_eq!(kept(|line| !matches!(line, DiffLine::Removed(_))), new.lines().collect::<Vec<&str>>());
+                assert_eq!(kept(|line| matches!(line, DiffLine::Same(_))).len(), lcs_len(old, new), "{:?} {:?}", old, new);
+            }
+        }
+    }
+
+    fn lcs_len(old: &str, new: &str) -> usize {
+        let (old, new): (Vec<&str>, Vec<&str>) = (old.lines().collect(), new.lines().collect());
+        let mut lcs = vec![vec![0usize; new.len() + 1]; old.len() + 1];
+        for i in (0..old.len()).rev() {
+            for j in (0..new.len()).rev() {
+                lcs[i][j] = if old[i] == new[j] { lcs[i + 1][j + 1] + 1 } else { lcs[i + 1][j].max(lcs[i][j + 1]) };
+            }
+        }
+        lcs[0][0]
+    }
+
+    #[test]
+    fn test_unified_diff() {
+        let old = "1\n2\n3\n4\n5\n6\n7\n8\n";
+        let new = "1\n2\n3\nfour\n5\n6\n7\n8\nnine\n";
+        assert_eq!(unified_diff(old, new, 1), "@@ -3,3 +3,3 @@\n 3\n-4\n+four\n 5\n@@ -8,1 +8,2 @@\n 8\n+nine\n");
+        assert_eq!(unified_diff(old, new, 3), "\
+@@ -1,8 +1,9 @@
+ 1
+ 2
+ 3
+-4
++four
+ 5
+ 6
+ 7
+ 8
++nine
+");
+        assert_eq!(unified_diff(old, old, 3), "");
+    }
+}
@@ -0,0 +1,63 @@
+#![allow(dead_code)]
+
+// -----------------------------------------------------------------
+// -----------------------------------------------------------------
+/// matches `path`, a relative path with its parts separated by '/', against a glob `pattern`:
+///     - `*` matches any characters within a part, and `?` matches any one character.
+///     - A `**` part matches any number of parts, including none.
+/// Empty and `.` parts are left out, so `./*.ttm` matches as `*.ttm` does.
+///
+/// # Examples
+/// ```
+/// use ttm::utils::glob::matches;
+/// assert!(matches("**/*.ttm", "archive/2021/week1.ttm"));
+/// assert!(matches("*.ttm", "work.ttm"));
+/// assert!(!matches("*.ttm", "archive/work.ttm"));
+/// ```
+pub fn matches(pattern: &str, path: &str) -> bool {
+    match_parts(&parts(pattern), &parts(path))
+}
+
+fn parts(s: &str) -> Vec<&str> {
+    s.split('/').filter(|part| !part.is_empty() && *part != ".").collect()
+}
+
+fn match_parts(pattern: &[&str], path: &[&str]) -> bool {
+    match pattern.split_first() {
+        None => path.is_empty(),
+        Some((&"**", rest)) => (0..=path.len()).any(|skip| match_parts(rest, &path[skip..])),
+        Some((part, rest)) => match path.split_first() {
+            Some((name, path_rest)) => match_part(part.as_bytes(), name.as_bytes()) && match_parts(rest, path_rest),
+            None => false,
+        },
+    }
+}
+
+/// matches a single part of a path, byte by byte, so `?` matches a single byte of a wider character
+fn match_part(pattern: &[u8], name: &[u8]) -> bool {
+    match pattern.split_first() {
+        None => name.is_empty(),
+        Some((b'*', rest)) => (0..=name.len()).any(|skip| match_part(rest, &name[skip..])),
+        Some((b'?', rest)) => !name.is_empty() && match_part(rest, &name[1..]),
+        Some((c, rest)) => name.first() == Some(c) && match_part(rest, &name[1..]),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_matches() {
+        assert!(matches("*.ttm", "work.ttm"));
+        assert!(!matches("*.ttm", "work.md"));
+        assert!(matches("**/*.ttm", "work.ttm"));
+        assert!(matches("**/*.ttm", "a/b/c.ttm"));
+        assert!(matches("archive/**", "archive/2021/w1.ttm"));
+        assert!(!matches("archive/**/*.ttm", "work/w1.ttm"));
+        assert!(matches("week-?.ttm", "week-1.ttm"));
+        assert!(!matches("week-?.ttm", "week-10.ttm"));
+        assert!(matches("w*k*.ttm", "work-week.ttm"));
+        assert!(matches("./*.ttm", "work.ttm"));
+    }
+}
@@ -1,3 +1,5 @@
 pub mod common;
+pub mod diff;
+pub mod glob;
 pub mod scanner;
 pub mod test;
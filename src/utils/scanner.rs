@@ -6,6 +6,8 @@
 
 #![allow(dead_code)]
 use super::common::CharUtils;
+use std::borrow::Cow;
+use std::fmt;
 
 
 pub struct StrScanner<'a> {
@@ -17,9 +19,9 @@ pub struct StrScanner<'a> {
 
 impl<'a> StrScanner<'a> {
     pub fn peek<T: FromNext>(&mut self) -> Result<(usize, T), T::Err> {
-        let (cur, elem) = T::next(&self.stream[self.cur..])?;
-        assert!(cur >= self.cur);
-        Ok((cur, elem))
+        let (len, elem) = T::next(&self.stream[self.cur..])?;
+        assert!(self.cur + len <= self.stream.len());
+        Ok((len, elem))
     }
 
     pub fn advance(&mut self, n: usize) {
@@ -54,6 +56,7 @@ impl<'a> StrScanner<'a> {
     /// Scans for the next token given a specified separator and retrieves both the token and separator
     /// ### Examples
     /// ```
+    /// # use ttm::utils::scanner::StrScanner;
     /// let mut scanner = StrScanner::create("Comma, Separated, Values!, 999");
     /// let is_sep = |s: &str| if s.starts_with(',') {Some(1)} else {None};
     /// assert_eq!(scanner.next_token(is_sep), Ok(("Comma".into(),      ",".into())));
@@ -89,12 +92,13 @@ impl<'a> StrScanner<'a> {
 
     /// # Examples
     /// ```
+    /// # use ttm::utils::scanner::StrScanner;
     /// let mut scanner = StrScanner::create("I love cereal!");
     /// assert_eq!(scanner.next_word(), Ok("I".into()));
     /// assert_eq!(scanner.next_word(), Ok("love".into()));
     /// assert_eq!(scanner.next_word(), Ok("cereal!".into()));
     /// assert_eq!(scanner.next_word(), Err(()));
-
+    ///
     /// let mut scanner = StrScanner::create("   Trim   your spaces!   ");
     /// assert_eq!(scanner.next_word(), Ok("Trim".into()));
     /// assert_eq!(scanner.next_word(), Ok("your".into()));
@@ -121,7 +125,6 @@ impl<'a> StrScanner<'a> {
     }
 
     pub fn peek_char(&mut self) -> Result<(usize, char), ()> {
-        println!("len: {}, cur: {}", self.stream.len(), self.cur);
         if self.cur == self.stream.len() {return Err(());}
         let out: char = self.stream[self.cur..].chars().next().unwrap();
         Ok((out.len_utf8(), out))
@@ -163,6 +166,7 @@ impl<'a> StrScanner<'a> {
     ///
     /// # Examples
     /// ```
+    /// # use ttm::utils::scanner::StrScanner;
     /// assert_eq!(StrScanner::create("10").next_int(), Ok(10));
     /// assert_eq!(StrScanner::create("00").next_int(), Ok(0));
     /// assert_eq!(StrScanner::create("-999").next_int(), Ok(-999));
@@ -184,6 +188,62 @@ impl<'a> StrScanner<'a> {
         Err(())
     }
 
+    /// like `match_next`, but reports where `exp` was expected
+    pub fn expect(&mut self, exp: &str) -> Result<(), ScanError> {
+        self.match_next(exp).map_err(|_| ScanError::new(self.cur, format!("'{}'", exp)))
+    }
+
+    /// advances the cursor past any whitespace
+    pub fn skip_whitespace(&mut self) {
+        let rest = &self.stream[self.cur..];
+        self.cur += rest.len() - rest.trim_start().len();
+    }
+
+    /// scans the next unsigned integer but does not advance the stream cursor
+    pub fn peek_integer(&mut self) -> Result<(usize, u32), ScanError> {
+        let rest = &self.stream[self.cur..];
+        let digits_of = |s: &str, radix: u32| s.find(|c: char| !c.is_digit(radix)).unwrap_or(s.len());
+
+        for (prefix, radix) in [("0x", 16), ("0o", 8), ("0b", 2)].iter() {
+            if rest.starts_with(prefix) {
+                let len = digits_of(&rest[2..], *radix);
+                if len > 0 {
+                    let num = u32::from_str_radix(&rest[2..2 + len], *radix)
+                        .map_err(|_| ScanError::new(self.cur, "an integer that fits in 32 bits"))?;
+                    return Ok((2 + len, num));
+                }
+            }
+        }
+
+        let len = digits_of(rest, 10);
+        if len == 0 {return Err(ScanError::new(self.cur, "an integer"))}
+        let num = rest[..len].parse::<u32>()
+            .map_err(|_| ScanError::new(self.cur, "an integer that fits in 32 bits"))?;
+        Ok((len, num))
+    }
+
+    /// an unsigned integer may be hexadecimal (0x), octal (0o), binary (0b) or decimal, as the
+    /// INTEGER token of `crate::ttm_io::common_regex`. The digits are taken greedily.
+    ///
+    /// # Examples
+    /// ```
+    /// # use ttm::utils::scanner::{ScanError, StrScanner};
+    /// assert_eq!(StrScanner::create("0x1F!").next_integer(), Ok(31));
+    /// assert_eq!(StrScanner::create("0b102").next_integer(), Ok(2));
+    /// assert_eq!(StrScanner::create("0xG").next_integer(), Ok(0));
+    /// assert_eq!(StrScanner::create("-1").next_integer(), Err(ScanError::new(0, "an integer")));
+    /// ```
+    pub fn next_integer(&mut self) -> Result<u32, ScanError> {
+        let (step, out) = self.peek_integer()?;
+        self.advance(step);
+        Ok(out)
+    }
+
+    /// whether the whole stream has been consumed
+    pub fn is_done(&self) -> bool {
+        self.cur == self.stream.len()
+    }
+
     pub fn create(stream: &'a str) -> Self {
         Self {stream: stream, cur: 0}
     }
@@ -197,6 +257,31 @@ pub trait FromNext: Sized {
     fn next(s: &str) -> Result<(usize, Self), Self::Err>;
 }
 
+/// reports what a `FromNext` parser expected, and where in the stream it was given
+#[derive(Debug, PartialEq, Clone)]
+pub struct ScanError {
+    /// byte offset into the stream
+    pub at: usize,
+    pub expected: Cow<'static, str>,
+}
+
+impl ScanError {
+    pub fn new(at: usize, expected: impl Into<Cow<'static, str>>) -> Self {
+        Self {at, expected: expected.into()}
+    }
+
+    /// the same error, for a parser that was given the stream from `cur` on
+    pub fn after(self, cur: usize) -> Self {
+        Self {at: self.at + cur, ..self}
+    }
+}
+
+impl fmt::Display for ScanError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "expected {} at {}", self.expected, self.at)
+    }
+}
+
 
 
 #[cfg(test)]
@@ -253,6 +338,25 @@ mod tests {
             assert_eq!(scanner.next_token(is_sep), Err(()));
         }
 
+        #[test]
+        fn test_next_integer() {
+            assert_eq!(StrScanner::create("10").next_integer(), Ok(10));
+            assert_eq!(StrScanner::create("0x1F!").next_integer(), Ok(31));
+            assert_eq!(StrScanner::create("0o17").next_integer(), Ok(15));
+            assert_eq!(StrScanner::create("0b102").next_integer(), Ok(2));
+            assert_eq!(StrScanner::create("-1").next_integer(), Err(ScanError::new(0, "an integer")));
+            assert_eq!(StrScanner::create("99999999999").next_integer(),
+                Err(ScanError::new(0, "an integer that fits in 32 bits")));
+
+            // a prefix without digits is a zero
+            let mut scanner = StrScanner::create("0xG");
+            assert_eq!(scanner.next_integer(), Ok(0));
+            assert_eq!(scanner.expect("x"), Ok(()));
+            assert_eq!(scanner.expect("F"), Err(ScanError::new(2, "'F'")));
+            scanner.skip_whitespace();
+            assert!(!scanner.is_done());
+        }
+
         #[test]
         fn test_next_line() {
             let mut scanner = StrScanner::create("